@@ -0,0 +1,77 @@
+//! Time to parse the whole specification, as `Grammar::load` does, but with
+//! the files read beforehand.
+//!
+//! `cargo bench` reads the specification of the `resources/spec` submodule
+//! of the checkout at `WASMMETA_PATH`, as the tests of the parsers do.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use species::Grammar;
+
+const RUNS: u32 = 20;
+
+fn main() {
+    let Ok(path) = env::var("WASMMETA_PATH") else {
+        eprintln!("`WASMMETA_PATH` is not set, nothing to parse");
+        return;
+    };
+    let root = Path::new(&path).join("resources/spec/document/core");
+    let mut files = vec![];
+    collect(&root, &mut files);
+    let sources: Vec<_> = files
+        .iter()
+        .map(|file| {
+            let content = fs::read_to_string(file).expect("readable file");
+            (
+                file.strip_prefix(&root).unwrap_or(file).to_path_buf(),
+                content,
+            )
+        })
+        .collect();
+    let bytes: usize = sources.iter().map(|(_, content)| content.len()).sum();
+
+    let mut times = vec![];
+    let mut productions = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let mut grammar = Grammar::new("spec");
+        for (file, content) in &sources {
+            grammar.add_source(file, content);
+        }
+        times.push(start.elapsed());
+        productions = grammar.productions().count();
+    }
+    times.sort();
+    let mean = times.iter().sum::<Duration>() / RUNS;
+    println!(
+        "{} files, {} KiB, {} productions: min {:?}, median {:?}, mean {:?} over {} runs",
+        sources.len(),
+        bytes / 1024,
+        productions,
+        times[0],
+        times[times.len() / 2],
+        mean,
+        RUNS
+    );
+}
+
+fn collect(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<_> = entries
+        .map(|e| e.expect("directory entry").path())
+        .collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            collect(&path, files);
+        } else if path.extension().is_some_and(|e| e == "rst") {
+            files.push(path);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Language server of the spec's `rst` sources, over stdio.
+//!
+//! It serves go-to-definition, hover and references of nonterminals, and
+//! publishes the parse errors and undefined nonterminals of a file when it is
+//! opened, edited or saved. See [`species::lsp::Workspace`].
+
+use std::{error::Error, fs, path::PathBuf};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Notification as _, PublishDiagnostics,
+    },
+    request::{GotoDefinition, HoverRequest, References, Request as _},
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeParams, MarkupContent, MarkupKind, OneOf,
+    PublishDiagnosticsParams, ReferenceParams, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+use serde_json::Value;
+use species::lsp::{Location, Workspace};
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        references_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    #[allow(deprecated)]
+    let root = params
+        .workspace_folders
+        .and_then(|folders| folders.into_iter().next().map(|f| f.uri))
+        .or(params.root_uri)
+        .and_then(|uri| uri.to_file_path().ok())
+        .unwrap_or_else(|| PathBuf::from("."));
+    // the locations sent back are file URIs, which take absolute paths
+    let root = fs::canonicalize(&root).unwrap_or(root);
+    let workspace = Workspace::load(&root).unwrap_or_else(|e| {
+        eprintln!("species-lsp: {}: {}", root.display(), e);
+        Workspace::new(&root)
+    });
+
+    Server {
+        connection,
+        workspace,
+    }
+    .run()?;
+    io_threads.join()?;
+    Ok(())
+}
+
+struct Server {
+    connection: Connection,
+    workspace: Workspace,
+}
+
+impl Server {
+    fn run(&mut self) -> Result<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    self.request(request)?;
+                }
+                Message::Notification(notification) => {
+                    // e.g. a document that is not a file, which is not served
+                    if let Err(e) = self.notification(notification) {
+                        eprintln!("species-lsp: {}", e);
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn request(&mut self, request: Request) -> Result<()> {
+        let response = match self.answer(&request.method, request.params) {
+            Ok(Some(result)) => Response::new_ok(request.id, result),
+            Ok(None) => Response::new_err(
+                request.id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request `{}`", request.method),
+            ),
+            Err(e) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, e.to_string()),
+        };
+        self.connection.sender.send(response.into())?;
+        Ok(())
+    }
+
+    /// The result of a request, or none if the method is not supported
+    fn answer(&self, method: &str, params: Value) -> Result<Option<Value>> {
+        let result = match method {
+            GotoDefinition::METHOD => {
+                let params = serde_json::from_value::<GotoDefinitionParams>(params)?
+                    .text_document_position_params;
+                let path = path(&params.text_document.uri)?;
+                let locations = self.workspace.definition(&path, params.position);
+                let locations = locations
+                    .into_iter()
+                    .map(lsp_location)
+                    .collect::<Result<_>>()?;
+                serde_json::to_value(GotoDefinitionResponse::Array(locations))?
+            }
+            HoverRequest::METHOD => {
+                let params =
+                    serde_json::from_value::<HoverParams>(params)?.text_document_position_params;
+                let path = path(&params.text_document.uri)?;
+                let hover = self
+                    .workspace
+                    .hover(&path, params.position)
+                    .map(|value| Hover {
+                        contents: HoverContents::Markup(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value,
+                        }),
+                        range: None,
+                    });
+                serde_json::to_value(hover)?
+            }
+            References::METHOD => {
+                let params: ReferenceParams = serde_json::from_value(params)?;
+                let position = params.text_document_position;
+                let path = path(&position.text_document.uri)?;
+                let declarations = params.context.include_declaration;
+                let locations = self
+                    .workspace
+                    .references(&path, position.position, declarations);
+                let locations: Vec<_> = locations
+                    .into_iter()
+                    .map(lsp_location)
+                    .collect::<Result<_>>()?;
+                serde_json::to_value(locations)?
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(result))
+    }
+
+    fn notification(&mut self, notification: Notification) -> Result<()> {
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let document = params.text_document;
+                self.workspace.update(&path(&document.uri)?, document.text);
+                document.uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                // the sync is full, so the last change is the whole text
+                let Some(change) = params.content_changes.into_iter().last() else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                self.workspace.update(&path(&uri)?, change.text);
+                uri
+            }
+            DidSaveTextDocument::METHOD => {
+                let params: lsp_types::DidSaveTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                params.text_document.uri
+            }
+            DidCloseTextDocument::METHOD => {
+                // an unsaved edit is dropped, so the file is read back
+                let params: lsp_types::DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                let path = path(&uri)?;
+                if let Ok(text) = std::fs::read_to_string(&path) {
+                    self.workspace.update(&path, text);
+                }
+                uri
+            }
+            _ => return Ok(()),
+        };
+        self.publish(uri)
+    }
+
+    fn publish(&self, uri: Url) -> Result<()> {
+        let diagnostics = self.workspace.diagnostics(&path(&uri)?);
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection.sender.send(notification.into())?;
+        Ok(())
+    }
+}
+
+fn path(uri: &Url) -> Result<PathBuf> {
+    uri.to_file_path()
+        .map_err(|()| format!("not a file: {}", uri).into())
+}
+
+fn lsp_location(location: Location) -> Result<lsp_types::Location> {
+    let uri = Url::from_file_path(&location.path)
+        .map_err(|()| format!("not an absolute path: {}", location.path.display()))?;
+    Ok(lsp_types::Location::new(uri, location.range))
+}
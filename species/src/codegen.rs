@@ -0,0 +1,582 @@
+pub mod arbitrary;
+pub mod decoder;
+pub mod encoder;
+pub mod text;
+pub mod types;
+
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use crate::{
+    parser::SeqKind,
+    spec::Spec,
+    syntax::{
+        expr::{join, Expr, Var},
+        function::{Arith, Cond, Op, Relation},
+        symbol::Symbol,
+        RhsElem,
+    },
+};
+
+use self::types::{
+    builtin, field_name, rust_ty, type_name, Fields, Ty, TypeDef, TypeModel, Variant,
+};
+
+/// Rust source generated from a specification
+#[derive(Debug)]
+pub struct Generated {
+    pub source: String,
+    /// Productions that could not be translated, and why
+    pub skipped: Vec<Skipped>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Skipped {
+    pub nonterminal: String,
+    pub reason: String,
+}
+
+/// Settings of [`module_with`]
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    /// Generate `arbitrary::Arbitrary` implementations and `proptest`
+    /// strategies for the types, behind features of the same names
+    pub arbitrary: bool,
+}
+
+impl Config {
+    pub fn arbitrary(mut self, arbitrary: bool) -> Self {
+        self.arbitrary = arbitrary;
+        self
+    }
+}
+
+/// Module with the abstract-syntax types, a decoder and an encoder of the
+/// binary format, and a parser of the text format
+pub fn module(spec: &Spec) -> Generated {
+    module_with(spec, &Config::default())
+}
+
+pub fn module_with(spec: &Spec, config: &Config) -> Generated {
+    let model = TypeModel::new(spec);
+    let (binary, untyped) = targets(spec, &model, 'B');
+    let decoders = decoder::Decoders::new(spec, &model, &binary);
+    let encoders = encoder::Encoders::new(spec, &model, &binary);
+    let (text, untyped_text) = targets(spec, &model, 'T');
+    let parsers = text::Parsers::new(spec, &model, &text);
+
+    let mut source = String::new();
+    source.push_str(&header(spec));
+    source.push_str(&model.emit());
+    source.push_str(&decoders.emit());
+    source.push_str(&encoders.emit());
+    source.push_str(&parsers.emit());
+    let arbitraries = config
+        .arbitrary
+        .then(|| arbitrary::Arbitraries::new(&model));
+    if let Some(arbitraries) = &arbitraries {
+        source.push_str(&arbitraries.emit());
+    }
+
+    let mut skipped = model.skipped().to_vec();
+    skipped.extend(untyped);
+    skipped.extend(decoders.skipped().iter().cloned());
+    skipped.extend(encoders.skipped().iter().cloned());
+    skipped.extend(untyped_text);
+    skipped.extend(parsers.skipped().iter().cloned());
+    if let Some(arbitraries) = &arbitraries {
+        skipped.extend(arbitraries.skipped().iter().cloned());
+    }
+    Generated { source, skipped }
+}
+
+/// Nonterminals of the binary (`B`) or text (`T`) format with the abstract
+/// type they stand for.
+///
+/// Those without any attribute, like `\Bmagic`, or that only extend the
+/// identifier context, like `\Tlabel`, stand for `()`. Those without an
+/// abstract counterpart whose attributes all name the value of one
+/// nonterminal, like `\Tparam`, stand for its type.
+fn targets(spec: &Spec, model: &TypeModel, format: char) -> (Vec<(String, Ty)>, Vec<Skipped>) {
+    let mut targets = vec![];
+    for nonterminal in spec.grammar().nonterminals() {
+        match format_of(nonterminal) {
+            Some((f, name)) if f == format && builtin(name).is_none() && nonterminal != SECTION => {
+                let alternatives = spec.alternatives_of(nonterminal);
+                let ty = model.ty(name).or_else(|| {
+                    alternatives
+                        .iter()
+                        .all(|a| a.elem.action().is_none_or(extends_context))
+                        .then_some(Ty::Unit)
+                });
+                targets.push((nonterminal, ty));
+            }
+            _ => {}
+        }
+    }
+
+    loop {
+        let known: HashMap<&str, Ty> = targets
+            .iter()
+            .filter_map(|(nt, ty)| Some((*nt, ty.clone()?)))
+            .collect();
+        let mut changed = false;
+        for (nonterminal, ty) in targets.iter_mut().filter(|(_, ty)| ty.is_none()) {
+            let alternatives = spec.alternatives_of(nonterminal);
+            let mut tys = alternatives.iter().map(|a| named(a.elem, &known));
+            if let Some(Some(first)) = tys.next() {
+                if tys.all(|ty| ty.as_ref() == Some(&first)) {
+                    *ty = Some(first);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut typed = vec![];
+    let mut skipped = vec![];
+    for (nonterminal, ty) in targets {
+        match ty {
+            Some(ty) => typed.push((nonterminal.to_string(), ty)),
+            None => skipped.push(Skipped {
+                nonterminal: nonterminal.to_string(),
+                reason: format!("abstract `{}` has no type", short(nonterminal)),
+            }),
+        }
+    }
+    (typed, skipped)
+}
+
+/// Type of the nonterminal whose value an attribute names, e.g. that of
+/// `\Tvaltype` for `t{:}\Tvaltype \Rightarrow t`, or of the section in
+/// `\X{ft}^\ast{:\,}\Bsection_1(\Bvec(\Bfunctype)) \Rightarrow \X{ft}^\ast`
+fn named(elem: &RhsElem, known: &HashMap<&str, Ty>) -> Option<Ty> {
+    let [Expr::Var(var)] = elem.action()? else {
+        return None;
+    };
+    elem.symbols().iter().find_map(|symbol| match symbol {
+        Symbol::SBind(bind) if key(bind.var()) == key(var) => match section(bind.symbol()) {
+            Some((_, content)) => Some(section_ty(bind.var(), symbol_ty(content, known)?)),
+            None => symbol_ty(bind.symbol(), known),
+        },
+        _ => None,
+    })
+}
+
+/// Type of a nonterminal, or of the vector `\Bvec(\Bx)`, among the known
+fn symbol_ty(symbol: &Symbol, known: &HashMap<&str, Ty>) -> Option<Ty> {
+    match symbol {
+        Symbol::SNonterm(nt) if nt.seq_kind().is_none() && nt.args().is_empty() => {
+            let name = short(&nt.name);
+            known.get(nt.name.as_str()).cloned().or_else(|| {
+                (format_of(&nt.name).is_some() && builtin(name).is_some())
+                    .then(|| Ty::Builtin(name.to_string()))
+            })
+        }
+        Symbol::SVec(vec) if vec.over().seq_kind().is_none() => {
+            let over = Symbol::SNonterm(vec.over().clone());
+            Some(Ty::Vec(Box::new(symbol_ty(&over, known)?)))
+        }
+        _ => None,
+    }
+}
+
+/// `\Bsection_N(\B{B})`, the frame of the sections of a module
+const SECTION: &str = "Bsection";
+
+/// Id and content of a section, e.g. `1` and `\Bvec(\Bfunctype)` in
+/// `\Bsection_1(\Bvec(\Bfunctype))`
+fn section(symbol: &Symbol) -> Option<(u8, &Symbol)> {
+    match symbol {
+        Symbol::SNonterm(nt) if nt.name == SECTION && nt.seq_kind().is_none() => {
+            match (nt.context()?.parse().ok()?, nt.args()) {
+                (id, [content]) => Some((id, content)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Type of a variable bound to a section whose content has type `content`.
+/// A section may be missing, which `\X{ft}^\ast` reads as an empty vector
+/// and `\X{st}^?` as no value.
+fn section_ty(var: &Var, content: Ty) -> Ty {
+    match (var.seq_kind.as_ref(), content) {
+        (Some(SeqKind::ManyPossibleEmpty), content @ Ty::Vec(_)) => content,
+        (_, content) => Ty::Option(Box::new(content)),
+    }
+}
+
+/// Whether an attribute extends the identifier context, e.g.
+/// `\{\ILABELS~v\} \compose I`
+fn extends_context(action: &[Expr]) -> bool {
+    matches!(action, [Expr::Compose(..)])
+}
+
+/// Translate every target into a function, dropping the targets whose
+/// translation fails or calls a dropped one until all translate
+fn fixed_point(
+    targets: &[(String, Ty)],
+    what: &str,
+    mut function: impl FnMut(&HashMap<String, Ty>, &str, &Ty) -> Result<String, String>,
+) -> (Vec<String>, Vec<Skipped>) {
+    let mut available: HashMap<String, Ty> = targets.iter().cloned().collect();
+    let mut skipped = vec![];
+    loop {
+        let mut functions = vec![];
+        let mut failed = vec![];
+        for (nonterminal, ty) in targets {
+            if !available.contains_key(nonterminal) {
+                continue;
+            }
+            match function(&available, nonterminal, ty) {
+                Ok(source) => functions.push(source),
+                Err(reason) => failed.push((nonterminal.clone(), reason)),
+            }
+        }
+        if failed.is_empty() {
+            return (functions, skipped);
+        }
+        for (nonterminal, reason) in failed {
+            available.remove(&nonterminal);
+            skipped.push(Skipped {
+                nonterminal,
+                reason: format!("{}: {}", what, reason),
+            });
+        }
+    }
+}
+
+fn header(spec: &Spec) -> String {
+    format!(
+        "// Generated by `species codegen` from `{}`. Do not edit.\n\n\
+         #![allow(dead_code, unused_variables, unreachable_patterns, clippy::all)]\n\n",
+        spec.grammar().name()
+    )
+}
+
+/// Nonterminals of the binary (`\B...`) and text (`\T...`) formats are
+/// prefixed, abstract ones are all lowercase
+pub(crate) fn format_of(nonterminal: &str) -> Option<(char, &str)> {
+    let mut chars = nonterminal.chars();
+    let prefix = chars.next().filter(|c| matches!(c, 'B' | 'T'))?;
+    let rest = chars.as_str();
+    rest.starts_with(|c: char| c.is_ascii_lowercase())
+        .then_some((prefix, rest))
+}
+
+/// Values named by binders in an alternative, keyed by [`key`], with the
+/// Rust expression holding them
+type Bindings = HashMap<String, (String, Ty)>;
+
+fn integer(nonterminal: &str) -> bool {
+    matches!(nonterminal, "byte" | "u32" | "u64" | "s33" | "i32" | "i64")
+}
+
+/// `12` in `12{:}\Bu32`, which the binary format expects literally
+fn literal(symbol: &Symbol) -> Option<u64> {
+    match symbol {
+        Symbol::SBind(bind) => match bind.symbol() {
+            Symbol::SNonterm(nt) if nt.name == "Bu32" && nt.seq_kind().is_none() => {
+                bind.var().literal()
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn binding<'b>(var: &Var, bindings: &'b Bindings) -> Result<&'b (String, Ty), String> {
+    bindings
+        .get(&key(var))
+        .ok_or_else(|| format!("`{}` is not bound", var))
+}
+
+/// A variable is the same regardless of its iteration, `\X{in}` binds
+/// `\X{in}^\ast`
+fn key(var: &Var) -> String {
+    format!("{}_{}", var.name, var.sub.as_deref().unwrap_or_default())
+}
+
+/// Rust variable of a binder, e.g. `v_rt_1` for `\X{rt}_1`
+fn local(var: &Var) -> String {
+    let mut local = format!("v_{}", var.name);
+    if let Some(sub) = &var.sub {
+        local.push('_');
+        local.push_str(sub);
+    }
+    local
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// `limits` for `Blimits`
+pub(crate) fn short(nonterminal: &str) -> &str {
+    format_of(nonterminal).map_or(nonterminal, |(_, name)| name)
+}
+
+fn indent(code: &str) -> String {
+    code.lines()
+        .map(|line| {
+            if line.is_empty() {
+                "\n".to_string()
+            } else {
+                format!("    {}\n", line)
+            }
+        })
+        .collect()
+}
+
+/// Rust expression of type `ty` for an attribute, where `overflow` is the
+/// error of a number that does not fit its type
+fn translate(
+    model: &TypeModel,
+    exprs: &[Expr],
+    ty: &Ty,
+    bindings: &Bindings,
+    overflow: &str,
+) -> Result<String, String> {
+    if let Ty::Box(inner) = ty {
+        return Ok(format!(
+            "Box::new({})",
+            translate(model, exprs, inner, bindings, overflow)?
+        ));
+    }
+    let resolved = model.resolve(ty);
+    let enum_def = match &resolved {
+        Ty::Named(name) => match model.get(name) {
+            Some(TypeDef::Enum(variants)) => Some((name, variants)),
+            _ => None,
+        },
+        _ => None,
+    };
+    // terminals only select variants, like `\END` in `\X{in}^\ast~\END`
+    let exprs: Vec<_> = match enum_def {
+        Some(_) => exprs.to_vec(),
+        None => exprs
+            .iter()
+            .filter(|e| !matches!(e, Expr::Term(_)))
+            .cloned()
+            .collect(),
+    };
+    let mismatch = || format!("cannot build `{}` from `{}`", rust_ty(ty), join(&exprs));
+
+    if let [Expr::Var(var)] = exprs.as_slice() {
+        let (local, var_ty) = binding(var, bindings)?;
+        match (model.resolve(var_ty), &resolved) {
+            (var_ty, ty) if var_ty == *ty => return Ok(local.clone()),
+            // e.g. a type index read as `\Bs33`
+            (Ty::Builtin(from), Ty::Builtin(to)) if integer(&from) && integer(to) => {
+                return Ok(format!(
+                    "{}::try_from({}).map_err(|_| {})?",
+                    builtin(to).unwrap_or_default(),
+                    local,
+                    overflow
+                ));
+            }
+            _ => {}
+        }
+    }
+    if let Some((name, variants)) = enum_def {
+        return variant(model, name, variants, &exprs, bindings, overflow);
+    }
+    match &resolved {
+        Ty::Option(inner) => match exprs.as_slice() {
+            [Expr::Epsilon] => Ok("None".to_string()),
+            exprs => Ok(format!(
+                "Some({})",
+                translate(model, exprs, inner, bindings, overflow)?
+            )),
+        },
+        Ty::Vec(_) => match exprs.as_slice() {
+            [Expr::List(items)] => translate(model, items, &resolved, bindings, overflow),
+            _ => Err(mismatch()),
+        },
+        Ty::Named(name) => match model.get(name) {
+            Some(TypeDef::Struct(fields)) => {
+                translate_fields(model, &type_name(name), fields, &exprs, bindings, overflow)
+            }
+            _ => Err(mismatch()),
+        },
+        _ => Err(mismatch()),
+    }
+}
+
+fn variant(
+    model: &TypeModel,
+    name: &str,
+    variants: &[Variant],
+    exprs: &[Expr],
+    bindings: &Bindings,
+    overflow: &str,
+) -> Result<String, String> {
+    let mismatch = || format!("cannot build `{}` from `{}`", type_name(name), join(exprs));
+    match exprs {
+        [Expr::Term(terminal), rest @ ..] => {
+            let variant = variants
+                .iter()
+                .find(|v| v.terminals.first() == Some(terminal))
+                .ok_or_else(mismatch)?;
+            let rest: Vec<_> = rest
+                .iter()
+                .filter(|e| !matches!(e, Expr::Term(t) if variant.terminals.contains(t)))
+                .cloned()
+                .collect();
+            let path = format!("{}::{}", type_name(name), variant.name);
+            translate_fields(model, &path, &variant.fields, &rest, bindings, overflow)
+        }
+        // a value wrapped into the variant of its type, like a
+        // `\numtype` into `\valtype` or `\epsilon` into `\valtype^?`
+        [expr] => variants
+            .iter()
+            .filter(|v| v.terminals.is_empty())
+            .find_map(|v| match &v.fields {
+                Fields::Tuple(tys) if tys.len() == 1 => {
+                    let expr = std::slice::from_ref(expr);
+                    let value = translate(model, expr, &tys[0], bindings, overflow);
+                    value
+                        .ok()
+                        .map(|value| format!("{}::{}({})", type_name(name), v.name, value))
+                }
+                _ => None,
+            })
+            .ok_or_else(mismatch),
+        _ => Err(mismatch()),
+    }
+}
+
+fn translate_fields(
+    model: &TypeModel,
+    path: &str,
+    fields: &Fields,
+    exprs: &[Expr],
+    bindings: &Bindings,
+    overflow: &str,
+) -> Result<String, String> {
+    let mismatch = || format!("cannot build `{}` from `{}`", path, join(exprs));
+    match (fields, exprs) {
+        (Fields::Unit, []) => Ok(path.to_string()),
+        (Fields::Named(fields), [Expr::Record(pairs)]) => {
+            let mut values = vec![];
+            for (key, ty) in fields {
+                let (_, value) = pairs
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .ok_or_else(|| format!("field `{}` of `{}` is missing", key, path))?;
+                let value = translate(model, value, ty, bindings, overflow)?;
+                values.push(format!("{}: {}", field_name(key), value));
+            }
+            Ok(format!("{} {{ {} }}", path, values.join(", ")))
+        }
+        (Fields::Tuple(tys), exprs) => {
+            let args: Vec<&[Expr]> = match exprs {
+                [Expr::Arrow(from, to)] if tys.len() == 2 => vec![from, to],
+                exprs if exprs.len() == tys.len() => {
+                    exprs.iter().map(std::slice::from_ref).collect()
+                }
+                _ => return Err(mismatch()),
+            };
+            let args = args
+                .iter()
+                .zip(tys)
+                .map(|(exprs, ty)| translate(model, exprs, ty, bindings, overflow))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("{}({})", path, args.join(", ")))
+        }
+        _ => Err(mismatch()),
+    }
+}
+
+/// Rust condition checking a side condition on bound numbers, e.g.
+/// `i128::from(v_x) >= 0` for `\iff x \geq 0`
+fn condition(cond: &str, bindings: &Bindings) -> Result<String, String> {
+    let unsupported = || format!("side condition `{}` is not supported", cond);
+    let cond = Cond::new(cond.strip_prefix(r"\iff").ok_or_else(unsupported)?);
+    translate_cond(&cond, bindings).ok_or_else(unsupported)
+}
+
+fn translate_cond(cond: &Cond, bindings: &Bindings) -> Option<String> {
+    match cond {
+        Cond::Compare(left, relation, right) => {
+            let relation = match relation {
+                Relation::Eq => "==",
+                Relation::Neq => "!=",
+                Relation::Lt => "<",
+                Relation::Gt => ">",
+                Relation::Le => "<=",
+                Relation::Ge => ">=",
+            };
+            Some(format!(
+                "{} {} {}",
+                translate_arith(left, bindings)?,
+                relation,
+                translate_arith(right, bindings)?
+            ))
+        }
+        Cond::And(conds) | Cond::Or(conds) => {
+            let conds = conds
+                .iter()
+                .map(|c| translate_cond(c, bindings).map(|c| format!("({})", c)))
+                .collect::<Option<Vec<_>>>()?;
+            let op = if matches!(cond, Cond::And(_)) {
+                " && "
+            } else {
+                " || "
+            };
+            Some(conds.join(op))
+        }
+        Cond::Other(_) => None,
+    }
+}
+
+/// Arithmetic on `i128`, which holds every integer of the binary format
+fn translate_arith(arith: &Arith, bindings: &Bindings) -> Option<String> {
+    match arith {
+        Arith::Num(n) => Some(n.to_string()),
+        Arith::Var(name) => {
+            let (_, var) = Var::parser(name).ok().filter(|(rest, _)| rest.is_empty())?;
+            match bindings.get(&key(&var))? {
+                (local, Ty::Builtin(ty)) if integer(ty) => Some(format!("i128::from({})", local)),
+                _ => None,
+            }
+        }
+        Arith::Neg(arith) => Some(format!("-{}", translate_arith(arith, bindings)?)),
+        Arith::Binary(base, Op::Pow, exponent) => match (&**base, &**exponent) {
+            (Arith::Num(base), Arith::Num(exponent)) => {
+                let exponent = u32::try_from(*exponent).ok()?;
+                i128::from(*base)
+                    .checked_pow(exponent)
+                    .map(|n| n.to_string())
+            }
+            _ => None,
+        },
+        Arith::Binary(left, op, right) => {
+            let op = match op {
+                Op::Add => "+",
+                Op::Sub => "-",
+                Op::Mul => "*",
+                _ => return None,
+            };
+            Some(format!(
+                "({} {} {})",
+                translate_arith(left, bindings)?,
+                op,
+                translate_arith(right, bindings)?
+            ))
+        }
+        _ => None,
+    }
+}
+
+impl Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.nonterminal, self.reason)
+    }
+}
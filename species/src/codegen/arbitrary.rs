@@ -0,0 +1,852 @@
+//! `arbitrary::Arbitrary` implementations and `proptest` strategies for the
+//! abstract-syntax types, behind the `arbitrary` and `proptest` features of
+//! the crate that includes the generated module.
+//!
+//! Every type `x` gets a function `arbitrary_x(u, depth)` and a strategy
+//! `arb_x(depth)`, where the depth counts the named types still to nest.
+//! At depth zero sequences are empty, options are `None` and enums only take
+//! the variants that terminate soonest, so that recursive types like `instr`
+//! stay finite. Sequences written `^+` always have an element.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::codegen::{
+    indent,
+    types::{
+        builtin, field_name, references, rust_ty, type_name, Fields, Ty, TypeDef, TypeModel,
+        Variant,
+    },
+    Skipped,
+};
+
+/// Nesting of named types used by `Arbitrary::arbitrary`
+const DEPTH: u32 = 4;
+
+/// Upper bound of the length of generated sequences
+const MAX_LEN: u32 = 4;
+
+/// proptest implements `Strategy` for tuples of up to 12 strategies
+const MAX_FIELDS: usize = 12;
+
+/// Generators of all types that have a finite value
+#[derive(Debug)]
+pub struct Arbitraries {
+    functions: Vec<String>,
+    skipped: Vec<Skipped>,
+}
+
+/// Whether a value is generated below the depth, with the shallowest values
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Nested,
+    Leaf,
+}
+
+struct Context<'m> {
+    /// Fewest named types nested in a value of each type
+    heights: &'m HashMap<String, usize>,
+}
+
+impl Arbitraries {
+    pub fn new(model: &TypeModel) -> Self {
+        let heights = heights(model);
+        let mut skipped = vec![];
+        let mut available: Vec<&str> = vec![];
+        for (name, def) in model.defs() {
+            match def {
+                _ if !heights.contains_key(name) => {
+                    skipped.push(skip(name, "arbitrary: has no finite value"))
+                }
+                TypeDef::Struct(fields) if len(fields) > MAX_FIELDS => {
+                    skipped.push(skip(name, "arbitrary: more than 12 fields"))
+                }
+                _ => available.push(name),
+            }
+        }
+
+        // drop types referring to dropped ones until none is left
+        loop {
+            let (kept, dropped): (Vec<&str>, Vec<&str>) = available.iter().partition(|name| {
+                let def = model.get(name).unwrap();
+                references(def)
+                    .iter()
+                    .all(|r| available.contains(&r.as_str()))
+            });
+            if dropped.is_empty() {
+                break;
+            }
+            for name in dropped {
+                skipped.push(skip(name, "arbitrary: refers to a skipped type"));
+            }
+            available = kept;
+        }
+
+        let context = Context { heights: &heights };
+        let functions = available
+            .iter()
+            .map(|name| context.function(name, model.get(name).unwrap()))
+            .collect();
+        Self { functions, skipped }
+    }
+
+    pub fn skipped(&self) -> &[Skipped] {
+        &self.skipped
+    }
+
+    /// Rust items of all generators
+    pub fn emit(&self) -> String {
+        let mut out = String::new();
+        for function in &self.functions {
+            out.push_str(function);
+        }
+        out
+    }
+}
+
+impl Context<'_> {
+    fn function(&self, name: &str, def: &TypeDef) -> String {
+        let ty = type_name(name);
+        let mut out = String::new();
+        let body = |mode| self.arbitrary_def(name, def, mode);
+        writeln!(
+            out,
+            "#[cfg(feature = \"arbitrary\")]\n\
+             pub fn arbitrary_{name}(u: &mut arbitrary::Unstructured<'_>, depth: u32) -> arbitrary::Result<{rust}> {{\n\
+             {}}}\n",
+            indent(&branches(body(Mode::Leaf), body(Mode::Nested))),
+            name = name,
+            rust = rust_ty(&Ty::Named(name.to_string())),
+        )
+        .unwrap();
+        if !matches!(def, TypeDef::Alias(_)) {
+            writeln!(
+                out,
+                "#[cfg(feature = \"arbitrary\")]\n\
+                 impl<'a> arbitrary::Arbitrary<'a> for {ty} {{\n    \
+                 fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {{\n        \
+                 arbitrary_{name}(u, {depth})\n    \
+                 }}\n\
+                 }}\n",
+                ty = ty,
+                name = name,
+                depth = DEPTH,
+            )
+            .unwrap();
+        }
+        let body = |mode| self.strategy_def(name, def, mode);
+        writeln!(
+            out,
+            "#[cfg(feature = \"proptest\")]\n\
+             pub fn arb_{name}(depth: u32) -> proptest::strategy::BoxedStrategy<{rust}> {{\n    \
+             use proptest::prelude::*;\n\
+             {}}}\n",
+            indent(&branches(body(Mode::Leaf), body(Mode::Nested))),
+            name = name,
+            rust = rust_ty(&Ty::Named(name.to_string())),
+        )
+        .unwrap();
+        out
+    }
+
+    /// Variants an enum takes in a mode, all of them unless at the depth
+    fn variants<'d>(&self, def: &'d TypeDef, mode: Mode) -> Vec<&'d Variant> {
+        let TypeDef::Enum(variants) = def else {
+            return vec![];
+        };
+        let heights: Vec<_> = variants
+            .iter()
+            .map(|v| fields_height(&v.fields, self.heights))
+            .collect();
+        let lowest = heights.iter().flatten().min().copied();
+        variants
+            .iter()
+            .zip(heights)
+            .filter(|(_, height)| mode == Mode::Nested || *height == lowest)
+            .map(|(v, _)| v)
+            .collect()
+    }
+
+    fn arbitrary_def(&self, name: &str, def: &TypeDef, mode: Mode) -> String {
+        let ty = type_name(name);
+        match def {
+            TypeDef::Alias(alias) => format!("Ok({})", self.arbitrary(alias, mode)),
+            TypeDef::Struct(fields) => format!("Ok({})", self.arbitrary_fields(&ty, fields, mode)),
+            TypeDef::Enum(_) => {
+                let variants = self.variants(def, mode);
+                let mut out = format!("Ok(match u.choose_index({})? {{\n", variants.len());
+                for (i, variant) in variants.iter().enumerate() {
+                    let path = format!("{}::{}", ty, variant.name);
+                    let value = self.arbitrary_fields(&path, &variant.fields, mode);
+                    writeln!(out, "    {} => {},", i, value).unwrap();
+                }
+                out.push_str("    _ => unreachable!(),\n})");
+                out
+            }
+        }
+    }
+
+    fn arbitrary_fields(&self, path: &str, fields: &Fields, mode: Mode) -> String {
+        match fields {
+            Fields::Unit => path.to_string(),
+            Fields::Tuple(tys) => {
+                let values: Vec<_> = tys.iter().map(|ty| self.arbitrary(ty, mode)).collect();
+                format!("{}({})", path, values.join(", "))
+            }
+            Fields::Named(fields) => {
+                let values: Vec<_> = fields
+                    .iter()
+                    .map(|(key, ty)| format!("{}: {}", field_name(key), self.arbitrary(ty, mode)))
+                    .collect();
+                format!("{} {{ {} }}", path, values.join(", "))
+            }
+        }
+    }
+
+    /// Expression generating a value of a type from `u`
+    fn arbitrary(&self, ty: &Ty, mode: Mode) -> String {
+        let sequence = |ty, min| {
+            format!(
+                "(0..u.int_in_range({}..={})?).map(|_| Ok({})).collect::<arbitrary::Result<Vec<_>>>()?",
+                min,
+                MAX_LEN,
+                self.arbitrary(ty, mode)
+            )
+        };
+        match (ty, mode) {
+            (Ty::Builtin(_), _) => "u.arbitrary()?".to_string(),
+            (Ty::Unit, _) => "()".to_string(),
+            (Ty::Named(name), Mode::Nested) => format!("arbitrary_{}(u, depth - 1)?", name),
+            (Ty::Named(name), Mode::Leaf) => format!("arbitrary_{}(u, 0)?", name),
+            (Ty::Box(ty), _) => format!("Box::new({})", self.arbitrary(ty, mode)),
+            (Ty::Option(_), Mode::Leaf) => "None".to_string(),
+            (Ty::Option(ty), Mode::Nested) => format!(
+                "if u.arbitrary()? {{ Some({}) }} else {{ None }}",
+                self.arbitrary(ty, mode)
+            ),
+            (Ty::Vec(_), Mode::Leaf) => "vec![]".to_string(),
+            (Ty::Vec(ty), Mode::Nested) => sequence(ty, 0),
+            (Ty::NonEmpty(ty), Mode::Leaf) => format!("vec![{}]", self.arbitrary(ty, mode)),
+            (Ty::NonEmpty(ty), Mode::Nested) => sequence(ty, 1),
+        }
+    }
+
+    fn strategy_def(&self, name: &str, def: &TypeDef, mode: Mode) -> String {
+        let ty = type_name(name);
+        match def {
+            TypeDef::Alias(alias) => format!("{}.boxed()", self.strategy(alias, mode)),
+            TypeDef::Struct(fields) => {
+                format!("{}.boxed()", self.strategy_fields(&ty, fields, mode))
+            }
+            TypeDef::Enum(_) => {
+                let mut out = "proptest::strategy::Union::new(vec![\n".to_string();
+                for variant in self.variants(def, mode) {
+                    let path = format!("{}::{}", ty, variant.name);
+                    let strategy = self.strategy_fields(&path, &variant.fields, mode);
+                    writeln!(out, "    {}.boxed(),", strategy).unwrap();
+                }
+                out.push_str("])\n.boxed()");
+                out
+            }
+        }
+    }
+
+    fn strategy_fields(&self, path: &str, fields: &Fields, mode: Mode) -> String {
+        let tys: Vec<&Ty> = match fields {
+            Fields::Unit => return format!("Just({})", path),
+            Fields::Tuple(tys) => tys.iter().collect(),
+            Fields::Named(fields) => fields.iter().map(|(_, ty)| ty).collect(),
+        };
+        let strategies: Vec<_> = tys.iter().map(|ty| self.strategy(ty, mode)).collect();
+        let vars: Vec<_> = (0..tys.len()).map(|i| format!("v{}", i)).collect();
+        let value = match fields {
+            Fields::Named(fields) => {
+                let values: Vec<_> = fields
+                    .iter()
+                    .zip(&vars)
+                    .map(|((key, _), var)| format!("{}: {}", field_name(key), var))
+                    .collect();
+                format!("{} {{ {} }}", path, values.join(", "))
+            }
+            _ => format!("{}({})", path, vars.join(", ")),
+        };
+        format!(
+            "{}.prop_map(|{}| {})",
+            tuple(&strategies),
+            tuple(&vars),
+            value
+        )
+    }
+
+    /// Strategy of the values of a type
+    fn strategy(&self, ty: &Ty, mode: Mode) -> String {
+        let sequence = |ty, min| {
+            format!(
+                "proptest::collection::vec({}, {}..={})",
+                self.strategy(ty, mode),
+                min,
+                MAX_LEN
+            )
+        };
+        match (ty, mode) {
+            (Ty::Builtin(name), _) => format!("any::<{}>()", builtin(name).unwrap_or("()")),
+            (Ty::Unit, _) => "Just(())".to_string(),
+            (Ty::Named(name), Mode::Nested) => format!("arb_{}(depth - 1)", name),
+            (Ty::Named(name), Mode::Leaf) => format!("arb_{}(0)", name),
+            (Ty::Box(ty), _) => format!("{}.prop_map(Box::new)", self.strategy(ty, mode)),
+            (Ty::Option(_), Mode::Leaf) => "Just(None)".to_string(),
+            (Ty::Option(ty), Mode::Nested) => {
+                format!("proptest::option::of({})", self.strategy(ty, mode))
+            }
+            (Ty::Vec(_), Mode::Leaf) => "Just(vec![])".to_string(),
+            (Ty::Vec(ty), Mode::Nested) => sequence(ty, 0),
+            (Ty::NonEmpty(ty), Mode::Leaf) => format!(
+                "proptest::collection::vec({}, 1..=1)",
+                self.strategy(ty, mode)
+            ),
+            (Ty::NonEmpty(ty), Mode::Nested) => sequence(ty, 1),
+        }
+    }
+}
+
+/// `if depth == 0 { leaf } else { nested }`, or one of them if they agree
+fn branches(leaf: String, nested: String) -> String {
+    if leaf == nested {
+        format!("{}\n", leaf)
+    } else {
+        format!(
+            "if depth == 0 {{\n{}}} else {{\n{}}}\n",
+            indent(&leaf),
+            indent(&nested)
+        )
+    }
+}
+
+/// Fewest named types nested in a value of each type, for the types that
+/// have a finite value
+fn heights(model: &TypeModel) -> HashMap<String, usize> {
+    let mut heights = HashMap::new();
+    loop {
+        let mut changed = false;
+        for (name, def) in model.defs() {
+            let height = match def {
+                TypeDef::Alias(ty) => ty_height(ty, &heights),
+                TypeDef::Struct(fields) => fields_height(fields, &heights),
+                TypeDef::Enum(variants) => variants
+                    .iter()
+                    .filter_map(|v| fields_height(&v.fields, &heights))
+                    .min(),
+            };
+            if let Some(height) = height.map(|h| h + 1) {
+                if heights.get(name).is_none_or(|h| height < *h) {
+                    heights.insert(name.clone(), height);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return heights;
+        }
+    }
+}
+
+fn fields_height(fields: &Fields, heights: &HashMap<String, usize>) -> Option<usize> {
+    let tys: Vec<&Ty> = match fields {
+        Fields::Unit => vec![],
+        Fields::Tuple(tys) => tys.iter().collect(),
+        Fields::Named(fields) => fields.iter().map(|(_, ty)| ty).collect(),
+    };
+    tys.into_iter()
+        .map(|ty| ty_height(ty, heights))
+        .try_fold(0, |max, h| Some(max.max(h?)))
+}
+
+/// Empty sequences and `None` nest no types
+fn ty_height(ty: &Ty, heights: &HashMap<String, usize>) -> Option<usize> {
+    match ty {
+        Ty::Builtin(_) | Ty::Unit | Ty::Vec(_) | Ty::Option(_) => Some(0),
+        Ty::Named(name) => heights.get(name).copied(),
+        Ty::NonEmpty(ty) | Ty::Box(ty) => ty_height(ty, heights),
+    }
+}
+
+/// `(a,)` or `(a, b)`
+fn tuple(items: &[String]) -> String {
+    match items {
+        [item] => format!("({},)", item),
+        items => format!("({})", items.join(", ")),
+    }
+}
+
+fn len(fields: &Fields) -> usize {
+    match fields {
+        Fields::Unit => 0,
+        Fields::Tuple(tys) => tys.len(),
+        Fields::Named(fields) => fields.len(),
+    }
+}
+
+fn skip(nonterminal: &str, reason: &str) -> Skipped {
+    Skipped {
+        nonterminal: nonterminal.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process::Command};
+
+    use crate::{
+        codegen::{self, decoder::tests::run, Config},
+        syntax::fixtures::TempDir,
+        Grammar, Spec,
+    };
+
+    fn spec() -> Spec {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "syntax/instructions.rst",
+            r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 \\
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \production{instruction} & \instr &::=&
+     \BLOCK~\numtype^?~\instr^\ast~\END ~|~
+     \LOOP~\instr^+~\END ~|~
+     \CONST~\numtype~\u64 ~|~
+     \NOP \\
+   \production{expression} & \expr &::=&
+     \instr^\ast~\END \\
+   \production{cycle} & \cycle &::=&
+     \CYCLE~\cycle \\
+   \end{array}
+",
+        );
+        assert!(grammar.errors().is_empty(), "{:?}", grammar.errors());
+        Spec::new(grammar)
+    }
+
+    #[test]
+    fn emit() {
+        let generated = codegen::module_with(&spec(), &Config::default().arbitrary(true));
+        let skipped: Vec<_> = generated.skipped.iter().map(|s| s.to_string()).collect();
+        assert_eq!(skipped, vec!["cycle: arbitrary: has no finite value"]);
+        let source = generated.source;
+        for item in [
+            // the shallowest variants below the depth, `^+` with one element
+            "    if depth == 0 {\n        proptest::strategy::Union::new(vec![\n            (Just(None), Just(vec![])).prop_map(|(v0, v1)| Instr::Block(v0, v1)).boxed(),\n            Just(Instr::Nop).boxed(),\n        ])\n        .boxed()\n    } else {",
+            "(proptest::collection::vec(arb_instr(depth - 1), 1..=4),).prop_map(|(v0,)| Instr::Loop(v0)).boxed(),",
+            "            1 => Instr::Loop((0..u.int_in_range(1..=4)?).map(|_| Ok(arbitrary_instr(u, depth - 1)?)).collect::<arbitrary::Result<Vec<_>>>()?),",
+            "Ok(Limits { lmin: u.arbitrary()?, lmax: if u.arbitrary()? { Some(u.arbitrary()?) } else { None } })",
+            "#[cfg(feature = \"arbitrary\")]\nimpl<'a> arbitrary::Arbitrary<'a> for Numtype {",
+        ] {
+            assert!(source.contains(item), "`{}` missing in\n{}", item, source);
+        }
+        assert!(!source.contains("arbitrary_cycle"));
+        assert!(!codegen::module(&spec()).source.contains("arbitrary"));
+    }
+
+    /// The generators are left out unless the features are enabled
+    #[test]
+    fn compile_without_features() {
+        let generated = codegen::module_with(&spec(), &Config::default().arbitrary(true));
+        let output = run(
+            "arbitrary",
+            &generated.source,
+            "mod generated;\n\nfn main() {\n    println!(\"{:?}\", generated::Instr::Nop);\n}\n",
+        );
+        assert_eq!(output, "Nop\n");
+    }
+
+    /// The parts of the `arbitrary` API the generators use. Choices are read
+    /// from the bytes, zero once they run out.
+    const ARBITRARY: &str = r#"
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub struct Error;
+
+pub struct Unstructured<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Unstructured<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn byte(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((byte, rest)) => {
+                self.data = rest;
+                *byte
+            }
+            None => 0,
+        }
+    }
+
+    pub fn int_in_range(&mut self, range: std::ops::RangeInclusive<u32>) -> Result<u32> {
+        let span = range.end() - range.start() + 1;
+        Ok(range.start() + u32::from(self.byte()) % span)
+    }
+
+    pub fn choose_index(&mut self, len: usize) -> Result<usize> {
+        match len {
+            0 => Err(Error),
+            len => Ok(usize::from(self.byte()) % len),
+        }
+    }
+
+    pub fn arbitrary<A: Arbitrary<'a>>(&mut self) -> Result<A> {
+        A::arbitrary(self)
+    }
+}
+
+pub trait Arbitrary<'a>: Sized {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self>;
+}
+
+macro_rules! numbers {
+    ($($ty:ty)*) => {$(
+        impl<'a> Arbitrary<'a> for $ty {
+            fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+                Ok(u.byte() as $ty)
+            }
+        }
+    )*};
+}
+
+numbers!(u8 u32 u64 i32 i64 f32 f64);
+
+impl<'a> Arbitrary<'a> for bool {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(u.byte() % 2 == 1)
+    }
+}
+
+impl<'a> Arbitrary<'a> for String {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(char::from(b'a' + u.byte() % 26).to_string())
+    }
+}
+"#;
+
+    /// The parts of the `proptest` API the strategies use, generating
+    /// values without shrinking
+    const PROPTEST: &str = r#"
+pub mod test_runner {
+    pub struct TestRunner(u64);
+
+    impl Default for TestRunner {
+        fn default() -> Self {
+            TestRunner(0x2545_f491_4f6c_dd1d)
+        }
+    }
+
+    impl TestRunner {
+        pub(crate) fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+}
+
+pub mod strategy {
+    use crate::test_runner::TestRunner;
+
+    pub trait ValueTree {
+        type Value;
+        fn current(&self) -> Self::Value;
+    }
+
+    pub struct Tree<T>(T);
+
+    impl<T: Clone> ValueTree for Tree<T> {
+        type Value = T;
+        fn current(&self) -> T {
+            self.0.clone()
+        }
+    }
+
+    pub trait Strategy {
+        type Value;
+
+        fn generate(&self, runner: &mut TestRunner) -> Self::Value;
+
+        fn new_tree(&self, runner: &mut TestRunner) -> Result<Tree<Self::Value>, String> {
+            Ok(Tree(self.generate(runner)))
+        }
+
+        fn boxed(self) -> BoxedStrategy<Self::Value>
+        where
+            Self: Sized + 'static,
+        {
+            BoxedStrategy(Box::new(self))
+        }
+
+        fn prop_map<O, F: Fn(Self::Value) -> O>(self, f: F) -> Map<Self, F>
+        where
+            Self: Sized,
+        {
+            Map(self, f)
+        }
+    }
+
+    pub struct BoxedStrategy<T>(Box<dyn Strategy<Value = T>>);
+
+    impl<T> Strategy for BoxedStrategy<T> {
+        type Value = T;
+        fn generate(&self, runner: &mut TestRunner) -> T {
+            self.0.generate(runner)
+        }
+    }
+
+    pub struct Map<S, F>(S, F);
+
+    impl<S: Strategy, O, F: Fn(S::Value) -> O> Strategy for Map<S, F> {
+        type Value = O;
+        fn generate(&self, runner: &mut TestRunner) -> O {
+            (self.1)(self.0.generate(runner))
+        }
+    }
+
+    pub struct Just<T>(pub T);
+
+    impl<T: Clone> Strategy for Just<T> {
+        type Value = T;
+        fn generate(&self, _: &mut TestRunner) -> T {
+            self.0.clone()
+        }
+    }
+
+    pub struct Union<S>(Vec<S>);
+
+    impl<S: Strategy> Union<S> {
+        pub fn new(options: impl IntoIterator<Item = S>) -> Self {
+            Union(options.into_iter().collect())
+        }
+    }
+
+    impl<S: Strategy> Strategy for Union<S> {
+        type Value = S::Value;
+        fn generate(&self, runner: &mut TestRunner) -> S::Value {
+            self.0[runner.below(self.0.len())].generate(runner)
+        }
+    }
+
+    macro_rules! tuples {
+        ($(($($s:ident $i:tt),*))*) => {$(
+            impl<$($s: Strategy),*> Strategy for ($($s,)*) {
+                type Value = ($($s::Value,)*);
+                fn generate(&self, runner: &mut TestRunner) -> Self::Value {
+                    ($(self.$i.generate(runner),)*)
+                }
+            }
+        )*};
+    }
+
+    tuples! {
+        (A 0)
+        (A 0, B 1)
+        (A 0, B 1, C 2)
+        (A 0, B 1, C 2, D 3)
+        (A 0, B 1, C 2, D 3, E 4)
+        (A 0, B 1, C 2, D 3, E 4, F 5)
+        (A 0, B 1, C 2, D 3, E 4, F 5, G 6)
+        (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7)
+        (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8)
+        (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9)
+        (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10)
+        (A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11)
+    }
+}
+
+pub mod arbitrary {
+    use crate::{strategy::Strategy, test_runner::TestRunner};
+
+    pub trait Arbitrary {
+        fn generate(runner: &mut TestRunner) -> Self;
+    }
+
+    macro_rules! numbers {
+        ($($ty:ty)*) => {$(
+            impl Arbitrary for $ty {
+                fn generate(runner: &mut TestRunner) -> Self {
+                    runner.below(256) as $ty
+                }
+            }
+        )*};
+    }
+
+    numbers!(u8 u32 u64 i32 i64 f32 f64);
+
+    impl Arbitrary for () {
+        fn generate(_: &mut TestRunner) -> Self {}
+    }
+
+    impl Arbitrary for String {
+        fn generate(runner: &mut TestRunner) -> Self {
+            char::from(b'a' + runner.below(26) as u8).to_string()
+        }
+    }
+
+    pub struct Any<T>(std::marker::PhantomData<T>);
+
+    impl<T: Arbitrary> Strategy for Any<T> {
+        type Value = T;
+        fn generate(&self, runner: &mut TestRunner) -> T {
+            T::generate(runner)
+        }
+    }
+
+    pub fn any<T: Arbitrary>() -> Any<T> {
+        Any(std::marker::PhantomData)
+    }
+}
+
+pub mod collection {
+    use crate::{strategy::Strategy, test_runner::TestRunner};
+
+    pub struct VecStrategy<S>(S, std::ops::RangeInclusive<usize>);
+
+    impl<S: Strategy> Strategy for VecStrategy<S> {
+        type Value = Vec<S::Value>;
+        fn generate(&self, runner: &mut TestRunner) -> Self::Value {
+            let (min, max) = (*self.1.start(), *self.1.end());
+            let len = min + runner.below(max - min + 1);
+            (0..len).map(|_| self.0.generate(runner)).collect()
+        }
+    }
+
+    pub fn vec<S: Strategy>(element: S, size: std::ops::RangeInclusive<usize>) -> VecStrategy<S> {
+        VecStrategy(element, size)
+    }
+}
+
+pub mod option {
+    use crate::{strategy::Strategy, test_runner::TestRunner};
+
+    pub struct OptionStrategy<S>(S);
+
+    impl<S: Strategy> Strategy for OptionStrategy<S> {
+        type Value = Option<S::Value>;
+        fn generate(&self, runner: &mut TestRunner) -> Self::Value {
+            match runner.below(2) {
+                0 => None,
+                _ => Some(self.0.generate(runner)),
+            }
+        }
+    }
+
+    pub fn of<S: Strategy>(element: S) -> OptionStrategy<S> {
+        OptionStrategy(element)
+    }
+}
+
+pub mod prelude {
+    pub use crate::{
+        arbitrary::any,
+        strategy::{BoxedStrategy, Just, Strategy},
+    };
+}
+"#;
+
+    /// Compile the generated module with the features enabled, against the
+    /// stubs of `arbitrary` and `proptest`, and run `main`
+    fn run_with_features(source: &str, main: &str) -> String {
+        let dir = TempDir::new("arbitrary-features");
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let mut externs = vec![];
+        for (name, stub) in [("arbitrary", ARBITRARY), ("proptest", PROPTEST)] {
+            let file = dir.join(format!("{}.rs", name));
+            fs::write(&file, stub).unwrap();
+            let status = Command::new(&rustc)
+                .args([
+                    "--edition",
+                    "2021",
+                    "--crate-type",
+                    "lib",
+                    "--crate-name",
+                    name,
+                ])
+                .arg("--out-dir")
+                .arg(&*dir)
+                .arg(&file)
+                .status()
+                .unwrap();
+            assert!(status.success(), "stub of {} does not compile", name);
+            let lib = dir.join(format!("lib{}.rlib", name));
+            externs.push(format!("{}={}", name, lib.display()));
+        }
+        fs::write(dir.join("generated.rs"), source).unwrap();
+        fs::write(dir.join("main.rs"), main).unwrap();
+        let mut command = Command::new(&rustc);
+        command.args(["--edition", "2021"]);
+        for feature in ["arbitrary", "proptest"] {
+            command.arg("--cfg").arg(format!("feature=\"{}\"", feature));
+        }
+        for extern_ in &externs {
+            command.arg("--extern").arg(extern_);
+        }
+        let binary = dir.join("arbitrary");
+        let status = command
+            .arg("-o")
+            .arg(&binary)
+            .arg(dir.join("main.rs"))
+            .status()
+            .unwrap();
+        assert!(status.success(), "generated generators do not compile");
+        let output = Command::new(binary).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// The generators type-check with the features enabled, values stay
+    /// finite at the default depth, and depth zero only takes the shallowest
+    /// values
+    #[test]
+    fn compile_with_features() {
+        let generated = codegen::module_with(&spec(), &Config::default().arbitrary(true));
+        let output = run_with_features(
+            &generated.source,
+            r#"
+mod generated;
+use arbitrary::{Arbitrary, Unstructured};
+use generated::*;
+use proptest::{strategy::{Strategy, ValueTree}, test_runner::TestRunner};
+
+fn main() {
+    for seed in 0u8..64 {
+        let data: Vec<u8> = (0..256).map(|i| (i as u8).wrapping_mul(seed).wrapping_add(seed)).collect();
+        let mut u = Unstructured::new(&data);
+        if let Instr::Loop(instrs) = Instr::arbitrary(&mut u).unwrap() {
+            assert!(!instrs.is_empty());
+        }
+        arbitrary_expr(&mut u, 4).unwrap();
+        Limits::arbitrary(&mut u).unwrap();
+        let mut u = Unstructured::new(&data);
+        println!("{:?}", arbitrary_instr(&mut u, 0).unwrap());
+    }
+    println!("{:?}", Instr::arbitrary(&mut Unstructured::new(&[])).unwrap());
+
+    let mut runner = TestRunner::default();
+    let (nested, leaf) = (arb_expr(4), arb_instr(0));
+    for _ in 0..64 {
+        nested.new_tree(&mut runner).unwrap().current();
+        println!("{:?}", leaf.new_tree(&mut runner).unwrap().current());
+    }
+}
+"#,
+        );
+        let mut lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 129);
+        assert_eq!(lines.remove(64), "Block(None, [])");
+        lines.sort();
+        lines.dedup();
+        assert_eq!(lines, vec!["Block(None, [])", "Nop"]);
+    }
+}
@@ -0,0 +1,1057 @@
+//! Decoder of the binary format, read off the `\B...` productions.
+//!
+//! Every binary nonterminal `\Bx` whose abstract counterpart `\x` has a
+//! type becomes a function `decode_x`. Alternatives are told apart by their
+//! leading `\hex{..}` bytes, by the bytes their first nonterminal starts
+//! with, or by a literal `\Bu32` following a shared byte, as in
+//! `\hex{FC}~~12{:}\Bu32`. The attribute right of `\Rightarrow` is
+//! translated according to the type it has to build. A side condition on the
+//! numbers read, like `(\iff x \geq 0)`, is checked before, and a production
+//! with any other side condition is skipped.
+//!
+//! The sections of a module, `\Bsection_N(\B{B})`, are read by
+//! `Reader::section`, which yields nothing when the next section has another
+//! id, so that `\X{ft}^\ast{:\,}\Bsection_1(\Bvec(\Bfunctype))` decodes an
+//! empty vector when the module has no type section.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+};
+
+use crate::{
+    codegen::{
+        condition, fixed_point, format_of, indent, key, literal, local, section, section_ty, short,
+        translate,
+        types::{builtin, rust_ty, Ty, TypeModel},
+        Bindings, Skipped,
+    },
+    parser::SeqKind,
+    spec::Spec,
+    syntax::{
+        symbol::{SNonterm, Symbol},
+        RhsElem,
+    },
+};
+
+/// Reader, errors and decoders of the builtin nonterminals
+const PRELUDE: &str = r#"#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    /// Input ended at the offset
+    Eof(usize),
+    /// Byte that no alternative of the production starts with
+    Unexpected {
+        offset: usize,
+        byte: u8,
+        production: &'static str,
+    },
+    /// `\Bu32` that no alternative of the production expects
+    UnknownCode {
+        offset: usize,
+        code: u32,
+        production: &'static str,
+    },
+    /// LEB128 number that does not fit its type
+    Overflow(usize),
+    InvalidUtf8(usize),
+    /// Input left after the decoded value
+    Trailing(usize),
+    /// Section at the offset whose content does not match its size
+    SectionSize(usize),
+    /// Alternative read from the offset whose values do not meet its side
+    /// condition, e.g. a negative type index
+    Condition {
+        offset: usize,
+        production: &'static str,
+    },
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offset == self.bytes.len()
+    }
+
+    pub fn peek(&self) -> Result<u8, DecodeError> {
+        self.bytes.get(self.offset).copied().ok_or(DecodeError::Eof(self.offset))
+    }
+
+    pub fn byte(&mut self) -> Result<u8, DecodeError> {
+        let byte = self.peek()?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    pub fn bytes(&mut self, n: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self
+            .offset
+            .checked_add(n)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(DecodeError::Eof(self.bytes.len()))?;
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    pub fn expect(&mut self, byte: u8, production: &'static str) -> Result<(), DecodeError> {
+        let offset = self.offset;
+        match self.byte()? {
+            b if b == byte => Ok(()),
+            byte => Err(DecodeError::Unexpected { offset, byte, production }),
+        }
+    }
+
+    /// Unsigned LEB128 number of at most `bits` bits
+    pub fn unsigned(&mut self, bits: u32) -> Result<u64, DecodeError> {
+        let start = self.offset;
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            let value = u64::from(byte & 0x7F);
+            if shift >= bits || (bits - shift < 7 && value >> (bits - shift) != 0) {
+                return Err(DecodeError::Overflow(start));
+            }
+            result |= value << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// Signed LEB128 number of at most `bits` bits
+    pub fn signed(&mut self, bits: u32) -> Result<i64, DecodeError> {
+        let start = self.offset;
+        let mut result = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            let value = byte & 0x7F;
+            if shift >= bits {
+                return Err(DecodeError::Overflow(start));
+            }
+            let left = bits - shift;
+            if left < 7 {
+                // the unused bits have to extend the sign
+                let mask = (0x7F >> (left - 1)) << (left - 1);
+                if value & mask != 0 && value & mask != mask {
+                    return Err(DecodeError::Overflow(start));
+                }
+            }
+            result |= i64::from(value) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+                return Ok(result);
+            }
+        }
+    }
+
+    pub fn vec<T>(
+        &mut self,
+        decode: impl Fn(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<Vec<T>, DecodeError> {
+        let len = decode_u32(self)?;
+        (0..len).map(|_| decode(self)).collect()
+    }
+
+    /// Section `id` with its content, or nothing if the next section, if
+    /// any, has another id
+    pub fn section<T>(
+        &mut self,
+        id: u8,
+        decode: impl FnOnce(&mut Self) -> Result<T, DecodeError>,
+    ) -> Result<Option<T>, DecodeError> {
+        if self.is_empty() || self.peek()? != id {
+            return Ok(None);
+        }
+        let start = self.offset;
+        self.byte()?;
+        let size = decode_u32(self)? as usize;
+        let end = self
+            .offset
+            .checked_add(size)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(DecodeError::Eof(self.bytes.len()))?;
+        let mut content = Reader {
+            bytes: &self.bytes[..end],
+            offset: self.offset,
+        };
+        let value = decode(&mut content).map_err(|e| match e {
+            DecodeError::Eof(_) => DecodeError::SectionSize(start),
+            e => e,
+        })?;
+        if content.offset != end {
+            return Err(DecodeError::SectionSize(start));
+        }
+        self.offset = end;
+        Ok(Some(value))
+    }
+}
+
+/// Decode a whole input, failing on trailing bytes
+pub fn decode_all<T>(
+    bytes: &[u8],
+    decode: impl Fn(&mut Reader<'_>) -> Result<T, DecodeError>,
+) -> Result<T, DecodeError> {
+    let mut r = Reader::new(bytes);
+    let value = decode(&mut r)?;
+    if r.is_empty() {
+        Ok(value)
+    } else {
+        Err(DecodeError::Trailing(r.offset()))
+    }
+}
+
+fn expect_u32(r: &mut Reader<'_>, code: u32, production: &'static str) -> Result<(), DecodeError> {
+    let offset = r.offset();
+    match decode_u32(r)? {
+        n if n == code => Ok(()),
+        code => Err(DecodeError::UnknownCode { offset, code, production }),
+    }
+}
+
+pub fn decode_byte(r: &mut Reader<'_>) -> Result<u8, DecodeError> {
+    r.byte()
+}
+
+pub fn decode_u32(r: &mut Reader<'_>) -> Result<u32, DecodeError> {
+    r.unsigned(32).map(|n| n as u32)
+}
+
+pub fn decode_u64(r: &mut Reader<'_>) -> Result<u64, DecodeError> {
+    r.unsigned(64)
+}
+
+pub fn decode_s33(r: &mut Reader<'_>) -> Result<i64, DecodeError> {
+    r.signed(33)
+}
+
+pub fn decode_i32(r: &mut Reader<'_>) -> Result<i32, DecodeError> {
+    r.signed(32).map(|n| n as i32)
+}
+
+pub fn decode_i64(r: &mut Reader<'_>) -> Result<i64, DecodeError> {
+    r.signed(64)
+}
+
+pub fn decode_f32(r: &mut Reader<'_>) -> Result<f32, DecodeError> {
+    let bytes = r.bytes(4)?;
+    Ok(f32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+pub fn decode_f64(r: &mut Reader<'_>) -> Result<f64, DecodeError> {
+    let bytes = r.bytes(8)?;
+    Ok(f64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+pub fn decode_name(r: &mut Reader<'_>) -> Result<String, DecodeError> {
+    let len = decode_u32(r)? as usize;
+    let offset = r.offset();
+    let bytes = r.bytes(len)?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidUtf8(offset))
+}
+
+"#;
+
+/// Decoder functions of all binary nonterminals that could be translated
+#[derive(Debug)]
+pub struct Decoders {
+    functions: Vec<String>,
+    skipped: Vec<Skipped>,
+}
+
+struct Context<'s> {
+    spec: &'s Spec,
+    model: &'s TypeModel,
+    /// Binary nonterminals that get a decoder, with the type they decode
+    decoders: &'s HashMap<String, Ty>,
+}
+
+impl Decoders {
+    pub fn new(spec: &Spec, model: &TypeModel, targets: &[(String, Ty)]) -> Self {
+        let (functions, skipped) = fixed_point(targets, "decoder", |decoders, nonterminal, ty| {
+            let context = Context {
+                spec,
+                model,
+                decoders,
+            };
+            context.function(nonterminal, ty)
+        });
+        Self { functions, skipped }
+    }
+
+    pub fn skipped(&self) -> &[Skipped] {
+        &self.skipped
+    }
+
+    /// Rust items of the reader and all decoders
+    pub fn emit(&self) -> String {
+        let mut out = PRELUDE.to_string();
+        for function in &self.functions {
+            out.push_str(function);
+        }
+        out
+    }
+}
+
+impl<'s> Context<'s> {
+    fn function(&self, nonterminal: &str, ty: &Ty) -> Result<String, String> {
+        let name = short(nonterminal);
+        let alternatives = self.spec.alternatives_of(nonterminal);
+        let entries = alternatives.iter().map(|a| (a.elem, 0)).collect();
+        let body = self.dispatch(name, entries, ty)?;
+        Ok(format!(
+            "pub fn decode_{}(r: &mut Reader<'_>) -> Result<{}, DecodeError> {{\n{}}}\n\n",
+            name,
+            rust_ty(ty),
+            indent(&body)
+        ))
+    }
+
+    /// Choose among alternatives that agree on their symbols before `pos`
+    fn dispatch(
+        &self,
+        production: &str,
+        entries: Vec<(&RhsElem, usize)>,
+        ty: &Ty,
+    ) -> Result<String, String> {
+        if let [(elem, pos)] = entries.as_slice() {
+            return self.sequence(production, elem, *pos, ty);
+        }
+        let symbols: Vec<_> = entries
+            .iter()
+            .map(|(e, pos)| e.symbols().get(*pos))
+            .collect();
+        if symbols.iter().any(Option::is_none) {
+            return Err("alternatives are prefixes of each other".to_string());
+        }
+
+        let mut out = String::new();
+        if symbols.iter().all(|s| s.and_then(literal).is_some()) {
+            let mut arms: BTreeMap<u64, Vec<_>> = BTreeMap::new();
+            for (symbol, (elem, pos)) in symbols.iter().zip(entries) {
+                let code = symbol.and_then(literal).unwrap_or_default();
+                arms.entry(code).or_default().push((elem, pos + 1));
+            }
+            out.push_str("let offset = r.offset();\nmatch decode_u32(r)? {\n");
+            for (code, entries) in arms {
+                let arm = self.dispatch(production, entries, ty)?;
+                writeln!(out, "    {} => {{\n{}    }}", code, indent(&indent(&arm))).unwrap();
+            }
+            writeln!(
+                out,
+                "    code => Err(DecodeError::UnknownCode {{ offset, code, production: {:?} }}),\n}}",
+                production
+            )
+            .unwrap();
+            return Ok(out);
+        }
+
+        let mut hex: BTreeMap<u8, Vec<_>> = BTreeMap::new();
+        let mut firsts = vec![];
+        let mut default = None;
+        for (symbol, (elem, pos)) in symbols.into_iter().flatten().zip(entries) {
+            if let Symbol::SHex(byte) = symbol {
+                hex.entry(*byte).or_default().push((elem, pos + 1));
+            } else if let Some(first) = self.first(symbol, &mut vec![]) {
+                firsts.push((first, elem, pos));
+            } else if default.replace((elem, pos)).is_some() {
+                return Err("alternatives cannot be told apart by their first byte".to_string());
+            }
+        }
+        let mut seen: BTreeSet<u8> = hex.keys().copied().collect();
+        for (first, _, _) in &firsts {
+            if !seen.is_disjoint(first) {
+                return Err("alternatives start with the same byte".to_string());
+            }
+            seen.extend(first);
+        }
+
+        out.push_str("match r.peek()? {\n");
+        for (byte, entries) in hex {
+            let arm = self.dispatch(production, entries, ty)?;
+            writeln!(
+                out,
+                "    0x{:02X} => {{\n        r.byte()?;\n{}    }}",
+                byte,
+                indent(&indent(&arm))
+            )
+            .unwrap();
+        }
+        for (first, elem, pos) in firsts {
+            let arm = self.sequence(production, elem, pos, ty)?;
+            let bytes: Vec<_> = first.iter().map(|b| format!("0x{:02X}", b)).collect();
+            writeln!(
+                out,
+                "    {} => {{\n{}    }}",
+                bytes.join(" | "),
+                indent(&indent(&arm))
+            )
+            .unwrap();
+        }
+        match default {
+            Some((elem, pos)) => {
+                let arm = self.sequence(production, elem, pos, ty)?;
+                writeln!(out, "    _ => {{\n{}    }}", indent(&indent(&arm))).unwrap();
+            }
+            None => writeln!(
+                out,
+                "    byte => Err(DecodeError::Unexpected {{ offset: r.offset(), byte, production: {:?} }}),",
+                production
+            )
+            .unwrap(),
+        }
+        out.push_str("}\n");
+        Ok(out)
+    }
+
+    /// Decode the symbols of an alternative from `pos` on and build its
+    /// attribute
+    fn sequence(
+        &self,
+        production: &str,
+        elem: &RhsElem,
+        pos: usize,
+        ty: &Ty,
+    ) -> Result<String, String> {
+        let mut out = String::new();
+        let mut bindings = Bindings::new();
+        let symbols = &elem.symbols()[pos..];
+        for (i, symbol) in symbols.iter().enumerate() {
+            match symbol {
+                Symbol::SHex(byte) => {
+                    writeln!(out, "r.expect(0x{:02X}, {:?})?;", byte, production).unwrap()
+                }
+                Symbol::SBind(bind) => match literal(symbol) {
+                    Some(code) => {
+                        writeln!(out, "expect_u32(r, {}, {:?})?;", code, production).unwrap()
+                    }
+                    None => {
+                        let (mut call, mut ty) = self.call(bind.symbol())?;
+                        if let (Some(_), Ty::Option(content)) = (section(bind.symbol()), &ty) {
+                            let bound = section_ty(bind.var(), (**content).clone());
+                            if !matches!(bound, Ty::Option(_)) {
+                                call.push_str(".unwrap_or_default()");
+                            }
+                            ty = bound;
+                        }
+                        let local = local(bind.var());
+                        writeln!(out, "let {} = {};", local, call).unwrap();
+                        bindings.insert(key(bind.var()), (local, ty));
+                    }
+                },
+                // `\Bcustomsec^\ast` between the sections, or the bytes
+                // that take up the rest of a custom section
+                Symbol::SNonterm(nt) if nt.seq_kind() == Some(&SeqKind::ManyPossibleEmpty) => {
+                    let (decoder, _) = self.decoder(nt)?;
+                    let more = match self.first_of(&nt.name, &mut vec![]) {
+                        Some(first) => {
+                            let bytes: Vec<_> =
+                                first.iter().map(|b| format!("0x{:02X}", b)).collect();
+                            format!(
+                                "!r.is_empty() && matches!(r.peek()?, {})",
+                                bytes.join(" | ")
+                            )
+                        }
+                        None if i + 1 == symbols.len() => "!r.is_empty()".to_string(),
+                        None => return Err(format!("where `{}` ends is not known", symbol)),
+                    };
+                    writeln!(out, "while {} {{\n    {}(r)?;\n}}", more, decoder).unwrap();
+                }
+                Symbol::SNonterm(_) | Symbol::SVec(_) => {
+                    let (call, _) = self.call(symbol)?;
+                    writeln!(out, "{};", call).unwrap();
+                }
+                Symbol::SGroup(group) => {
+                    // `(\X{in}{:}\Binstr)^\ast~\hex{0B}` repeats until the byte
+                    let (Some(seq_kind), [Symbol::SBind(bind)], Some(Symbol::SHex(end))) =
+                        (group.seq_kind(), group.symbols(), symbols.get(i + 1))
+                    else {
+                        return Err(format!("`{}` is not supported", symbol));
+                    };
+                    if seq_kind.to_string() != r"^\ast" {
+                        return Err(format!("`{}` is not supported", symbol));
+                    }
+                    let (call, ty) = self.call(bind.symbol())?;
+                    let local = local(bind.var());
+                    writeln!(
+                        out,
+                        "let mut {0} = Vec::new();\nwhile r.peek()? != 0x{1:02X} {{\n    {0}.push({2});\n}}",
+                        local, end, call
+                    )
+                    .unwrap();
+                    bindings.insert(key(bind.var()), (local, Ty::Vec(Box::new(ty))));
+                }
+                symbol => return Err(format!("`{}` is not supported", symbol)),
+            }
+        }
+
+        if let Some(cond) = elem.cond() {
+            writeln!(
+                out,
+                "if !({}) {{\n    return Err(DecodeError::Condition {{ offset: start, production: {:?} }});\n}}",
+                condition(cond, &bindings)?,
+                production
+            )
+            .unwrap();
+        }
+        let overflow = "DecodeError::Overflow(start)";
+        let value = match elem.action() {
+            Some(action) => translate(self.model, action, ty, &bindings, overflow)?,
+            None if *ty == Ty::Unit => "()".to_string(),
+            None => return Err(format!("alternative `{}` has no attribute", elem)),
+        };
+        if elem.cond().is_some() || value.contains(overflow) {
+            out.insert_str(0, "let start = r.offset();\n");
+        }
+        writeln!(out, "Ok({})", value).unwrap();
+        Ok(out)
+    }
+
+    /// Call decoding a nonterminal, e.g. `decode_u32(r)?`, and its type
+    fn call(&self, symbol: &Symbol) -> Result<(String, Ty), String> {
+        if let Some((id, content)) = section(symbol) {
+            let (call, ty) = self.call(content)?;
+            return Ok((
+                format!("r.section({}, |r| Ok({}))?", id, call),
+                Ty::Option(Box::new(ty)),
+            ));
+        }
+        match symbol {
+            Symbol::SNonterm(nt) if nt.seq_kind().is_none() && nt.args().is_empty() => {
+                let (decoder, ty) = self.decoder(nt)?;
+                Ok((format!("{}(r)?", decoder), ty))
+            }
+            Symbol::SVec(vec) if vec.head() == "Bvec" && vec.over().seq_kind().is_none() => {
+                let (decoder, ty) = self.decoder(vec.over())?;
+                Ok((format!("r.vec({})?", decoder), Ty::Vec(Box::new(ty))))
+            }
+            symbol => Err(format!("`{}` is not supported", symbol)),
+        }
+    }
+
+    fn decoder(&self, nt: &SNonterm) -> Result<(String, Ty), String> {
+        let name = short(&nt.name);
+        if let Some(ty) = self.decoders.get(&nt.name) {
+            Ok((format!("decode_{}", name), ty.clone()))
+        } else if format_of(&nt.name).is_some() && builtin(name).is_some() {
+            Ok((format!("decode_{}", name), Ty::Builtin(name.to_string())))
+        } else {
+            Err(format!("`{}` has no decoder", nt))
+        }
+    }
+
+    /// Bytes a symbol can start with, if they are known
+    fn first(&self, symbol: &Symbol, visiting: &mut Vec<String>) -> Option<BTreeSet<u8>> {
+        if let Some((id, _)) = section(symbol) {
+            return Some(BTreeSet::from([id]));
+        }
+        match symbol {
+            Symbol::SHex(byte) => Some(BTreeSet::from([*byte])),
+            Symbol::SBind(bind) => self.first(bind.symbol(), visiting),
+            Symbol::SNonterm(nt) if nt.seq_kind().is_none() => self.first_of(&nt.name, visiting),
+            _ => None,
+        }
+    }
+
+    /// Bytes any alternative of a nonterminal starts with
+    fn first_of(&self, nonterminal: &str, visiting: &mut Vec<String>) -> Option<BTreeSet<u8>> {
+        let alternatives = self.spec.alternatives_of(nonterminal);
+        if alternatives.is_empty() || visiting.iter().any(|v| v == nonterminal) {
+            return None;
+        }
+        visiting.push(nonterminal.to_string());
+        let first = alternatives
+            .iter()
+            .try_fold(BTreeSet::new(), |mut first, a| {
+                first.extend(self.first(a.elem.symbols().first()?, visiting)?);
+                Some(first)
+            });
+        visiting.pop();
+        first
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::{collections::BTreeMap, env, fmt::Write, fs, process::Command};
+
+    use crate::{
+        codegen::{self, section, short},
+        coverage::wast,
+        syntax::{
+            fixtures::{self, TempDir},
+            symbol::Symbol,
+        },
+        Grammar, Spec,
+    };
+
+    const TYPES: &str = r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 \\
+   \production{reference type} & \reftype &::=&
+     \FUNCREF ~|~ \EXTERNREF \\
+   \production{value type} & \valtype &::=&
+     \numtype ~|~ \reftype \\
+   \production{result type} & \resulttype &::=&
+     [\vec(\valtype)] \\
+   \production{function type} & \functype &::=&
+     \resulttype \to \resulttype \\
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \production{table type} & \tabletype &::=&
+     \limits~\reftype \\
+   \production{block type} & \blocktype &::=&
+     \typeidx ~|~ \valtype^? \\
+   \production{type index} & \typeidx &::=& \u32 \\
+   \production{instruction} & \instr &::=&
+     \NOP ~|~
+     \BLOCK~\blocktype~\instr^\ast~\END \\
+   \production{instruction} & \instr &::=&
+     \dots ~|~
+     \MEMORYFILL ~|~
+     \TABLECOPY~\typeidx~\typeidx \\
+   \production{expression} & \expr &::=&
+     \instr^\ast~\END \\
+   \end{array}
+";
+
+    const BINARY_TYPES: &str = r"
+.. math::
+   \begin{array}{llclll@{\qquad\qquad}l}
+   \production{number type} & \Bnumtype &::=&
+     \hex{7F} &\Rightarrow& \I32 \\ &&|&
+     \hex{7E} &\Rightarrow& \I64 \\
+   \production{reference type} & \Breftype &::=&
+     \hex{70} &\Rightarrow& \FUNCREF \\ &&|&
+     \hex{6F} &\Rightarrow& \EXTERNREF \\
+   \production{value type} & \Bvaltype &::=&
+     t{:}\Bnumtype &\Rightarrow& t \\ &&|&
+     t{:}\Breftype &\Rightarrow& t \\
+   \production{result type} & \Bresulttype &::=&
+     t^\ast{:\,}\Bvec(\Bvaltype) &\Rightarrow& [t^\ast] \\
+   \production{function type} & \Bfunctype &::=&
+     \hex{60}~~\X{rt}_1{:\,}\Bresulttype~~\X{rt}_2{:\,}\Bresulttype
+       &\Rightarrow& \X{rt}_1 \to \X{rt}_2 \\
+   \production{limits} & \Blimits &::=&
+     \hex{00}~~n{:}\Bu32 &\Rightarrow& \{ \LMIN~n, \LMAX~\epsilon \} \\ &&|&
+     \hex{01}~~n{:}\Bu32~~m{:}\Bu32 &\Rightarrow& \{ \LMIN~n, \LMAX~m \} \\
+   \production{table type} & \Btabletype &::=&
+     \X{et}{:}\Breftype~~\X{lim}{:}\Blimits &\Rightarrow& \X{lim}~\X{et} \\
+   \production{type index} & \Btypeidx &::=& x{:}\Bu32 &\Rightarrow& x \\
+   \production{magic} & \Bmagic &::=& \hex{00}~\hex{61}~\hex{73}~\hex{6D} \\
+   \production{byte vector} & \Bbytes &::=& b^\ast{:}\Bvec(\Bbyte) &\Rightarrow& b^\ast \\
+   \end{array}
+
+.. math::
+   \begin{array}{llclll}
+   \production{instruction} & \Binstr &::=&
+     \hex{01} &\Rightarrow& \NOP \\ &&|&
+     \hex{02}~~\X{bt}{:}\Bblocktype~~(\X{in}{:}\Binstr)^\ast~~\hex{0B}
+       &\Rightarrow& \BLOCK~\X{bt}~\X{in}^\ast~\END \\ &&|&
+     \hex{FC}~~11{:}\Bu32~~\hex{00} &\Rightarrow& \MEMORYFILL \\ &&|&
+     \hex{FC}~~14{:}\Bu32~~x{:}\Btypeidx~~y{:}\Btypeidx
+       &\Rightarrow& \TABLECOPY~x~y \\
+   \production{block type} & \Bblocktype &::=&
+     \hex{40} &\Rightarrow& \epsilon \\ &&|&
+     t{:}\Bvaltype &\Rightarrow& t \\ &&|&
+     x{:}\Bs33 &\Rightarrow& x \qquad (\iff x \geq 0) \\
+   \production{expression} & \Bexpr &::=&
+     (\X{in}{:}\Binstr)^\ast~~\hex{0B} &\Rightarrow& \X{in}^\ast~\END \\
+   \end{array}
+";
+
+    const MODULES: &str = r"
+.. math::
+   \begin{array}{llll}
+   \production{function index} & \funcidx &::=& \u32 \\
+   \production{start function} & \start &::=& \{ \SFUNC~\funcidx \} \\
+   \production{module} & \module &::=&
+     \{ \MTYPES~\vec(\functype), \MSTART~\start^? \} \\
+   \end{array}
+";
+
+    const BINARY_MODULES: &str = r"
+.. math::
+   \begin{array}{llclll}
+   \production{section} & \Bsection_N(\B{B}) &::=&
+     N{:}\Bbyte~~\X{size}{:}\Bu32~~\X{cont}{:}\B{B}
+       &\Rightarrow& \X{cont} & (\iff \X{size} = ||\B{B}||) \\ &&|&
+     \epsilon &\Rightarrow& \epsilon \\
+   \production{custom section} & \Bcustomsec &::=&
+     \Bsection_0(\Bcustom) \\
+   \production{custom data} & \Bcustom &::=&
+     \Bname~~\Bbyte^\ast \\
+   \production{type section} & \Btypesec &::=&
+     \X{ft}^\ast{:\,}\Bsection_1(\Bvec(\Bfunctype)) &\Rightarrow& \X{ft}^\ast \\
+   \production{start section} & \Bstartsec &::=&
+     \X{st}^?{:\,}\Bsection_8(\Bstart) &\Rightarrow& \X{st}^? \\
+   \production{start function} & \Bstart &::=&
+     x{:}\Bfuncidx &\Rightarrow& \{ \SFUNC~x \} \\
+   \production{function index} & \Bfuncidx &::=& x{:}\Bu32 &\Rightarrow& x \\
+   \production{version} & \Bversion &::=& \hex{01}~\hex{00}~\hex{00}~\hex{00} \\
+   \end{array}
+
+.. math::
+   \begin{array}{llcllll}
+   \production{module} & \Bmodule &::=&
+     \Bmagic~~\Bversion \\ &&&
+     \Bcustomsec^\ast \\ &&&
+     \X{ft}^\ast{:\,}\Btypesec \\ &&&
+     \Bcustomsec^\ast \\ &&&
+     \X{st}^?{:\,}\Bstartsec \\ &&&
+     \Bcustomsec^\ast
+     &\Rightarrow& \{ \MTYPES~\X{ft}^\ast, \MSTART~\X{st}^? \} \\
+   \end{array}
+";
+
+    pub(in crate::codegen) fn spec() -> Spec {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source("syntax/types.rst", TYPES);
+        grammar.add_source("binary/types.rst", BINARY_TYPES);
+        Spec::new(grammar)
+    }
+
+    /// [`spec`] with the sections of a module
+    pub(in crate::codegen) fn module_spec() -> Spec {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source("syntax/types.rst", TYPES);
+        grammar.add_source("syntax/modules.rst", MODULES);
+        grammar.add_source("binary/types.rst", BINARY_TYPES);
+        grammar.add_source("binary/modules.rst", BINARY_MODULES);
+        assert!(grammar.errors().is_empty(), "{:?}", grammar.errors());
+        Spec::new(grammar)
+    }
+
+    #[test]
+    fn parse_binary_productions() {
+        let spec = spec();
+        assert!(spec.grammar().errors().is_empty());
+        let limits = spec.alternatives_of("Blimits");
+        assert_eq!(
+            limits[1].elem.to_string(),
+            r"\hex{01}~n{:}\Bu32~m{:}\Bu32 \Rightarrow \{ \LMIN~n, \LMAX~m \}"
+        );
+        let blocktype = spec.alternatives_of("Bblocktype");
+        assert_eq!(blocktype[2].elem.cond(), Some(r"\iff x \geq 0"));
+    }
+
+    #[test]
+    fn skipped() {
+        let generated = codegen::module(&spec());
+        let skipped: Vec<_> = generated.skipped.iter().map(|s| s.to_string()).collect();
+        assert!(skipped.is_empty(), "{:?}", skipped);
+    }
+
+    /// Compile the generated module with a `main` using it, and run it
+    pub(in crate::codegen) fn run(name: &str, source: &str, main: &str) -> String {
+        let dir = TempDir::new(name);
+        fs::write(dir.join("generated.rs"), source).unwrap();
+        fs::write(dir.join("main.rs"), main).unwrap();
+
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let status = Command::new(rustc)
+            .args(["--edition", "2021", "-o"])
+            .arg(dir.join(name))
+            .arg(dir.join("main.rs"))
+            .status()
+            .unwrap();
+        assert!(status.success(), "generated {} does not compile", name);
+        let output = Command::new(dir.join(name)).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Compile the generated decoder with `rustc` and run it on a few inputs
+    #[test]
+    fn decode() {
+        let generated = codegen::module(&spec());
+        let output = run(
+            "decoder",
+            &generated.source,
+            r#"
+mod generated;
+use generated::*;
+
+fn main() {
+    println!("{:?}", decode_all(&[0x60, 0x01, 0x7F, 0x02, 0x6F, 0x7E], decode_functype));
+    println!("{:?}", decode_all(&[0x01, 0x80, 0x01, 0x02], decode_limits));
+    println!("{:?}", decode_all(&[0x6F, 0x00, 0x05], decode_tabletype));
+    println!("{:?}", decode_all(&[0x00, 0x61, 0x73, 0x6D], decode_magic));
+    println!("{:?}", decode_all(&[0x02, 0x40, 0x01, 0xFC, 0x0E, 0x01, 0x02, 0x0B], decode_instr));
+    println!("{:?}", decode_all(&[0x05], decode_blocktype));
+    println!("{:?}", decode_all(&[0x41], decode_blocktype));
+    println!("{:?}", decode_all(&[0x01, 0x0B], decode_expr));
+    println!("{:?}", decode_all(&[0x60, 0x01, 0x7B], decode_functype));
+    println!("{:?}", decode_all(&[0x00, 0x80, 0x80, 0x80, 0x80, 0x10], decode_limits));
+    println!("{:?}", decode_all(&[0x00, 0x00, 0x00], decode_limits));
+}
+"#,
+        );
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Ok(Functype([Numtype(I32)], [Reftype(Externref), Numtype(I64)]))",
+                "Ok(Limits { lmin: 128, lmax: Some(2) })",
+                "Ok(Tabletype(Limits { lmin: 5, lmax: None }, Externref))",
+                "Ok(())",
+                "Ok(Block(Valtype(None), [Nop, Tablecopy(1, 2)]))",
+                "Ok(Typeidx(5))",
+                r#"Err(Condition { offset: 0, production: "blocktype" })"#,
+                "Ok([Nop])",
+                r#"Err(Unexpected { offset: 2, byte: 123, production: "valtype" })"#,
+                "Err(Overflow(1))",
+                "Err(Trailing(2))",
+            ]
+        );
+    }
+
+    /// Sections of a module, in order, each of them optional
+    #[test]
+    fn decode_module() {
+        let generated = codegen::module(&module_spec());
+        let output = run(
+            "decoder-module",
+            &generated.source,
+            r#"
+mod generated;
+use generated::*;
+
+const HEADER: [u8; 8] = [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+
+fn module(sections: &[u8]) -> Result<Module, DecodeError> {
+    decode_all(&[&HEADER[..], sections].concat(), decode_module)
+}
+
+fn main() {
+    println!("{:?}", module(&[]));
+    println!("{:?}", module(&[
+        0x00, 0x04, 0x01, 0x61, 0x01, 0x02,
+        0x01, 0x05, 0x01, 0x60, 0x01, 0x7F, 0x00,
+        0x08, 0x01, 0x03,
+    ]));
+    println!("{:?}", module(&[0x08, 0x01, 0x03, 0x01, 0x05, 0x01, 0x60, 0x01, 0x7F, 0x00]));
+    println!("{:?}", module(&[0x01, 0x06, 0x01, 0x60, 0x01, 0x7F, 0x00, 0x08]));
+    println!("{:?}", module(&[0x08, 0x01, 0x80, 0x01]));
+}
+"#,
+        );
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Ok(Module { mtypes: [], mstart: None })",
+                "Ok(Module { mtypes: [Functype([Numtype(I32)], [])], mstart: Some(Start { sfunc: 3 }) })",
+                "Err(Trailing(11))",
+                "Err(SectionSize(8))",
+                "Err(SectionSize(8))",
+            ]
+        );
+    }
+
+    /// Rust helper checking one value: decoded, and if `encode` is given,
+    /// encoded and decoded again into the same value
+    const CHECK: &str = r#"fn check<T: PartialEq + std::fmt::Debug>(
+    r: &mut Reader<'_>,
+    decode: fn(&mut Reader<'_>) -> Result<T, DecodeError>,
+    encode: Option<fn(&T, &mut Vec<u8>) -> Result<(), EncodeError>>,
+) -> Result<(), String> {
+    let value = decode(r).map_err(|e| format!("{:?}", e))?;
+    let Some(encode) = encode else {
+        return Ok(());
+    };
+    let bytes = encode_all(&value, encode).map_err(|e| format!("{:?}", e))?;
+    match decode_all(&bytes, decode) {
+        Ok(again) if again == value => Ok(()),
+        again => Err(format!("{:?} encodes as {:?}, read back as {:?}", value, bytes, again)),
+    }
+}
+
+"#;
+
+    /// Main checking each of `modules` with `decode_module`, or if it was not
+    /// generated, section by section with the generated section decoders,
+    /// skipping the sections without one. It prints `Ok(true)` for a module
+    /// read with skipped sections. With `round_trip`, the values are also
+    /// encoded and decoded again where an encoder was generated.
+    fn suite_main(
+        spec: &Spec,
+        source: &str,
+        modules: &[(String, Vec<u8>)],
+        round_trip: bool,
+    ) -> String {
+        let generated = |f: &str, name: &str| source.contains(&format!("pub fn {}_{}(", f, name));
+        let check = |name: &str| {
+            let encode = match round_trip && generated("encode", name) {
+                true => format!("Some(encode_{})", name),
+                false => "None".to_string(),
+            };
+            format!("check(&mut r, decode_{}, {})?", name, encode)
+        };
+        let mut main = format!("mod generated;\nuse generated::*;\n\n{}", CHECK);
+        main.push_str(
+            "fn decode(bytes: &[u8]) -> Result<bool, String> {\n    \
+             let mut r = Reader::new(bytes);\n",
+        );
+        if generated("decode", "module") {
+            writeln!(main, "    {};\n    let skipped = false;", check("module")).unwrap();
+        } else {
+            let mut arms = BTreeMap::new();
+            for nonterminal in spec.grammar().nonterminals() {
+                let alternatives = spec.alternatives_of(nonterminal);
+                let id = alternatives.first().and_then(|a| match a.elem.symbols() {
+                    [Symbol::SBind(bind)] => section(bind.symbol()),
+                    [symbol] => section(symbol),
+                    _ => None,
+                });
+                if let Some((id, _)) = id.filter(|_| generated("decode", short(nonterminal))) {
+                    arms.insert(id, short(nonterminal));
+                }
+            }
+            main.push_str(
+                "    let mut skipped = false;\n    \
+                 let error = |e| format!(\"{:?}\", e);\n    \
+                 r.bytes(8).map_err(error)?;\n    \
+                 while !r.is_empty() {\n        \
+                 match r.peek().map_err(error)? {\n",
+            );
+            for (id, name) in arms {
+                writeln!(main, "            {} => {},", id, check(name)).unwrap();
+            }
+            main.push_str(
+                "            _ => {\n                \
+                 skipped = true;\n                \
+                 r.byte().map_err(error)?;\n                \
+                 let size = decode_u32(&mut r).map_err(error)?;\n                \
+                 r.bytes(size as usize).map_err(error)?;\n            \
+                 }\n        }\n    }\n",
+            );
+        }
+        main.push_str(
+            "    match r.is_empty() {\n        \
+             true => Ok(skipped),\n        \
+             false => Err(format!(\"{:?}\", DecodeError::Trailing(r.offset()))),\n    \
+             }\n}\n\n",
+        );
+        main.push_str("const MODULES: &[(&str, &[u8])] = &[\n");
+        for (name, bytes) in modules {
+            let bytes: String = bytes.iter().map(|b| format!("\\x{:02X}", b)).collect();
+            writeln!(main, "    ({:?}, b\"{}\"),", name, bytes).unwrap();
+        }
+        main.push_str(
+            "];\n\nfn main() {\n    \
+             for (name, bytes) in MODULES {\n        \
+             println!(\"{} {:?}\", name, decode(bytes));\n    \
+             }\n}\n",
+        );
+        main
+    }
+
+    /// Check the `(module binary ...)` forms of `scripts` with the code
+    /// generated from `spec`, printing the failures and the counts of each
+    /// script. Modules of `assert_malformed` must be rejected, unless they
+    /// were read with skipped sections, and all others must be read.
+    pub(in crate::codegen) fn check_scripts(
+        name: &str,
+        spec: &Spec,
+        scripts: &[(String, String)],
+        round_trip: bool,
+    ) {
+        let generated = codegen::module(spec);
+        let mut modules = vec![];
+        let mut malformed = vec![];
+        for (script, source) in scripts {
+            let tokens = wast::tokenize(source).unwrap();
+            for module in wast::binary_modules(&tokens) {
+                modules.push((format!("{}:{}", script, module.line), module.bytes));
+                malformed.push(module.command == Some("assert_malformed"));
+            }
+        }
+        let main = suite_main(spec, &generated.source, &modules, round_trip);
+        let output = run(name, &generated.source, &main);
+        assert_eq!(output.lines().count(), modules.len());
+
+        // read, rejected and unchecked, by script
+        let mut counts: BTreeMap<&str, [usize; 3]> = BTreeMap::new();
+        let mut failures = vec![];
+        for ((line, (module, _)), malformed) in output.lines().zip(&modules).zip(malformed) {
+            let script = module.split(':').next().unwrap_or_default();
+            let count = counts.entry(script).or_default();
+            let result = &line[module.len() + 1..];
+            match (malformed, result) {
+                (false, "Ok(false)" | "Ok(true)") => count[0] += 1,
+                (true, "Ok(true)") => count[2] += 1,
+                (true, "Ok(false)") => {
+                    failures.push(format!("{}: malformed, but accepted", module))
+                }
+                (true, _) => count[1] += 1,
+                (false, error) => failures.push(format!("{}: {}", module, error)),
+            }
+        }
+        for (script, [read, rejected, unchecked]) in &counts {
+            println!(
+                "{}: {} read, {} malformed rejected, {} malformed unchecked",
+                script, read, rejected, unchecked
+            );
+        }
+        assert!(!modules.is_empty(), "no binary modules");
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    /// Check the binary modules of the test suite of the checkout
+    pub(in crate::codegen) fn check_suite(name: &str, round_trip: bool) {
+        let path = fixtures::wasmmeta_path();
+        let spec = fixtures::wasmmeta_spec(&path);
+        check_scripts(name, &spec, &fixtures::scripts(&path), round_trip);
+    }
+
+    /// Script of well-formed and malformed binary modules of [`module_spec`]
+    const SCRIPT: &str = r#"
+(module binary "\00asm" "\01\00\00\00")
+(module binary "\00asm" "\01\00\00\00" "\01\05\01\60\01\7f\00" "\08\01\03")
+(assert_invalid (module binary "\00asm" "\01\00\00\00" "\08\01\03") "unknown function")
+(assert_malformed (module binary "\00asm" "\01\00\00\00" "\01\06\01\60\01\7f\00\08") "section size mismatch")
+(assert_malformed (module binary "\00asm" "\01\00\00\00" "\08\01\03" "\01\05\01\60\01\7f\00") "junk after last section")
+"#;
+
+    /// The checks of the test suite on a script
+    #[test]
+    fn scripts() {
+        let scripts = [("modules.wast".to_string(), SCRIPT.to_string())];
+        check_scripts("decoder-scripts", &module_spec(), &scripts, false);
+    }
+
+    /// A malformed module that is read fails the checks
+    #[test]
+    #[should_panic(expected = "accepted.wast:1: malformed, but accepted")]
+    fn accepted() {
+        let script = r#"(assert_malformed (module binary "\00asm" "\01\00\00\00") "")"#;
+        let scripts = [("accepted.wast".to_string(), script.to_string())];
+        check_scripts("decoder-accepted", &module_spec(), &scripts, false);
+    }
+
+    /// Decode the binary modules of the test suite
+    #[test]
+    #[ignore = "needs a checkout of wasmmeta in `WASMMETA_PATH`"]
+    fn suite() {
+        check_suite("decoder-suite", false);
+    }
+}
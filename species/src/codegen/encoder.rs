@@ -0,0 +1,702 @@
+//! Encoder of the binary format, reading the `\B...` productions right to
+//! left.
+//!
+//! The attribute of every alternative becomes a pattern over the abstract
+//! type, binding the variables its binders name. The first alternative whose
+//! pattern matches a value writes its symbols, so a value with several
+//! encodings gets the one the specification lists first, and a value that
+//! none matches is an `EncodeError`.
+//!
+//! A section is written only if it holds a value, so a module without
+//! functions has no function section, and an iteration that the attribute
+//! does not name, like the custom sections `\Bcustomsec^\ast`, is written
+//! zero times.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    codegen::{
+        binding, fixed_point, format_of, indent, integer, key, literal, local, section, section_ty,
+        short,
+        types::{builtin, rust_ty, type_name, Fields, Ty, TypeDef, TypeModel, Variant},
+        Bindings, Skipped,
+    },
+    parser::SeqKind,
+    spec::Spec,
+    syntax::{
+        expr::{join, Expr, Var},
+        symbol::{SBind, SNonterm, Symbol},
+        RhsElem,
+    },
+};
+
+/// Writers of LEB128 numbers and encoders of the builtin nonterminals
+const PRELUDE: &str = r#"#[derive(Debug, Clone, PartialEq)]
+pub enum EncodeError {
+    /// Value that no alternative of the production encodes
+    Unmatched {
+        production: &'static str,
+        value: String,
+    },
+    /// Number or length that does not fit the type the production writes
+    Overflow { production: &'static str },
+}
+
+/// Unsigned LEB128 number, in as few bytes as possible
+pub fn write_unsigned(out: &mut Vec<u8>, mut n: u64) {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Signed LEB128 number, in as few bytes as possible
+pub fn write_signed(out: &mut Vec<u8>, mut n: i64) {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        if (n == 0 && byte & 0x40 == 0) || (n == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Length written as a `\Bu32`
+fn length(n: usize, production: &'static str) -> Result<u32, EncodeError> {
+    u32::try_from(n).map_err(|_| EncodeError::Overflow { production })
+}
+
+pub fn encode_vec<T>(
+    items: &[T],
+    out: &mut Vec<u8>,
+    encode: impl Fn(&T, &mut Vec<u8>) -> Result<(), EncodeError>,
+) -> Result<(), EncodeError> {
+    encode_u32(&length(items.len(), "vec")?, out)?;
+    for item in items {
+        encode(item, out)?;
+    }
+    Ok(())
+}
+
+/// Section `id` holding `value` after the size of its content, or nothing
+/// without a value
+pub fn encode_section<T: ?Sized>(
+    id: u8,
+    value: Option<&T>,
+    out: &mut Vec<u8>,
+    encode: impl Fn(&T, &mut Vec<u8>) -> Result<(), EncodeError>,
+) -> Result<(), EncodeError> {
+    if let Some(value) = value {
+        let content = encode_all(value, encode)?;
+        out.push(id);
+        encode_u32(&length(content.len(), "section")?, out)?;
+        out.extend(content);
+    }
+    Ok(())
+}
+
+/// Encode a value into a fresh buffer
+pub fn encode_all<T: ?Sized>(
+    value: &T,
+    encode: impl Fn(&T, &mut Vec<u8>) -> Result<(), EncodeError>,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut out = Vec::new();
+    encode(value, &mut out)?;
+    Ok(out)
+}
+
+pub fn encode_byte(value: &u8, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    out.push(*value);
+    Ok(())
+}
+
+pub fn encode_u32(value: &u32, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    write_unsigned(out, u64::from(*value));
+    Ok(())
+}
+
+pub fn encode_u64(value: &u64, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    write_unsigned(out, *value);
+    Ok(())
+}
+
+pub fn encode_s33(value: &i64, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    if !(-(1 << 32)..1 << 32).contains(value) {
+        return Err(EncodeError::Overflow { production: "s33" });
+    }
+    write_signed(out, *value);
+    Ok(())
+}
+
+pub fn encode_i32(value: &i32, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    write_signed(out, i64::from(*value));
+    Ok(())
+}
+
+pub fn encode_i64(value: &i64, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    write_signed(out, *value);
+    Ok(())
+}
+
+pub fn encode_f32(value: &f32, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    out.extend(value.to_le_bytes());
+    Ok(())
+}
+
+pub fn encode_f64(value: &f64, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    out.extend(value.to_le_bytes());
+    Ok(())
+}
+
+pub fn encode_name(value: &String, out: &mut Vec<u8>) -> Result<(), EncodeError> {
+    encode_u32(&length(value.len(), "name")?, out)?;
+    out.extend(value.as_bytes());
+    Ok(())
+}
+
+"#;
+
+/// Encoder functions of all binary nonterminals that could be translated
+#[derive(Debug)]
+pub struct Encoders {
+    functions: Vec<String>,
+    skipped: Vec<Skipped>,
+}
+
+struct Context<'s> {
+    spec: &'s Spec,
+    model: &'s TypeModel,
+    /// Binary nonterminals that get an encoder, with the type they encode
+    encoders: &'s HashMap<String, Ty>,
+}
+
+impl Encoders {
+    pub fn new(spec: &Spec, model: &TypeModel, targets: &[(String, Ty)]) -> Self {
+        let (functions, skipped) = fixed_point(targets, "encoder", |encoders, nonterminal, ty| {
+            let context = Context {
+                spec,
+                model,
+                encoders,
+            };
+            context.function(nonterminal, ty)
+        });
+        Self { functions, skipped }
+    }
+
+    pub fn skipped(&self) -> &[Skipped] {
+        &self.skipped
+    }
+
+    /// Rust items of the writers and all encoders
+    pub fn emit(&self) -> String {
+        let mut out = PRELUDE.to_string();
+        for function in &self.functions {
+            out.push_str(function);
+        }
+        out
+    }
+}
+
+impl<'s> Context<'s> {
+    fn function(&self, nonterminal: &str, ty: &Ty) -> Result<String, String> {
+        let name = short(nonterminal);
+        let mut arms = String::new();
+        for alternative in self.spec.alternatives_of(nonterminal) {
+            let (pattern, body) = self.alternative(name, alternative.elem, ty)?;
+            writeln!(arms, "{} => {{\n{}}}", pattern, indent(&body)).unwrap();
+        }
+        writeln!(
+            arms,
+            "value => {{\n    return Err(EncodeError::Unmatched {{\n        \
+             production: {:?},\n        value: format!(\"{{:?}}\", value),\n    }})\n}}",
+            name
+        )
+        .unwrap();
+        Ok(format!(
+            "pub fn encode_{}(value: &{}, out: &mut Vec<u8>) -> Result<(), EncodeError> {{\n    \
+             match value {{\n{}    }}\n    Ok(())\n}}\n\n",
+            name,
+            rust_ty(ty),
+            indent(&indent(&arms))
+        ))
+    }
+
+    /// Pattern matching the attribute of an alternative, and the statements
+    /// writing its symbols
+    fn alternative(
+        &self,
+        production: &str,
+        elem: &RhsElem,
+        ty: &Ty,
+    ) -> Result<(String, String), String> {
+        // types the binders decode, to match them against the attribute
+        let mut binders = HashMap::new();
+        for symbol in elem.symbols() {
+            let bind = match symbol {
+                Symbol::SBind(bind) if literal(symbol).is_none() => bind,
+                Symbol::SGroup(group) => match group.symbols() {
+                    [Symbol::SBind(bind)] => {
+                        let (_, ty) = self.encoder(bind.symbol())?;
+                        binders.insert(key(bind.var()), Ty::Vec(Box::new(ty)));
+                        continue;
+                    }
+                    _ => return Err(format!("`{}` is not supported", symbol)),
+                },
+                _ => continue,
+            };
+            let (_, ty) = self.bound(bind)?;
+            binders.insert(key(bind.var()), ty);
+        }
+
+        let mut bindings = Bindings::new();
+        let pattern = match elem.action() {
+            Some(action) => self.pattern(action, ty, &binders, &mut bindings)?,
+            None if *ty == Ty::Unit => "()".to_string(),
+            None => return Err(format!("alternative `{}` has no attribute", elem)),
+        };
+
+        let mut out = String::new();
+        for symbol in elem.symbols() {
+            match symbol {
+                Symbol::SHex(byte) => writeln!(out, "out.push(0x{:02X});", byte).unwrap(),
+                Symbol::SBind(bind) => match literal(symbol) {
+                    Some(code) => writeln!(out, "encode_u32(&{}, out)?;", code).unwrap(),
+                    None => {
+                        let (call, ty) = self.bound(bind)?;
+                        let value = self.argument(bind.var(), &ty, &bindings, production)?;
+                        writeln!(out, "{}?;", call.replace("VALUE", &value)).unwrap();
+                    }
+                },
+                Symbol::SNonterm(nt) if nt.seq_kind() == Some(&SeqKind::ManyPossibleEmpty) => {
+                    writeln!(out, "// zero times `{}`", symbol).unwrap()
+                }
+                Symbol::SNonterm(nt) => match self.encoder(symbol)? {
+                    (call, Ty::Unit) => {
+                        writeln!(out, "{}?;", call.replace("VALUE", "&()")).unwrap()
+                    }
+                    _ => return Err(format!("`{}` is not named in the attribute", nt)),
+                },
+                Symbol::SGroup(group) => {
+                    let [Symbol::SBind(bind)] = group.symbols() else {
+                        return Err(format!("`{}` is not supported", symbol));
+                    };
+                    let (call, _) = self.encoder(bind.symbol())?;
+                    let (local, _) = binding(bind.var(), &bindings)?;
+                    writeln!(
+                        out,
+                        "for item in {} {{\n    {}?;\n}}",
+                        local,
+                        call.replace("VALUE", "item")
+                    )
+                    .unwrap();
+                }
+                symbol => return Err(format!("`{}` is not supported", symbol)),
+            }
+        }
+        Ok((pattern, out))
+    }
+
+    /// Call encoding a nonterminal with `VALUE` in place of the value, and
+    /// the type it encodes
+    fn encoder(&self, symbol: &Symbol) -> Result<(String, Ty), String> {
+        match symbol {
+            Symbol::SNonterm(nt) if nt.seq_kind().is_none() => {
+                let (encoder, ty) = self.encoder_of(nt)?;
+                Ok((format!("{}(VALUE, out)", encoder), ty))
+            }
+            Symbol::SVec(vec) if vec.head() == "Bvec" && vec.over().seq_kind().is_none() => {
+                let (encoder, ty) = self.encoder_of(vec.over())?;
+                Ok((
+                    format!("encode_vec(VALUE, out, {})", encoder),
+                    Ty::Vec(Box::new(ty)),
+                ))
+            }
+            symbol => Err(format!("`{}` is not supported", symbol)),
+        }
+    }
+
+    /// Call encoding the value of a binder, and the type it binds. A section
+    /// binds its content, and is left out when that is an empty vector.
+    fn bound(&self, bind: &SBind) -> Result<(String, Ty), String> {
+        let Some((id, content)) = section(bind.symbol()) else {
+            return self.encoder(bind.symbol());
+        };
+        let (call, ty) = self.encoder(content)?;
+        let call = call.replace("VALUE", "value");
+        match section_ty(bind.var(), ty.clone()) {
+            Ty::Option(_) => Ok((
+                format!(
+                    "encode_section({}, VALUE.as_ref(), out, |value, out| {})",
+                    id, call
+                ),
+                Ty::Option(Box::new(ty)),
+            )),
+            bound => Ok((
+                format!(
+                    "encode_section({}, Some(VALUE).filter(|v| !v.is_empty()), out, |value, out| {})",
+                    id, call
+                ),
+                bound,
+            )),
+        }
+    }
+
+    fn encoder_of(&self, nt: &SNonterm) -> Result<(String, Ty), String> {
+        let name = short(&nt.name);
+        if let Some(ty) = self.encoders.get(&nt.name) {
+            Ok((format!("encode_{}", name), ty.clone()))
+        } else if format_of(&nt.name).is_some() && builtin(name).is_some() {
+            Ok((format!("encode_{}", name), Ty::Builtin(name.to_string())))
+        } else {
+            Err(format!("`{}` has no encoder", nt))
+        }
+    }
+
+    /// Reference to the value of a binder, converted to the type its
+    /// encoder takes. An integer that does not fit is an overflow of
+    /// `production`.
+    fn argument(
+        &self,
+        var: &Var,
+        ty: &Ty,
+        bindings: &Bindings,
+        production: &str,
+    ) -> Result<String, String> {
+        let (local, var_ty) = binding(var, bindings)
+            .map_err(|_| format!("`{}` is not named in the attribute", var))?;
+        match (self.model.resolve(var_ty), self.model.resolve(ty)) {
+            (var_ty, ty) if var_ty == ty => Ok(local.clone()),
+            (Ty::Builtin(from), Ty::Builtin(to)) if integer(&from) && integer(&to) => Ok(format!(
+                "&{}::try_from(*{}).map_err(|_| EncodeError::Overflow {{ production: {:?} }})?",
+                builtin(&to).unwrap_or_default(),
+                local,
+                production
+            )),
+            (Ty::Builtin(_), Ty::Builtin(to)) => Ok(format!(
+                "&(*{} as {})",
+                local,
+                builtin(&to).unwrap_or_default()
+            )),
+            _ => Err(format!(
+                "`{}` does not have the type `{}` encodes",
+                var,
+                rust_ty(ty)
+            )),
+        }
+    }
+
+    /// Pattern of type `ty` for an attribute, the inverse of the decoder's
+    /// translation
+    fn pattern(
+        &self,
+        exprs: &[Expr],
+        ty: &Ty,
+        binders: &HashMap<String, Ty>,
+        bindings: &mut Bindings,
+    ) -> Result<String, String> {
+        let resolved = self.model.resolve(ty);
+        let enum_def = match &resolved {
+            Ty::Named(name) => match self.model.get(name) {
+                Some(TypeDef::Enum(variants)) => Some((name, variants)),
+                _ => None,
+            },
+            _ => None,
+        };
+        let exprs: Vec<_> = match enum_def {
+            Some(_) => exprs.to_vec(),
+            None => exprs
+                .iter()
+                .filter(|e| !matches!(e, Expr::Term(_)))
+                .cloned()
+                .collect(),
+        };
+        let mismatch = || format!("cannot match `{}` with `{}`", rust_ty(ty), join(&exprs));
+
+        if let [Expr::Var(var)] = exprs.as_slice() {
+            let binder = binders
+                .get(&key(var))
+                .ok_or_else(|| format!("`{}` is not bound", var))?;
+            let matches = match (self.model.resolve(binder), &resolved) {
+                (binder, ty) if binder == *ty => true,
+                (Ty::Builtin(from), Ty::Builtin(to)) => integer(&from) && integer(to),
+                _ => false,
+            };
+            if matches {
+                let local = local(var);
+                bindings.insert(key(var), (local.clone(), ty.clone()));
+                return Ok(local);
+            }
+        }
+        if let Ty::Box(_) = ty {
+            return Err(mismatch());
+        }
+        if let Some((name, variants)) = enum_def {
+            return self.variant(name, variants, &exprs, binders, bindings);
+        }
+        match &resolved {
+            Ty::Option(inner) => match exprs.as_slice() {
+                [Expr::Epsilon] => Ok("None".to_string()),
+                exprs => Ok(format!(
+                    "Some({})",
+                    self.pattern(exprs, inner, binders, bindings)?
+                )),
+            },
+            Ty::Vec(_) => match exprs.as_slice() {
+                [Expr::List(items)] => self.pattern(items, &resolved, binders, bindings),
+                _ => Err(mismatch()),
+            },
+            Ty::Named(name) => match self.model.get(name) {
+                Some(TypeDef::Struct(fields)) => {
+                    self.fields(&type_name(name), fields, &exprs, binders, bindings)
+                }
+                _ => Err(mismatch()),
+            },
+            _ => Err(mismatch()),
+        }
+    }
+
+    fn variant(
+        &self,
+        name: &str,
+        variants: &[Variant],
+        exprs: &[Expr],
+        binders: &HashMap<String, Ty>,
+        bindings: &mut Bindings,
+    ) -> Result<String, String> {
+        let mismatch = || format!("cannot match `{}` with `{}`", type_name(name), join(exprs));
+        match exprs {
+            [Expr::Term(terminal), rest @ ..] => {
+                let variant = variants
+                    .iter()
+                    .find(|v| v.terminals.first() == Some(terminal))
+                    .ok_or_else(mismatch)?;
+                let rest: Vec<_> = rest
+                    .iter()
+                    .filter(|e| !matches!(e, Expr::Term(t) if variant.terminals.contains(t)))
+                    .cloned()
+                    .collect();
+                let path = format!("{}::{}", type_name(name), variant.name);
+                self.fields(&path, &variant.fields, &rest, binders, bindings)
+            }
+            [expr] => variants
+                .iter()
+                .filter(|v| v.terminals.is_empty())
+                .find_map(|v| match &v.fields {
+                    Fields::Tuple(tys) if tys.len() == 1 => {
+                        let mut attempt = bindings.clone();
+                        let exprs = std::slice::from_ref(expr);
+                        let pattern = self.pattern(exprs, &tys[0], binders, &mut attempt).ok()?;
+                        *bindings = attempt;
+                        Some(format!("{}::{}({})", type_name(name), v.name, pattern))
+                    }
+                    _ => None,
+                })
+                .ok_or_else(mismatch),
+            _ => Err(mismatch()),
+        }
+    }
+
+    fn fields(
+        &self,
+        path: &str,
+        fields: &Fields,
+        exprs: &[Expr],
+        binders: &HashMap<String, Ty>,
+        bindings: &mut Bindings,
+    ) -> Result<String, String> {
+        let mismatch = || format!("cannot match `{}` with `{}`", path, join(exprs));
+        match (fields, exprs) {
+            (Fields::Unit, []) => Ok(path.to_string()),
+            (Fields::Named(fields), [Expr::Record(pairs)]) => {
+                let mut patterns = vec![];
+                for (key, ty) in fields {
+                    let (_, value) = pairs
+                        .iter()
+                        .find(|(k, _)| k == key)
+                        .ok_or_else(|| format!("field `{}` of `{}` is missing", key, path))?;
+                    let pattern = self.pattern(value, ty, binders, bindings)?;
+                    patterns.push(format!("{}: {}", super::types::field_name(key), pattern));
+                }
+                Ok(format!("{} {{ {} }}", path, patterns.join(", ")))
+            }
+            (Fields::Tuple(tys), exprs) => {
+                let args: Vec<&[Expr]> = match exprs {
+                    [Expr::Arrow(from, to)] if tys.len() == 2 => vec![from, to],
+                    exprs if exprs.len() == tys.len() => {
+                        exprs.iter().map(std::slice::from_ref).collect()
+                    }
+                    _ => return Err(mismatch()),
+                };
+                let mut patterns = vec![];
+                for (exprs, ty) in args.iter().zip(tys) {
+                    patterns.push(self.pattern(exprs, ty, binders, bindings)?);
+                }
+                Ok(format!("{}({})", path, patterns.join(", ")))
+            }
+            _ => Err(mismatch()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        codegen::{
+            self,
+            decoder::tests::{check_suite, module_spec, run, spec},
+        },
+        Grammar, Spec,
+    };
+
+    #[test]
+    fn functions() {
+        let generated = codegen::module(&spec());
+        assert!(generated.source.contains(
+            "pub fn encode_limits(value: &Limits, out: &mut Vec<u8>) -> Result<(), EncodeError> {\n    \
+             match value {\n        \
+             Limits { lmin: v_n, lmax: None } => {\n            out.push(0x00);\n            \
+             encode_u32(v_n, out)?;\n        }\n"
+        ));
+        assert!(generated.source.contains(
+            "Blocktype::Typeidx(v_x) => {\n            encode_s33(&i64::try_from(*v_x)\
+             .map_err(|_| EncodeError::Overflow { production: \"blocktype\" })?, out)?;"
+        ));
+    }
+
+    /// Encoding a decoded value gives back the canonical bytes
+    #[test]
+    fn round_trip() {
+        let generated = codegen::module(&spec());
+        let output = run(
+            "encoder",
+            &generated.source,
+            r#"
+mod generated;
+use generated::*;
+
+fn round_trip<T: std::fmt::Debug>(
+    bytes: &[u8],
+    decode: impl Fn(&mut Reader<'_>) -> Result<T, DecodeError>,
+    encode: impl Fn(&T, &mut Vec<u8>) -> Result<(), EncodeError>,
+) {
+    let value = decode_all(bytes, decode).unwrap();
+    println!("{:?}", encode_all(&value, encode).unwrap() == bytes);
+}
+
+fn main() {
+    round_trip(&[0x60, 0x01, 0x7F, 0x02, 0x6F, 0x7E], decode_functype, encode_functype);
+    round_trip(&[0x01, 0x80, 0x01, 0x02], decode_limits, encode_limits);
+    round_trip(&[0x6F, 0x00, 0x05], decode_tabletype, encode_tabletype);
+    round_trip(&[0x00, 0x61, 0x73, 0x6D], decode_magic, encode_magic);
+    round_trip(&[0x02, 0x40, 0x01, 0xFC, 0x0E, 0x01, 0x02, 0x0B], decode_instr, encode_instr);
+    round_trip(&[0x02, 0x7F, 0xFC, 0x0B, 0x00, 0x0B], decode_instr, encode_instr);
+    round_trip(&[0x05], decode_blocktype, encode_blocktype);
+    round_trip(&[0x01, 0x01, 0x0B], decode_expr, encode_expr);
+    println!("{:?}", encode_all(&-64i64, encode_i64));
+    println!("{:?}", encode_all(&u32::MAX, encode_u32));
+}
+"#,
+        );
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines[..8], ["true"; 8]);
+        assert_eq!(lines[8..], ["Ok([64])", "Ok([255, 255, 255, 255, 15])"]);
+    }
+
+    /// A value without an encoding and a number out of the range of its
+    /// encoder are errors
+    #[test]
+    fn errors() {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "syntax/types.rst",
+            r"
+.. math::
+   \begin{array}{llll}
+   \production{reference type} & \reftype &::=&
+     \FUNCREF ~|~ \EXTERNREF \\
+   \end{array}
+",
+        );
+        grammar.add_source(
+            "binary/types.rst",
+            r"
+.. math::
+   \begin{array}{llclll@{\qquad\qquad}l}
+   \production{reference type} & \Breftype &::=&
+     \hex{70} &\Rightarrow& \FUNCREF \\
+   \end{array}
+",
+        );
+        let generated = codegen::module(&Spec::new(grammar));
+        let output = run(
+            "encoder-errors",
+            &generated.source,
+            r#"
+mod generated;
+use generated::*;
+
+fn main() {
+    println!("{:?}", encode_all(&Reftype::Funcref, encode_reftype));
+    println!("{:?}", encode_all(&Reftype::Externref, encode_reftype));
+    println!("{:?}", encode_all(&-(1i64 << 32), encode_s33));
+    println!("{:?}", encode_all(&(1i64 << 32), encode_s33));
+}
+"#,
+        );
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "Ok([112])",
+                r#"Err(Unmatched { production: "reftype", value: "Externref" })"#,
+                "Ok([128, 128, 128, 128, 112])",
+                r#"Err(Overflow { production: "s33" })"#,
+            ]
+        );
+    }
+
+    /// Sections holding no value are left out, and custom sections dropped
+    #[test]
+    fn modules() {
+        let generated = codegen::module(&module_spec());
+        let output = run(
+            "encoder-module",
+            &generated.source,
+            r#"
+mod generated;
+use generated::*;
+
+fn main() {
+    let header = [0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+    let sections = [
+        0x00, 0x04, 0x01, 0x61, 0x01, 0x02,
+        0x01, 0x05, 0x01, 0x60, 0x01, 0x7F, 0x00,
+        0x08, 0x01, 0x03,
+    ];
+    for bytes in [&header[..], &[&header[..], &sections[..]].concat()] {
+        let module = decode_all(bytes, decode_module).unwrap();
+        println!("{:?}", &encode_all(&module, encode_module).unwrap()[8..]);
+    }
+}
+"#,
+        );
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines, ["[]", "[1, 5, 1, 96, 1, 127, 0, 8, 1, 3]"]);
+    }
+
+    /// Decode the binary modules of the test suite, encode the values again
+    /// and decode them back. The bytes may differ, as the suite also writes
+    /// numbers in more bytes than needed.
+    #[test]
+    #[ignore = "needs a checkout of wasmmeta in `WASMMETA_PATH`"]
+    fn suite() {
+        check_suite("encoder-suite", true);
+    }
+}
@@ -0,0 +1,1623 @@
+//! Parser of the text format, read off the `\T...` productions.
+//!
+//! Every text nonterminal `\Tx` becomes a function `parse_x` over the tokens
+//! of the source. All alternatives of a production are tried and the one
+//! reading the most tokens wins, so that `n~m` is preferred over `n` for
+//! limits. Identifiers are resolved through the identifier context `I`, which
+//! has a space for every field that a side condition like
+//! `(\iff I.\ILABELS[l] = v)` looks up, and that alternatives like
+//! `\{\ILABELS~v\} \compose I` extend. Abbreviations are applied by rewriting
+//! the tokens of their short form into the long one before a production is
+//! read.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    codegen::{
+        fixed_point, format_of, indent, key, local, short, translate,
+        types::{builtin, ident, rust_ty, Ty, TypeModel},
+        Bindings, Skipped,
+    },
+    parser::SeqKind,
+    spec::Spec,
+    syntax::{
+        expr::{Expr, Var},
+        symbol::{SNonterm, Symbol},
+        Abbreviation, RhsElem,
+    },
+};
+
+/// Tokenizer, parser state, errors and parsers of the builtin nonterminals
+const PRELUDE: &str = r#"#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    LParen,
+    RParen,
+    /// Keyword, number or identifier
+    Atom(String),
+    Str(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// Byte offset of a character that starts no token
+    Lex(usize),
+    /// Token that no alternative of the production accepts
+    Unexpected {
+        token: usize,
+        production: &'static str,
+    },
+    /// Identifier that the identifier context does not bind
+    Unbound { token: usize, id: String },
+    /// Number that does not fit its type
+    Overflow(usize),
+    /// Tokens left after the parsed value
+    Trailing(usize),
+}
+
+impl ParseError {
+    fn token(&self) -> usize {
+        match self {
+            ParseError::Lex(_) => 0,
+            ParseError::Unexpected { token, .. } | ParseError::Unbound { token, .. } => *token,
+            ParseError::Overflow(token) | ParseError::Trailing(token) => *token,
+        }
+    }
+}
+
+fn delimits(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r' | b'(' | b')' | b'"' | b';')
+}
+
+pub fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let bytes = source.as_bytes();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b' ' | b'\t' | b'\n' | b'\r', _) => i += 1,
+            (b';', Some(b';')) => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            (b'(', Some(b';')) => {
+                let start = i;
+                let mut depth = 0;
+                loop {
+                    match (bytes.get(i), bytes.get(i + 1)) {
+                        (Some(b'('), Some(b';')) => depth += 1,
+                        (Some(b';'), Some(b')')) => depth -= 1,
+                        (Some(_), _) => {
+                            i += 1;
+                            continue;
+                        }
+                        (None, _) => return Err(ParseError::Lex(start)),
+                    }
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            (b'(', _) => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            (b')', _) => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            (b'"', _) => {
+                let start = i;
+                let mut string = vec![];
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        None => return Err(ParseError::Lex(start)),
+                        Some(b'"') => break,
+                        Some(b'\\') => {
+                            let escaped = match bytes.get(i + 1) {
+                                Some(b'n') => b'\n',
+                                Some(b't') => b'\t',
+                                Some(b'r') => b'\r',
+                                Some(&b @ (b'"' | b'\'' | b'\\')) => b,
+                                Some(b'u') => {
+                                    let braced = source[i + 2..].strip_prefix('{');
+                                    let hex = braced.and_then(|rest| rest.split_once('}'));
+                                    let hex = hex.map_or("", |(hex, _)| hex);
+                                    let c = Some(hex)
+                                        .filter(|hex| separated(hex, 16))
+                                        .and_then(|hex| u32::from_str_radix(&hex.replace('_', ""), 16).ok())
+                                        .and_then(char::from_u32)
+                                        .ok_or(ParseError::Lex(i))?;
+                                    string.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+                                    i += hex.len() + 4;
+                                    continue;
+                                }
+                                _ => {
+                                    let hex = source.get(i + 1..i + 3);
+                                    let hex = hex.filter(|h| h.bytes().all(|b| b.is_ascii_hexdigit()));
+                                    let byte = hex.and_then(|h| u8::from_str_radix(h, 16).ok());
+                                    string.push(byte.ok_or(ParseError::Lex(i))?);
+                                    i += 3;
+                                    continue;
+                                }
+                            };
+                            string.push(escaped);
+                            i += 2;
+                        }
+                        Some(&byte) => {
+                            string.push(byte);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::Str(string));
+                i += 1;
+            }
+            _ => {
+                let start = i;
+                while i < bytes.len() && !delimits(bytes[i]) {
+                    i += 1;
+                }
+                if start == i {
+                    return Err(ParseError::Lex(start));
+                }
+                tokens.push(Token::Atom(source[start..i].to_string()));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+pub struct Parser {
+    tokens: std::rc::Rc<Vec<Token>>,
+    pos: usize,
+    pub ctx: Context,
+}
+
+impl Parser {
+    pub fn new(source: &str, ctx: Context) -> Result<Self, ParseError> {
+        let tokens = std::rc::Rc::new(tokenize(source)?);
+        Ok(Self { tokens, pos: 0, ctx })
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.tokens.len()
+    }
+
+    pub fn unexpected(&self, production: &'static str) -> ParseError {
+        ParseError::Unexpected { token: self.pos, production }
+    }
+
+    pub fn keyword(&mut self, keyword: &str, production: &'static str) -> Result<(), ParseError> {
+        let matches = match (self.tokens.get(self.pos), keyword) {
+            (Some(Token::LParen), "(") | (Some(Token::RParen), ")") => true,
+            (Some(Token::Atom(atom)), keyword) => atom == keyword,
+            _ => false,
+        };
+        if !matches {
+            return Err(self.unexpected(production));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    pub fn atom(&mut self, production: &'static str) -> Result<String, ParseError> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Atom(atom)) => {
+                let atom = atom.clone();
+                self.pos += 1;
+                Ok(atom)
+            }
+            _ => Err(self.unexpected(production)),
+        }
+    }
+
+    pub fn string(&mut self, production: &'static str) -> Result<Vec<u8>, ParseError> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Str(string)) => {
+                let string = string.clone();
+                self.pos += 1;
+                Ok(string)
+            }
+            _ => Err(self.unexpected(production)),
+        }
+    }
+
+    fn rest(&self) -> usize {
+        self.tokens.len() - self.pos
+    }
+
+    /// Try every alternative and keep the one reading the most tokens, the
+    /// first of them on a tie
+    pub fn alt<T>(
+        &mut self,
+        production: &'static str,
+        alternatives: &[fn(&mut Parser) -> Result<T, ParseError>],
+    ) -> Result<T, ParseError> {
+        let mut best: Option<(Parser, T)> = None;
+        let mut error = self.unexpected(production);
+        for alternative in alternatives {
+            let mut p = self.clone();
+            match alternative(&mut p) {
+                Ok(value) => {
+                    if best.as_ref().map_or(true, |(b, _)| p.rest() < b.rest()) {
+                        best = Some((p, value));
+                    }
+                }
+                // report the error furthest in, preferring a specific one
+                Err(e) => {
+                    let specific = !matches!(e, ParseError::Unexpected { .. });
+                    if e.token() > error.token() || (e.token() == error.token() && specific) {
+                        error = e;
+                    }
+                }
+            }
+        }
+        match best {
+            Some((p, value)) => {
+                *self = p;
+                Ok(value)
+            }
+            None => Err(error),
+        }
+    }
+
+    pub fn opt<T>(&mut self, parse: impl Fn(&mut Parser) -> Result<T, ParseError>) -> Option<T> {
+        let mut p = self.clone();
+        let value = parse(&mut p).ok()?;
+        *self = p;
+        Some(value)
+    }
+
+    pub fn many<T>(&mut self, parse: impl Fn(&mut Parser) -> Result<T, ParseError>) -> Vec<T> {
+        let mut items = vec![];
+        loop {
+            let rest = self.rest();
+            match self.opt(&parse) {
+                Some(item) if self.rest() < rest => items.push(item),
+                _ => return items,
+            }
+        }
+    }
+
+    /// Tokens that `parse` reads
+    pub fn span<T>(
+        &mut self,
+        parse: impl Fn(&mut Parser) -> Result<T, ParseError>,
+    ) -> Result<Vec<Token>, ParseError> {
+        let start = self.pos;
+        parse(self)?;
+        Ok(self.tokens[start..self.pos].to_vec())
+    }
+
+    /// Replace the tokens read since `start`, and continue at `start`
+    pub fn rewrite(&mut self, start: usize, tokens: Vec<Token>) {
+        std::rc::Rc::make_mut(&mut self.tokens).splice(start..self.pos, tokens);
+        self.pos = start;
+    }
+
+    /// Apply an abbreviation, if its short form is next and differs from
+    /// the long one
+    pub fn expand(&mut self, rewrite: fn(&mut Parser) -> Result<(), ParseError>) -> bool {
+        let mut p = self.clone();
+        if rewrite(&mut p).is_err() || p.tokens == self.tokens {
+            return false;
+        }
+        self.tokens = p.tokens;
+        true
+    }
+
+    /// Index of the identifier just read in a space of the identifier context
+    pub fn lookup(&self, space: &[Option<String>], id: &str) -> Result<u32, ParseError> {
+        match space.iter().position(|bound| bound.as_deref() == Some(id)) {
+            Some(index) => Ok(index as u32),
+            None => Err(ParseError::Unbound {
+                token: self.pos - 1,
+                id: id.to_string(),
+            }),
+        }
+    }
+}
+
+/// Parse a whole source, failing on trailing tokens
+pub fn parse_all<T>(
+    source: &str,
+    ctx: Context,
+    parse: impl Fn(&mut Parser) -> Result<T, ParseError>,
+) -> Result<T, ParseError> {
+    let mut p = Parser::new(source, ctx)?;
+    let value = parse(&mut p)?;
+    if p.is_empty() {
+        Ok(value)
+    } else {
+        Err(ParseError::Trailing(p.pos()))
+    }
+}
+
+/// Whether the text is digits of `radix` with single `_` between them
+fn separated(text: &str, radix: u32) -> bool {
+    !text.is_empty()
+        && !text.starts_with('_')
+        && !text.ends_with('_')
+        && !text.contains("__")
+        && text.chars().all(|c| c == '_' || c.is_digit(radix))
+}
+
+/// Decimal or hexadecimal digits with `_` separators
+fn digits(text: &str, token: usize) -> Result<u64, ParseError> {
+    let (digits, radix) = match text.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (text, 10),
+    };
+    if !separated(digits, radix) {
+        return Err(ParseError::Unexpected { token, production: "number" });
+    }
+    u64::from_str_radix(&digits.replace('_', ""), radix).map_err(|_| ParseError::Overflow(token))
+}
+
+fn unsigned(p: &mut Parser, bits: u32) -> Result<u64, ParseError> {
+    let token = p.pos();
+    let n = digits(&p.atom("number")?, token)?;
+    if bits < 64 && n >> bits != 0 {
+        return Err(ParseError::Overflow(token));
+    }
+    Ok(n)
+}
+
+/// Signed integer, or an unsigned one wrapped into the signed range
+fn signed(p: &mut Parser, bits: u32) -> Result<i64, ParseError> {
+    let token = p.pos();
+    let atom = p.atom("number")?;
+    let (negative, text) = match atom.as_bytes().first() {
+        Some(b'-') => (true, &atom[1..]),
+        Some(b'+') => (false, &atom[1..]),
+        _ => {
+            let n = digits(&atom, token)?;
+            if bits < 64 && n >> bits != 0 {
+                return Err(ParseError::Overflow(token));
+            }
+            let shift = 64 - bits;
+            return Ok(((n << shift) as i64) >> shift);
+        }
+    };
+    let n = digits(text, token)?;
+    let limit = 1u64 << (bits - 1);
+    match negative {
+        true if n <= limit => Ok((n as i64).wrapping_neg()),
+        false if n < limit => Ok(n as i64),
+        _ => Err(ParseError::Overflow(token)),
+    }
+}
+
+/// Integer digits, fraction digits and exponent of a number `p.q`,
+/// `p.qEn` or `p.qPn`, the exponent starting with one of `marker`
+fn parts<'a>(text: &'a str, radix: u32, marker: [char; 2]) -> Option<(&'a str, &'a str, i64)> {
+    let (mantissa, exponent) = match text.split_once(marker) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (text, None),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    if !separated(int, radix) || !(frac.is_empty() || separated(frac, radix)) {
+        return None;
+    }
+    let Some(exponent) = exponent else {
+        return Some((int, frac, 0));
+    };
+    let (negative, exponent) = match exponent.as_bytes().first() {
+        Some(b'-') => (true, &exponent[1..]),
+        Some(b'+') => (false, &exponent[1..]),
+        _ => (false, exponent),
+    };
+    if !separated(exponent, 10) {
+        return None;
+    }
+    // any exponent past this one overflows or underflows every float
+    let n = exponent
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .fold(0, |n: i64, d| (n * 10 + i64::from(d)).min(1 << 20));
+    Some((int, frac, if negative { -n } else { n }))
+}
+
+/// Bits of the float of `signif` significand and `exp_bits` exponent bits
+/// nearest to `m * 2^e`, ties to even, where `sticky` tells that nonzero
+/// bits below `m` were dropped; none if it rounds to infinity
+fn round(m: u64, e: i64, sticky: bool, signif: u32, exp_bits: u32) -> Option<u64> {
+    if m == 0 {
+        return Some(0);
+    }
+    let bias = (1 << (exp_bits - 1)) - 1;
+    let top = 63 - i64::from(m.leading_zeros()) + e;
+    // exponent of the last bit kept, at least that of the subnormals
+    let mut last = top.max(1 - bias) - i64::from(signif);
+    let shift = last - e;
+    let mut kept = if shift <= 0 {
+        m << -shift
+    } else {
+        let shift = shift.min(100) as u32;
+        let kept = u128::from(m) >> shift;
+        let rest = u128::from(m) - (kept << shift);
+        let half = 1 << (shift - 1);
+        let up = rest > half || rest == half && (sticky || kept & 1 == 1);
+        kept as u64 + u64::from(up)
+    };
+    if kept >> (signif + 1) != 0 {
+        kept >>= 1;
+        last += 1;
+    }
+    let biased = match kept >> signif {
+        0 => 0,
+        _ => last + i64::from(signif) + bias,
+    };
+    if biased >= (1 << exp_bits) - 1 {
+        return None;
+    }
+    Some((biased as u64) << signif | kept & ((1 << signif) - 1))
+}
+
+/// Magnitude of a hexadecimal float `0x...`, rounded to nearest
+fn hexfloat(text: &str, signif: u32, exp_bits: u32, token: usize) -> Result<u64, ParseError> {
+    let unexpected = || ParseError::Unexpected { token, production: "float" };
+    let (int, frac, exponent) = parts(text, 16, ['p', 'P']).ok_or_else(unexpected)?;
+    // the digits read into `m` while it has room, with the exponent of its
+    // last bit, and whether a digit dropped past them is not zero
+    let (mut m, mut e, mut sticky) = (0u64, exponent, false);
+    let digits = int.chars().map(|c| (c, false));
+    for (c, fraction) in digits.chain(frac.chars().map(|c| (c, true))) {
+        let Some(d) = c.to_digit(16) else {
+            continue;
+        };
+        if m >> 60 == 0 {
+            m = m << 4 | u64::from(d);
+            e -= if fraction { 4 } else { 0 };
+        } else {
+            sticky |= d != 0;
+            e += if fraction { 0 } else { 4 };
+        }
+    }
+    round(m, e, sticky, signif, exp_bits).ok_or(ParseError::Overflow(token))
+}
+
+/// Magnitude of a decimal float, rounded to nearest by the standard library
+fn decimal(text: &str, signif: u32, exp_bits: u32, token: usize) -> Result<u64, ParseError> {
+    let unexpected = || ParseError::Unexpected { token, production: "float" };
+    let (int, frac, exponent) = parts(text, 10, ['e', 'E']).ok_or_else(unexpected)?;
+    let text = format!("{}.{}e{}", int.replace('_', ""), frac.replace('_', ""), exponent);
+    let bits = match signif {
+        23 => text.parse::<f32>().map(|f| u64::from(f.to_bits())),
+        _ => text.parse::<f64>().map(f64::to_bits),
+    };
+    let inf = ((1 << exp_bits) - 1) << signif;
+    match bits {
+        Ok(bits) if bits & inf == inf => Err(ParseError::Overflow(token)),
+        Ok(bits) => Ok(bits),
+        Err(_) => Err(unexpected()),
+    }
+}
+
+/// Bits of a float of `signif` significand and `exp_bits` exponent bits:
+/// a signed decimal or hexadecimal number rounded to nearest, `inf`, `nan`,
+/// or `nan:0x` with the payload of the NaN
+fn float(p: &mut Parser, signif: u32, exp_bits: u32) -> Result<u64, ParseError> {
+    let token = p.pos();
+    let atom = p.atom("float")?;
+    let (sign, magnitude) = match atom.as_bytes().first() {
+        Some(b'-') => (1 << (signif + exp_bits), &atom[1..]),
+        Some(b'+') => (0, &atom[1..]),
+        _ => (0, &atom[..]),
+    };
+    let inf = ((1 << exp_bits) - 1) << signif;
+    let bits = match magnitude {
+        "inf" => inf,
+        "nan" => inf | 1 << (signif - 1),
+        _ => match (magnitude.strip_prefix("nan:0x"), magnitude.strip_prefix("0x")) {
+            (Some(payload), _) => match digits(&format!("0x{}", payload), token)? {
+                n if n >= 1 && n >> signif == 0 => inf | n,
+                _ => return Err(ParseError::Overflow(token)),
+            },
+            (None, Some(hex)) => hexfloat(hex, signif, exp_bits, token)?,
+            (None, None) => decimal(magnitude, signif, exp_bits, token)?,
+        },
+    };
+    Ok(sign | bits)
+}
+
+pub fn parse_byte(p: &mut Parser) -> Result<u8, ParseError> {
+    unsigned(p, 8).map(|n| n as u8)
+}
+
+pub fn parse_u32(p: &mut Parser) -> Result<u32, ParseError> {
+    unsigned(p, 32).map(|n| n as u32)
+}
+
+pub fn parse_u64(p: &mut Parser) -> Result<u64, ParseError> {
+    unsigned(p, 64)
+}
+
+pub fn parse_s33(p: &mut Parser) -> Result<i64, ParseError> {
+    signed(p, 33)
+}
+
+pub fn parse_i32(p: &mut Parser) -> Result<i32, ParseError> {
+    signed(p, 32).map(|n| n as i32)
+}
+
+pub fn parse_i64(p: &mut Parser) -> Result<i64, ParseError> {
+    signed(p, 64)
+}
+
+pub fn parse_f32(p: &mut Parser) -> Result<f32, ParseError> {
+    float(p, 23, 8).map(|bits| f32::from_bits(bits as u32))
+}
+
+pub fn parse_f64(p: &mut Parser) -> Result<f64, ParseError> {
+    float(p, 52, 11).map(f64::from_bits)
+}
+
+pub fn parse_name(p: &mut Parser) -> Result<String, ParseError> {
+    let token = p.pos();
+    let string = p.string("name")?;
+    String::from_utf8(string).map_err(|_| ParseError::Unexpected { token, production: "name" })
+}
+
+/// Identifier without its `$`
+pub fn parse_id(p: &mut Parser) -> Result<String, ParseError> {
+    let token = p.pos();
+    match p.atom("id")?.strip_prefix('$') {
+        Some(id) if !id.is_empty() => Ok(id.to_string()),
+        _ => Err(ParseError::Unexpected { token, production: "id" }),
+    }
+}
+
+"#;
+
+/// Parser functions of all text nonterminals that could be translated
+#[derive(Debug)]
+pub struct Parsers {
+    functions: Vec<String>,
+    /// Index spaces of the identifier context
+    spaces: Vec<String>,
+    skipped: Vec<Skipped>,
+}
+
+struct Context<'s> {
+    spec: &'s Spec,
+    model: &'s TypeModel,
+    /// Text nonterminals that get a parser, with the type they parse
+    parsers: &'s HashMap<String, Ty>,
+    /// Abbreviations of the production of each nonterminal
+    abbreviations: &'s HashMap<String, Vec<&'s Abbreviation>>,
+}
+
+/// How often a nonterminal of an abbreviation occurs
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Hole {
+    One,
+    Opt,
+    Many,
+}
+
+impl Parsers {
+    pub fn new(spec: &Spec, model: &TypeModel, targets: &[(String, Ty)]) -> Self {
+        // identifiers are read by the builtin `parse_id`
+        let targets: Vec<_> = targets
+            .iter()
+            .filter(|(nt, _)| short(nt) != "id")
+            .cloned()
+            .collect();
+
+        let mut spaces = vec![];
+        for (nonterminal, _) in &targets {
+            for alternative in spec.alternatives_of(nonterminal) {
+                let elem = alternative.elem;
+                let lookup = elem.cond().and_then(lookup).map(|(space, _, _)| space);
+                let extended = extensions(elem).into_iter().map(|(space, _, _)| space);
+                for space in lookup.into_iter().chain(extended) {
+                    if !spaces.contains(&space) {
+                        spaces.push(space);
+                    }
+                }
+            }
+        }
+
+        let mut abbreviations: HashMap<String, Vec<&Abbreviation>> = HashMap::new();
+        let mut skipped = vec![];
+        for (_, abbreviation) in spec.grammar().abbreviations() {
+            let nonterminal = abbreviation.name().and_then(|name| {
+                spec.grammar()
+                    .productions()
+                    .map(|(_, p)| p)
+                    .find(|p| {
+                        p.name() == name && matches!(format_of(p.nonterminal()), Some(('T', _)))
+                    })
+                    .map(|p| p.nonterminal().to_string())
+            });
+            match nonterminal {
+                Some(nonterminal) => abbreviations
+                    .entry(nonterminal)
+                    .or_default()
+                    .push(abbreviation),
+                None => skipped.push(Skipped {
+                    nonterminal: abbreviation.name().unwrap_or_default().to_string(),
+                    reason: format!("abbreviation `{}` names no text production", abbreviation),
+                }),
+            }
+        }
+
+        let (functions, dropped) = fixed_point(&targets, "parser", |parsers, nonterminal, ty| {
+            let context = Context {
+                spec,
+                model,
+                parsers,
+                abbreviations: &abbreviations,
+            };
+            context.function(nonterminal, ty)
+        });
+        skipped.extend(dropped);
+
+        // abbreviations whose nonterminals have no parser are left out
+        let parsers: HashMap<_, _> = targets.iter().cloned().collect();
+        let context = Context {
+            spec,
+            model,
+            parsers: &parsers,
+            abbreviations: &abbreviations,
+        };
+        for (nonterminal, _) in &targets {
+            for abbreviation in abbreviations.get(nonterminal).into_iter().flatten() {
+                if let Err(reason) = context.rewrite(abbreviation, 0) {
+                    skipped.push(Skipped {
+                        nonterminal: nonterminal.clone(),
+                        reason: format!("abbreviation `{}`: {}", abbreviation, reason),
+                    });
+                }
+            }
+        }
+
+        Self {
+            functions,
+            spaces,
+            skipped,
+        }
+    }
+
+    pub fn skipped(&self) -> &[Skipped] {
+        &self.skipped
+    }
+
+    /// Rust items of the tokenizer, the identifier context and all parsers
+    pub fn emit(&self) -> String {
+        let mut out = PRELUDE.to_string();
+        out.push_str("/// Identifier context `I`, the identifiers bound in each index space\n");
+        out.push_str("#[derive(Debug, Clone, Default, PartialEq)]\npub struct Context {\n");
+        for space in &self.spaces {
+            writeln!(out, "    pub {}: Vec<Option<String>>,", ident(space)).unwrap();
+        }
+        out.push_str("}\n\n");
+        for function in &self.functions {
+            out.push_str(function);
+        }
+        out
+    }
+}
+
+impl<'s> Context<'s> {
+    fn function(&self, nonterminal: &str, ty: &Ty) -> Result<String, String> {
+        let name = short(nonterminal);
+        let mut out = String::new();
+
+        // the abbreviations are applied before the alternatives are tried
+        let mut expansions = vec![];
+        for (i, abbreviation) in self
+            .abbreviations
+            .get(nonterminal)
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            if let Ok(rewrite) = self.rewrite(abbreviation, i) {
+                writeln!(out, "/// `{}`", abbreviation).unwrap();
+                out.push_str(&rewrite);
+                expansions.push(format!("p.expand(expand_{}_{})", name, i));
+            }
+        }
+
+        let alternatives = self.spec.alternatives_of(nonterminal);
+        let mut body = String::new();
+        if !expansions.is_empty() {
+            writeln!(body, "while {} {{}}", expansions.join(" || ")).unwrap();
+        }
+        if let [alternative] = alternatives.as_slice() {
+            body.push_str(&self.sequence(name, alternative.elem, ty)?);
+        } else {
+            let mut names = vec![];
+            for (i, alternative) in alternatives.iter().enumerate() {
+                let sequence = self.sequence(name, alternative.elem, ty)?;
+                writeln!(
+                    out,
+                    "fn parse_{}_{}(p: &mut Parser) -> Result<{}, ParseError> {{\n{}}}\n",
+                    name,
+                    i,
+                    rust_ty(ty),
+                    indent(&sequence)
+                )
+                .unwrap();
+                names.push(format!("parse_{}_{}", name, i));
+            }
+            writeln!(body, "p.alt({:?}, &[{}])", name, names.join(", ")).unwrap();
+        }
+        writeln!(
+            out,
+            "pub fn parse_{}(p: &mut Parser) -> Result<{}, ParseError> {{\n{}}}\n",
+            name,
+            rust_ty(ty),
+            indent(&body)
+        )
+        .unwrap();
+        Ok(out)
+    }
+
+    /// Read the symbols of an alternative and build its attribute
+    fn sequence(&self, production: &str, elem: &RhsElem, ty: &Ty) -> Result<String, String> {
+        let mut out = String::new();
+        let mut bindings = Bindings::new();
+        // the context an alternative extends, like a label for its block,
+        // is restored once it is read
+        let mut scoped = false;
+        for symbol in elem.symbols() {
+            match symbol {
+                Symbol::SText(keyword) => {
+                    writeln!(out, "p.keyword({:?}, {:?})?;", keyword, production).unwrap()
+                }
+                Symbol::SNonterm(nt) if short(&nt.name) == "epsilon" => {}
+                Symbol::SBind(bind) if self.extends(bind.symbol()) || self.extends(symbol) => {
+                    if !scoped {
+                        out.push_str("let outer = p.ctx.clone();\n");
+                        scoped = true;
+                    }
+                    let (call, _) = self.call(bind.symbol())?;
+                    writeln!(out, "{};", call).unwrap();
+                }
+                Symbol::SBind(bind) => {
+                    let (call, ty) = self.call(bind.symbol())?;
+                    let local = local(bind.var());
+                    writeln!(out, "let {} = {};", local, call).unwrap();
+                    bindings.insert(key(bind.var()), (local, ty));
+                }
+                Symbol::SNonterm(_) | Symbol::SVec(_) => {
+                    let (call, _) = self.call(symbol)?;
+                    writeln!(out, "{};", call).unwrap();
+                }
+                Symbol::SGroup(group) => {
+                    // `(t{:}\Tresult)^?` or `(\X{in}{:}\Tinstr_{I'})^\ast`
+                    let [Symbol::SBind(bind)] = group.symbols() else {
+                        return Err(format!("`{}` is not supported", symbol));
+                    };
+                    let Symbol::SNonterm(nt) = bind.symbol() else {
+                        return Err(format!("`{}` is not supported", symbol));
+                    };
+                    if nt.seq_kind().is_some() {
+                        return Err(format!("`{}` is not supported", symbol));
+                    }
+                    let repeated = SNonterm::new(nt.name.clone(), group.seq_kind().cloned());
+                    let (call, ty) = self.call(&Symbol::SNonterm(repeated))?;
+                    let local = local(bind.var());
+                    writeln!(out, "let {} = {};", local, call).unwrap();
+                    bindings.insert(key(bind.var()), (local, ty));
+                }
+                symbol => return Err(format!("`{}` is not supported", symbol)),
+            }
+        }
+
+        // `(\iff I.\ILABELS[l] = v)` binds `l` to the index of `v`
+        if let Some((space, index, id)) = elem.cond().and_then(lookup) {
+            let (id, _) = bindings
+                .get(&id)
+                .cloned()
+                .ok_or_else(|| format!("`{}` is not bound", id))?;
+            let local = format!("v_{}", index.trim_end_matches('_'));
+            writeln!(
+                out,
+                "let {} = p.lookup(&p.ctx.{}, &{})?;",
+                local,
+                ident(&space),
+                id
+            )
+            .unwrap();
+            bindings.insert(index, (local, Ty::Builtin("u32".to_string())));
+        }
+
+        let extensions = extensions(elem);
+        let overflow = "ParseError::Overflow(start)";
+        let value = match elem.action() {
+            Some(_) if !extensions.is_empty() => {
+                for (space, value, prepend) in extensions {
+                    let value = match value {
+                        Some(var) => {
+                            let (local, _) = bindings
+                                .get(&key(&var))
+                                .ok_or_else(|| format!("`{}` is not bound", var))?;
+                            format!("Some({}.clone())", local)
+                        }
+                        None => "None".to_string(),
+                    };
+                    match prepend {
+                        true => writeln!(out, "p.ctx.{}.insert(0, {});", ident(&space), value),
+                        false => writeln!(out, "p.ctx.{}.push({});", ident(&space), value),
+                    }
+                    .unwrap();
+                }
+                "()".to_string()
+            }
+            Some(action) => translate(self.model, action, ty, &bindings, overflow)?,
+            None if *ty == Ty::Unit => "()".to_string(),
+            None => return Err(format!("alternative `{}` has no attribute", elem)),
+        };
+        if scoped {
+            out.push_str("p.ctx = outer;\n");
+        }
+        if value.contains(overflow) {
+            out.insert_str(0, "let start = p.pos();\n");
+        }
+        writeln!(out, "Ok({})", value).unwrap();
+        Ok(out)
+    }
+
+    /// Whether a symbol is a nonterminal that only extends the identifier
+    /// context, like `\Tlabel_I`
+    fn extends(&self, symbol: &Symbol) -> bool {
+        match symbol {
+            Symbol::SNonterm(nt) => {
+                self.parsers.get(&nt.name) == Some(&Ty::Unit)
+                    && self
+                        .spec
+                        .alternatives_of(&nt.name)
+                        .iter()
+                        .any(|a| !extensions(a.elem).is_empty())
+            }
+            _ => false,
+        }
+    }
+
+    /// Call reading a nonterminal, e.g. `parse_u32(p)?` or
+    /// `p.many(parse_instr)`, and the type it reads
+    fn call(&self, symbol: &Symbol) -> Result<(String, Ty), String> {
+        match symbol {
+            Symbol::SNonterm(nt) => {
+                let (parser, ty) = self.parser(nt)?;
+                Ok(match nt.seq_kind() {
+                    None => (format!("{}(p)?", parser), ty),
+                    Some(SeqKind::OptSeq) => {
+                        (format!("p.opt({})", parser), Ty::Option(Box::new(ty)))
+                    }
+                    Some(SeqKind::ManyPossibleEmpty) => {
+                        (format!("p.many({})", parser), Ty::Vec(Box::new(ty)))
+                    }
+                    Some(seq_kind) => {
+                        return Err(format!("`{}` is not supported", seq_kind));
+                    }
+                })
+            }
+            // `\Tvec` is a plain sequence in the text format
+            Symbol::SVec(vec) if vec.head() == "Tvec" && vec.over().seq_kind().is_none() => {
+                let (parser, ty) = self.parser(vec.over())?;
+                Ok((format!("p.many({})", parser), Ty::Vec(Box::new(ty))))
+            }
+            symbol => Err(format!("`{}` is not supported", symbol)),
+        }
+    }
+
+    fn parser(&self, nt: &SNonterm) -> Result<(String, Ty), String> {
+        let name = short(&nt.name);
+        if let Some(ty) = self.parsers.get(&nt.name) {
+            Ok((format!("parse_{}", name), ty.clone()))
+        } else if format_of(&nt.name).is_none() {
+            Err(format!("`{}` has no parser", nt))
+        } else if name == "id" {
+            // identifiers are strings, like names
+            Ok(("parse_id".to_string(), Ty::Builtin("name".to_string())))
+        } else if builtin(name).is_some() {
+            Ok((format!("parse_{}", name), Ty::Builtin(name.to_string())))
+        } else {
+            Err(format!("`{}` has no parser", nt))
+        }
+    }
+
+    /// Function rewriting the short form of an abbreviation into the long
+    /// one, e.g. `(param i32 i64)` into `(param i32) (param i64)`
+    fn rewrite(&self, abbreviation: &Abbreviation, i: usize) -> Result<String, String> {
+        if abbreviation.cond().is_some() {
+            return Err("side conditions are not supported".to_string());
+        }
+        let production = abbreviation.name().unwrap_or_default();
+        let mut holes = HashMap::new();
+        let mut matching = String::new();
+        for symbol in abbreviation.short() {
+            match symbol {
+                Symbol::SText(keyword) => {
+                    writeln!(matching, "p.keyword({:?}, {:?})?;", keyword, production).unwrap()
+                }
+                Symbol::SNonterm(nt) => {
+                    let (parser, _) = self.parser(nt)?;
+                    let (hole, span) = match nt.seq_kind() {
+                        None => (Hole::One, format!("p.span({})?", parser)),
+                        Some(SeqKind::OptSeq) => {
+                            (Hole::Opt, format!("p.opt(|p| p.span({}))", parser))
+                        }
+                        Some(SeqKind::ManyPossibleEmpty) => {
+                            (Hole::Many, format!("p.many(|p| p.span({}))", parser))
+                        }
+                        Some(seq_kind) => return Err(format!("`{}` is not supported", seq_kind)),
+                    };
+                    if holes.insert(nt.name.clone(), hole).is_some() {
+                        return Err(format!("`{}` occurs twice", nt));
+                    }
+                    writeln!(matching, "let h_{} = {};", short(&nt.name), span).unwrap();
+                }
+                symbol => return Err(format!("`{}` is not supported", symbol)),
+            }
+        }
+        let tokens = tokens(abbreviation.long(), &holes)?;
+
+        let name = self
+            .abbreviations
+            .iter()
+            .find(|(_, abbreviations)| abbreviations.iter().any(|a| std::ptr::eq(*a, abbreviation)))
+            .map_or("", |(nt, _)| short(nt));
+        Ok(format!(
+            "fn expand_{}_{}(p: &mut Parser) -> Result<(), ParseError> {{\n    let start = p.pos();\n{}    let mut tokens = vec![];\n{}    p.rewrite(start, tokens);\n    Ok(())\n}}\n\n",
+            name,
+            i,
+            indent(&matching),
+            indent(&tokens)
+        ))
+    }
+}
+
+/// Statements pushing the tokens of the long form of an abbreviation
+fn tokens(symbols: &[Symbol], holes: &HashMap<String, Hole>) -> Result<String, String> {
+    let mut out = String::new();
+    for symbol in symbols {
+        match symbol {
+            Symbol::SText(keyword) => {
+                let token = match keyword.as_str() {
+                    "(" => "Token::LParen".to_string(),
+                    ")" => "Token::RParen".to_string(),
+                    keyword => format!("Token::Atom({:?}.to_string())", keyword),
+                };
+                writeln!(out, "tokens.push({});", token).unwrap();
+            }
+            Symbol::SNonterm(nt) => {
+                let hole = holes
+                    .get(&nt.name)
+                    .ok_or_else(|| format!("`{}` is not in the short form", nt))?;
+                let h = format!("h_{}", short(&nt.name));
+                match (hole, nt.seq_kind()) {
+                    (Hole::One, None) => writeln!(out, "tokens.extend({}.iter().cloned());", h),
+                    (Hole::Opt, Some(SeqKind::OptSeq)) | (Hole::Many, Some(_)) => writeln!(
+                        out,
+                        "for h in &{} {{\n    tokens.extend(h.iter().cloned());\n}}",
+                        h
+                    ),
+                    _ => return Err(format!("`{}` does not match the short form", nt)),
+                }
+                .unwrap();
+            }
+            // `(\text{(}~\text{param}~\Tvaltype~\text{)})^\ast` repeats its
+            // symbols for every value of the one repeated nonterminal
+            Symbol::SGroup(group) => {
+                let repeated: Vec<_> = holes
+                    .iter()
+                    .filter(|(_, hole)| **hole != Hole::One)
+                    .filter(|(name, _)| group.symbols().iter().any(|s| mentions(s, name)))
+                    .collect();
+                let name = match (repeated.as_slice(), group.seq_kind()) {
+                    ([], None) => {
+                        out.push_str(&tokens(group.symbols(), holes)?);
+                        continue;
+                    }
+                    ([(name, hole)], Some(seq_kind)) => match (hole, seq_kind) {
+                        (Hole::Opt, SeqKind::OptSeq) | (Hole::Many, SeqKind::ManyPossibleEmpty) => {
+                            name
+                        }
+                        _ => return Err(format!("`{}` does not match the short form", symbol)),
+                    },
+                    _ => return Err(format!("`{}` is not supported", symbol)),
+                };
+                let mut inner = holes.clone();
+                inner.insert(name.to_string(), Hole::One);
+                let h = format!("h_{}", short(name));
+                let body = tokens(group.symbols(), &inner)?;
+                writeln!(out, "for {0} in &{0} {{\n{1}}}", h, indent(&body)).unwrap();
+            }
+            symbol => return Err(format!("`{}` is not supported", symbol)),
+        }
+    }
+    Ok(out)
+}
+
+fn mentions(symbol: &Symbol, nonterminal: &str) -> bool {
+    match symbol {
+        Symbol::SNonterm(nt) => nt.name == nonterminal,
+        Symbol::SGroup(group) => group.symbols().iter().any(|s| mentions(s, nonterminal)),
+        _ => false,
+    }
+}
+
+/// Space, index and identifier of a side condition like
+/// `\iff I.\ILABELS[l] = v`, the variables by [`key`]
+fn lookup(cond: &str) -> Option<(String, String, String)> {
+    let rest = cond.strip_prefix(r"\iff")?.trim_start();
+    let rest = rest.strip_prefix(r"I.\I")?;
+    let (field, rest) = rest.split_once('[')?;
+    let (index, rest) = rest.split_once(']')?;
+    let id = rest.trim_start().strip_prefix('=')?.trim();
+    let (_, index) = Var::parser(index)
+        .ok()
+        .filter(|(tail, _)| tail.is_empty())?;
+    let (_, id) = Var::parser(id).ok().filter(|(tail, _)| tail.is_empty())?;
+    Some((field.to_lowercase(), key(&index), key(&id)))
+}
+
+/// Spaces an alternative extends with `\{\ILABELS~v\} \compose I`, the
+/// variable bound or `None` for `\epsilon`, and whether it is prepended
+fn extensions(elem: &RhsElem) -> Vec<(String, Option<Var>, bool)> {
+    let Some([Expr::Compose(left, right)]) = elem.action() else {
+        return vec![];
+    };
+    let (record, prepend) = match (left.as_slice(), right.as_slice()) {
+        ([Expr::Record(pairs)], [Expr::Var(_)]) => (pairs, true),
+        ([Expr::Var(_)], [Expr::Record(pairs)]) => (pairs, false),
+        _ => return vec![],
+    };
+    record
+        .iter()
+        .filter_map(|(field, value)| {
+            let space = field.strip_prefix('I')?.to_lowercase();
+            let value = match value.as_slice() {
+                [Expr::Var(var)] => Some(var.clone()),
+                [Expr::Epsilon] => None,
+                _ => return None,
+            };
+            Some((space, value, prepend))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, fmt::Write};
+
+    use crate::{
+        codegen::{self, decoder::tests::run, short},
+        coverage::wast::{self, Token, TokenKind},
+        syntax::{fixtures, symbol::Symbol},
+        Grammar, Spec,
+    };
+
+    fn grammar() -> Grammar {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "syntax/types.rst",
+            r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 \\
+   \production{reference type} & \reftype &::=&
+     \FUNCREF ~|~ \EXTERNREF \\
+   \production{value type} & \valtype &::=&
+     \numtype ~|~ \reftype \\
+   \production{result type} & \resulttype &::=&
+     [\vec(\valtype)] \\
+   \production{function type} & \functype &::=&
+     \resulttype \to \resulttype \\
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \production{block type} & \blocktype &::=&
+     \typeidx ~|~ \valtype^? \\
+   \production{type index} & \typeidx &::=& \u32 \\
+   \production{label index} & \labelidx &::=& \u32 \\
+   \production{instruction} & \instr &::=&
+     \NOP ~|~
+     \BR~\labelidx ~|~
+     \BLOCK~\blocktype~\instr^\ast~\END \\
+   \end{array}
+",
+        );
+        grammar.add_source(
+            "text/types.rst",
+            r"
+.. math::
+   \begin{array}{llcll@{\qquad\qquad}l}
+   \production{number type} & \Tnumtype &::=&
+     \text{i32} &\Rightarrow& \I32 \\ &&|&
+     \text{i64} &\Rightarrow& \I64 \\
+   \production{reference type} & \Treftype &::=&
+     \text{funcref} &\Rightarrow& \FUNCREF \\ &&|&
+     \text{externref} &\Rightarrow& \EXTERNREF \\
+   \production{value type} & \Tvaltype &::=&
+     t{:}\Tnumtype &\Rightarrow& t \\ &&|&
+     t{:}\Treftype &\Rightarrow& t \\
+   \production{function type} & \Tfunctype &::=&
+     \text{(}~\text{func}~~t_1^\ast{:}\Tvec(\Tparam)~~t_2^\ast{:}\Tvec(\Tresult)~\text{)}
+       &\Rightarrow& [t_1^\ast] \to [t_2^\ast] \\
+   \production{parameter} & \Tparam &::=&
+     \text{(}~\text{param}~~\Tid^?~~t{:}\Tvaltype~\text{)}
+       &\Rightarrow& t \\
+   \production{result} & \Tresult &::=&
+     \text{(}~\text{result}~~t{:}\Tvaltype~\text{)}
+       &\Rightarrow& t \\
+   \production{limits} & \Tlimits &::=&
+     n{:}\Tu32 &\Rightarrow& \{ \LMIN~n, \LMAX~\epsilon \} \\ &&|&
+     n{:}\Tu32~~m{:}\Tu32 &\Rightarrow& \{ \LMIN~n, \LMAX~m \} \\
+   \end{array}
+
+.. math::
+   \begin{array}{llclll}
+   \production{parameter} &
+     \text{(}~~\text{param}~~\Tvaltype^\ast~~\text{)} &\equiv&
+     (\text{(}~~\text{param}~~\Tvaltype~~\text{)})^\ast \\
+   \production{result} &
+     \text{(}~~\text{result}~~\Tvaltype^\ast~~\text{)} &\equiv&
+     (\text{(}~~\text{result}~~\Tvaltype~~\text{)})^\ast \\
+   \end{array}
+",
+        );
+        grammar.add_source(
+            "text/instructions.rst",
+            r"
+.. math::
+   \begin{array}{llcllll}
+   \production{label} & \Tlabel_I &::=&
+     v{:}\Tid &\Rightarrow& \{\ILABELS~v\} \compose I \\ &&|&
+     \epsilon &\Rightarrow& \{\ILABELS~(\epsilon)\} \compose I \\
+   \production{instruction} & \Tinstr_I &::=&
+     \text{nop} &\Rightarrow& \NOP \\ &&|&
+     \text{br}~~l{:}\Tlabelidx_I &\Rightarrow& \BR~l \\ &&|&
+     \text{block}~~I'{:}\Tlabel_I~~\X{bt}{:}\Tblocktype_I~~(\X{in}{:}\Tinstr_{I'})^\ast~~\text{end}~~\Tid^?
+       &\Rightarrow& \BLOCK~\X{bt}~\X{in}^\ast~\END
+       \qquad (\iff \Tid^? = \epsilon \vee \Tid^? = \Tlabel) \\
+   \production{block type} & \Tblocktype_I &::=&
+     (t{:}\Tresult)^? &\Rightarrow& t^? \\ &&|&
+     \text{(}~\text{type}~~x{:}\Ttypeidx_I~\text{)} &\Rightarrow& x \\
+   \production{type index} & \Ttypeidx_I &::=&
+     x{:}\Tu32 &\Rightarrow& x \\ &&|&
+     v{:}\Tid &\Rightarrow& x \qquad (\iff I.\ITYPES[x] = v) \\
+   \production{label index} & \Tlabelidx_I &::=&
+     l{:}\Tu32 &\Rightarrow& l \\ &&|&
+     v{:}\Tid &\Rightarrow& l \qquad (\iff I.\ILABELS[l] = v) \\
+   \end{array}
+",
+        );
+        grammar
+    }
+
+    fn spec() -> Spec {
+        Spec::new(grammar())
+    }
+
+    #[test]
+    fn parse_text_productions() {
+        let spec = spec();
+        assert!(spec.grammar().errors().is_empty());
+        assert_eq!(spec.grammar().abbreviations().count(), 2);
+        let block = spec.alternatives_of("Tinstr");
+        assert_eq!(block[2].elem.symbols()[1].to_string(), r"I'{:}\Tlabel_I");
+        let label = spec.alternatives_of("Tlabel");
+        assert_eq!(
+            label[1].elem.to_string(),
+            r"\epsilon \Rightarrow \{ \ILABELS~\epsilon \} \compose I"
+        );
+    }
+
+    #[test]
+    fn skipped() {
+        let generated = codegen::module(&spec());
+        assert_eq!(generated.skipped, vec![]);
+        assert!(generated
+            .source
+            .contains("pub struct Context {\n    pub labels: Vec<Option<String>>,\n    pub types: Vec<Option<String>>,\n}"));
+    }
+
+    /// Compile the generated parser with `rustc` and run it on a few sources
+    #[test]
+    fn parse() {
+        let generated = codegen::module(&spec());
+        let output = run(
+            "parser",
+            &generated.source,
+            r#"
+mod generated;
+use generated::*;
+
+fn main() {
+    let types = Context { types: vec![None, Some("t".to_string())], ..Context::default() };
+    println!("{:?}", parse_all("(func (param i32 i64) (param $x i32) (result externref))", Context::default(), parse_functype));
+    println!("{:?}", parse_all("(func (param) (param i32))", Context::default(), parse_functype));
+    println!("{:?}", parse_all("1 0x1_0", Context::default(), parse_limits));
+    println!("{:?}", parse_all("block $outer (result i32) block br $outer br 0 end end $outer", Context::default(), parse_instr));
+    println!("{:?}", parse_all("block (type $t) (; empty ;) end ;; done", types, parse_instr));
+    println!("{:?}", parse_all("br $missing", Context::default(), parse_instr));
+    println!("{:?}", parse_all("4294967296", Context::default(), parse_limits));
+    println!("{:?}", parse_all("(func (param i32)", Context::default(), parse_functype));
+}
+"#,
+        );
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Ok(Functype([Numtype(I32), Numtype(I64), Numtype(I32)], [Reftype(Externref)]))",
+                "Ok(Functype([Numtype(I32)], []))",
+                "Ok(Limits { lmin: 1, lmax: Some(16) })",
+                "Ok(Block(Valtype(Some(Numtype(I32))), [Block(Valtype(None), [Br(1), Br(0)])]))",
+                "Ok(Block(Typeidx(1), []))",
+                r#"Err(Unbound { token: 1, id: "missing" })"#,
+                "Err(Overflow(0))",
+                r#"Err(Unexpected { token: 6, production: "functype" })"#,
+            ]
+        );
+    }
+
+    /// Floats of the lexical grammar: decimal and hexadecimal numbers rounded
+    /// to nearest, ties to even, infinities and NaNs with their payload
+    #[test]
+    fn floats() {
+        let generated = codegen::module(&spec());
+        let output = run(
+            "parser-floats",
+            &generated.source,
+            r#"
+mod generated;
+use generated::*;
+
+fn main() {
+    for source in [
+        "0x1.8p3", "0x1p-1074", "0x1p-1075", "0x1.8p-1075", "-0x0p0", "0X1p0",
+        "0x1.fffffffffffff8p1023", "0x_1p0", "0x1p", "1_000.5e-1", "1.", ".5",
+        "1e309", "1__0", "nan:0x8000000000000", "-nan:0x1", "+inf",
+    ] {
+        let value = parse_all(source, Context::default(), parse_f64);
+        println!("{} {:x?}", source, value.map(f64::to_bits));
+    }
+    for source in [
+        "0x1.fffffep127", "0x1.ffffffp127", "0x1.000001p0", "0x1.000001000000000001p0",
+        "0x1.fffffcp-127", "0x1p-149", "3.4028235e38", "1e39", "-nan", "nan:0x200000",
+        "nan:0x800000", "nan:0x0", "nan:0x",
+    ] {
+        let value = parse_all(source, Context::default(), parse_f32);
+        println!("{} {:x?}", source, value.map(f32::to_bits));
+    }
+}
+"#,
+        );
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "0x1.8p3 Ok(4028000000000000)",
+                "0x1p-1074 Ok(1)",
+                "0x1p-1075 Ok(0)",
+                "0x1.8p-1075 Ok(1)",
+                "-0x0p0 Ok(8000000000000000)",
+                r#"0X1p0 Err(Unexpected { token: 0, production: "float" })"#,
+                "0x1.fffffffffffff8p1023 Err(Overflow(0))",
+                r#"0x_1p0 Err(Unexpected { token: 0, production: "float" })"#,
+                r#"0x1p Err(Unexpected { token: 0, production: "float" })"#,
+                "1_000.5e-1 Ok(4059033333333333)",
+                "1. Ok(3ff0000000000000)",
+                r#".5 Err(Unexpected { token: 0, production: "float" })"#,
+                "1e309 Err(Overflow(0))",
+                r#"1__0 Err(Unexpected { token: 0, production: "float" })"#,
+                "nan:0x8000000000000 Ok(7ff8000000000000)",
+                "-nan:0x1 Ok(fff0000000000001)",
+                "+inf Ok(7ff0000000000000)",
+                "0x1.fffffep127 Ok(7f7fffff)",
+                "0x1.ffffffp127 Err(Overflow(0))",
+                "0x1.000001p0 Ok(3f800000)",
+                "0x1.000001000000000001p0 Ok(3f800001)",
+                "0x1.fffffcp-127 Ok(7fffff)",
+                "0x1p-149 Ok(1)",
+                "3.4028235e38 Ok(7f7fffff)",
+                "1e39 Err(Overflow(0))",
+                "-nan Ok(ffc00000)",
+                "nan:0x200000 Ok(7fa00000)",
+                "nan:0x800000 Err(Overflow(0))",
+                "nan:0x0 Err(Overflow(0))",
+                r#"nan:0x Err(Unexpected { token: 0, production: "number" })"#,
+            ]
+        );
+    }
+
+    /// Strings with `\u{...}` escapes of Unicode scalar values, and byte
+    /// escapes of two hexadecimal digits
+    #[test]
+    fn strings() {
+        let generated = codegen::module(&spec());
+        let output = run(
+            "parser-strings",
+            &generated.source,
+            r##"
+mod generated;
+use generated::*;
+
+fn main() {
+    for source in [
+        r#""\u{41}\u{1_F600}\u{e9}\t\00""#, r#""\u{D800}""#, r#""\u{110000}""#,
+        r#""\u{}""#, r#""\u41""#, r#""\u{41"#, r#""\+f""#, r#"x "\ff""#,
+    ] {
+        println!("{:?}", tokenize(source));
+    }
+}
+"##,
+        );
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Ok([Str([65, 240, 159, 152, 128, 195, 169, 9, 0])])",
+                "Err(Lex(1))",
+                "Err(Lex(1))",
+                "Err(Lex(1))",
+                "Err(Lex(1))",
+                "Err(Lex(1))",
+                "Err(Lex(1))",
+                "Ok([Atom(\"x\"), Str([255])])",
+            ]
+        );
+    }
+
+    /// Parsers of the forms of a module, by the keyword they start with:
+    /// the whole `module` if it was generated, otherwise each field with the
+    /// parser of the nonterminal that `\Tmodulefield` names for it
+    fn field_parsers<'s>(spec: &'s Spec, source: &str) -> BTreeMap<&'s str, &'s str> {
+        let generated = |name: &str| source.contains(&format!("pub fn parse_{}(", name));
+        let keyword = |nonterminal: &'s str| {
+            let alternatives = spec.alternatives_of(nonterminal);
+            match alternatives.first()?.elem.symbols() {
+                [Symbol::SText(open), Symbol::SText(keyword), ..] if open == "(" => {
+                    Some(keyword.as_str())
+                }
+                _ => None,
+            }
+        };
+        let mut parsers = BTreeMap::new();
+        if generated("module") {
+            parsers.insert("module", "module");
+            return parsers;
+        }
+        for alternative in spec.alternatives_of("Tmodulefield") {
+            let [Symbol::SBind(bind)] = alternative.elem.symbols() else {
+                continue;
+            };
+            let Symbol::SNonterm(nt) = bind.symbol() else {
+                continue;
+            };
+            let name = short(&nt.name);
+            if let Some(keyword) = keyword(&nt.name).filter(|_| generated(name)) {
+                parsers.insert(keyword, name);
+            }
+        }
+        parsers
+    }
+
+    /// Forms of a module to parse, with the keyword they start with: the
+    /// whole module if `module` has a parser, otherwise each of its fields
+    fn module_forms(module: &[Token<'_>], whole: bool) -> Vec<(String, String)> {
+        if whole {
+            return vec![("module".to_string(), wast::source(module))];
+        }
+        let mut forms = vec![];
+        let mut depth = 0;
+        let mut start = 0;
+        for (i, token) in module.iter().enumerate() {
+            match token.kind {
+                TokenKind::LParen => {
+                    depth += 1;
+                    start = if depth == 2 { i } else { start };
+                }
+                TokenKind::RParen => {
+                    depth -= 1;
+                    if depth != 1 {
+                        continue;
+                    }
+                    let keyword = match module[start + 1].kind {
+                        TokenKind::Atom(keyword) => keyword,
+                        _ => "",
+                    };
+                    forms.push((keyword.to_string(), wast::source(&module[start..=i])));
+                }
+                _ => {}
+            }
+        }
+        forms
+    }
+
+    /// Source of the module of each `(assert_malformed (module quote ...))`
+    /// command, with the line of the command, or none if its strings are not
+    /// UTF-8
+    fn quoted_modules(tokens: &[Token<'_>]) -> Vec<(usize, Option<String>)> {
+        let mut modules = vec![];
+        for (i, token) in tokens.iter().enumerate() {
+            let kinds: Vec<_> = tokens[i..].iter().take(5).map(|t| t.kind).collect();
+            let [TokenKind::LParen, TokenKind::Atom("assert_malformed"), TokenKind::LParen, TokenKind::Atom("module"), TokenKind::Atom("quote")] =
+                kinds[..]
+            else {
+                continue;
+            };
+            let strings: Option<Vec<_>> = tokens[i + 5..wast::closing(tokens, i + 2) - 1]
+                .iter()
+                .map(|t| match t.kind {
+                    TokenKind::Str(s) => String::from_utf8(wast::unescape(s)?).ok(),
+                    _ => None,
+                })
+                .collect();
+            let source = strings.map(|strings| format!("(module {})", strings.join(" ")));
+            modules.push((token.line, source));
+        }
+        modules
+    }
+
+    /// Parse the text modules of `scripts` with the parser generated from
+    /// `spec`, in an empty identifier context, printing the failures and the
+    /// counts of each script. The forms of well-formed modules must parse or
+    /// only miss an identifier of the context. The modules of
+    /// `assert_malformed` must be rejected, unless a form has no parser, is
+    /// only rejected for an identifier, or the module has several fields,
+    /// which are parsed apart.
+    fn check_scripts(name: &str, spec: &Spec, scripts: &[(String, String)]) {
+        let generated = codegen::module(spec);
+        let parsers = field_parsers(spec, &generated.source);
+        let whole = parsers.contains_key("module");
+
+        // the modules to parse, by where they are, whether they are
+        // malformed and the forms to parse, with the keyword they start with
+        let mut modules = vec![];
+        for (script, source) in scripts {
+            let tokens = wast::tokenize(source).unwrap();
+            for module in wast::modules(&tokens) {
+                let at = format!("{}:{}", script, tokens[module.start].line);
+                modules.push((at, false, module_forms(&tokens[module], whole)));
+            }
+            for (line, source) in quoted_modules(&tokens) {
+                let tokens = source.as_deref().map(wast::tokenize);
+                let forms = match tokens {
+                    Some(Ok(tokens)) => module_forms(&tokens, whole),
+                    _ => vec![],
+                };
+                modules.push((format!("{}:{}", script, line), true, forms));
+            }
+        }
+
+        let mut main = "mod generated;\nuse generated::*;\n\n".to_string();
+        main.push_str(
+            "fn parse(keyword: &str, source: &str) -> Option<Result<(), ParseError>> {\n",
+        );
+        main.push_str("    let ctx = Context::default();\n    match keyword {\n");
+        for (keyword, parser) in &parsers {
+            writeln!(
+                main,
+                "        {:?} => Some(parse_all(source, ctx, parse_{}).map(|_| ())),",
+                keyword, parser
+            )
+            .unwrap();
+        }
+        main.push_str("        _ => None,\n    }\n}\n\nconst FORMS: &[(&str, &str)] = &[\n");
+        for (_, _, forms) in &modules {
+            for (keyword, form) in forms {
+                writeln!(main, "    ({:?}, {:?}),", keyword, form).unwrap();
+            }
+        }
+        main.push_str(
+            "];\n\nfn main() {\n    \
+             for (keyword, source) in FORMS {\n        \
+             println!(\"{:?}\", parse(keyword, source));\n    \
+             }\n}\n",
+        );
+        let output = run(name, &generated.source, &main);
+        let mut lines = output.lines();
+        let forms = modules
+            .iter()
+            .map(|(_, _, forms)| forms.len())
+            .sum::<usize>();
+        assert_eq!(output.lines().count(), forms);
+
+        // parsed, unbound, without a parser, malformed rejected and
+        // malformed unchecked, by script
+        let mut counts: BTreeMap<&str, [usize; 5]> = BTreeMap::new();
+        let mut failures = vec![];
+        for (at, malformed, forms) in &modules {
+            let script = at.split(':').next().unwrap_or_default();
+            let count = counts.entry(script).or_default();
+            let results: Vec<_> = lines.by_ref().take(forms.len()).collect();
+            let unbound = |line: &&str| line.starts_with("Some(Err(Unbound");
+            if *malformed {
+                let rejected = |line: &&str| line.starts_with("Some(Err(") && !unbound(line);
+                if results.iter().any(rejected) {
+                    count[3] += 1;
+                } else if results.len() == 1 && results[0] == "Some(Ok(()))" {
+                    failures.push(format!("{}: malformed, but parsed", at));
+                } else {
+                    count[4] += 1;
+                }
+                continue;
+            }
+            for (line, (keyword, _)) in results.iter().zip(forms) {
+                match *line {
+                    "Some(Ok(()))" => count[0] += 1,
+                    "None" => count[2] += 1,
+                    line if unbound(&line) => count[1] += 1,
+                    error => failures.push(format!("{} ({}): {}", at, keyword, error)),
+                }
+            }
+        }
+        for (script, [parsed, unbound, unparsed, rejected, unchecked]) in &counts {
+            println!(
+                "{}: {} parsed, {} unbound, {} without a parser, \
+                 {} malformed rejected, {} malformed unchecked",
+                script, parsed, unbound, unparsed, rejected, unchecked
+            );
+        }
+        assert!(forms > 0, "no forms to parse");
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    /// Script of well-formed and malformed modules of [`field_spec`]
+    const SCRIPT: &str = r#"
+(module (func (param i32) (result i64)) (func))
+(assert_invalid (module (func (param $x i32) (param $x i64))) "duplicate local")
+(assert_malformed (module quote "(func (param i33))") "unknown type")
+(assert_malformed (module quote "(func (result i32) (param i32))") "unexpected token")
+(assert_malformed (module quote "(type (func))") "unknown field")
+(assert_malformed (module quote "(func (param i32)) (func)" "(func (result i33))") "unknown type")
+"#;
+
+    /// [`spec`] with the functions types as the fields of a module
+    fn field_spec() -> Spec {
+        let mut grammar = grammar();
+        grammar.add_source(
+            "text/modules.rst",
+            r"
+.. math::
+   \begin{array}{llclll}
+   \production{module field} & \Tmodulefield_I &::=&
+     \X{ft}{:}\Tfunctype &\Rightarrow& \X{ft} \\
+   \end{array}
+",
+        );
+        Spec::new(grammar)
+    }
+
+    /// The checks of the test suite on a script
+    #[test]
+    fn scripts() {
+        let scripts = [("fields.wast".to_string(), SCRIPT.to_string())];
+        check_scripts("parser-scripts", &field_spec(), &scripts);
+    }
+
+    /// A malformed module that parses fails the checks
+    #[test]
+    #[should_panic(expected = "accepted.wast:1: malformed, but parsed")]
+    fn accepted() {
+        let script = r#"(assert_malformed (module quote "(func (param i32))") "")"#;
+        let scripts = [("accepted.wast".to_string(), script.to_string())];
+        check_scripts("parser-accepted", &field_spec(), &scripts);
+    }
+
+    /// Parse the text modules of the test suite
+    #[test]
+    #[ignore = "needs a checkout of wasmmeta in `WASMMETA_PATH`"]
+    fn suite() {
+        let path = fixtures::wasmmeta_path();
+        let spec = fixtures::wasmmeta_spec(&path);
+        check_scripts("parser-suite", &spec, &fixtures::scripts(&path));
+    }
+}
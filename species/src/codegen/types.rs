@@ -0,0 +1,525 @@
+//! Rust types for the abstract syntax.
+//!
+//! A nonterminal whose alternatives each start with a terminal becomes an
+//! enum, a record becomes a struct, a function type or a juxtaposition of
+//! nonterminals becomes a tuple struct and a single symbol becomes an alias.
+
+use std::{collections::HashMap, fmt::Write};
+
+use crate::{
+    codegen::{format_of, Skipped},
+    parser::SeqKind,
+    spec::Spec,
+    syntax::{
+        symbol::{SNonterm, Symbol},
+        RhsElem,
+    },
+};
+
+/// Nonterminals whose values are primitive, with their Rust type
+const BUILTINS: &[(&str, &str)] = &[
+    ("byte", "u8"),
+    ("u32", "u32"),
+    ("u64", "u64"),
+    ("s33", "i64"),
+    ("i32", "i32"),
+    ("i64", "i64"),
+    ("f32", "f32"),
+    ("f64", "f64"),
+    ("name", "String"),
+];
+
+/// Type names the generated code uses itself
+const RESERVED: &[&str] = &[
+    "Box",
+    "Context",
+    "DecodeError",
+    "Option",
+    "ParseError",
+    "Parser",
+    "Reader",
+    "Result",
+    "String",
+    "Token",
+    "Vec",
+];
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Ty {
+    /// Abstract nonterminal with a [`TypeDef`]
+    Named(String),
+    /// Nonterminal in [`BUILTINS`]
+    Builtin(String),
+    Vec(Box<Ty>),
+    /// Sequence of at least one value, e.g. `\instr^+`, which is a `Vec`
+    NonEmpty(Box<Ty>),
+    Option(Box<Ty>),
+    Box(Box<Ty>),
+    Unit,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypeDef {
+    Alias(Ty),
+    Struct(Fields),
+    Enum(Vec<Variant>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Fields {
+    Unit,
+    Tuple(Vec<Ty>),
+    /// Record fields, keyed by terminal, e.g. `LMIN`
+    Named(Vec<(String, Ty)>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Variant {
+    pub name: String,
+    /// Terminals of the alternative, the first of which names the variant
+    pub terminals: Vec<String>,
+    pub fields: Fields,
+}
+
+/// Types of all abstract nonterminals that have a supported shape
+#[derive(Debug)]
+pub struct TypeModel {
+    defs: Vec<(String, TypeDef)>,
+    index: HashMap<String, usize>,
+    skipped: Vec<Skipped>,
+}
+
+impl TypeModel {
+    pub fn new(spec: &Spec) -> Self {
+        let mut candidates = vec![];
+        let mut skipped = vec![];
+        for nonterminal in spec.grammar().nonterminals() {
+            if format_of(nonterminal).is_some() || builtin(nonterminal).is_some() {
+                continue;
+            }
+            let alternatives = spec.alternatives_of(nonterminal);
+            let elems: Vec<_> = alternatives.iter().map(|a| a.elem).collect();
+            match type_def(nonterminal, &elems) {
+                Ok(def) => candidates.push((nonterminal.to_string(), def)),
+                Err(reason) => skipped.push(skip(nonterminal, reason)),
+            }
+        }
+
+        // drop types referring to skipped or undefined ones until none is left
+        loop {
+            let defined: Vec<_> = candidates.iter().map(|(n, _)| n.clone()).collect();
+            let (kept, dropped): (Vec<_>, Vec<_>) = candidates
+                .into_iter()
+                .map(|(name, def)| {
+                    let missing = references(&def).into_iter().find(|r| !defined.contains(r));
+                    (name, def, missing)
+                })
+                .partition(|(_, _, missing)| missing.is_none());
+            candidates = kept.into_iter().map(|(n, d, _)| (n, d)).collect();
+            if dropped.is_empty() {
+                break;
+            }
+            for (name, _, missing) in dropped {
+                let missing = missing.unwrap_or_default();
+                skipped.push(skip(
+                    &name,
+                    format!("refers to `{}` without a type", missing),
+                ));
+            }
+        }
+
+        let index = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (name.clone(), i))
+            .collect();
+        Self {
+            defs: candidates,
+            index,
+            skipped,
+        }
+    }
+
+    pub fn defs(&self) -> &[(String, TypeDef)] {
+        &self.defs
+    }
+
+    pub fn get(&self, nonterminal: &str) -> Option<&TypeDef> {
+        self.index.get(nonterminal).map(|&i| &self.defs[i].1)
+    }
+
+    pub fn skipped(&self) -> &[Skipped] {
+        &self.skipped
+    }
+
+    /// Type of the values of an abstract nonterminal
+    pub fn ty(&self, nonterminal: &str) -> Option<Ty> {
+        if builtin(nonterminal).is_some() {
+            Some(Ty::Builtin(nonterminal.to_string()))
+        } else {
+            self.get(nonterminal)
+                .map(|_| Ty::Named(nonterminal.to_string()))
+        }
+    }
+
+    /// Expand aliases and boxes, so that types that Rust considers equal
+    /// compare equal
+    pub fn resolve(&self, ty: &Ty) -> Ty {
+        match ty {
+            Ty::Named(name) => match self.get(name) {
+                Some(TypeDef::Alias(ty)) => self.resolve(ty),
+                _ => ty.clone(),
+            },
+            Ty::Vec(ty) | Ty::NonEmpty(ty) => Ty::Vec(Box::new(self.resolve(ty))),
+            Ty::Option(ty) => Ty::Option(Box::new(self.resolve(ty))),
+            Ty::Box(ty) => self.resolve(ty),
+            Ty::Builtin(_) | Ty::Unit => ty.clone(),
+        }
+    }
+
+    /// Rust items for all types
+    pub fn emit(&self) -> String {
+        let mut out = String::new();
+        for (name, def) in &self.defs {
+            let name = type_name(name);
+            let derive = "#[derive(Debug, Clone, PartialEq)]\n";
+            match def {
+                TypeDef::Alias(ty) => {
+                    writeln!(out, "pub type {} = {};\n", name, rust_ty(ty)).unwrap();
+                }
+                TypeDef::Struct(Fields::Named(fields)) => {
+                    writeln!(out, "{}pub struct {} {{", derive, name).unwrap();
+                    for (key, ty) in fields {
+                        writeln!(out, "    pub {}: {},", field_name(key), rust_ty(ty)).unwrap();
+                    }
+                    out.push_str("}\n\n");
+                }
+                TypeDef::Struct(fields) => {
+                    writeln!(
+                        out,
+                        "{}pub struct {}{};\n",
+                        derive,
+                        name,
+                        emit_fields(fields, "pub ")
+                    )
+                    .unwrap();
+                }
+                TypeDef::Enum(variants) => {
+                    writeln!(out, "{}pub enum {} {{", derive, name).unwrap();
+                    for variant in variants {
+                        let fields = emit_fields(&variant.fields, "");
+                        writeln!(out, "    {}{},", variant.name, fields).unwrap();
+                    }
+                    out.push_str("}\n\n");
+                }
+            }
+        }
+        out
+    }
+}
+
+fn emit_fields(fields: &Fields, vis: &str) -> String {
+    match fields {
+        Fields::Unit => String::new(),
+        Fields::Tuple(tys) => {
+            let tys: Vec<_> = tys
+                .iter()
+                .map(|t| format!("{}{}", vis, rust_ty(t)))
+                .collect();
+            format!("({})", tys.join(", "))
+        }
+        Fields::Named(fields) => {
+            let fields: Vec<_> = fields
+                .iter()
+                .map(|(key, ty)| format!("{}{}: {}", vis, field_name(key), rust_ty(ty)))
+                .collect();
+            format!(" {{ {} }}", fields.join(", "))
+        }
+    }
+}
+
+fn type_def(nonterminal: &str, elems: &[&RhsElem]) -> Result<TypeDef, String> {
+    let ty = |nt: &SNonterm| nonterm_ty(nonterminal, nt);
+    match elems {
+        [] => Err("no alternatives".to_string()),
+        [elem] if !matches!(elem.symbols().first(), Some(Symbol::STerm(_))) => {
+            let symbols: Vec<_> = elem
+                .symbols()
+                .iter()
+                .filter(|s| !matches!(s, Symbol::STerm(_)))
+                .collect();
+            match symbols.as_slice() {
+                [Symbol::SRecord(record)] => record_fields(nonterminal, record.pairs())
+                    .map(|fields| TypeDef::Struct(Fields::Named(fields))),
+                [Symbol::SArrow(arrow)] => Ok(TypeDef::Struct(Fields::Tuple(vec![
+                    ty(arrow.from()),
+                    ty(arrow.to()),
+                ]))),
+                [symbol] => symbol_ty(nonterminal, symbol).map(TypeDef::Alias),
+                symbols => symbols
+                    .iter()
+                    .map(|s| symbol_ty(nonterminal, s))
+                    .collect::<Result<_, _>>()
+                    .map(|tys| TypeDef::Struct(Fields::Tuple(tys))),
+            }
+        }
+        elems => {
+            let mut variants: Vec<Variant> = vec![];
+            for elem in elems {
+                let variant = variant(nonterminal, elem)?;
+                if variants.iter().any(|v| v.name == variant.name) {
+                    return Err(format!("two alternatives named `{}`", variant.name));
+                }
+                variants.push(variant);
+            }
+            Ok(TypeDef::Enum(variants))
+        }
+    }
+}
+
+fn variant(nonterminal: &str, elem: &RhsElem) -> Result<Variant, String> {
+    let terminals: Vec<_> = elem
+        .symbols()
+        .iter()
+        .filter_map(|s| match s {
+            Symbol::STerm(name) => Some(name.clone()),
+            _ => None,
+        })
+        .collect();
+    let others: Vec<_> = elem
+        .symbols()
+        .iter()
+        .filter(|s| !matches!(s, Symbol::STerm(_)))
+        .collect();
+
+    match (elem.symbols().first(), others.as_slice()) {
+        (Some(Symbol::STerm(terminal)), others) => {
+            let fields = match others {
+                [] => Fields::Unit,
+                [Symbol::SRecord(record)] => {
+                    Fields::Named(record_fields(nonterminal, record.pairs())?)
+                }
+                others => Fields::Tuple(
+                    others
+                        .iter()
+                        .map(|s| symbol_ty(nonterminal, s))
+                        .collect::<Result<_, _>>()?,
+                ),
+            };
+            Ok(Variant {
+                name: variant_name(terminal),
+                terminals,
+                fields,
+            })
+        }
+        (Some(Symbol::SNonterm(nt)), [_]) => Ok(Variant {
+            name: type_name(&nt.name),
+            terminals,
+            fields: Fields::Tuple(vec![nonterm_ty(nonterminal, nt)]),
+        }),
+        _ => Err(format!("alternative `{}` has no leading terminal", elem)),
+    }
+}
+
+fn record_fields(owner: &str, pairs: &[(String, Symbol)]) -> Result<Vec<(String, Ty)>, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| Ok((key.clone(), symbol_ty(owner, value)?)))
+        .collect()
+}
+
+fn symbol_ty(owner: &str, symbol: &Symbol) -> Result<Ty, String> {
+    match symbol {
+        Symbol::SNonterm(nt) => Ok(nonterm_ty(owner, nt)),
+        Symbol::SVec(vec) => Ok(Ty::Vec(Box::new(nonterm_ty(owner, vec.over())))),
+        Symbol::SBracedVec(vec) => Ok(Ty::Vec(Box::new(nonterm_ty(owner, vec.inner().over())))),
+        symbol => Err(format!("`{}` has no type", symbol)),
+    }
+}
+
+/// Type of a nonterminal occurring in the productions of `owner`. Direct
+/// recursion is boxed.
+fn nonterm_ty(owner: &str, nt: &SNonterm) -> Ty {
+    let ty = if builtin(&nt.name).is_some() {
+        Ty::Builtin(nt.name.clone())
+    } else {
+        Ty::Named(nt.name.clone())
+    };
+    match nt.seq_kind() {
+        None if nt.name == owner => Ty::Box(Box::new(ty)),
+        None => ty,
+        Some(SeqKind::OptSeq) if nt.name == owner => Ty::Option(Box::new(Ty::Box(Box::new(ty)))),
+        Some(SeqKind::OptSeq) => Ty::Option(Box::new(ty)),
+        Some(SeqKind::ManyNonEmpty) => Ty::NonEmpty(Box::new(ty)),
+        Some(_) => Ty::Vec(Box::new(ty)),
+    }
+}
+
+/// Abstract nonterminals a type definition refers to
+pub fn references(def: &TypeDef) -> Vec<String> {
+    fn ty_refs(ty: &Ty, refs: &mut Vec<String>) {
+        match ty {
+            Ty::Named(name) => refs.push(name.clone()),
+            Ty::Vec(ty) | Ty::NonEmpty(ty) | Ty::Option(ty) | Ty::Box(ty) => ty_refs(ty, refs),
+            Ty::Builtin(_) | Ty::Unit => {}
+        }
+    }
+    fn fields_refs(fields: &Fields, refs: &mut Vec<String>) {
+        match fields {
+            Fields::Unit => {}
+            Fields::Tuple(tys) => tys.iter().for_each(|t| ty_refs(t, refs)),
+            Fields::Named(fields) => fields.iter().for_each(|(_, t)| ty_refs(t, refs)),
+        }
+    }
+
+    let mut refs = vec![];
+    match def {
+        TypeDef::Alias(ty) => ty_refs(ty, &mut refs),
+        TypeDef::Struct(fields) => fields_refs(fields, &mut refs),
+        TypeDef::Enum(variants) => variants
+            .iter()
+            .for_each(|v| fields_refs(&v.fields, &mut refs)),
+    }
+    refs
+}
+
+fn skip(nonterminal: &str, reason: impl Into<String>) -> Skipped {
+    Skipped {
+        nonterminal: nonterminal.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// Rust type of a builtin nonterminal, e.g. `i64` for `s33`
+pub fn builtin(nonterminal: &str) -> Option<&'static str> {
+    BUILTINS
+        .iter()
+        .find(|(name, _)| *name == nonterminal)
+        .map(|(_, ty)| *ty)
+}
+
+pub fn rust_ty(ty: &Ty) -> String {
+    match ty {
+        Ty::Named(name) => type_name(name),
+        Ty::Builtin(name) => builtin(name).unwrap_or("()").to_string(),
+        Ty::Vec(ty) | Ty::NonEmpty(ty) => format!("Vec<{}>", rust_ty(ty)),
+        Ty::Option(ty) => format!("Option<{}>", rust_ty(ty)),
+        Ty::Box(ty) => format!("Box<{}>", rust_ty(ty)),
+        Ty::Unit => "()".to_string(),
+    }
+}
+
+/// `Functype` for `functype`
+pub fn type_name(nonterminal: &str) -> String {
+    let mut chars = nonterminal.chars().filter(|c| c.is_ascii_alphanumeric());
+    let name: String = chars
+        .next()
+        .map(|c| c.to_ascii_uppercase())
+        .into_iter()
+        .chain(chars)
+        .collect();
+    if RESERVED.contains(&name.as_str()) {
+        name + "Ty"
+    } else {
+        name
+    }
+}
+
+/// `Tableget` for `TABLEGET`
+pub fn variant_name(terminal: &str) -> String {
+    type_name(&terminal.to_ascii_lowercase())
+}
+
+/// `lmin` for `LMIN`
+pub fn field_name(key: &str) -> String {
+    ident(&key.to_ascii_lowercase())
+}
+
+/// Escape keywords, e.g. `r#in`
+pub fn ident(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "as", "break", "const", "continue", "else", "enum", "false", "fn", "for", "if", "impl",
+        "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "static",
+        "struct", "trait", "true", "type", "use", "where", "while",
+    ];
+    if KEYWORDS.contains(&name) {
+        format!("r#{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    use super::*;
+
+    pub(crate) fn spec() -> Spec {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "syntax/types.rst",
+            r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 \\
+   \production{value type} & \valtype &::=&
+     \numtype ~|~ \reftype \\
+   \production{reference type} & \reftype &::=&
+     \FUNCREF ~|~ \EXTERNREF \\
+   \production{result type} & \resulttype &::=&
+     [\vec(\valtype)] \\
+   \production{function type} & \functype &::=&
+     \resulttype \to \resulttype \\
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \production{table type} & \tabletype &::=&
+     \limits~\reftype \\
+   \production{element segment mode} & \elemmode &::=&
+     \EPASSIVE ~|~
+     \EACTIVE~\{ \ETABLE~\tableidx, \EOFFSET~\u32 \} \\
+   \production{table index} & \tableidx &::=& \u32 \\
+   \production{element type} & \elemtype &::=& \funcref \\
+   \end{array}
+",
+        );
+        assert!(grammar.errors().is_empty(), "{:?}", grammar.errors());
+        Spec::new(grammar)
+    }
+
+    #[test]
+    fn type_defs() {
+        let model = TypeModel::new(&spec());
+        assert_eq!(
+            model.get("functype"),
+            Some(&TypeDef::Struct(Fields::Tuple(vec![
+                Ty::Named("resulttype".to_string()),
+                Ty::Named("resulttype".to_string())
+            ])))
+        );
+        assert_eq!(
+            model.resolve(&Ty::Named("resulttype".to_string())),
+            Ty::Vec(Box::new(Ty::Named("valtype".to_string())))
+        );
+        assert!(matches!(model.get("valtype"), Some(TypeDef::Enum(v)) if v[1].name == "Reftype"));
+        assert_eq!(
+            model.skipped(),
+            &[skip("elemtype", "refers to `funcref` without a type")]
+        );
+    }
+
+    #[test]
+    fn emit() {
+        let source = TypeModel::new(&spec()).emit();
+        for item in [
+            "pub enum Numtype {\n    I32,\n    I64,\n}",
+            "pub type Resulttype = Vec<Valtype>;",
+            "pub struct Functype(pub Resulttype, pub Resulttype);",
+            "pub struct Limits {\n    pub lmin: u32,\n    pub lmax: Option<u32>,\n}",
+            "    Eactive { etable: Tableidx, eoffset: u32 },",
+        ] {
+            assert!(source.contains(item), "`{}` missing in\n{}", item, source);
+        }
+    }
+}
@@ -0,0 +1,607 @@
+//! Coverage of the text format by the `.wast` scripts of the test suite.
+//!
+//! Every `(module ...)` of a script is read with the `\T...` productions,
+//! starting from `\Tmodule`, the way the generated parser reads it: all
+//! alternatives of a production are tried and the one reading the most tokens
+//! wins, and iterations are greedy. The alternatives and abbreviations of the
+//! parse are recorded for the script. Lexical nonterminals, e.g. `\Tu32` and
+//! `\Tid`, are read as one token each instead of with their productions over
+//! characters, and side conditions are not checked.
+
+pub mod wast;
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt::{self, Display},
+};
+
+use crate::{
+    codegen::{format_of, short},
+    parser::SeqKind,
+    spec::{Alternative, Spec},
+    syntax::{
+        symbol::{SNonterm, Symbol},
+        visit::Visit,
+        Abbreviation,
+    },
+    Origin,
+};
+
+use self::wast::{LexError, Token, TokenKind};
+
+const START: &str = "Tmodule";
+
+/// Nonterminals read as one token, a `$` identifier, a string or a number
+const LEXICAL: &[&str] = &[
+    "id", "name", "string", "byte", "u32", "u64", "s33", "i32", "i64", "f32", "f64",
+];
+
+/// Alternatives and abbreviations of the text format exercised by each script
+pub struct Coverage<'s> {
+    items: Vec<Item<'s>>,
+    /// Items of each nonterminal, alternatives before abbreviations
+    by_nonterminal: HashMap<&'s str, Vec<usize>>,
+    tests: Vec<Test>,
+}
+
+/// Alternative or abbreviation of a text nonterminal reachable from
+/// `\Tmodule`
+#[derive(Debug)]
+pub enum Item<'s> {
+    Alternative(Alternative<'s>),
+    Abbreviation {
+        nonterminal: &'s str,
+        origin: &'s Origin,
+        abbreviation: &'s Abbreviation,
+    },
+}
+
+/// Modules of one script and what they exercise
+#[derive(Debug)]
+pub struct Test {
+    pub name: String,
+    /// Line of each module in text format
+    pub modules: Vec<usize>,
+    /// Lines of the modules that do not parse
+    pub failures: Vec<usize>,
+    exercised: BTreeSet<usize>,
+}
+
+impl<'s> Item<'s> {
+    pub fn nonterminal(&self) -> &'s str {
+        match self {
+            Item::Alternative(alternative) => alternative.production.nonterminal(),
+            Item::Abbreviation { nonterminal, .. } => nonterminal,
+        }
+    }
+
+    pub fn origin(&self) -> &'s Origin {
+        match self {
+            Item::Alternative(alternative) => alternative.origin,
+            Item::Abbreviation { origin, .. } => origin,
+        }
+    }
+
+    /// Symbols read from the source, the short form of an abbreviation
+    fn symbols(&self) -> &'s [Symbol] {
+        match self {
+            Item::Alternative(alternative) => alternative.elem.symbols(),
+            Item::Abbreviation { abbreviation, .. } => abbreviation.short(),
+        }
+    }
+
+    /// Whether all of its symbols can be read from tokens
+    pub fn is_supported(&self) -> bool {
+        self.symbols().iter().all(supported)
+    }
+}
+
+impl<'s> Coverage<'s> {
+    pub fn new(spec: &'s Spec) -> Self {
+        let mut abbreviations: HashMap<&str, Vec<_>> = HashMap::new();
+        for (origin, abbreviation) in spec.grammar().abbreviations() {
+            let nonterminal = spec
+                .grammar()
+                .productions()
+                .map(|(_, p)| p)
+                .find(|p| {
+                    Some(p.name()) == abbreviation.name()
+                        && matches!(format_of(p.nonterminal()), Some(('T', _)))
+                })
+                .map(|p| p.nonterminal());
+            if let Some(nonterminal) = nonterminal {
+                abbreviations
+                    .entry(nonterminal)
+                    .or_default()
+                    .push((origin, abbreviation));
+            }
+        }
+
+        let mut coverage = Self {
+            items: vec![],
+            by_nonterminal: HashMap::new(),
+            tests: vec![],
+        };
+        let mut pending = vec![START];
+        while let Some(nonterminal) = pending.pop() {
+            let lexical = LEXICAL.contains(&short(nonterminal));
+            if lexical || coverage.by_nonterminal.contains_key(nonterminal) {
+                continue;
+            }
+            let mut items: Vec<_> = spec
+                .alternatives_of(nonterminal)
+                .into_iter()
+                .map(Item::Alternative)
+                .collect();
+            for (origin, abbreviation) in abbreviations.get(nonterminal).into_iter().flatten() {
+                items.push(Item::Abbreviation {
+                    nonterminal,
+                    origin,
+                    abbreviation,
+                });
+            }
+            let mut ids = vec![];
+            for item in items {
+                let mut names = Nonterminals::default();
+                item.symbols().iter().for_each(|s| names.visit_symbol(s));
+                pending.extend(names.0);
+                ids.push(coverage.items.len());
+                coverage.items.push(item);
+            }
+            coverage.by_nonterminal.insert(nonterminal, ids);
+        }
+        coverage
+    }
+
+    /// Read the modules of a `.wast` script, recorded as test `name`
+    pub fn add_script(&mut self, name: impl Into<String>, source: &str) -> Result<&Test, LexError> {
+        let tokens = wast::tokenize(source)?;
+        let mut test = Test {
+            name: name.into(),
+            modules: vec![],
+            failures: vec![],
+            exercised: BTreeSet::new(),
+        };
+        for module in wast::modules(&tokens) {
+            let line = tokens[module.start].line;
+            test.modules.push(line);
+            let mut recognizer = Recognizer {
+                coverage: self,
+                tokens: &tokens[module.clone()],
+                memo: HashMap::new(),
+                trail: vec![],
+            };
+            match recognizer.nonterminal(START, 0) {
+                Some(end) if end == module.len() => test.exercised.extend(recognizer.trail),
+                _ => test.failures.push(line),
+            }
+        }
+        self.tests.push(test);
+        Ok(self.tests.last().unwrap())
+    }
+
+    pub fn items(&self) -> &[Item<'s>] {
+        &self.items
+    }
+
+    pub fn tests(&self) -> &[Test] {
+        &self.tests
+    }
+
+    /// Alternatives and abbreviations that the modules of a test read
+    pub fn exercised_by<'c>(&'c self, test: &'c Test) -> impl Iterator<Item = &'c Item<'s>> {
+        test.exercised.iter().map(|&i| &self.items[i])
+    }
+
+    /// Nonterminals that the modules of a test read
+    pub fn productions_exercised_by(&self, test: &Test) -> BTreeSet<&'s str> {
+        self.exercised_by(test).map(Item::nonterminal).collect()
+    }
+
+    /// Supported alternatives and abbreviations that no test exercises
+    pub fn unexercised(&self) -> Vec<&Item<'s>> {
+        let exercised: BTreeSet<usize> = self
+            .tests
+            .iter()
+            .flat_map(|t| t.exercised.iter().copied())
+            .collect();
+        (0..self.items.len())
+            .filter(|i| !exercised.contains(i) && self.items[*i].is_supported())
+            .map(|i| &self.items[i])
+            .collect()
+    }
+}
+
+/// Nonterminals that a symbol mentions
+#[derive(Default)]
+struct Nonterminals<'ast>(Vec<&'ast str>);
+
+impl<'ast> Visit<'ast> for Nonterminals<'ast> {
+    fn visit_snonterm(&mut self, node: &'ast SNonterm) {
+        self.0.push(&node.name);
+    }
+}
+
+fn supported(symbol: &Symbol) -> bool {
+    match symbol {
+        Symbol::SText(_) => true,
+        Symbol::SNonterm(nt) => nt.seq_kind() != Some(&SeqKind::ManyN),
+        Symbol::SBind(bind) => supported(bind.symbol()),
+        Symbol::SVec(vec) => vec.head() == "Tvec" && vec.over().seq_kind().is_none(),
+        Symbol::SGroup(group) => {
+            group.seq_kind() != Some(&SeqKind::ManyN) && group.symbols().iter().all(supported)
+        }
+        _ => false,
+    }
+}
+
+/// End of a parse and the items it read
+type Parse = (usize, Vec<usize>);
+
+/// Recursive descent over the tokens of one module
+struct Recognizer<'c, 's, 't> {
+    coverage: &'c Coverage<'s>,
+    tokens: &'t [Token<'t>],
+    /// End and items of the parse of a nonterminal at a token
+    memo: HashMap<(&'s str, usize), Option<Parse>>,
+    /// Items of the parse so far
+    trail: Vec<usize>,
+}
+
+impl<'s> Recognizer<'_, 's, '_> {
+    fn nonterminal(&mut self, name: &'s str, pos: usize) -> Option<usize> {
+        let token = self.tokens.get(pos).map(|t| t.kind);
+        if let Some(matches) = lexical(short(name), token) {
+            return matches.then_some(pos + 1);
+        }
+        if let Some(memo) = self.memo.get(&(name, pos)) {
+            let (end, items) = memo.clone()?;
+            self.trail.extend(items);
+            return Some(end);
+        }
+        // a nonterminal in progress at the same token fails, so left
+        // recursion ends
+        self.memo.insert((name, pos), None);
+
+        let mut best: Option<Parse> = None;
+        let coverage = self.coverage;
+        for &item in coverage.by_nonterminal.get(name).into_iter().flatten() {
+            let mark = self.trail.len();
+            if let Some(end) = self.symbols(coverage.items[item].symbols(), pos) {
+                if best.as_ref().is_none_or(|(best, _)| end > *best) {
+                    let mut items = vec![item];
+                    items.extend_from_slice(&self.trail[mark..]);
+                    best = Some((end, items));
+                }
+            }
+            self.trail.truncate(mark);
+        }
+        self.memo.insert((name, pos), best.clone());
+        let (end, items) = best?;
+        self.trail.extend(items);
+        Some(end)
+    }
+
+    fn symbols(&mut self, symbols: &'s [Symbol], mut pos: usize) -> Option<usize> {
+        let mut i = 0;
+        while i < symbols.len() {
+            // `\text{offset=}o{:}\Tu32` is one token, e.g. `offset=4`
+            if let (Symbol::SText(keyword), Some(value)) = (&symbols[i], symbols.get(i + 1)) {
+                if let (Some(name), true) = (lexical_name(value), keyword.ends_with('=')) {
+                    let Some(TokenKind::Atom(atom)) = self.tokens.get(pos).map(|t| t.kind) else {
+                        return None;
+                    };
+                    let value = atom.strip_prefix(keyword.as_str())?;
+                    lexical(name, Some(TokenKind::Atom(value)))
+                        .unwrap_or(false)
+                        .then_some(())?;
+                    pos += 1;
+                    i += 2;
+                    continue;
+                }
+            }
+            pos = self.symbol(&symbols[i], pos)?;
+            i += 1;
+        }
+        Some(pos)
+    }
+
+    fn symbol(&mut self, symbol: &'s Symbol, pos: usize) -> Option<usize> {
+        match symbol {
+            Symbol::SText(keyword) => {
+                let expected = match keyword.as_str() {
+                    "(" => TokenKind::LParen,
+                    ")" => TokenKind::RParen,
+                    keyword => TokenKind::Atom(keyword),
+                };
+                let token = self.tokens.get(pos).map(|t| t.kind);
+                (token == Some(expected)).then_some(pos + 1)
+            }
+            Symbol::SNonterm(nt) if short(&nt.name) == "epsilon" => Some(pos),
+            Symbol::SNonterm(nt) => {
+                self.repeat(nt.seq_kind(), pos, |r, pos| r.nonterminal(&nt.name, pos))
+            }
+            Symbol::SBind(bind) => self.symbol(bind.symbol(), pos),
+            // `\Tvec` is a plain sequence in the text format
+            Symbol::SVec(vec) if vec.head() == "Tvec" && vec.over().seq_kind().is_none() => {
+                let over = &vec.over().name;
+                self.repeat(Some(&SeqKind::ManyPossibleEmpty), pos, |r, pos| {
+                    r.nonterminal(over, pos)
+                })
+            }
+            Symbol::SGroup(group) => self.repeat(group.seq_kind(), pos, |r, pos| {
+                r.symbols(group.symbols(), pos)
+            }),
+            _ => None,
+        }
+    }
+
+    fn repeat(
+        &mut self,
+        seq_kind: Option<&SeqKind>,
+        mut pos: usize,
+        mut one: impl FnMut(&mut Self, usize) -> Option<usize>,
+    ) -> Option<usize> {
+        let many = match seq_kind {
+            None => return one(self, pos),
+            Some(SeqKind::OptSeq) => false,
+            Some(SeqKind::ManyPossibleEmpty | SeqKind::ManyNonEmpty) => true,
+            Some(SeqKind::ManyN) => return None,
+        };
+        let mut count = 0;
+        loop {
+            let mark = self.trail.len();
+            match one(self, pos) {
+                Some(end) if end > pos => {
+                    pos = end;
+                    count += 1;
+                }
+                _ => {
+                    self.trail.truncate(mark);
+                    break;
+                }
+            }
+            if !many {
+                break;
+            }
+        }
+        (count > 0 || seq_kind != Some(&SeqKind::ManyNonEmpty)).then_some(pos)
+    }
+}
+
+/// Lexical nonterminal of a symbol that is read once, e.g. `u32` of
+/// `o{:}\Tu32`
+fn lexical_name(symbol: &Symbol) -> Option<&str> {
+    match symbol {
+        Symbol::SBind(bind) => lexical_name(bind.symbol()),
+        Symbol::SNonterm(nt) if nt.seq_kind().is_none() => {
+            let name = short(&nt.name);
+            LEXICAL.contains(&name).then_some(name)
+        }
+        _ => None,
+    }
+}
+
+/// Whether a token is of a lexical nonterminal, or `None` for other
+/// nonterminals
+fn lexical(name: &str, token: Option<TokenKind<'_>>) -> Option<bool> {
+    if !LEXICAL.contains(&name) {
+        return None;
+    }
+    Some(match (name, token) {
+        ("name" | "string", Some(TokenKind::Str(_))) => true,
+        ("id", Some(TokenKind::Atom(atom))) => atom.len() > 1 && atom.starts_with('$'),
+        ("byte" | "u32" | "u64", Some(TokenKind::Atom(atom))) => unsigned(atom),
+        ("s33" | "i32" | "i64", Some(TokenKind::Atom(atom))) => unsigned(unsign(atom)),
+        ("f32" | "f64", Some(TokenKind::Atom(atom))) => float(unsign(atom)),
+        _ => false,
+    })
+}
+
+fn unsign(atom: &str) -> &str {
+    atom.strip_prefix(['+', '-']).unwrap_or(atom)
+}
+
+/// `42`, `1_000` or `0xFF`
+fn unsigned(atom: &str) -> bool {
+    let (digits, radix) = match atom.strip_prefix("0x") {
+        Some(hex) => (hex, 16),
+        None => (atom, 10),
+    };
+    digits.starts_with(|c: char| c.is_digit(radix))
+        && digits.chars().all(|c| c.is_digit(radix) || c == '_')
+}
+
+/// `1.5`, `0x1p-3`, `inf`, `nan` or `nan:0x200000`
+fn float(atom: &str) -> bool {
+    match atom.strip_prefix("nan:") {
+        Some(payload) => payload.starts_with("0x") && unsigned(payload),
+        None => {
+            atom == "inf"
+                || atom == "nan"
+                || atom.starts_with(|c: char| c.is_ascii_digit())
+                    && atom
+                        .chars()
+                        .all(|c| c.is_ascii_hexdigit() || "._xpP+-".contains(c))
+        }
+    }
+}
+
+impl Display for Item<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let origin = self.origin();
+        write!(f, "{}:{}: ", origin.file.display(), origin.line)?;
+        match self {
+            Item::Alternative(alternative) => {
+                write!(f, "\\{} ::= {}", self.nonterminal(), alternative.elem)
+            }
+            Item::Abbreviation { abbreviation, .. } => {
+                write!(f, "\\{}: {}", self.nonterminal(), abbreviation)
+            }
+        }
+    }
+}
+
+impl Display for Coverage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unexercised = self.unexercised();
+        let supported = self.items.iter().filter(|i| i.is_supported()).count();
+        let modules: usize = self.tests.iter().map(|t| t.modules.len()).sum();
+        writeln!(
+            f,
+            "{} of {} alternatives and abbreviations exercised by {} modules of {} scripts",
+            supported - unexercised.len(),
+            supported,
+            modules,
+            self.tests.len()
+        )?;
+        if !unexercised.is_empty() {
+            writeln!(f, "never exercised:")?;
+            for item in unexercised {
+                writeln!(f, "  {}", item)?;
+            }
+        }
+        let unsupported: Vec<_> = self.items.iter().filter(|i| !i.is_supported()).collect();
+        if !unsupported.is_empty() {
+            writeln!(f, "not read:")?;
+            for item in unsupported {
+                writeln!(f, "  {}", item)?;
+            }
+        }
+        let failures: Vec<_> = self
+            .tests
+            .iter()
+            .flat_map(|t| t.failures.iter().map(move |line| (&t.name, line)))
+            .collect();
+        if !failures.is_empty() {
+            writeln!(f, "modules that do not parse:")?;
+            for (name, line) in failures {
+                writeln!(f, "  {}:{}", name, line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    use super::*;
+
+    fn spec() -> Spec {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "text/modules.rst",
+            r"
+.. math::
+   \begin{array}{llcll}
+   \production{module} & \Tmodule &::=&
+     \text{(}~\text{module}~~\Tid^?~~(m{:}\Tmodulefield_I)^\ast~\text{)}
+       &\Rightarrow& m^\ast \\
+   \production{module field} & \Tmodulefield_I &::=&
+     f{:}\Tfunc_I &\Rightarrow& f \\ &&|&
+     m{:}\Tmem_I &\Rightarrow& m \\
+   \production{function} & \Tfunc_I &::=&
+     \text{(}~\text{func}~~\Tid^?~~t{:}\Tvec(\Tparam)~~(\X{in}{:}\Tinstr_I)^\ast~\text{)}
+       &\Rightarrow& \X{in}^\ast \\
+   \production{parameter} & \Tparam &::=&
+     \text{(}~\text{param}~~\Tid^?~~t{:}\Tvaltype~\text{)} &\Rightarrow& t \\
+   \production{value type} & \Tvaltype &::=&
+     \text{i32} &\Rightarrow& \I32 \\ &&|&
+     \text{i64} &\Rightarrow& \I64 \\
+   \production{memory} & \Tmem_I &::=&
+     \text{(}~\text{memory}~~\Tid^?~~n{:}\Tu32~\text{)} &\Rightarrow& n \\
+   \production{instruction} & \Tinstr_I &::=&
+     \text{nop} &\Rightarrow& \NOP \\ &&|&
+     \text{i32.const}~~n{:}\Ti32 &\Rightarrow& \CONST~n \\ &&|&
+     \text{local.get}~~x{:}\Tu32 &\Rightarrow& \LOCALGET~x \\ &&|&
+     \text{i32.load}~~\text{offset=}o{:}\Tu32 &\Rightarrow& \LOAD~o \\
+   \production{unused} & \Tunused &::=&
+     \text{unused} &\Rightarrow& \NOP \\
+   \end{array}
+
+.. math::
+   \begin{array}{llclll}
+   \production{instruction} &
+     \text{(}~~\Tinstr~~\text{)} &\equiv& \Tinstr \\
+   \end{array}
+",
+        );
+        assert!(grammar.errors().is_empty(), "{:?}", grammar.errors());
+        Spec::new(grammar)
+    }
+
+    #[test]
+    fn reachable() {
+        let spec = spec();
+        let coverage = Coverage::new(&spec);
+        let mut nonterminals: Vec<_> = coverage.items().iter().map(Item::nonterminal).collect();
+        nonterminals.dedup();
+        nonterminals.sort();
+        assert_eq!(
+            nonterminals,
+            vec![
+                "Tfunc",
+                "Tinstr",
+                "Tmem",
+                "Tmodule",
+                "Tmodulefield",
+                "Tparam",
+                "Tvaltype"
+            ]
+        );
+        assert!(coverage.items().iter().all(Item::is_supported));
+    }
+
+    #[test]
+    fn scripts() {
+        let spec = spec();
+        let mut coverage = Coverage::new(&spec);
+        let test = coverage
+            .add_script(
+                "func.wast",
+                r#"(module $m
+  (func (param i32) (param $x i64) nop (i32.const -1))
+)
+(assert_invalid (module (func i32.load offset=0x10)) "type mismatch")
+(module (func br 0))
+(module binary "\00asm")
+"#,
+            )
+            .unwrap();
+        assert_eq!(test.modules, vec![1, 4, 5]);
+        assert_eq!(test.failures, vec![5]);
+        let test = &coverage.tests()[0];
+        assert_eq!(
+            coverage.productions_exercised_by(test),
+            BTreeSet::from([
+                "Tfunc",
+                "Tinstr",
+                "Tmodule",
+                "Tmodulefield",
+                "Tparam",
+                "Tvaltype"
+            ])
+        );
+        coverage
+            .add_script("memory.wast", "(module (memory 1))")
+            .unwrap();
+
+        assert_eq!(
+            coverage.to_string(),
+            r"12 of 13 alternatives and abbreviations exercised by 4 modules of 2 scripts
+never exercised:
+  text/modules.rst:3: \Tinstr ::= \text{local.get}~x{:}\Tu32 \Rightarrow \LOCALGET~x
+modules that do not parse:
+  func.wast:5
+"
+        );
+        assert_eq!(
+            coverage
+                .add_script("open.wast", "(module \"open")
+                .unwrap_err(),
+            LexError(1)
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenKind<'a> {
+    LParen,
+    RParen,
+    /// Keyword, number or identifier
+    Atom(&'a str),
+    /// String as written, without its quotes
+    Str(&'a str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
+    /// Line on which the token starts
+    pub line: usize,
+}
+
+/// Line of a character that starts no token or of an unterminated comment
+/// or string
+#[derive(Debug, PartialEq)]
+pub struct LexError(pub usize);
+
+/// Tokens of a `.wast` script, without whitespace and comments
+pub fn tokenize(source: &str) -> Result<Vec<Token<'_>>, LexError> {
+    let bytes = source.as_bytes();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        let kind = match (bytes[i], bytes.get(i + 1)) {
+            (b'\n', _) => {
+                line += 1;
+                i += 1;
+                continue;
+            }
+            (b' ' | b'\t' | b'\r', _) => {
+                i += 1;
+                continue;
+            }
+            (b';', Some(b';')) => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            (b'(', Some(b';')) => {
+                let first = line;
+                let mut depth = 0;
+                loop {
+                    match (bytes.get(i), bytes.get(i + 1)) {
+                        (Some(b'('), Some(b';')) => depth += 1,
+                        (Some(b';'), Some(b')')) => depth -= 1,
+                        (Some(b), _) => {
+                            line += usize::from(*b == b'\n');
+                            i += 1;
+                            continue;
+                        }
+                        (None, _) => return Err(LexError(first)),
+                    }
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                continue;
+            }
+            (b'(', _) => {
+                i += 1;
+                TokenKind::LParen
+            }
+            (b')', _) => {
+                i += 1;
+                TokenKind::RParen
+            }
+            (b'"', _) => {
+                i += 1;
+                loop {
+                    match bytes.get(i) {
+                        None | Some(b'\n') => return Err(LexError(line)),
+                        Some(b'"') => break,
+                        Some(b'\\') => i += 2,
+                        Some(_) => i += 1,
+                    }
+                }
+                i += 1;
+                TokenKind::Str(&source[start + 1..i - 1])
+            }
+            _ => {
+                while i < bytes.len() && !delimits(bytes[i]) {
+                    i += 1;
+                }
+                if start == i {
+                    return Err(LexError(line));
+                }
+                TokenKind::Atom(&source[start..i])
+            }
+        };
+        tokens.push(Token { kind, line });
+    }
+    Ok(tokens)
+}
+
+fn delimits(byte: u8) -> bool {
+    matches!(
+        byte,
+        b' ' | b'\t' | b'\n' | b'\r' | b'(' | b')' | b'"' | b';'
+    )
+}
+
+/// Spans of the `(module ...)` forms in text format, wherever they occur in
+/// the script, e.g. in `(assert_invalid (module ...) "type mismatch")`.
+/// Modules written as `binary` or `quote` strings are left out.
+pub fn modules(tokens: &[Token<'_>]) -> Vec<Range<usize>> {
+    let mut modules = vec![];
+    let mut i = 0;
+    while i + 1 < tokens.len() {
+        let opens =
+            tokens[i].kind == TokenKind::LParen && tokens[i + 1].kind == TokenKind::Atom("module");
+        if !opens {
+            i += 1;
+            continue;
+        }
+        let end = closing(tokens, i);
+        let mut next = tokens[i + 2..end].iter().map(|t| t.kind);
+        let first = match next.next() {
+            Some(TokenKind::Atom(id)) if id.starts_with('$') => next.next(),
+            first => first,
+        };
+        if !matches!(first, Some(TokenKind::Atom("binary" | "quote"))) {
+            modules.push(i..end);
+        }
+        i = end;
+    }
+    modules
+}
+
+/// Source of tokens, separated by single spaces and without comments
+pub fn source(tokens: &[Token<'_>]) -> String {
+    let texts: Vec<_> = tokens
+        .iter()
+        .map(|token| match token.kind {
+            TokenKind::LParen => "(".to_string(),
+            TokenKind::RParen => ")".to_string(),
+            TokenKind::Atom(atom) => atom.to_string(),
+            TokenKind::Str(s) => format!("\"{}\"", s),
+        })
+        .collect();
+    texts.join(" ")
+}
+
+/// Module written as `(module binary "..." ...)`
+#[derive(Debug, PartialEq)]
+pub struct BinaryModule<'a> {
+    /// Line of the `(module`
+    pub line: usize,
+    pub bytes: Vec<u8>,
+    /// Command the module is an argument of, e.g. `assert_malformed`, or
+    /// none at the top of the script
+    pub command: Option<&'a str>,
+}
+
+/// The `(module binary ...)` forms of the script whose strings are valid.
+/// The forms are not parsed as modules, so that the bytes of
+/// `(assert_malformed (module binary ...))` are kept as written.
+pub fn binary_modules<'a>(tokens: &[Token<'a>]) -> Vec<BinaryModule<'a>> {
+    let mut modules = vec![];
+    let mut command = None;
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate() {
+        match token.kind {
+            TokenKind::LParen => depth += 1,
+            TokenKind::RParen => depth -= 1,
+            _ => continue,
+        }
+        if token.kind != TokenKind::LParen {
+            continue;
+        }
+        let head = match tokens.get(i + 1).map(|t| t.kind) {
+            Some(TokenKind::Atom(head)) => head,
+            _ => continue,
+        };
+        if depth == 1 {
+            command = Some(head);
+        }
+        if head != "module" {
+            continue;
+        }
+        let end = closing(tokens, i);
+        let mut args = tokens[i + 2..end - 1].iter().map(|t| t.kind).peekable();
+        args.next_if(|kind| matches!(kind, TokenKind::Atom(id) if id.starts_with('$')));
+        if args.next() != Some(TokenKind::Atom("binary")) {
+            continue;
+        }
+        let strings: Option<Vec<_>> = args
+            .map(|kind| match kind {
+                TokenKind::Str(s) => unescape(s),
+                _ => None,
+            })
+            .collect();
+        if let Some(strings) = strings {
+            modules.push(BinaryModule {
+                line: token.line,
+                bytes: strings.concat(),
+                command: command.filter(|_| depth > 1),
+            });
+        }
+    }
+    modules
+}
+
+/// Bytes of a string as written, e.g. `\00asm`, or none if an escape is
+/// invalid
+pub fn unescape(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let escaped = match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            c @ ('"' | '\'' | '\\') => c,
+            'u' => {
+                let rest = chars.as_str().strip_prefix('{')?;
+                let (hex, rest) = rest.split_once('}')?;
+                let c = char::from_u32(u32::from_str_radix(&hex.replace('_', ""), 16).ok()?)?;
+                chars = rest.chars();
+                c
+            }
+            high => {
+                let low = chars.next()?;
+                let byte = (high.to_digit(16)? << 4) | low.to_digit(16)?;
+                bytes.push(byte as u8);
+                continue;
+            }
+        };
+        let mut buf = [0; 4];
+        bytes.extend(escaped.encode_utf8(&mut buf).as_bytes());
+    }
+    Some(bytes)
+}
+
+/// Atom of an item of `items` that is a single token
+pub fn atom<'a>(item: &[Token<'a>]) -> Option<&'a str> {
+    match item {
+        [Token {
+            kind: TokenKind::Atom(atom),
+            ..
+        }] => Some(atom),
+        _ => None,
+    }
+}
+
+/// Keyword a parenthesized form starts with, e.g. `param`
+pub fn keyword<'a>(form: &[Token<'a>]) -> Option<&'a str> {
+    match form.get(..2)? {
+        [Token {
+            kind: TokenKind::LParen,
+            ..
+        }, Token {
+            kind: TokenKind::Atom(keyword),
+            ..
+        }] => Some(keyword),
+        _ => None,
+    }
+}
+
+/// Atoms and parenthesized forms of a form, after its keyword
+pub fn items<'t, 'a>(form: &'t [Token<'a>]) -> Vec<&'t [Token<'a>]> {
+    let mut items = vec![];
+    let mut i = 2;
+    while i + 1 < form.len() {
+        let end = match form[i].kind {
+            TokenKind::LParen => closing(form, i),
+            _ => i + 1,
+        };
+        items.push(&form[i..end]);
+        i = end;
+    }
+    items
+}
+
+/// End of the parenthesized form opening at `start`, after its closing
+/// parenthesis
+pub(crate) fn closing(tokens: &[Token<'_>], start: usize) -> usize {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().skip(start) {
+        match token.kind {
+            TokenKind::LParen => depth += 1,
+            TokenKind::RParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script() {
+        let source = r#"(module $m
+  (func (export "f") (result i32) (i32.const 1)) ;; one
+)
+(assert_return (invoke "f") (i32.const 1))
+(; a (; nested ;) comment ;)
+(assert_invalid
+  (module (func (result i32) (nop)))
+  "type mismatch")
+(module binary "\00asm" "\01\00\00\00")
+(module $q quote "(func)")
+"#;
+        let tokens = tokenize(source).unwrap();
+        assert_eq!(tokens[2].kind, TokenKind::Atom("$m"));
+        assert_eq!(tokens[7].kind, TokenKind::Str("f"));
+        assert_eq!(tokens[7].line, 2);
+        let modules = modules(&tokens);
+        let lines: Vec<_> = modules.iter().map(|m| tokens[m.start].line).collect();
+        assert_eq!(lines, vec![1, 7]);
+        assert_eq!(tokens[modules[0].end - 1].kind, TokenKind::RParen);
+        assert_eq!(tokens[modules[0].end - 1].line, 3);
+        assert_eq!(
+            super::source(&tokens[modules[0].clone()]),
+            r#"( module $m ( func ( export "f" ) ( result i32 ) ( i32.const 1 ) ) )"#
+        );
+        let module = &tokens[modules[0].clone()];
+        assert_eq!(keyword(module), Some("module"));
+        let fields = items(module);
+        assert_eq!(fields.len(), 2);
+        assert_eq!(atom(fields[0]), Some("$m"));
+        assert_eq!(keyword(fields[1]), Some("func"));
+        assert_eq!(atom(fields[1]), None);
+        let lines: Vec<_> = items(fields[1]).iter().map(|item| item[0].line).collect();
+        assert_eq!(lines, vec![2, 2, 2]);
+
+        assert_eq!(tokenize("(module\n\"open"), Err(LexError(2)));
+        assert_eq!(tokenize("(; open"), Err(LexError(1)));
+    }
+
+    #[test]
+    fn binary() {
+        let source = r#"(module binary "\00asm" "\01\00\00\00")
+(module $m binary "\00asm" "\01\00\00\00" "\00\05\04n\u{e9}t")
+(assert_malformed (module binary "\00asm" "\02\00\00\00") "unknown binary version")
+(module $q quote "(func)")
+(module binary "\0")
+"#;
+        let tokens = tokenize(source).unwrap();
+        let modules = binary_modules(&tokens);
+        let header = b"\0asm\x01\0\0\0";
+        assert_eq!(
+            modules,
+            vec![
+                BinaryModule {
+                    line: 1,
+                    bytes: header.to_vec(),
+                    command: None,
+                },
+                BinaryModule {
+                    line: 2,
+                    bytes: [&header[..], b"\0\x05\x04n\xc3\xa9t"].concat(),
+                    command: None,
+                },
+                BinaryModule {
+                    line: 3,
+                    bytes: b"\0asm\x02\0\0\0".to_vec(),
+                    command: Some("assert_malformed"),
+                },
+            ]
+        );
+        assert_eq!(
+            unescape(r#"\t\"\'\\\u{1_F600}"#),
+            Some("\t\"'\\\u{1F600}".into())
+        );
+        assert_eq!(unescape(r"\q"), None);
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(tokenize("(module)\n(module \"a\\\""), Err(LexError(2)));
+        assert_eq!(tokenize("(module (; a (; b ;)\n)"), Err(LexError(1)));
+        assert_eq!(tokenize("(module\n\n  \"a\nb\")"), Err(LexError(3)));
+
+        // an unbalanced module runs to the end of the script
+        let tokens = tokenize("(module (func)\n(assert_return (invoke \"f\"))").unwrap();
+        assert_eq!(modules(&tokens), vec![0..tokens.len()]);
+        assert_eq!(modules(&tokenize(")) module (").unwrap()), vec![]);
+    }
+}
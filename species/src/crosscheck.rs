@@ -0,0 +1,361 @@
+//! Comparison of the abstract syntax with the types of the reference
+//! interpreter, e.g. `interpreter/syntax/ast.ml` and `types.ml`.
+//!
+//! An OCaml type is compared with the nonterminal of the same name, up to
+//! underscores and primes, so `num_type` with `numtype` and `instr'` with
+//! `instr`. Constructors are compared with the terminals of the
+//! nonterminal's alternatives, e.g. `LocalGet` with `\LOCALGET`, and record
+//! fields with record fields, e.g. `min` with `\LMIN`.
+//!
+//! The grammar of the SpecTec sources is compared in [`spectec`].
+
+pub mod ocaml;
+pub mod spectec;
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+};
+
+use crate::{
+    codegen::format_of,
+    spec::Spec,
+    syntax::{
+        symbol::Symbol,
+        visit::{self, Visit},
+    },
+};
+
+use self::ocaml::{TypeDef, TypeKind};
+
+/// Types of the reference interpreter that are named differently from their
+/// nonterminal, after normalization
+const RENAMES: &[(&str, &str)] = &[
+    ("valuetype", "valtype"),
+    ("mutability", "mut"),
+    ("elemsegment", "elem"),
+    ("datasegment", "data"),
+];
+
+/// Differences between the nonterminals and the OCaml types they match
+#[derive(Debug, PartialEq)]
+pub struct CrossCheck<'a> {
+    pub pairs: Vec<Pair<'a>>,
+}
+
+/// A nonterminal and the OCaml type of the same name
+#[derive(Debug, PartialEq)]
+pub struct Pair<'a> {
+    pub nonterminal: &'a str,
+    pub ty: &'a TypeDef,
+    pub findings: Vec<Finding<'a>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Finding<'a> {
+    /// Constructor that no terminal of the nonterminal stands for
+    ConstructorNotInSpec(&'a str),
+    /// Alternative of which no terminal is a constructor, by its first
+    /// terminal
+    TerminalNotInOcaml(&'a str),
+    /// Field of the OCaml record that no record of the nonterminal has
+    FieldNotInSpec(&'a str),
+    /// Field of a record of the nonterminal that the OCaml record lacks
+    FieldNotInOcaml(&'a str),
+}
+
+/// Terminals and record fields of one alternative
+#[derive(Default)]
+struct Names<'ast> {
+    terminals: Vec<&'ast str>,
+    fields: Vec<&'ast str>,
+}
+
+impl<'ast> Visit<'ast> for Names<'ast> {
+    fn visit_sterm(&mut self, name: &'ast str) {
+        self.terminals.push(name);
+    }
+
+    fn visit_field(&mut self, key: &'ast str, value: &'ast Symbol) {
+        self.fields.push(key);
+        visit::visit_field(self, key, value);
+    }
+}
+
+impl<'a> CrossCheck<'a> {
+    pub fn new(spec: &'a Spec, types: &'a [TypeDef]) -> Self {
+        let mut nonterminals = BTreeMap::new();
+        for (_, production) in spec.grammar().productions() {
+            let nonterminal = production.nonterminal();
+            if format_of(nonterminal).is_none() {
+                nonterminals.insert(normalize(nonterminal), nonterminal);
+            }
+        }
+
+        let mut pairs = vec![];
+        for ty in types {
+            if matches!(ty.kind, TypeKind::Alias(_)) {
+                continue;
+            }
+            let name = normalize(&ty.name);
+            let name = RENAMES
+                .iter()
+                .find(|(from, _)| *from == name)
+                .map_or(name.as_str(), |(_, to)| to);
+            if let Some(nonterminal) = nonterminals.get(name) {
+                pairs.push(Pair::new(spec, nonterminal, ty));
+            }
+        }
+        Self { pairs }
+    }
+
+    /// Whether every matched type agrees with its nonterminal
+    pub fn is_consistent(&self) -> bool {
+        self.pairs.iter().all(|pair| pair.findings.is_empty())
+    }
+}
+
+impl<'a> Pair<'a> {
+    fn new(spec: &'a Spec, nonterminal: &'a str, ty: &'a TypeDef) -> Self {
+        let alternatives: Vec<_> = spec
+            .alternatives_of(nonterminal)
+            .into_iter()
+            .map(|alternative| {
+                let mut names = Names::default();
+                names.visit_rhs_elem(alternative.elem);
+                names
+            })
+            .collect();
+
+        let mut findings = vec![];
+        match &ty.kind {
+            TypeKind::Variant(constructors) => {
+                let keys: Vec<_> = constructors
+                    .iter()
+                    .map(|c| constructor_key(&c.name, &ty.name))
+                    .collect();
+                for (constructor, key) in constructors.iter().zip(&keys) {
+                    let found = alternatives
+                        .iter()
+                        .any(|names| names.terminals.iter().any(|t| terminal_key(t) == *key));
+                    if !found {
+                        findings.push(Finding::ConstructorNotInSpec(&constructor.name));
+                    }
+                }
+                for names in &alternatives {
+                    let Some(first) = names.terminals.first() else {
+                        continue;
+                    };
+                    let found = names
+                        .terminals
+                        .iter()
+                        .any(|t| keys.contains(&terminal_key(t)));
+                    let finding = Finding::TerminalNotInOcaml(first);
+                    if !found && !findings.contains(&finding) {
+                        findings.push(finding);
+                    }
+                }
+            }
+            TypeKind::Record(fields) => {
+                let mut spec_fields: Vec<&str> = vec![];
+                for names in &alternatives {
+                    for field in &names.fields {
+                        if !spec_fields.contains(field) {
+                            spec_fields.push(field);
+                        }
+                    }
+                }
+                for field in fields {
+                    if !spec_fields.iter().any(|s| field_matches(s, &field.name)) {
+                        findings.push(Finding::FieldNotInSpec(&field.name));
+                    }
+                }
+                for field in spec_fields {
+                    if !fields.iter().any(|f| field_matches(field, &f.name)) {
+                        findings.push(Finding::FieldNotInOcaml(field));
+                    }
+                }
+            }
+            TypeKind::Alias(_) => {}
+        }
+        Self {
+            nonterminal,
+            ty,
+            findings,
+        }
+    }
+}
+
+/// `numtype` for `num_type` and `instr` for `instr'`
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// `I32` for `i32.`
+fn terminal_key(terminal: &str) -> String {
+    normalize(terminal).to_ascii_uppercase()
+}
+
+/// `LOCALGET` for `LocalGet`, leaving out the suffix that repeats the type,
+/// e.g. `I32` for `I32Type` of `num_type`
+fn constructor_key(constructor: &str, ty: &str) -> String {
+    let suffix = ty.trim_end_matches('\'').rsplit('_').next().unwrap_or(ty);
+    let key = terminal_key(constructor);
+    let suffix = terminal_key(suffix);
+    match key.strip_suffix(&suffix) {
+        Some(stripped) if !stripped.is_empty() => stripped.to_string(),
+        _ => key,
+    }
+}
+
+/// Whether a record field of the spec, e.g. `LMIN`, is the OCaml field, e.g.
+/// `min`, possibly with the one letter prefix of its record
+fn field_matches(spec: &str, ocaml: &str) -> bool {
+    let ocaml = terminal_key(ocaml);
+    spec == ocaml || spec.get(1..) == Some(ocaml.as_str())
+}
+
+impl Display for CrossCheck<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for pair in &self.pairs {
+            if !pair.findings.is_empty() {
+                write!(f, "{}", pair)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Pair<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ~ {} ({}:{})",
+            self.nonterminal,
+            self.ty.name,
+            self.ty.file.display(),
+            self.ty.line
+        )?;
+        for finding in &self.findings {
+            writeln!(f, "  {}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Finding<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::ConstructorNotInSpec(name) => {
+                write!(f, "constructor `{}` is not in the spec", name)
+            }
+            Finding::TerminalNotInOcaml(name) => {
+                write!(f, "terminal `{}` is not in the reference interpreter", name)
+            }
+            Finding::FieldNotInSpec(name) => write!(f, "field `{}` is not in the spec", name),
+            Finding::FieldNotInOcaml(name) => {
+                write!(f, "field `{}` is not in the reference interpreter", name)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    use super::*;
+
+    fn spec() -> Spec {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "syntax/types.rst",
+            r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 ~|~ \F32 ~|~ \F64 \\
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \production{value type} & \valtype &::=&
+     \numtype \\
+   \production{instruction} & \instr &::=&
+     \K{i32.}\CONST~\i32 \\&&|&
+     \NOP \\&&|&
+     \LOCALGET~\localidx \\&&|&
+     \LOCALTEE~\localidx \\
+   \end{array}
+",
+        );
+        assert!(grammar.errors().is_empty(), "{:?}", grammar.errors());
+        Spec::new(grammar)
+    }
+
+    #[test]
+    fn findings() {
+        let spec = spec();
+        let types = TypeDef::parse(
+            "types.ml",
+            r"type num_type = I32Type | I64Type | F32Type | F64Type
+type value_type = NumType of num_type | VecType of vec_type
+type limits = {min : int32; maximum : int32 option}
+
+type var = int32 Source.phrase
+and instr = instr' Source.phrase
+and instr' =
+  | Nop
+  | Const of num
+  | LocalGet of var
+  | LocalSet of var
+",
+        );
+        let check = CrossCheck::new(&spec, &types);
+        let pairs: Vec<_> = check
+            .pairs
+            .iter()
+            .map(|p| (p.nonterminal, p.ty.name.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("numtype", "num_type"),
+                ("valtype", "value_type"),
+                ("limits", "limits"),
+                ("instr", "instr'"),
+            ]
+        );
+        assert!(!check.is_consistent());
+        assert_eq!(
+            check.to_string(),
+            "valtype ~ value_type (types.ml:2)
+  constructor `NumType` is not in the spec
+  constructor `VecType` is not in the spec
+limits ~ limits (types.ml:3)
+  field `maximum` is not in the spec
+  field `LMAX` is not in the reference interpreter
+instr ~ instr' (types.ml:7)
+  constructor `LocalSet` is not in the spec
+  terminal `LOCALTEE` is not in the reference interpreter
+"
+        );
+    }
+
+    #[test]
+    fn unmatched() {
+        let spec = spec();
+        assert!(CrossCheck::new(&spec, &[]).pairs.is_empty());
+        // aliases, types without a nonterminal and definitions that do not
+        // parse are not compared
+        let types = TypeDef::parse(
+            "types.ml",
+            "type num_type = int32\ntype pack_size = Pack8\ntype limits =\n",
+        );
+        let check = CrossCheck::new(&spec, &types);
+        assert!(check.pairs.is_empty());
+        assert!(check.is_consistent());
+        assert_eq!(check.to_string(), "");
+    }
+}
@@ -0,0 +1,303 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{char, multispace0, multispace1, satisfy},
+    combinator::{opt, recognize},
+    multi::many0,
+    sequence::pair,
+};
+
+use crate::{nom_err, PResult};
+
+/// Type definition of an OCaml source, e.g.
+/// `type num_type = I32Type | I64Type | F32Type | F64Type`
+#[derive(Debug, PartialEq)]
+pub struct TypeDef {
+    /// Name as written, e.g. `instr'`
+    pub name: String,
+    pub kind: TypeKind,
+    pub file: PathBuf,
+    /// Line on which the definition starts
+    pub line: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TypeKind {
+    Variant(Vec<Constructor>),
+    Record(Vec<Field>),
+    /// Any other type expression, e.g. `instr' Source.phrase`
+    Alias(String),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Constructor {
+    pub name: String,
+    /// Type of the arguments, e.g. `result_type * result_type`
+    pub args: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub ty: String,
+}
+
+impl TypeDef {
+    /// Type definitions of an OCaml file, e.g. `interpreter/syntax/ast.ml`
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Vec<Self>> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)?;
+        Ok(Self::parse(path, &content))
+    }
+
+    /// Type definitions of an OCaml source. Each starts on a line of its own
+    /// with `type` or, after another one, `and`.
+    pub fn parse(file: impl AsRef<Path>, content: &str) -> Vec<Self> {
+        let content = strip_comments(content);
+        let lines: Vec<&str> = content.lines().collect();
+        let mut definitions = vec![];
+        let mut in_type = false;
+        for (i, line) in lines.iter().enumerate() {
+            let starts_item = line.starts_with(|c: char| c.is_ascii_alphabetic());
+            if !starts_item {
+                continue;
+            }
+            in_type = line.starts_with("type ") || (in_type && line.starts_with("and "));
+            if !in_type {
+                continue;
+            }
+            let end = lines[i + 1..]
+                .iter()
+                .position(|l| l.starts_with(|c: char| c.is_ascii_alphabetic()))
+                .map_or(lines.len(), |n| i + 1 + n);
+            let source = lines[i..end].join("\n");
+            if let Ok((_, (name, kind))) = definition(&source) {
+                definitions.push(Self {
+                    name: name.to_string(),
+                    kind,
+                    file: file.as_ref().to_path_buf(),
+                    line: i + 1,
+                });
+            }
+        }
+        definitions
+    }
+}
+
+/// `type 'a limits = {min : 'a; max : 'a option}`
+fn definition(input: &str) -> PResult<'_, (&str, TypeKind)> {
+    let (input, _) = alt((tag("type"), tag("and")))(input)?;
+    let (input, _) = multispace1(input)?;
+    let (input, _) = opt(pair(type_params, multispace1))(input)?;
+    let (input, name) = recognize(pair(
+        satisfy(|c| c.is_ascii_lowercase() || c == '_'),
+        many0(satisfy(|c| {
+            c.is_ascii_alphanumeric() || c == '_' || c == '\''
+        })),
+    ))(input)?;
+    let (input, _) = multispace0(input)?;
+    let (input, _) = char('=')(input)?;
+    let (input, _) = multispace0(input)?;
+    let (input, kind) = match input.strip_prefix('{') {
+        Some(fields) => record(fields)?,
+        None => match variant(input) {
+            Ok(variant) => variant,
+            Err(_) => ("", TypeKind::Alias(squeeze(input))),
+        },
+    };
+    Ok((input, (name, kind)))
+}
+
+/// `'a` or `('a, 'b)`
+fn type_params(input: &str) -> PResult<'_, &str> {
+    alt((
+        recognize(pair(char('\''), ident)),
+        recognize(pair(
+            char('('),
+            pair(many0(satisfy(|c| c != ')')), char(')')),
+        )),
+    ))(input)
+}
+
+fn ident(input: &str) -> PResult<'_, &str> {
+    recognize(pair(
+        satisfy(|c| c.is_ascii_alphabetic() || c == '_'),
+        many0(satisfy(|c| {
+            c.is_ascii_alphanumeric() || c == '_' || c == '\''
+        })),
+    ))(input)
+}
+
+/// `| Block of block_type * instr list | Nop`
+fn variant(input: &str) -> PResult<'_, TypeKind> {
+    let mut input = input;
+    let mut constructors = vec![];
+    loop {
+        let (tail, _) = multispace0(input)?;
+        let (tail, bar) = opt(char('|'))(tail)?;
+        if bar.is_none() && !constructors.is_empty() {
+            break;
+        }
+        let (tail, _) = multispace0(tail)?;
+        let (tail, name) = ident(tail)?;
+        // a module path like `Source.phrase` is an alias
+        if !name.starts_with(|c: char| c.is_ascii_uppercase()) || tail.starts_with('.') {
+            return nom_err!(input, Nom, nom::error::ErrorKind::Verify);
+        }
+        let (tail, _) = multispace0(tail)?;
+        let (tail, args) = match tail.strip_prefix("of") {
+            Some(args) if args.starts_with(char::is_whitespace) => {
+                let end = top_level_end(args, &['|']);
+                (&args[end..], Some(squeeze(&args[..end])))
+            }
+            _ => (tail, None),
+        };
+        constructors.push(Constructor {
+            name: name.to_string(),
+            args,
+        });
+        input = tail;
+    }
+    Ok((input, TypeKind::Variant(constructors)))
+}
+
+/// `min : 'a; max : 'a option}`, after the opening brace
+fn record(input: &str) -> PResult<'_, TypeKind> {
+    let mut input = input;
+    let mut fields = vec![];
+    loop {
+        let (tail, _) = multispace0(input)?;
+        if let Some(tail) = tail.strip_prefix('}') {
+            return Ok((tail, TypeKind::Record(fields)));
+        }
+        let (tail, _) = opt(pair(tag("mutable"), multispace1))(tail)?;
+        let (tail, name) = ident(tail)?;
+        let (tail, _) = multispace0(tail)?;
+        let (tail, _) = char(':')(tail)?;
+        let end = top_level_end(tail, &[';', '}']);
+        fields.push(Field {
+            name: name.to_string(),
+            ty: squeeze(&tail[..end]),
+        });
+        input = tail[end..].strip_prefix(';').unwrap_or(&tail[end..]);
+    }
+}
+
+/// Offset of the first of the `stops` outside of parentheses and brackets
+fn top_level_end(input: &str, stops: &[char]) -> usize {
+    let mut depth = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth > 0 => depth -= 1,
+            c if depth == 0 && stops.contains(&c) => return i,
+            _ => {}
+        }
+    }
+    input.len()
+}
+
+/// Collapse the whitespace of a type expression
+fn squeeze(source: &str) -> String {
+    source.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Blank out `(* ... *)` comments, which nest, keeping the line breaks
+fn strip_comments(source: &str) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    let mut depth = 0;
+    let mut rest = source;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("(*") {
+            depth += 1;
+            stripped.push_str("  ");
+            rest = &rest[2..];
+        } else if depth > 0 && rest.starts_with("*)") {
+            depth -= 1;
+            stripped.push_str("  ");
+            rest = &rest[2..];
+        } else {
+            stripped.push(if depth > 0 && c != '\n' { ' ' } else { c });
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn definitions() {
+        let definitions = TypeDef::parse(
+            "types.ml",
+            r#"(* Types *)
+
+type num_type = I32Type | I64Type | F32Type | F64Type
+type 'a limits = {min : 'a; max : 'a option}
+type func_type = FuncType of result_type * result_type
+
+let string_of_num_type = function
+  | I32Type -> "i32"
+and foo = function
+  | I64Type -> "i64"
+
+type var = int32 Source.phrase
+and instr' =
+  | Unreachable                       (* trap unconditionally *)
+  | Block of block_type * instr list  (* execute in sequence (* nested *) *)
+  | LocalGet of var
+"#,
+        );
+        let names: Vec<_> = definitions
+            .iter()
+            .map(|d| (d.line, d.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (3, "num_type"),
+                (4, "limits"),
+                (5, "func_type"),
+                (12, "var"),
+                (13, "instr'")
+            ]
+        );
+        let TypeKind::Record(fields) = &definitions[1].kind else {
+            panic!("{:?}", definitions[1]);
+        };
+        assert_eq!(fields[1].name, "max");
+        assert_eq!(fields[1].ty, "'a option");
+        assert_eq!(
+            definitions[3].kind,
+            TypeKind::Alias("int32 Source.phrase".to_string())
+        );
+        let TypeKind::Variant(constructors) = &definitions[4].kind else {
+            panic!("{:?}", definitions[4]);
+        };
+        assert_eq!(constructors[1].name, "Block");
+        assert_eq!(
+            constructors[1].args.as_deref(),
+            Some("block_type * instr list")
+        );
+    }
+
+    #[test]
+    fn unreadable() {
+        assert!(TypeDef::load("no/such/ast.ml").is_err());
+        // a definition that does not parse is left out
+        let definitions = TypeDef::parse(
+            "types.ml",
+            "type = I32Type\ntype 'a = {min : 'a}\ntype pack_size = Pack8 | Pack16\n",
+        );
+        let names: Vec<_> = definitions.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, vec!["pack_size"]);
+        assert_eq!(TypeDef::parse("types.ml", "(* type open"), vec![]);
+    }
+}
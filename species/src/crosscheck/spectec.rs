@@ -0,0 +1,427 @@
+//! Comparison of the grammar of the `rst` sources with the one of the SpecTec
+//! sources of the same checkout, e.g. to check a migration to SpecTec.
+//!
+//! Nonterminals are aligned by name, leaving out those of the binary and text
+//! formats, which SpecTec writes as `grammar` declarations. The alternatives
+//! of a nonterminal are aligned by their constructor, the first terminal, or
+//! by their nonterminal if they are only one, e.g. `\numtype` of `\valtype`.
+//! The uses of a nonterminal in two aligned alternatives must have the same
+//! iterations, in order, where `\vec(x)` is `x^\ast`, and the records of a
+//! nonterminal the same fields.
+
+use std::fmt::{self, Display};
+
+use crate::{
+    codegen::format_of,
+    grammar::{Grammar, Origin},
+    parser::SeqKind,
+    syntax::{symbol::Symbol, RhsElem},
+};
+
+use super::terminal_key;
+
+/// Differences between the nonterminals of the `rst` and SpecTec sources
+#[derive(Debug, PartialEq)]
+pub struct SpecTecCheck<'g> {
+    pub pairs: Vec<Pair<'g>>,
+    /// Nonterminals defined in the `rst` sources only
+    pub only_rst: Vec<&'g str>,
+    /// Nonterminals defined in the SpecTec sources only
+    pub only_spectec: Vec<&'g str>,
+}
+
+/// A nonterminal defined in both sources
+#[derive(Debug, PartialEq)]
+pub struct Pair<'g> {
+    pub nonterminal: &'g str,
+    pub rst: &'g Origin,
+    pub spectec: &'g Origin,
+    pub findings: Vec<Finding<'g>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Finding<'g> {
+    /// Alternative of the `rst` sources that the SpecTec sources lack
+    NotInSpecTec(&'g RhsElem),
+    /// Alternative of the SpecTec sources that the `rst` sources lack
+    NotInRst(&'g RhsElem),
+    /// Nonterminal that two aligned alternatives iterate differently
+    Iteration {
+        nonterminal: &'g str,
+        rst: Option<SeqKind>,
+        spectec: Option<SeqKind>,
+    },
+    /// Field of a record of the `rst` sources that the SpecTec records lack
+    FieldNotInSpecTec(&'g str),
+    /// Field of a record of the SpecTec sources that the `rst` records lack
+    FieldNotInRst(&'g str),
+}
+
+/// What an alternative is aligned by
+#[derive(PartialEq)]
+enum Key {
+    Constructor(String),
+    Nonterminal(String),
+    /// Neither, aligned only if it is the last one left on both sides
+    Other,
+}
+
+impl<'g> SpecTecCheck<'g> {
+    pub fn new(rst: &'g Grammar, spectec: &'g Grammar) -> Self {
+        let abstract_syntax = |grammar: &'g Grammar| {
+            grammar
+                .nonterminals()
+                .into_iter()
+                .filter(|nt| format_of(nt).is_none())
+                .collect::<Vec<_>>()
+        };
+        let rst_names = abstract_syntax(rst);
+        let spectec_names = abstract_syntax(spectec);
+
+        let mut pairs = vec![];
+        let mut only_rst = vec![];
+        for nonterminal in &rst_names {
+            match spectec_names.iter().find(|nt| *nt == nonterminal) {
+                Some(_) => pairs.push(Pair::new(nonterminal, rst, spectec)),
+                None => only_rst.push(*nonterminal),
+            }
+        }
+        let only_spectec = spectec_names
+            .into_iter()
+            .filter(|nt| !rst_names.contains(nt))
+            .collect();
+        Self {
+            pairs,
+            only_rst,
+            only_spectec,
+        }
+    }
+
+    /// Whether every nonterminal of both sources agrees
+    pub fn is_consistent(&self) -> bool {
+        self.pairs.iter().all(|pair| pair.findings.is_empty())
+    }
+}
+
+impl<'g> Pair<'g> {
+    fn new(nonterminal: &'g str, rst: &'g Grammar, spectec: &'g Grammar) -> Self {
+        let alternatives = |grammar: &'g Grammar| {
+            grammar
+                .alternatives(nonterminal)
+                .into_iter()
+                .map(|(_, elem)| elem)
+                .collect::<Vec<_>>()
+        };
+        let rst_elems = alternatives(rst);
+        let mut spectec_elems = alternatives(spectec);
+
+        let mut findings = vec![];
+        let mut unaligned = vec![];
+        for elem in rst_elems.iter().copied() {
+            let key = key(elem);
+            let aligned = match key {
+                Key::Other => None,
+                _ => spectec_elems.iter().position(|e| self::key(e) == key),
+            };
+            match aligned {
+                Some(i) => iterations(elem, spectec_elems.remove(i), &mut findings),
+                None => unaligned.push(elem),
+            }
+        }
+        // e.g. the record of `\limits`, or `\mut~\valtype` of `\globaltype`
+        if let ([elem], [other]) = (unaligned.as_slice(), spectec_elems.as_slice()) {
+            if key(elem) == Key::Other && key(other) == Key::Other {
+                iterations(elem, other, &mut findings);
+                unaligned.clear();
+                spectec_elems.clear();
+            }
+        }
+        findings.extend(unaligned.into_iter().map(Finding::NotInSpecTec));
+        findings.extend(spectec_elems.into_iter().map(Finding::NotInRst));
+
+        let rst_fields = fields(&rst_elems);
+        let spectec_fields = fields(&alternatives(spectec));
+        for field in &rst_fields {
+            if !spectec_fields.contains(field) {
+                findings.push(Finding::FieldNotInSpecTec(field));
+            }
+        }
+        for field in &spectec_fields {
+            if !rst_fields.contains(field) {
+                findings.push(Finding::FieldNotInRst(field));
+            }
+        }
+
+        let origin = |grammar: &'g Grammar| {
+            grammar
+                .productions()
+                .find(|(_, p)| p.nonterminal() == nonterminal)
+                .map(|(origin, _)| origin)
+                .expect("the nonterminal is defined")
+        };
+        Self {
+            nonterminal,
+            rst: origin(rst),
+            spectec: origin(spectec),
+            findings,
+        }
+    }
+}
+
+fn key(elem: &RhsElem) -> Key {
+    match elem.symbols.as_slice() {
+        [Symbol::SNonterm(nt)] => Key::Nonterminal(nt.name.clone()),
+        symbols => match symbols.iter().find_map(first_terminal) {
+            Some(terminal) => Key::Constructor(terminal_key(terminal)),
+            None => Key::Other,
+        },
+    }
+}
+
+fn first_terminal(symbol: &Symbol) -> Option<&str> {
+    match symbol {
+        Symbol::STerm(name) => Some(name),
+        Symbol::SGroup(group) => group.symbols.iter().find_map(first_terminal),
+        _ => None,
+    }
+}
+
+/// The iteration mismatches of two aligned alternatives, between the uses of
+/// a nonterminal in the same order, e.g. the second `\u32` of each record
+fn iterations<'g>(rst: &'g RhsElem, spectec: &'g RhsElem, findings: &mut Vec<Finding<'g>>) {
+    let mut rst_uses = vec![];
+    uses_of(&rst.symbols, None, &mut rst_uses);
+    let mut spectec_uses = vec![];
+    uses_of(&spectec.symbols, None, &mut spectec_uses);
+
+    for (i, (nonterminal, seq_kind)) in rst_uses.iter().enumerate() {
+        let nth = rst_uses[..i]
+            .iter()
+            .filter(|(nt, _)| nt == nonterminal)
+            .count();
+        let Some((_, other)) = spectec_uses
+            .iter()
+            .filter(|(nt, _)| nt == nonterminal)
+            .nth(nth)
+        else {
+            continue;
+        };
+        let finding = Finding::Iteration {
+            nonterminal,
+            rst: seq_kind.clone(),
+            spectec: other.clone(),
+        };
+        if seq_kind != other && !findings.contains(&finding) {
+            findings.push(finding);
+        }
+    }
+}
+
+/// Nonterminals used by symbols, with the iteration of their own or of the
+/// innermost group around them that has one
+fn uses_of<'g>(
+    symbols: &'g [Symbol],
+    outer: Option<&SeqKind>,
+    uses: &mut Vec<(&'g str, Option<SeqKind>)>,
+) {
+    let many = Some(&SeqKind::ManyPossibleEmpty);
+    for symbol in symbols {
+        match symbol {
+            Symbol::SNonterm(nt) => uses.push((&nt.name, nt.seq_kind.as_ref().or(outer).cloned())),
+            Symbol::SVec(vec) => uses.push((&vec.over.name, many.cloned())),
+            Symbol::SBracedVec(vec) => uses.push((&vec.inner.over.name, many.cloned())),
+            Symbol::SArrow(arrow) => {
+                uses.push((&arrow.from.name, arrow.from.seq_kind.clone()));
+                uses.push((&arrow.to.name, arrow.to.seq_kind.clone()));
+            }
+            Symbol::SRecord(record) => {
+                for (_, value) in &record.pairs {
+                    uses_of(std::slice::from_ref(value), outer, uses);
+                }
+            }
+            Symbol::SGroup(group) => {
+                uses_of(&group.symbols, group.seq_kind.as_ref().or(outer), uses)
+            }
+            Symbol::SBind(bind) => uses_of(std::slice::from_ref(&bind.symbol), outer, uses),
+            Symbol::STerm(_) | Symbol::SHex(_) | Symbol::SText(_) => {}
+        }
+    }
+}
+
+/// Fields of the records of some alternatives, in order
+fn fields<'g>(elems: &[&'g RhsElem]) -> Vec<&'g str> {
+    let mut fields = vec![];
+    for elem in elems {
+        for symbol in &elem.symbols {
+            if let Symbol::SRecord(record) = symbol {
+                for (key, _) in &record.pairs {
+                    if !fields.contains(&key.as_str()) {
+                        fields.push(key.as_str());
+                    }
+                }
+            }
+        }
+    }
+    fields
+}
+
+fn seq_kind(seq_kind: &Option<SeqKind>) -> String {
+    match seq_kind {
+        Some(seq_kind) => seq_kind.to_string(),
+        None => "none".to_string(),
+    }
+}
+
+impl Display for SpecTecCheck<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for pair in &self.pairs {
+            if !pair.findings.is_empty() {
+                write!(f, "{}", pair)?;
+            }
+        }
+        if !self.only_rst.is_empty() {
+            writeln!(f, "only in the rst sources: {}", self.only_rst.join(", "))?;
+        }
+        if !self.only_spectec.is_empty() {
+            writeln!(
+                f,
+                "only in the SpecTec sources: {}",
+                self.only_spectec.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Pair<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ({}:{} ~ {}:{})",
+            self.nonterminal,
+            self.rst.file.display(),
+            self.rst.line,
+            self.spectec.file.display(),
+            self.spectec.line
+        )?;
+        for finding in &self.findings {
+            writeln!(f, "  {}", finding)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Finding<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::NotInSpecTec(elem) => write!(f, "`{}` is not in the SpecTec sources", elem),
+            Finding::NotInRst(elem) => write!(f, "`{}` is not in the rst sources", elem),
+            Finding::Iteration {
+                nonterminal,
+                rst,
+                spectec,
+            } => write!(
+                f,
+                r"`\{}` is iterated {} in the rst sources and {} in SpecTec",
+                nonterminal,
+                seq_kind(rst),
+                seq_kind(spectec)
+            ),
+            Finding::FieldNotInSpecTec(name) => {
+                write!(f, "field `{}` is not in the SpecTec sources", name)
+            }
+            Finding::FieldNotInRst(name) => write!(f, "field `{}` is not in the rst sources", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn findings() {
+        let mut rst = Grammar::new("rst");
+        rst.add_source(
+            "syntax/types.rst",
+            r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 ~|~ \F32 ~|~ \F64 \\
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \production{result type} & \resulttype &::=&
+     [\vec(\valtype)] \\
+   \production{element segment mode} & \elemmode &::=&
+     \EPASSIVE ~|~ \EACTIVE~\{ \ETABLE~\tableidx, \EOFFSET~\expr \} \\
+   \production{instruction} & \Binstr &::=& \hex{01} \Rightarrow \NOP \\
+   \end{array}
+",
+        );
+        assert!(rst.errors().is_empty(), "{:?}", rst.errors());
+
+        let mut spectec = Grammar::new("spectec");
+        spectec.add_spectec_source(
+            "1-syntax.watsup",
+            r#"
+syntax numtype = I32 | I64 | F32 | F64
+syntax limits = { LMIN u32, LMAX u32 }
+syntax resulttype = list(valtype)
+syntax elemmode = ACTIVE tableidx expr | PASSIVE | DECLARE
+syntax vectype = V128
+"#,
+        );
+        assert!(spectec.errors().is_empty(), "{:?}", spectec.errors());
+
+        let check = SpecTecCheck::new(&rst, &spectec);
+        assert!(!check.is_consistent());
+        assert_eq!(check.only_rst, Vec::<&str>::new());
+        assert_eq!(check.only_spectec, vec!["vectype"]);
+        assert_eq!(
+            check.to_string(),
+            r"limits (syntax/types.rst:3 ~ 1-syntax.watsup:3)
+  `\u32` is iterated ^? in the rst sources and none in SpecTec
+elemmode (syntax/types.rst:3 ~ 1-syntax.watsup:5)
+  `\EPASSIVE` is not in the SpecTec sources
+  `\EACTIVE~\{ \ETABLE~\tableidx, \EOFFSET~\expr \}` is not in the SpecTec sources
+  `\ACTIVE~\tableidx~\expr` is not in the rst sources
+  `\PASSIVE` is not in the rst sources
+  `\DECLARE` is not in the rst sources
+  field `ETABLE` is not in the SpecTec sources
+  field `EOFFSET` is not in the SpecTec sources
+only in the SpecTec sources: vectype
+"
+        );
+    }
+
+    #[test]
+    fn unparsed_declarations() {
+        let mut rst = Grammar::new("rst");
+        rst.add_source(
+            "syntax/types.rst",
+            r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 \\
+   \end{array}
+",
+        );
+        let mut spectec = Grammar::new("spectec");
+        spectec.add_spectec_source(
+            "1-syntax.watsup",
+            "syntax numtype = I32 | (I64\nsyntax vectype = V128\n",
+        );
+        assert_eq!(spectec.errors().len(), 1);
+
+        // a declaration that does not parse leaves its nonterminal undefined
+        let check = SpecTecCheck::new(&rst, &spectec);
+        assert!(check.pairs.is_empty());
+        assert_eq!(check.only_rst, vec!["numtype"]);
+        assert_eq!(check.only_spectec, vec!["vectype"]);
+
+        let empty = Grammar::new("spectec");
+        assert!(SpecTecCheck::new(&rst, &empty).is_consistent());
+    }
+}
@@ -0,0 +1,426 @@
+use std::fmt::{self, Display};
+
+use crate::{
+    grammar::Grammar,
+    parser::SeqKind,
+    syntax::{
+        symbol::{SNonterm, Symbol},
+        RhsElem,
+    },
+};
+
+/// Structural difference between two grammars
+#[derive(Debug, PartialEq)]
+pub struct GrammarDiff<'g> {
+    pub added: Vec<&'g str>,
+    pub removed: Vec<&'g str>,
+    pub changed: Vec<NonterminalDiff<'g>>,
+}
+
+/// Changes within a nonterminal present in both grammars
+#[derive(Debug, PartialEq)]
+pub struct NonterminalDiff<'g> {
+    pub nonterminal: &'g str,
+    pub added: Vec<&'g RhsElem>,
+    pub removed: Vec<&'g RhsElem>,
+    pub modified: Vec<AlternativeDiff<'g>>,
+}
+
+/// An alternative that kept its shape, e.g. its leading terminal, but whose
+/// symbols changed
+#[derive(Debug, PartialEq)]
+pub struct AlternativeDiff<'g> {
+    pub old: &'g RhsElem,
+    pub new: &'g RhsElem,
+    pub fields: Vec<FieldChange<'g>>,
+    pub seq_kinds: Vec<SeqKindChange<'g>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FieldChange<'g> {
+    Added(&'g str),
+    Removed(&'g str),
+    Renamed {
+        from: &'g str,
+        to: &'g str,
+    },
+    Retyped {
+        field: &'g str,
+        from: &'g Symbol,
+        to: &'g Symbol,
+    },
+}
+
+/// The iteration of a nonterminal use changed, e.g. `\u32` to `\u32^?`
+#[derive(Debug, PartialEq)]
+pub struct SeqKindChange<'g> {
+    pub nonterminal: &'g str,
+    pub from: Option<&'g SeqKind>,
+    pub to: Option<&'g SeqKind>,
+}
+
+impl<'g> GrammarDiff<'g> {
+    pub fn new(old: &'g Grammar, new: &'g Grammar) -> Self {
+        let old_names = old.nonterminals();
+        let new_names = new.nonterminals();
+
+        let added = new_names
+            .iter()
+            .filter(|n| !old_names.contains(n))
+            .copied()
+            .collect();
+        let removed = old_names
+            .iter()
+            .filter(|n| !new_names.contains(n))
+            .copied()
+            .collect();
+        let changed = new_names
+            .iter()
+            .filter(|n| old_names.contains(n))
+            .filter_map(|n| NonterminalDiff::new(n, old, new))
+            .collect();
+
+        Self {
+            added,
+            removed,
+            changed,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl<'g> NonterminalDiff<'g> {
+    fn new(nonterminal: &'g str, old: &'g Grammar, new: &'g Grammar) -> Option<Self> {
+        let alternatives = |grammar: &'g Grammar| {
+            grammar
+                .alternatives(nonterminal)
+                .into_iter()
+                .map(|(_, e)| e)
+                .collect()
+        };
+        Self::between(nonterminal, alternatives(old), alternatives(new))
+    }
+
+    /// Changes from one set of alternatives of a nonterminal to another, or
+    /// none if they are the same up to order
+    pub fn between(
+        nonterminal: &'g str,
+        old_elems: Vec<&'g RhsElem>,
+        new_elems: Vec<&'g RhsElem>,
+    ) -> Option<Self> {
+        let mut removed: Vec<&RhsElem> = old_elems
+            .iter()
+            .filter(|e| !new_elems.contains(e))
+            .copied()
+            .collect();
+        let mut added: Vec<&RhsElem> = new_elems
+            .iter()
+            .filter(|e| !old_elems.contains(e))
+            .copied()
+            .collect();
+
+        let mut modified = vec![];
+        let mut i = 0;
+        while i < added.len() {
+            let paired = removed
+                .iter()
+                .position(|old| same_shape(old, added[i], removed.len() == 1 && added.len() == 1));
+            match paired {
+                Some(j) => {
+                    let old = removed.remove(j);
+                    let new = added.remove(i);
+                    modified.push(AlternativeDiff::new(old, new));
+                }
+                None => i += 1,
+            }
+        }
+
+        if added.is_empty() && removed.is_empty() && modified.is_empty() {
+            return None;
+        }
+        Some(Self {
+            nonterminal,
+            added,
+            removed,
+            modified,
+        })
+    }
+}
+
+impl<'g> AlternativeDiff<'g> {
+    fn new(old: &'g RhsElem, new: &'g RhsElem) -> Self {
+        let mut diff = Self {
+            old,
+            new,
+            fields: vec![],
+            seq_kinds: vec![],
+        };
+        if old.symbols.len() == new.symbols.len() {
+            for (o, n) in old.symbols.iter().zip(&new.symbols) {
+                diff.symbol(o, n);
+            }
+        }
+        diff
+    }
+
+    fn symbol(&mut self, old: &'g Symbol, new: &'g Symbol) {
+        match (old, new) {
+            (Symbol::SNonterm(o), Symbol::SNonterm(n)) => self.nonterm(o, n),
+            (Symbol::SVec(o), Symbol::SVec(n)) => self.nonterm(&o.over, &n.over),
+            (Symbol::SBracedVec(o), Symbol::SBracedVec(n)) => {
+                self.nonterm(&o.inner.over, &n.inner.over)
+            }
+            (Symbol::SArrow(o), Symbol::SArrow(n)) => {
+                self.nonterm(&o.from, &n.from);
+                self.nonterm(&o.to, &n.to);
+            }
+            (Symbol::SRecord(o), Symbol::SRecord(n)) => self.record(&o.pairs, &n.pairs),
+            _ => {}
+        }
+    }
+
+    fn nonterm(&mut self, old: &'g SNonterm, new: &'g SNonterm) {
+        if old.name == new.name && old.seq_kind != new.seq_kind {
+            self.seq_kinds.push(SeqKindChange {
+                nonterminal: &new.name,
+                from: old.seq_kind.as_ref(),
+                to: new.seq_kind.as_ref(),
+            });
+        }
+    }
+
+    fn record(&mut self, old: &'g [(String, Symbol)], new: &'g [(String, Symbol)]) {
+        let find = |pairs: &'g [(String, Symbol)], key: &str| pairs.iter().find(|(k, _)| k == key);
+
+        for (i, (key, value)) in old.iter().enumerate() {
+            match find(new, key) {
+                Some((_, new_value)) if same_kind(value, new_value) => {
+                    self.symbol(value, new_value)
+                }
+                Some((_, new_value)) => self.fields.push(FieldChange::Retyped {
+                    field: key,
+                    from: value,
+                    to: new_value,
+                }),
+                None => match new.get(i) {
+                    // Same position and value under a name unknown to `old`
+                    Some((new_key, new_value))
+                        if new_value == value && find(old, new_key).is_none() =>
+                    {
+                        self.fields.push(FieldChange::Renamed {
+                            from: key,
+                            to: new_key,
+                        })
+                    }
+                    _ => self.fields.push(FieldChange::Removed(key)),
+                },
+            }
+        }
+
+        for (key, _) in new {
+            let renamed = self
+                .fields
+                .iter()
+                .any(|c| matches!(c, FieldChange::Renamed { to, .. } if to == key));
+            if find(old, key).is_none() && !renamed {
+                self.fields.push(FieldChange::Added(key));
+            }
+        }
+    }
+}
+
+/// Whether two alternatives are versions of each other: they start with the
+/// same terminal, or they are the only alternatives left unmatched
+fn same_shape(old: &RhsElem, new: &RhsElem, only_candidates: bool) -> bool {
+    match (old.symbols.first(), new.symbols.first()) {
+        (Some(Symbol::STerm(o)), Some(Symbol::STerm(n))) => o == n,
+        (Some(o), Some(n)) => only_candidates && same_kind(o, n),
+        _ => false,
+    }
+}
+
+/// Whether two symbols only differ in names and iterations
+fn same_kind(old: &Symbol, new: &Symbol) -> bool {
+    match (old, new) {
+        (Symbol::SNonterm(o), Symbol::SNonterm(n)) => o.name == n.name,
+        (Symbol::SVec(o), Symbol::SVec(n)) => o.over.name == n.over.name,
+        (Symbol::SBracedVec(o), Symbol::SBracedVec(n)) => o.inner.over.name == n.inner.over.name,
+        (Symbol::SArrow(o), Symbol::SArrow(n)) => {
+            o.from.name == n.from.name && o.to.name == n.to.name
+        }
+        (Symbol::STerm(o), Symbol::STerm(n)) => o == n,
+        (Symbol::SRecord(_), Symbol::SRecord(_)) => true,
+        _ => false,
+    }
+}
+
+fn seq_kind(seq_kind: Option<&SeqKind>) -> String {
+    match seq_kind {
+        Some(seq_kind) => seq_kind.to_string(),
+        None => "none".to_string(),
+    }
+}
+
+impl Display for GrammarDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in &self.added {
+            writeln!(f, r"+ \{}", name)?;
+        }
+        for name in &self.removed {
+            writeln!(f, r"- \{}", name)?;
+        }
+        for diff in &self.changed {
+            write!(f, "{}", diff)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for NonterminalDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, r"~ \{}", self.nonterminal)?;
+        for elem in &self.added {
+            writeln!(f, "    + {}", elem)?;
+        }
+        for elem in &self.removed {
+            writeln!(f, "    - {}", elem)?;
+        }
+        for diff in &self.modified {
+            writeln!(f, "    ~ {}", diff.old)?;
+            writeln!(f, "      {}", diff.new)?;
+            for field in &diff.fields {
+                match field {
+                    FieldChange::Added(key) => writeln!(f, r"      field \{} added", key)?,
+                    FieldChange::Removed(key) => writeln!(f, r"      field \{} removed", key)?,
+                    FieldChange::Renamed { from, to } => {
+                        writeln!(f, r"      field \{} renamed to \{}", from, to)?
+                    }
+                    FieldChange::Retyped { field, from, to } => {
+                        writeln!(f, r"      field \{}: {} -> {}", field, from, to)?
+                    }
+                }
+            }
+            for change in &diff.seq_kinds {
+                writeln!(
+                    f,
+                    r"      \{}: {} -> {}",
+                    change.nonterminal,
+                    seq_kind(change.from),
+                    seq_kind(change.to)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grammar(name: &str, productions: &str) -> Grammar {
+        let mut grammar = Grammar::new(name);
+        let source = format!(
+            ".. math::\n   \\begin{{array}}{{llll}}\n{}\n   \\end{{array}}\n",
+            productions
+        );
+        grammar.add_source("syntax/types.rst", &source);
+        assert!(grammar.errors().is_empty(), "{:?}", grammar.errors());
+        grammar
+    }
+
+    #[test]
+    fn nonterminals_added_and_removed() {
+        let old = grammar(
+            "old",
+            r"   \production{number type} & \numtype &::=& \I32 ~|~ \I64 \\
+   \production{vector type} & \vectype &::=& \V128 \\",
+        );
+        let new = grammar(
+            "new",
+            r"   \production{number type} & \numtype &::=&
+     \I32 ~|~
+     \I64 \\
+   \production{index type} & \idxtype &::=& \I32 ~|~ \I64 \\",
+        );
+        let diff = GrammarDiff::new(&old, &new);
+        assert_eq!(diff.added, vec!["idxtype"]);
+        assert_eq!(diff.removed, vec!["vectype"]);
+        assert!(diff.changed.is_empty());
+    }
+
+    #[test]
+    fn alternatives_added_and_removed() {
+        let old = grammar(
+            "old",
+            r"   \production{number type} & \numtype &::=& \I32 ~|~ \I64 ~|~ \F32 \\",
+        );
+        let new = grammar(
+            "new",
+            r"   \production{number type} & \numtype &::=& \I32 ~|~ \I64 ~|~ \F64 \\",
+        );
+        let diff = GrammarDiff::new(&old, &new);
+        let changed = &diff.changed[0];
+        assert_eq!(changed.nonterminal, "numtype");
+        assert_eq!(changed.added.len(), 1);
+        assert_eq!(changed.removed.len(), 1);
+        assert_eq!(diff.to_string(), "~ \\numtype\n    + \\F64\n    - \\F32\n");
+    }
+
+    #[test]
+    fn record_fields() {
+        let old = grammar(
+            "old",
+            r"   \production{limits} & \limits &::=& \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \production{table} & \table &::=& \{ \TTYPE~\tabletype \} \\",
+        );
+        let new = grammar(
+            "new",
+            r"   \production{limits} & \limits &::=& \{ \LMIN~\u32, \LMAXIMUM~\u32^?, \LIDX~\idxtype \} \\
+   \production{table} & \table &::=& \{ \TTYPE~\tabletype^\ast \} \\",
+        );
+        let diff = GrammarDiff::new(&old, &new);
+
+        let limits = &diff.changed[0].modified[0];
+        assert_eq!(
+            limits.fields,
+            vec![
+                FieldChange::Renamed {
+                    from: "LMAX",
+                    to: "LMAXIMUM"
+                },
+                FieldChange::Added("LIDX"),
+            ]
+        );
+
+        let table = &diff.changed[1].modified[0];
+        assert_eq!(
+            table.seq_kinds,
+            vec![SeqKindChange {
+                nonterminal: "tabletype",
+                from: None,
+                to: Some(&SeqKind::ManyPossibleEmpty),
+            }]
+        );
+    }
+
+    #[test]
+    fn constructor_arguments() {
+        let old = grammar(
+            "old",
+            r"   \production{element segment mode} & \elemmode &::=& \EPASSIVE ~|~ \EACTIVE~\tableidx \\",
+        );
+        let new = grammar(
+            "new",
+            r"   \production{element segment mode} & \elemmode &::=& \EPASSIVE ~|~ \EACTIVE~\tableidx^? ~|~ \EDECLARATIVE \\",
+        );
+        let diff = GrammarDiff::new(&old, &new);
+        let elemmode = &diff.changed[0];
+        assert_eq!(elemmode.added.len(), 1);
+        assert!(elemmode.removed.is_empty());
+        assert_eq!(elemmode.modified[0].seq_kinds[0].to, Some(&SeqKind::OptSeq));
+    }
+}
@@ -0,0 +1,170 @@
+use std::{borrow::Cow, fmt};
+
+use nom::error::{ContextError, ErrorKind, ParseError};
+
+/// Error produced by the `species` parsers.
+///
+/// Every variant records the input at which it was raised. Context frames
+/// (`in production `limits` → record → pair `LMAX``) wrap the innermost error
+/// with [`SpeciesError::Context`], outermost frame first.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SpeciesError<I> {
+    /// A production must start with `\production{...}`
+    ExpectedProduction(I),
+    /// A command that cannot appear at this position
+    UnknownMacro(I, String),
+    /// An opening `{`, `[`, `(` or `\{` without its closing counterpart
+    UnbalancedBrace(I),
+    /// A command that was expected to be a terminal, e.g. a record field
+    NotATerminal(I, String),
+    /// A command that was expected to be a nonterminal
+    NotANonterminal(I, String),
+    /// Error raised by a nom combinator
+    Nom(I, ErrorKind),
+    /// Additional context around an inner error
+    Context(I, Cow<'static, str>, Box<SpeciesError<I>>),
+}
+
+impl<I> SpeciesError<I> {
+    /// Input at which the innermost error was raised
+    pub fn input(&self) -> &I {
+        match self {
+            Self::Context(_, _, inner) => inner.input(),
+            Self::ExpectedProduction(input)
+            | Self::UnknownMacro(input, _)
+            | Self::UnbalancedBrace(input)
+            | Self::NotATerminal(input, _)
+            | Self::NotANonterminal(input, _)
+            | Self::Nom(input, _) => input,
+        }
+    }
+
+    /// The error without its context frames
+    pub fn root(&self) -> &Self {
+        match self {
+            Self::Context(_, _, inner) => inner.root(),
+            _ => self,
+        }
+    }
+
+    /// Context frames, outermost first
+    pub fn context(&self) -> Vec<&str> {
+        let mut frames = vec![];
+        let mut error = self;
+        while let Self::Context(_, ctx, inner) = error {
+            frames.push(ctx.as_ref());
+            error = inner;
+        }
+        frames
+    }
+
+    /// Wrap the error in a context frame
+    pub fn in_context(self, input: I, ctx: impl Into<Cow<'static, str>>) -> Self {
+        Self::Context(input, ctx.into(), Box::new(self))
+    }
+
+    /// Convert the input positions, e.g. to detach the error from its source
+    pub fn map_input<J>(self, f: &impl Fn(I) -> J) -> SpeciesError<J> {
+        match self {
+            Self::ExpectedProduction(input) => SpeciesError::ExpectedProduction(f(input)),
+            Self::UnknownMacro(input, name) => SpeciesError::UnknownMacro(f(input), name),
+            Self::UnbalancedBrace(input) => SpeciesError::UnbalancedBrace(f(input)),
+            Self::NotATerminal(input, name) => SpeciesError::NotATerminal(f(input), name),
+            Self::NotANonterminal(input, name) => SpeciesError::NotANonterminal(f(input), name),
+            Self::Nom(input, kind) => SpeciesError::Nom(f(input), kind),
+            Self::Context(input, ctx, inner) => {
+                SpeciesError::Context(f(input), ctx, Box::new(inner.map_input(f)))
+            }
+        }
+    }
+}
+
+impl SpeciesError<&str> {
+    /// Copy the input positions so that the error outlives the source
+    pub fn into_owned(self) -> SpeciesError<String> {
+        self.map_input(&|input: &str| input.to_string())
+    }
+}
+
+impl<I> ParseError<I> for SpeciesError<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        Self::Nom(input, kind)
+    }
+
+    fn append(_input: I, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// Prefer the branch that got further into a production
+    fn or(self, other: Self) -> Self {
+        if self.context().len() > other.context().len() {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+impl<I> ContextError<I> for SpeciesError<I> {
+    fn add_context(input: I, ctx: &'static str, other: Self) -> Self {
+        other.in_context(input, ctx)
+    }
+}
+
+impl<I: AsRef<str>> fmt::Display for SpeciesError<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frames = self.context();
+        if !frames.is_empty() {
+            write!(f, "in {}: ", frames.join(" → "))?;
+        }
+
+        match self.root() {
+            Self::ExpectedProduction(_) => write!(f, r"expected `\production`")?,
+            Self::UnknownMacro(_, name) => write!(f, r"unexpected macro `\{}`", name)?,
+            Self::UnbalancedBrace(_) => write!(f, "unbalanced brace")?,
+            Self::NotATerminal(_, name) => write!(f, r"`\{}` is not a terminal", name)?,
+            Self::NotANonterminal(_, name) => write!(f, r"`\{}` is not a nonterminal", name)?,
+            Self::Nom(_, kind) => write!(f, "{}", kind.description())?,
+            Self::Context(..) => unreachable!(),
+        }
+
+        write!(f, " at `{}`", snippet(self.input().as_ref()))
+    }
+}
+
+impl<I: AsRef<str> + fmt::Debug> std::error::Error for SpeciesError<I> {}
+
+fn snippet(input: &str) -> &str {
+    let input = input.trim_start();
+    let line = input.lines().next().unwrap_or_default();
+    match line.char_indices().nth(40) {
+        Some((i, _)) => &line[..i],
+        None => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn context_stack() {
+        let error = SpeciesError::NotATerminal(r"\u32^? \}", "u32".to_string())
+            .in_context("", "pair `LMAX`")
+            .in_context("", "record")
+            .in_context("", "production `limits`");
+
+        assert_eq!(
+            error.context(),
+            vec!["production `limits`", "record", "pair `LMAX`"]
+        );
+        assert_eq!(
+            error.root(),
+            &SpeciesError::NotATerminal(r"\u32^? \}", "u32".to_string())
+        );
+        assert_eq!(
+            error.to_string(),
+            r"in production `limits` → record → pair `LMAX`: `\u32` is not a terminal at `\u32^? \}`"
+        );
+    }
+}
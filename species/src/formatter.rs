@@ -0,0 +1,407 @@
+//! Canonical layout of the production blocks of `rst` sources.
+//!
+//! A block is read into the rows and cells of its array, and written back
+//! with one statement per production or abbreviation: the cells left of the
+//! relation on one line, each padded to the widest of its column in the
+//! block so that the relations line up, and the alternatives on the next
+//! one, joined by `~|~`, or one per line, each but the last ending in
+//! `\\&&|&`, when they do not fit in the width. Runs of spaces and ties between symbols become a
+//! single space or their ties, e.g. `\I32 ~ \I64` becomes `\I32~\I64`, and
+//! groups and side conditions are kept as written.
+//!
+//! Only blocks of productions whose right-hand sides are in one column are
+//! formatted, and only when the new layout reads back to the same
+//! [`MathBlock`]. Other blocks, e.g. records aligned over several columns,
+//! and the prose around the blocks are left untouched.
+
+use crate::{
+    syntax::{
+        array::{Array, Row},
+        MathBlock,
+    },
+    tex::{self, Token},
+};
+
+#[derive(Debug, Clone)]
+pub struct Formatter {
+    width: usize,
+}
+
+/// A formatted source, with the lines of the blocks that changed, from 1
+#[derive(Debug, PartialEq)]
+pub struct Formatted {
+    pub text: String,
+    pub changed: Vec<usize>,
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self { width: 80 }
+    }
+}
+
+impl Formatter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Put the alternatives of a production one per line past `width`
+    /// columns, 80 by default
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn format(&self, source: &str) -> Formatted {
+        let lines: Vec<&str> = source.split_inclusive('\n').collect();
+        let mut formatted = Formatted {
+            text: String::with_capacity(source.len()),
+            changed: vec![],
+        };
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            formatted.text.push_str(line);
+            i += 1;
+            if !line.starts_with(".. math::") {
+                continue;
+            }
+            let len = lines[i..]
+                .iter()
+                .position(|line| line.trim_end_matches(['\r', '\n']).is_empty())
+                .unwrap_or(lines.len() - i);
+            let block: Vec<&str> = lines[i..i + len]
+                .iter()
+                .map(|line| line.trim_end_matches(['\r', '\n']))
+                .collect();
+            match self.block(&block) {
+                Some(new) if new != block => {
+                    let newline = if lines[i].ends_with("\r\n") {
+                        "\r\n"
+                    } else {
+                        "\n"
+                    };
+                    for line in &new {
+                        formatted.text.push_str(line);
+                        formatted.text.push_str(newline);
+                    }
+                    // no line break after the last line if there was none
+                    if !lines[i + len - 1].ends_with('\n') {
+                        formatted
+                            .text
+                            .truncate(formatted.text.len() - newline.len());
+                    }
+                    formatted.changed.push(i + 1);
+                }
+                _ => lines[i..i + len]
+                    .iter()
+                    .for_each(|line| formatted.text.push_str(line)),
+            }
+            i += len;
+        }
+        formatted
+    }
+
+    /// Lines of a production block in canonical layout, or none if it is
+    /// not one the formatter reads
+    fn block(&self, lines: &[&str]) -> Option<Vec<String>> {
+        let source = lines.concat();
+        if !source.contains(r"\production") {
+            return None;
+        }
+        let Ok(("", before)) = MathBlock::parser(&source) else {
+            return None;
+        };
+        let (_, array) = Array::parser(&source).ok()?;
+        let relation = array.relation_column();
+        let rows: Vec<_> = array.rows.iter().filter(|row| !row.is_blank()).collect();
+        // a right-hand side over several columns
+        if rows
+            .iter()
+            .any(|row| row.cells.iter().skip(relation + 2).any(|c| !c.is_empty()))
+        {
+            return None;
+        }
+
+        let indent = &lines[0][..lines[0].len() - lines[0].trim_start().len()];
+        let begin = source.find(r"\begin{array}")? + r"\begin{array}".len();
+        let (spec, _) = tex::group(source[begin..].trim_start())?;
+        let mut out = vec![format!(r"{}\begin{{array}}{{{}}}", indent, spec)];
+
+        let mut statements = vec![];
+        let mut start = 0;
+        while start < rows.len() {
+            let end = rows[start + 1..]
+                .iter()
+                .position(|row| matches!(row.cell(relation), "::=" | r"\equiv"))
+                .map_or(rows.len(), |i| start + 1 + i);
+            statements.push(&rows[start..end]);
+            start = end;
+        }
+        // the widest of each column left of the relation
+        let mut widths = vec![0; relation];
+        for statement in &statements {
+            for (i, width) in widths.iter_mut().enumerate() {
+                *width = (*width).max(spacing(statement[0].cell(i))?.chars().count());
+            }
+        }
+        for statement in statements {
+            out.extend(self.statement(indent, relation, &widths, statement)?);
+        }
+        out.push(format!(r"{}\end{{array}}", indent));
+
+        let after = out.concat();
+        match MathBlock::parser(&after) {
+            Ok(("", block)) if block == before => Some(out),
+            _ => None,
+        }
+    }
+
+    /// Lines of the rows of one production or abbreviation, the cells left
+    /// of the relation padded to `widths`
+    fn statement(
+        &self,
+        indent: &str,
+        relation: usize,
+        widths: &[usize],
+        rows: &[&Row<'_>],
+    ) -> Option<Vec<String>> {
+        let first = rows[0];
+        let prefix = (0..relation)
+            .map(|i| Some(format!("{:1$}", spacing(first.cell(i))?, widths[i])))
+            .collect::<Option<Vec<_>>>()?
+            .join(" & ");
+        let rel = first.cell(relation);
+        let head = format!("{}{} &{}&", indent, prefix, rel);
+        let body = format!("{}  ", indent);
+
+        // the right-hand side, continuation rows joined to the row before
+        let mut rhs = String::new();
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 && (0..relation).any(|i| !row.cell(i).is_empty()) {
+                return None;
+            }
+            if row.cell(relation) == "|" {
+                rhs.push_str(" | ");
+            } else {
+                rhs.push(' ');
+            }
+            rhs.push_str(row.cell(relation + 1));
+        }
+        let rhs = rhs.trim_start_matches([' ', '|']);
+
+        if rel == r"\equiv" {
+            return Some(vec![head, format!(r"{}{} \\", body, spacing(rhs)?)]);
+        }
+        let alternatives = alternatives(rhs)
+            .into_iter()
+            .map(spacing)
+            .collect::<Option<Vec<_>>>()?;
+        let line = format!(r"{}{} \\", body, alternatives.join(" ~|~ "));
+        if line.chars().count() <= self.width {
+            return Some(vec![head, line]);
+        }
+        let separator = format!(r" \\{}|&", "&".repeat(relation));
+        let last = alternatives.len() - 1;
+        let mut lines = vec![head];
+        for (i, alternative) in alternatives.iter().enumerate() {
+            let end = if i == last { r" \\" } else { &separator };
+            lines.push(format!("{}{}{}", body, alternative, end));
+        }
+        Some(lines)
+    }
+}
+
+/// Alternatives of a right-hand side, split at the `|` outside groups
+fn alternatives(rhs: &str) -> Vec<&str> {
+    let mut alternatives = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (offset, token) in tex::tokens(rhs) {
+        match token {
+            Token::BeginGroup => depth += 1,
+            Token::EndGroup => depth -= 1,
+            Token::Char('|') if depth == 0 => {
+                alternatives.push(&rhs[start..offset]);
+                start = offset + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&rhs[start..]);
+    alternatives
+}
+
+/// The symbols of a cell with canonical spacing between them, or none if
+/// it has a comment, which the layout could not keep
+fn spacing(cell: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut ties = 0;
+    let mut space = false;
+    let mut rest = cell.trim();
+    while let Some((token, tail)) = tex::next(rest) {
+        let mut end = rest.len() - tail.len();
+        match token {
+            Token::Space if rest.starts_with('%') => return None,
+            Token::Space => {
+                space = true;
+                rest = tail;
+                continue;
+            }
+            Token::Tie => {
+                ties += 1;
+                rest = tail;
+                continue;
+            }
+            Token::BeginGroup => end = rest.len() - tex::group(rest).map_or("", |(_, t)| t).len(),
+            // side conditions are kept as written
+            Token::Char('(') if tail.starts_with(r"\iff") => {
+                end = rest.len() - condition_end(rest).map_or("", |t| t).len();
+            }
+            _ => {}
+        }
+        if out.is_empty() {
+            // layout before the first symbol
+        } else if ties > 0 {
+            out.push_str(&"~".repeat(ties));
+        } else if space {
+            out.push(' ');
+        }
+        ties = 0;
+        space = false;
+        out.push_str(&rest[..end]);
+        rest = &rest[end..];
+    }
+    Some(out)
+}
+
+/// The input after the `)` closing the `(` it starts with
+fn condition_end(input: &str) -> Option<&str> {
+    let mut depth = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 1 => return Some(&input[i + 1..]),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPES: &str = r"Number Types
+
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=& \I32 ~ | ~\I64
+     ~|~ \F32 ~|~ \F64 \\
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32,  \LMAX~\u32^? \} \\
+   \end{array}
+
+Prose with :math:`\I32 ~ \I64`.
+
+.. math::
+   \begin{array}{llll}
+   \production{instruction} & \Binstr &::=& \hex{00} \Rightarrow \UNREACHABLE
+     \\&&|& \hex{01}  \Rightarrow \NOP \\&&|&
+     \hex{1A} \Rightarrow \DROP \\&&|&
+     \hex{FC}~~i{:}\Bu32 \Rightarrow \X{op}_i \qquad (\iff  i < 8) \\
+   \end{array}
+";
+
+    #[test]
+    fn canonical_layout() {
+        let formatted = Formatter::new().width(60).format(TYPES);
+        assert_eq!(formatted.changed, vec![4, 14]);
+        assert_eq!(
+            formatted.text,
+            r"Number Types
+
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 ~|~ \F32 ~|~ \F64 \\
+   \production{limits}      & \limits  &::=&
+     \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \end{array}
+
+Prose with :math:`\I32 ~ \I64`.
+
+.. math::
+   \begin{array}{llll}
+   \production{instruction} & \Binstr &::=&
+     \hex{00} \Rightarrow \UNREACHABLE \\&&|&
+     \hex{01} \Rightarrow \NOP \\&&|&
+     \hex{1A} \Rightarrow \DROP \\&&|&
+     \hex{FC}~~i{:}\Bu32 \Rightarrow \X{op}_i \qquad (\iff  i < 8) \\
+   \end{array}
+"
+        );
+        let again = Formatter::new().width(60).format(&formatted.text);
+        assert!(again.changed.is_empty());
+        assert_eq!(again.text, formatted.text);
+
+        // wide enough for the instructions on one line
+        let wide = Formatter::new().width(200).format(&formatted.text);
+        assert_eq!(wide.changed, vec![14]);
+    }
+
+    /// The relations of a block line up, and a formatted block is left as
+    /// it is, whatever widths its cells had before
+    #[test]
+    fn aligned() {
+        let source = r".. math::
+   \begin{array}{llcll}
+   \production{parameter} & \Tparam &::=& \text{(}~\text{param}~~t{:}\Tvaltype~\text{)} \\
+   \production{result}  &  \Tresult &::=& \text{(}~\text{result}~~t{:}\Tvaltype~\text{)} \\
+   \production{parameter}&\text{(}~\text{param}~~\Tvaltype^\ast~\text{)} &\equiv&
+     (\text{(}~\text{param}~~\Tvaltype~\text{)})^\ast \\
+   \end{array}
+";
+        let formatted = Formatter::new().width(100).format(source);
+        assert_eq!(
+            formatted.text,
+            r".. math::
+   \begin{array}{llcll}
+   \production{parameter} & \Tparam                                        &::=&
+     \text{(}~\text{param}~~t{:}\Tvaltype~\text{)} \\
+   \production{result}    & \Tresult                                       &::=&
+     \text{(}~\text{result}~~t{:}\Tvaltype~\text{)} \\
+   \production{parameter} & \text{(}~\text{param}~~\Tvaltype^\ast~\text{)} &\equiv&
+     (\text{(}~\text{param}~~\Tvaltype~\text{)})^\ast \\
+   \end{array}
+"
+        );
+        for width in [40, 100] {
+            let once = Formatter::new().width(width).format(source);
+            let twice = Formatter::new().width(width).format(&once.text);
+            assert!(twice.changed.is_empty(), "{}", twice.text);
+            assert_eq!(twice.text, once.text);
+        }
+    }
+
+    #[test]
+    fn untouched_blocks() {
+        // a record aligned over two columns, and a comment
+        let source = r".. math::
+   \begin{array}{llcl@{\qquad}l}
+   \production{module} & \module &::=& \{ &
+     \MTYPES~\vec(\functype), \\&&&&
+     \MSTART~\start^? \quad\} \\
+   \end{array}
+
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=& % comment
+     \I32 ~|~ \I64 \\
+   \end{array}";
+        let formatted = Formatter::new().format(source);
+        assert!(formatted.changed.is_empty());
+        assert_eq!(formatted.text, source);
+    }
+}
@@ -0,0 +1,693 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs,
+    path::Path,
+};
+
+use crate::{
+    parser::SeqKind,
+    spec::Spec,
+    syntax::{
+        symbol::{SNonterm, Symbol},
+        RhsElem,
+    },
+};
+
+/// Instance of a nonterminal, following the structure of its productions
+#[derive(Debug, PartialEq, Clone)]
+pub enum Node {
+    /// Terminal, e.g. `I32`
+    Term(String),
+    /// Chosen alternative of a nonterminal
+    Nonterm { name: String, children: Vec<Node> },
+    /// `\{ \LMIN~..., \LMAX~... \}`
+    Record(Vec<(String, Node)>),
+    /// Vector or iteration
+    Seq(Vec<Node>),
+    /// Function type like `\resulttype \to \resulttype`
+    Arrow(Box<Node>, Box<Node>),
+    /// Value of a nonterminal without production, e.g. `\u32` or `\name`
+    Value(String),
+}
+
+/// Settings of a [`Generator`]
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub seed: u64,
+    /// Nesting of nonterminals after which the generator only picks the
+    /// alternatives that terminate soonest
+    pub max_depth: usize,
+    /// Upper bound of the length of vectors and iterations
+    pub max_len: usize,
+    weights: HashMap<String, Vec<u32>>,
+}
+
+/// Random generator of grammar instances
+pub struct Generator<'s> {
+    spec: &'s Spec,
+    config: Config,
+    rng: Rng,
+    /// Shortest derivation of each nonterminal
+    heights: HashMap<&'s str, usize>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            max_depth: 8,
+            max_len: 3,
+            weights: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Relative weights of the alternatives of a nonterminal, in the order of
+    /// [`Spec::alternatives_of`]. Missing weights default to 1, and a weight
+    /// of 0 disables an alternative unless the depth limit requires it.
+    pub fn weights(mut self, nonterminal: impl Into<String>, weights: Vec<u32>) -> Self {
+        self.weights.insert(nonterminal.into(), weights);
+        self
+    }
+
+    /// Add the weights of a TOML file mapping nonterminals to the weights of
+    /// their alternatives, e.g. `instr = [1, 0, 3]`
+    pub fn parse_weights(mut self, source: &str) -> Result<Self, String> {
+        let weights: HashMap<String, Vec<u32>> =
+            toml::from_str(source).map_err(|e| e.to_string())?;
+        self.weights.extend(weights);
+        Ok(self)
+    }
+
+    pub fn load_weights(self, path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.parse_weights(&source)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Nonterminals given weights, in no particular order
+    pub fn weighted(&self) -> impl Iterator<Item = &str> {
+        self.weights.keys().map(String::as_str)
+    }
+
+    fn weight(&self, nonterminal: &str, alternative: usize) -> u32 {
+        self.weights
+            .get(nonterminal)
+            .and_then(|w| w.get(alternative))
+            .copied()
+            .unwrap_or(1)
+    }
+}
+
+impl<'s> Generator<'s> {
+    pub fn new(spec: &'s Spec, config: Config) -> Self {
+        let rng = Rng::new(config.seed);
+        let heights = heights(spec);
+        Self {
+            spec,
+            config,
+            rng,
+            heights,
+        }
+    }
+
+    /// Generate an instance of a nonterminal, or `None` if the specification
+    /// does not define it or it has no finite instance, see
+    /// [`Generator::infinite`]
+    pub fn generate(&mut self, nonterminal: &str) -> Option<Node> {
+        self.spec.production(nonterminal)?;
+        if height(nonterminal, &self.heights) == usize::MAX {
+            return None;
+        }
+        Some(self.nonterm(nonterminal, 0))
+    }
+
+    /// Nonterminals of which every alternative uses itself, directly or
+    /// not, e.g. `\cycle ::= \CYCLE~\cycle`, so that none has a finite
+    /// instance
+    pub fn infinite(&self) -> Vec<&'s str> {
+        let mut infinite: Vec<_> = self
+            .heights
+            .iter()
+            .filter(|(_, &h)| h == usize::MAX)
+            .map(|(&name, _)| name)
+            .collect();
+        infinite.sort_unstable();
+        infinite
+    }
+
+    fn nonterm(&mut self, name: &str, depth: usize) -> Node {
+        let spec = self.spec;
+        let alternatives = spec.alternatives_of(name);
+        if alternatives.is_empty() {
+            return self.value(name);
+        }
+
+        // alternatives without a finite instance are never picked
+        let heights: Vec<usize> = alternatives
+            .iter()
+            .map(|a| self.elem_height(a.elem))
+            .collect();
+        let candidates: Vec<(usize, u32)> = if depth >= self.config.max_depth {
+            let shortest = heights.iter().copied().min().unwrap();
+            (0..alternatives.len())
+                .filter(|&i| heights[i] == shortest)
+                .map(|i| (i, 1))
+                .collect()
+        } else {
+            (0..alternatives.len())
+                .filter(|&i| heights[i] != usize::MAX)
+                .map(|i| (i, self.config.weight(name, i)))
+                .collect()
+        };
+        let choice = self.rng.weighted(&candidates).unwrap_or(0);
+
+        let children = alternatives[choice]
+            .elem
+            .symbols()
+            .iter()
+            .map(|symbol| self.symbol(symbol, depth + 1))
+            .collect();
+        Node::Nonterm {
+            name: name.to_string(),
+            children,
+        }
+    }
+
+    fn symbol(&mut self, symbol: &Symbol, depth: usize) -> Node {
+        match symbol {
+            Symbol::STerm(name) => Node::Term(name.clone()),
+            Symbol::SNonterm(nt) => self.snonterm(nt, depth),
+            Symbol::SRecord(record) => Node::Record(
+                record
+                    .pairs()
+                    .iter()
+                    .map(|(key, value)| (key.clone(), self.symbol(value, depth)))
+                    .collect(),
+            ),
+            Symbol::SVec(vec) => self.seq(vec.over(), &SeqKind::ManyPossibleEmpty, depth),
+            Symbol::SBracedVec(vec) => {
+                self.seq(vec.inner().over(), &SeqKind::ManyPossibleEmpty, depth)
+            }
+            Symbol::SArrow(arrow) => Node::Arrow(
+                Box::new(self.snonterm(arrow.from(), depth)),
+                Box::new(self.snonterm(arrow.to(), depth)),
+            ),
+            Symbol::SHex(byte) => Node::Value(format!("0x{:02X}", byte)),
+            Symbol::SText(keyword) => Node::Value(keyword.clone()),
+            Symbol::SBind(bind) => self.symbol(bind.symbol(), depth),
+            Symbol::SGroup(group) => {
+                let items = |g: &mut Self| {
+                    let items = group.symbols().iter();
+                    Node::Seq(items.map(|s| g.symbol(s, depth)).collect())
+                };
+                match group.seq_kind() {
+                    None => items(self),
+                    Some(seq_kind) => {
+                        let height = group
+                            .symbols()
+                            .iter()
+                            .map(|s| symbol_height(s, &self.heights))
+                            .max()
+                            .unwrap_or(0);
+                        let len = self.len(seq_kind, height, depth);
+                        Node::Seq((0..len).map(|_| items(self)).collect())
+                    }
+                }
+            }
+        }
+    }
+
+    fn snonterm(&mut self, nt: &SNonterm, depth: usize) -> Node {
+        match nt.seq_kind() {
+            None => self.nonterm(&nt.name, depth),
+            Some(seq_kind) => self.seq(nt, seq_kind, depth),
+        }
+    }
+
+    fn seq(&mut self, nt: &SNonterm, seq_kind: &SeqKind, depth: usize) -> Node {
+        let len = self.len(seq_kind, height(&nt.name, &self.heights), depth);
+        let items = (0..len).map(|_| self.nonterm(&nt.name, depth)).collect();
+        Node::Seq(items)
+    }
+
+    /// Length of an iteration of something of the given height, the fewest
+    /// items it allows past the depth limit, and none if the items have no
+    /// finite instance
+    fn len(&mut self, seq_kind: &SeqKind, height: usize, depth: usize) -> usize {
+        let (min, max) = match seq_kind {
+            SeqKind::OptSeq => (0, 1),
+            SeqKind::ManyNonEmpty => (1, self.config.max_len.max(1)),
+            SeqKind::ManyPossibleEmpty | SeqKind::ManyN => (0, self.config.max_len),
+        };
+        if height == usize::MAX {
+            0
+        } else if depth >= self.config.max_depth {
+            min
+        } else {
+            min + self.rng.below(max - min + 1)
+        }
+    }
+
+    fn value(&mut self, name: &str) -> Node {
+        let value = match name {
+            "byte" => self.rng.below(0x100).to_string(),
+            "f32" | "f64" => format!("{}.{}", self.rng.below(1000), self.rng.below(10)),
+            "name" => {
+                let len = self.rng.below(self.config.max_len + 1);
+                let name: String = (0..len)
+                    .map(|_| (b'a' + self.rng.below(26) as u8) as char)
+                    .collect();
+                format!("{:?}", name)
+            }
+            _ => match int_width(name) {
+                Some(bits) if name.starts_with('s') => self.rng.signed(bits).to_string(),
+                Some(bits) => self.rng.int(bits).to_string(),
+                None => format!(r"\{}", name),
+            },
+        };
+        Node::Value(value)
+    }
+
+    fn elem_height(&self, elem: &RhsElem) -> usize {
+        elem_height(elem, &self.heights)
+    }
+}
+
+/// Bit width of integer nonterminals like `u32`, `s33` or `i64`
+fn int_width(name: &str) -> Option<u32> {
+    let bits = name.strip_prefix(['u', 's', 'i'])?.parse().ok()?;
+    (1..=64).contains(&bits).then_some(bits)
+}
+
+/// Shortest derivation of every nonterminal, computed as a fixed point.
+/// Nonterminals without production count as leaves.
+fn heights(spec: &Spec) -> HashMap<&str, usize> {
+    let nonterminals = spec.grammar().nonterminals();
+    let mut heights: HashMap<&str, usize> = nonterminals
+        .iter()
+        .map(|&name| (name, usize::MAX))
+        .collect();
+    loop {
+        let mut changed = false;
+        for &name in &nonterminals {
+            let height = spec
+                .alternatives_of(name)
+                .iter()
+                .map(|a| elem_height(a.elem, &heights))
+                .min()
+                .unwrap_or(0);
+            if height < heights[name] {
+                heights.insert(name, height);
+                changed = true;
+            }
+        }
+        if !changed {
+            return heights;
+        }
+    }
+}
+
+fn elem_height(elem: &RhsElem, heights: &HashMap<&str, usize>) -> usize {
+    elem.symbols()
+        .iter()
+        .map(|s| symbol_height(s, heights))
+        .max()
+        .unwrap_or(0)
+        .saturating_add(1)
+}
+
+fn symbol_height(symbol: &Symbol, heights: &HashMap<&str, usize>) -> usize {
+    let nonterm = |nt: &SNonterm| match nt.seq_kind() {
+        Some(SeqKind::ManyNonEmpty) | None => height(&nt.name, heights),
+        Some(_) => 0,
+    };
+    match symbol {
+        Symbol::STerm(_)
+        | Symbol::SVec(_)
+        | Symbol::SBracedVec(_)
+        | Symbol::SHex(_)
+        | Symbol::SText(_) => 0,
+        Symbol::SNonterm(nt) => nonterm(nt),
+        Symbol::SRecord(record) => record
+            .pairs()
+            .iter()
+            .map(|(_, value)| match value {
+                Symbol::SNonterm(nt) => nonterm(nt),
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0),
+        Symbol::SArrow(arrow) => nonterm(arrow.from()).max(nonterm(arrow.to())),
+        Symbol::SBind(bind) => symbol_height(bind.symbol(), heights),
+        Symbol::SGroup(group) => match group.seq_kind() {
+            Some(SeqKind::ManyNonEmpty) | None => group
+                .symbols()
+                .iter()
+                .map(|s| symbol_height(s, heights))
+                .max()
+                .unwrap_or(0),
+            Some(_) => 0,
+        },
+    }
+}
+
+fn height(name: &str, heights: &HashMap<&str, usize>) -> usize {
+    heights.get(name).copied().unwrap_or(0)
+}
+
+/// Deterministic xorshift64* generator, so that a seed reproduces a tree
+/// independently of the platform
+struct Rng(u64);
+
+impl Rng {
+    /// State mixed from the seed by splitmix64, so that close seeds give
+    /// unrelated streams
+    fn new(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        // xorshift gets stuck on 0, which one seed mixes to
+        Self((z ^ (z >> 31)).max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// Integer of the given bit width, biased towards small values
+    fn int(&mut self, bits: u32) -> u64 {
+        let bits = 1 + self.below(bits as usize) as u32;
+        self.next() >> (64 - bits)
+    }
+
+    /// Signed integer of the given bit width, in two's complement, biased
+    /// towards small magnitudes
+    fn signed(&mut self, bits: u32) -> i64 {
+        let bits = 1 + self.below(bits as usize) as u32;
+        (self.next() as i64) >> (64 - bits)
+    }
+
+    fn weighted(&mut self, candidates: &[(usize, u32)]) -> Option<usize> {
+        let total: u64 = candidates.iter().map(|&(_, w)| w as u64).sum();
+        if total == 0 {
+            return candidates.first().map(|&(i, _)| i);
+        }
+        let mut pick = self.next() % total;
+        for &(i, weight) in candidates {
+            if pick < weight as u64 {
+                return Some(i);
+            }
+            pick -= weight as u64;
+        }
+        unreachable!()
+    }
+}
+
+impl Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Node::Term(name) => write!(f, "{}", name),
+            Node::Nonterm { name, children } => {
+                write!(f, "({}", name)?;
+                for child in children {
+                    write!(f, " {}", child)?;
+                }
+                write!(f, ")")
+            }
+            Node::Record(pairs) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, " {} {}", key, value)?;
+                }
+                write!(f, " }}")
+            }
+            Node::Seq(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Node::Arrow(from, to) => write!(f, "{} -> {}", from, to),
+            Node::Value(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Grammar;
+
+    use super::*;
+
+    fn spec() -> Spec {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "syntax/types.rst",
+            r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 ~|~ \F32 ~|~ \F64 \\
+   \production{value type} & \valtype &::=&
+     \numtype ~|~ \reftype \\
+   \production{reference type} & \reftype &::=&
+     \FUNCREF ~|~ \EXTERNREF \\
+   \production{result type} & \resulttype &::=&
+     [\vec(\valtype)] \\
+   \production{function type} & \functype &::=&
+     \resulttype \to \resulttype \\
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \production{instruction} & \instr &::=&
+     \NOP ~|~
+     \BLOCK~\instr^\ast~\END \\
+   \production{expression} & \expr &::=&
+     \instr^+ \\
+   \production{cycle} & \cycle &::=&
+     \CYCLE~\cycle \\
+   \production{statement} & \stmt &::=&
+     \LOOP~\cycle ~|~
+     \BLOCK~\cycle^\ast ~|~
+     \SEQ~(\numtype~\reftype)^+ ~|~
+     \OPT~(\BR~\cycle)^? \\
+   \end{array}
+",
+        );
+        assert!(grammar.errors().is_empty(), "{:?}", grammar.errors());
+        Spec::new(grammar)
+    }
+
+    fn depth(node: &Node) -> usize {
+        match node {
+            Node::Nonterm { children, .. } => 1 + children.iter().map(depth).max().unwrap_or(0),
+            Node::Record(pairs) => pairs.iter().map(|(_, v)| depth(v)).max().unwrap_or(0),
+            Node::Seq(items) => items.iter().map(depth).max().unwrap_or(0),
+            Node::Arrow(from, to) => depth(from).max(depth(to)),
+            Node::Term(_) | Node::Value(_) => 0,
+        }
+    }
+
+    #[test]
+    fn reproducible() {
+        let spec = spec();
+        let mut first = Generator::new(&spec, Config::default().seed(7));
+        let mut second = Generator::new(&spec, Config::default().seed(7));
+        for _ in 0..10 {
+            assert_eq!(first.generate("functype"), second.generate("functype"));
+        }
+        assert!(first.generate("module").is_none());
+    }
+
+    #[test]
+    fn records_and_options() {
+        let spec = spec();
+        let mut generator = Generator::new(&spec, Config::default().seed(1));
+        for _ in 0..20 {
+            let limits = generator.generate("limits").unwrap();
+            let Node::Nonterm { children, .. } = &limits else {
+                panic!("{}", limits)
+            };
+            let Node::Record(pairs) = &children[0] else {
+                panic!("{}", limits)
+            };
+            assert_eq!(pairs[0].0, "LMIN");
+            assert!(matches!(pairs[0].1, Node::Nonterm { .. } | Node::Value(_)));
+            assert!(matches!(&pairs[1].1, Node::Seq(items) if items.len() <= 1));
+        }
+    }
+
+    #[test]
+    fn non_empty_iterations() {
+        let spec = spec();
+        let mut generator = Generator::new(&spec, Config::default().seed(3).max_len(2));
+        for _ in 0..20 {
+            let expr = generator.generate("expr").unwrap();
+            let Node::Nonterm { children, .. } = &expr else {
+                panic!("{}", expr)
+            };
+            assert!(matches!(&children[0], Node::Seq(items) if (1..=2).contains(&items.len())));
+        }
+    }
+
+    #[test]
+    fn depth_limit() {
+        let spec = spec();
+        let config = Config::default()
+            .seed(5)
+            .max_depth(3)
+            .weights("instr", vec![0, 1]);
+        let mut generator = Generator::new(&spec, config);
+        for _ in 0..20 {
+            let expr = generator.generate("expr").unwrap();
+            assert!(depth(&expr) <= 5, "{}", expr);
+        }
+    }
+
+    #[test]
+    fn weights() {
+        let spec = spec();
+        let config = Config::default().weights("numtype", vec![0, 0, 0, 1]);
+        let mut generator = Generator::new(&spec, config);
+        for _ in 0..10 {
+            let numtype = generator.generate("numtype").unwrap();
+            assert_eq!(numtype.to_string(), "(numtype F64)");
+        }
+    }
+
+    #[test]
+    fn weights_file() {
+        let spec = spec();
+        let config = Config::default()
+            .parse_weights("numtype = [0, 0, 0, 1]\ninstr = [1]")
+            .unwrap();
+        let mut weighted: Vec<_> = config.weighted().collect();
+        weighted.sort_unstable();
+        assert_eq!(weighted, ["instr", "numtype"]);
+        let mut generator = Generator::new(&spec, config);
+        assert_eq!(
+            generator.generate("numtype").unwrap().to_string(),
+            "(numtype F64)"
+        );
+
+        assert!(Config::default().parse_weights("numtype = [-1]").is_err());
+        assert!(Config::default().parse_weights("numtype = 1").is_err());
+        let error = Config::default()
+            .load_weights("missing.toml")
+            .err()
+            .unwrap();
+        assert!(error.starts_with("missing.toml: "), "{}", error);
+    }
+
+    /// Every seed gives a state that xorshift does not get stuck on,
+    /// including the one the state used to be zero for
+    #[test]
+    fn seeds() {
+        for seed in [0, 1, u64::MAX, 0x9E37_79B9_7F4A_7C15] {
+            let mut rng = Rng::new(seed);
+            let values: Vec<_> = (0..4).map(|_| rng.next()).collect();
+            assert!(values.windows(2).all(|pair| pair[0] != pair[1]), "{}", seed);
+        }
+        assert_ne!(Rng::new(0).next(), Rng::new(1).next());
+    }
+
+    /// Signed integers take both signs over their whole range, unsigned
+    /// ones are never negative
+    #[test]
+    fn integers() {
+        let spec = spec();
+        let mut generator = Generator::new(&spec, Config::default().seed(17));
+        let values = |generator: &mut Generator, name: &str| -> Vec<i128> {
+            (0..500)
+                .map(|_| match generator.value(name) {
+                    Node::Value(value) => value.parse().unwrap(),
+                    node => panic!("{}", node),
+                })
+                .collect()
+        };
+        let s33 = values(&mut generator, "s33");
+        assert!(s33.iter().all(|n| (-(1 << 32)..1 << 32).contains(n)));
+        assert!(s33.iter().any(|&n| n < -(1 << 16)));
+        assert!(s33.iter().any(|&n| n > 1 << 16));
+        let u8 = values(&mut generator, "u8");
+        assert!(u8.iter().all(|n| (0..1 << 8).contains(n)));
+        let i64 = values(&mut generator, "i64");
+        assert!(i64.iter().all(|&n| n >= 0));
+    }
+
+    #[test]
+    fn print() {
+        let spec = spec();
+        let mut generator = Generator::new(&spec, Config::default().seed(11));
+        let functype = generator.generate("functype").unwrap().to_string();
+        assert!(
+            functype.starts_with("(functype (resulttype ["),
+            "{}",
+            functype
+        );
+        assert!(functype.contains("]) -> (resulttype ["), "{}", functype);
+    }
+
+    #[test]
+    fn infinite() {
+        let spec = spec();
+        let mut generator = Generator::new(&spec, Config::default().seed(13).max_len(2));
+        assert_eq!(generator.infinite(), vec!["cycle"]);
+        assert_eq!(generator.generate("cycle"), None);
+        for _ in 0..20 {
+            let stmt = generator.generate("stmt").unwrap();
+            let Node::Nonterm { children, .. } = &stmt else {
+                panic!("{}", stmt)
+            };
+            match &children[..] {
+                [Node::Term(t), Node::Seq(items)] if t == "BLOCK" || t == "OPT" => {
+                    assert!(items.is_empty(), "{}", stmt)
+                }
+                // `(\numtype~\reftype)^+` repeats the pair
+                [Node::Term(t), Node::Seq(items)] if t == "SEQ" => {
+                    assert!((1..=2).contains(&items.len()), "{}", stmt);
+                    for item in items {
+                        assert!(
+                            matches!(item, Node::Seq(pair) if pair.len() == 2),
+                            "{}",
+                            stmt
+                        );
+                    }
+                }
+                _ => panic!("{}", stmt),
+            }
+        }
+    }
+}
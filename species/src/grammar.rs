@@ -0,0 +1,731 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use nom::error::ErrorKind;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math_blocks,
+    parser::{memo::memoized, ws},
+    spectec,
+    syntax::{
+        function::FunctionDef, reduction::Reduction, rule::Rule, Abbreviation, MathBlock,
+        Production, RhsElem,
+    },
+    SpecLoader, SpeciesError,
+};
+
+/// Where a production was read from
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct Origin {
+    /// Name of the specification, e.g. `spec` or `memory64`
+    pub spec: String,
+    /// Path of the `rst` file, relative to `document/core`
+    pub file: PathBuf,
+    /// Line on which the math block starts
+    pub line: usize,
+}
+
+/// Productions of one specification, each tagged with its origin
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Grammar {
+    name: String,
+    productions: Vec<(Origin, Production)>,
+    abbreviations: Vec<(Origin, Abbreviation)>,
+    rules: Vec<(Origin, Rule)>,
+    reductions: Vec<(Origin, Reduction)>,
+    functions: Vec<(Origin, FunctionDef)>,
+    /// Not cached, see [`SpecLoader`](crate::SpecLoader)
+    #[serde(skip)]
+    errors: Vec<(Origin, SpeciesError<String>)>,
+}
+
+/// Grammars of several specifications, e.g. upstream and its proposal forks
+#[derive(Debug, Default)]
+pub struct SpecSet {
+    grammars: Vec<Grammar>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChangeKind {
+    Added,
+    Changed,
+}
+
+/// A nonterminal that a specification adds or changes relative to another
+#[derive(Debug, PartialEq)]
+pub struct Change<'g> {
+    pub nonterminal: &'g str,
+    pub kind: ChangeKind,
+    pub origins: Vec<&'g Origin>,
+}
+
+impl Grammar {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            productions: vec![],
+            abbreviations: vec![],
+            rules: vec![],
+            reductions: vec![],
+            functions: vec![],
+            errors: vec![],
+        }
+    }
+
+    /// Parse every `rst` file of a local specification checkout.
+    ///
+    /// `root` is either the repository root, which contains `document/core`,
+    /// or the `document/core` directory itself.
+    pub fn load(name: impl Into<String>, root: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(SpecLoader::new(name).load(root)?.grammar)
+    }
+
+    /// Parse every `rst` file of a revision of a local git checkout, reading
+    /// the files with `git show` so that the working tree is left untouched
+    pub fn load_git(
+        name: impl Into<String>,
+        repo: impl AsRef<Path>,
+        rev: &str,
+    ) -> io::Result<Self> {
+        let repo = repo.as_ref();
+        let listing = git(repo, &["ls-tree", "-r", "--name-only", rev])?;
+        let files: Vec<&str> = listing.lines().filter(|f| f.ends_with(".rst")).collect();
+        let prefix = "document/core/";
+        let in_core = files.iter().any(|f| f.starts_with(prefix));
+
+        let mut grammar = Self::new(name);
+        for file in files {
+            let relative = match file.strip_prefix(prefix) {
+                Some(relative) => relative,
+                None if in_core => continue,
+                None => file,
+            };
+            let content = git(repo, &["show", &format!("{}:{}", rev, file)])?;
+            grammar.add_source(relative, &content);
+        }
+        Ok(grammar)
+    }
+
+    /// Parse the production blocks, the typing rules, the reduction rules and
+    /// the numeric definitions of one `rst` source into the grammar
+    pub fn add_source(&mut self, file: impl AsRef<Path>, content: &str) {
+        for (line, block) in math_blocks(content) {
+            let origin = Origin {
+                spec: self.name.clone(),
+                file: file.as_ref().to_path_buf(),
+                line,
+            };
+            memoized(&block, || {
+                if block.contains(r"\production") {
+                    self.add_productions(origin, &block);
+                } else if block.contains(r"\stepto") {
+                    self.add_reductions(origin, &block);
+                } else if block.contains(r"\frac") && block.contains(r"\vdash") {
+                    self.add_rules(origin, &block);
+                } else if FunctionDef::is_definition(&block).is_ok() {
+                    self.add_functions(origin, &block);
+                }
+            });
+        }
+    }
+
+    /// Read the `syntax` declarations of one SpecTec source, a `.watsup` or
+    /// `.spectec` file, into the grammar, see [`spectec`](crate::spectec)
+    pub fn add_spectec_source(&mut self, file: impl AsRef<Path>, content: &str) {
+        for (line, definition) in spectec::syntax_definitions(content) {
+            let origin = Origin {
+                spec: self.name.clone(),
+                file: file.as_ref().to_path_buf(),
+                line,
+            };
+            match definition {
+                Ok(production) => self.productions.push((origin, production)),
+                Err(error) => self.errors.push((origin, error)),
+            }
+        }
+    }
+
+    fn add_productions(&mut self, origin: Origin, block: &str) {
+        match MathBlock::parser(block) {
+            Ok(("", mb)) => {
+                let productions = mb.productions.into_iter();
+                self.productions
+                    .extend(productions.map(|p| (origin.clone(), p)));
+                let abbreviations = mb.abbreviations.into_iter();
+                self.abbreviations
+                    .extend(abbreviations.map(|a| (origin.clone(), a)));
+            }
+            Ok((rest, _)) => {
+                let error = SpeciesError::Nom(rest.to_string(), ErrorKind::Eof);
+                self.errors.push((origin, error));
+            }
+            Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                self.errors.push((origin, e.into_owned()));
+            }
+            Err(nom::Err::Incomplete(_)) => unreachable!("parsers are complete"),
+        }
+    }
+
+    fn add_reductions(&mut self, origin: Origin, block: &str) {
+        match Reduction::block(block) {
+            Ok(("", reductions)) => {
+                self.reductions
+                    .extend(reductions.into_iter().map(|r| (origin.clone(), r)));
+            }
+            Ok((rest, _)) => {
+                let error = SpeciesError::Nom(rest.to_string(), ErrorKind::Eof);
+                self.errors.push((origin, error));
+            }
+            Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                self.errors.push((origin, e.into_owned()));
+            }
+            Err(nom::Err::Incomplete(_)) => unreachable!("parsers are complete"),
+        }
+    }
+
+    fn add_functions(&mut self, origin: Origin, block: &str) {
+        match FunctionDef::block(block) {
+            Ok(("", functions)) => {
+                self.functions
+                    .extend(functions.into_iter().map(|f| (origin.clone(), f)));
+            }
+            Ok((rest, _)) => {
+                let error = SpeciesError::Nom(rest.to_string(), ErrorKind::Eof);
+                self.errors.push((origin, error));
+            }
+            Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                let error = e
+                    .into_owned()
+                    .in_context(block.to_string(), "numeric definition");
+                self.errors.push((origin, error));
+            }
+            Err(nom::Err::Incomplete(_)) => unreachable!("parsers are complete"),
+        }
+    }
+
+    fn add_rules(&mut self, origin: Origin, block: &str) {
+        let (mut input, _) = ws(block).expect("ws never fails");
+        while !input.is_empty() {
+            match Rule::parser(input) {
+                Ok((tail, rule)) => {
+                    self.rules.push((origin.clone(), rule));
+                    input = ws(tail).expect("ws never fails").0;
+                }
+                Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                    let error = e.into_owned().in_context(input.to_string(), "typing rule");
+                    self.errors.push((origin, error));
+                    return;
+                }
+                Err(nom::Err::Incomplete(_)) => unreachable!("parsers are complete"),
+            }
+        }
+    }
+
+    /// Append the productions, rules and errors of another grammar, e.g. one
+    /// read from another file of the same specification
+    pub fn append(&mut self, other: Grammar) {
+        self.productions.extend(other.productions);
+        self.abbreviations.extend(other.abbreviations);
+        self.rules.extend(other.rules);
+        self.reductions.extend(other.reductions);
+        self.functions.extend(other.functions);
+        self.errors.extend(other.errors);
+    }
+
+    /// Tag everything in the grammar as read from `file` of the
+    /// specification `name`
+    pub(crate) fn relabel(&mut self, name: &str, file: &Path) {
+        self.name = name.to_string();
+        let origins = (self.productions.iter_mut().map(|(o, _)| o))
+            .chain(self.abbreviations.iter_mut().map(|(o, _)| o))
+            .chain(self.rules.iter_mut().map(|(o, _)| o))
+            .chain(self.reductions.iter_mut().map(|(o, _)| o))
+            .chain(self.functions.iter_mut().map(|(o, _)| o))
+            .chain(self.errors.iter_mut().map(|(o, _)| o));
+        for origin in origins {
+            origin.spec = name.to_string();
+            origin.file = file.to_path_buf();
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn productions(&self) -> impl Iterator<Item = (&Origin, &Production)> {
+        self.productions.iter().map(|(o, p)| (o, p))
+    }
+
+    /// Abbreviations of the text format, i.e. the `\equiv` rules
+    pub fn abbreviations(&self) -> impl Iterator<Item = (&Origin, &Abbreviation)> {
+        self.abbreviations.iter().map(|(o, a)| (o, a))
+    }
+
+    /// Typing rules of the validation chapter, i.e. the `\frac` blocks
+    pub fn rules(&self) -> impl Iterator<Item = (&Origin, &Rule)> {
+        self.rules.iter().map(|(o, r)| (o, r))
+    }
+
+    /// Reduction rules of the execution chapter, i.e. the `\stepto` rows
+    pub fn reductions(&self) -> impl Iterator<Item = (&Origin, &Reduction)> {
+        self.reductions.iter().map(|(o, r)| (o, r))
+    }
+
+    /// Definitions of the numerics chapter, e.g. `\iadd_N(i_1, i_2) = ...`
+    pub fn functions(&self) -> impl Iterator<Item = (&Origin, &FunctionDef)> {
+        self.functions.iter().map(|(o, f)| (o, f))
+    }
+
+    /// The `index`-th production, in the order of [`Grammar::productions`]
+    pub(crate) fn entry(&self, index: usize) -> (&Origin, &Production) {
+        let (origin, production) = &self.productions[index];
+        (origin, production)
+    }
+
+    /// Math blocks that look like productions but failed to parse
+    pub fn errors(&self) -> &[(Origin, SpeciesError<String>)] {
+        &self.errors
+    }
+
+    /// Defined nonterminals in order of first definition
+    pub fn nonterminals(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = vec![];
+        for (_, production) in &self.productions {
+            let name = production.nonterminal();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+
+    /// Alternatives of a nonterminal, gathered over all the productions that
+    /// define or continue (`\dots`) it
+    pub fn alternatives(&self, nonterminal: &str) -> Vec<(&Origin, &RhsElem)> {
+        self.productions
+            .iter()
+            .filter(|(_, p)| p.nonterminal() == nonterminal)
+            .flat_map(|(o, p)| p.rhs.elems.iter().map(move |e| (o, e)))
+            .filter(|(_, e)| !e.is_continuation())
+            .collect()
+    }
+
+    fn origins(&self, nonterminal: &str) -> Vec<&Origin> {
+        let mut origins: Vec<&Origin> = vec![];
+        for (origin, production) in &self.productions {
+            if production.nonterminal() == nonterminal && !origins.contains(&origin) {
+                origins.push(origin);
+            }
+        }
+        origins
+    }
+}
+
+impl SpecSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a specification checkout under the given name
+    pub fn load(
+        &mut self,
+        name: impl Into<String>,
+        root: impl AsRef<Path>,
+    ) -> io::Result<&Grammar> {
+        let grammar = Grammar::load(name, root)?;
+        Ok(self.insert(grammar))
+    }
+
+    /// Add a grammar, replacing an earlier one of the same name
+    pub fn insert(&mut self, grammar: Grammar) -> &Grammar {
+        self.grammars.retain(|g| g.name != grammar.name);
+        self.grammars.push(grammar);
+        self.grammars.last().unwrap()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Grammar> {
+        self.grammars.iter().find(|g| g.name == name)
+    }
+
+    pub fn grammars(&self) -> impl Iterator<Item = &Grammar> {
+        self.grammars.iter()
+    }
+
+    /// Nonterminals that `proposal` adds or whose alternatives differ from
+    /// `base`, or `None` if either specification is not loaded
+    pub fn changes(&self, base: &str, proposal: &str) -> Option<Vec<Change<'_>>> {
+        let base = self.get(base)?;
+        let proposal = self.get(proposal)?;
+        let base_names = base.nonterminals();
+
+        let mut changes = vec![];
+        for nonterminal in proposal.nonterminals() {
+            let kind = if !base_names.contains(&nonterminal) {
+                ChangeKind::Added
+            } else if !same_alternatives(
+                &base.alternatives(nonterminal),
+                &proposal.alternatives(nonterminal),
+            ) {
+                ChangeKind::Changed
+            } else {
+                continue;
+            };
+            changes.push(Change {
+                nonterminal,
+                kind,
+                origins: proposal.origins(nonterminal),
+            });
+        }
+        Some(changes)
+    }
+}
+
+fn same_alternatives(xs: &[(&Origin, &RhsElem)], ys: &[(&Origin, &RhsElem)]) -> bool {
+    xs.len() == ys.len()
+        && xs.iter().all(|(_, x)| ys.iter().any(|(_, y)| x == y))
+        && ys.iter().all(|(_, y)| xs.iter().any(|(_, x)| x == y))
+}
+
+pub(crate) fn git(repo: &Path, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(stderr.trim().to_string()));
+    }
+    String::from_utf8(output.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub(crate) fn document_root(root: &Path) -> PathBuf {
+    let core = root.join("document").join("core");
+    if core.is_dir() {
+        core
+    } else {
+        root.to_path_buf()
+    }
+}
+
+/// All `rst` files below `dir`, in a stable order
+pub(crate) fn rst_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    source_files(dir, &["rst"])
+}
+
+/// The `rst` files and the SpecTec sources below `dir`, in a stable order
+pub(crate) fn spec_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    source_files(dir, &["rst", "watsup", "spectec"])
+}
+
+fn source_files(dir: &Path, extensions: &[&str]) -> io::Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    let mut files = vec![];
+    for path in entries {
+        if path.is_dir() {
+            files.extend(source_files(&path, extensions)?);
+        } else if path
+            .extension()
+            .is_some_and(|ext| extensions.iter().any(|e| ext == *e))
+        {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::syntax::fixtures::TempDir;
+
+    use super::*;
+
+    const TYPES: &str = r"
+Number Types
+~~~~~~~~~~~~
+
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 \\
+   \end{array}
+
+Limits
+~~~~~~
+
+.. math::
+   \begin{array}{llll}
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \end{array}
+";
+
+    const PROPOSAL_TYPES: &str = r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 \\
+   \end{array}
+
+.. math::
+   \begin{array}{llll}
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u64, \LMAX~\u64^? \} \\
+   \production{index type} & \idxtype &::=&
+     \I32 ~|~ \I64 \\
+   \end{array}
+";
+
+    fn spec_root(name: &str, types: &str) -> TempDir {
+        let root = TempDir::new(name);
+        let syntax = root.join("document").join("core").join("syntax");
+        fs::create_dir_all(&syntax).unwrap();
+        fs::write(syntax.join("types.rst"), types).unwrap();
+        root
+    }
+
+    #[test]
+    fn load_tags_origins() {
+        let root = spec_root("origins", TYPES);
+        let grammar = Grammar::load("spec", &root).unwrap();
+
+        assert!(grammar.errors().is_empty());
+        assert_eq!(grammar.nonterminals(), vec!["numtype", "limits"]);
+
+        let origins: Vec<_> = grammar.productions().map(|(o, _)| o.clone()).collect();
+        assert_eq!(
+            origins,
+            vec![
+                Origin {
+                    spec: "spec".to_string(),
+                    file: PathBuf::from("syntax/types.rst"),
+                    line: 6,
+                },
+                Origin {
+                    spec: "spec".to_string(),
+                    file: PathBuf::from("syntax/types.rst"),
+                    line: 15,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn load_git_revisions() {
+        let root = spec_root("git", TYPES);
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&root)
+                .args([
+                    "-c",
+                    "user.name=species",
+                    "-c",
+                    "user.email=species@localhost",
+                ])
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success());
+        };
+        git(&["init", "-q"]);
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "types"]);
+        fs::write(root.join("document/core/syntax/types.rst"), PROPOSAL_TYPES).unwrap();
+        git(&["commit", "-q", "-a", "-m", "memory64"]);
+
+        let old = Grammar::load_git("old", &root, "HEAD~1").unwrap();
+        let new = Grammar::load_git("new", &root, "HEAD").unwrap();
+        let missing = Grammar::load_git("missing", &root, "no-such-rev");
+
+        assert_eq!(old.nonterminals(), vec!["numtype", "limits"]);
+        assert_eq!(new.nonterminals(), vec!["numtype", "limits", "idxtype"]);
+        assert_eq!(
+            new.productions().next().unwrap().0.file,
+            PathBuf::from("syntax/types.rst")
+        );
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn continued_alternatives() {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "syntax/instructions.rst",
+            r"
+.. math::
+   \begin{array}{llll}
+   \production{instruction} & \instr &::=&
+     \NOP ~|~ \UNREACHABLE \\
+   \end{array}
+
+.. math::
+   \begin{array}{llll}
+   \production{instruction} & \instr &::=&
+     \dots ~|~ \DROP \\
+   \end{array}
+",
+        );
+        assert_eq!(grammar.alternatives("instr").len(), 3);
+    }
+
+    #[test]
+    fn record_errors() {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "syntax/types.rst",
+            r"
+.. math::
+   \begin{array}{llll}
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \lmax~\u32^? \} \\
+   \end{array}
+",
+        );
+        assert_eq!(grammar.productions().count(), 0);
+        let (origin, error) = &grammar.errors()[0];
+        assert_eq!(origin.line, 3);
+        assert_eq!(error.context()[0], "production `limits`");
+    }
+
+    #[test]
+    fn typing_rules() {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "valid/instructions.rst",
+            r"
+.. math::
+   \frac{
+   }{
+     C \vdash \NOP : [] \to []
+   }
+
+.. math::
+   \frac{
+     C.\CLOCALS[x] = t
+   }{
+     C \vdash \LOCALGET~x : [] \to [t]
+   }
+
+.. math::
+   \frac{
+   }{
+     C \vdash t.\CONST~c : [] \to [t]
+   }
+",
+        );
+        let rules: Vec<_> = grammar
+            .rules()
+            .map(|(o, r)| (o.line, r.to_string()))
+            .collect();
+        assert_eq!(
+            rules,
+            vec![
+                (3, r"\frac{}{C \vdash \NOP : [] \to []}".to_string()),
+                (
+                    9,
+                    r"\frac{C.\CLOCALS[x] = t}{C \vdash \LOCALGET~x : [] \to [t]}".to_string()
+                ),
+            ]
+        );
+        let (origin, error) = &grammar.errors()[0];
+        assert_eq!(origin.line, 16);
+        assert_eq!(error.context(), vec!["typing rule", "conclusion"]);
+    }
+
+    #[test]
+    fn reduction_rules() {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "exec/instructions.rst",
+            r"
+.. math::
+   \begin{array}{lcl@{\qquad}l}
+   \val~\DROP &\stepto& \epsilon \\
+   \end{array}
+
+.. math::
+   F; (\LOCALGET~x) &\stepto& F; \val & (\iff F.\ALOCALS[x] = \val)
+
+.. math::
+   \NOP &\stepto& \val ? \val
+",
+        );
+        let reductions: Vec<_> = grammar
+            .reductions()
+            .map(|(o, r)| (o.line, r.to_string()))
+            .collect();
+        assert_eq!(
+            reductions,
+            vec![
+                (3, r"\val~\DROP \stepto \epsilon".to_string()),
+                (
+                    8,
+                    r"F; (\LOCALGET~x) \stepto F; \val (\iff F.\ALOCALS[x] = \val)".to_string()
+                ),
+            ]
+        );
+        let (origin, error) = &grammar.errors()[0];
+        assert_eq!(origin.line, 11);
+        assert_eq!(error.context(), vec!["reduction"]);
+    }
+
+    #[test]
+    fn numeric_definitions() {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "exec/numerics.rst",
+            r"
+.. math::
+   \begin{array}{@{}lcll}
+   \idivu_N(i_1, 0) &=& \{\} \\
+   \idivu_N(i_1, i_2) &=& \truncz(i_1 / i_2) \\
+   \end{array}
+
+.. math::
+   \F{expand}(\X{ft}) &=& \X{ft}
+
+.. math::
+   \iadd_N(i_1, i_2) &=& (i_1 + i_2) \mod 2^N \\
+   \isub_N(i_1 &=& i_2
+",
+        );
+        let functions: Vec<_> = grammar
+            .functions()
+            .map(|(o, f)| (o.line, f.name(), f.cases().len()))
+            .collect();
+        assert_eq!(functions, vec![(3, "idivu", 2)]);
+        let (origin, error) = &grammar.errors()[0];
+        assert_eq!(origin.line, 12);
+        assert_eq!(error.context(), vec!["numeric definition"]);
+    }
+
+    #[test]
+    fn proposal_changes() {
+        let base = spec_root("base", TYPES);
+        let memory64 = spec_root("memory64", PROPOSAL_TYPES);
+        let mut specs = SpecSet::new();
+        specs.load("spec", &base).unwrap();
+        specs.load("memory64", &memory64).unwrap();
+
+        let changes = specs.changes("spec", "memory64").unwrap();
+        let changes: Vec<_> = changes.iter().map(|c| (c.nonterminal, c.kind)).collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("limits", ChangeKind::Changed),
+                ("idxtype", ChangeKind::Added)
+            ]
+        );
+        assert!(specs.changes("spec", "threads").is_none());
+    }
+}
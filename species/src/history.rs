@@ -0,0 +1,362 @@
+//! History of one production over the commits of a spec checkout.
+//!
+//! The first-parent commits that touch the `rst` sources are walked from the
+//! oldest with `git log`, and at each of them the changed files that mention
+//! the nonterminal are read with `git show` and parsed again. A commit is
+//! part of the history when the alternatives of the nonterminal, over all
+//! the files, differ structurally from the commit before, as by
+//! [`NonterminalDiff`]: reflowing a block or moving it to another file is
+//! not a change.
+
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    io,
+    path::Path,
+};
+
+use crate::{diff::NonterminalDiff, grammar::git, syntax::RhsElem, Grammar};
+
+/// Commits of a nonterminal, oldest first
+#[derive(Debug, PartialEq)]
+pub struct History {
+    pub nonterminal: String,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub commit: Commit,
+    pub event: Event,
+    /// The alternatives added, removed and modified, one per line, as
+    /// printed by `species diff`
+    pub diff: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Commit {
+    pub hash: String,
+    /// Author date, e.g. `2019-05-02`
+    pub date: String,
+    pub author: String,
+    pub subject: String,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Event {
+    Introduced,
+    Changed,
+    Removed,
+}
+
+impl History {
+    /// Walk the commits of `repo` up to `rev`, e.g. `HEAD`, for the changes
+    /// of `nonterminal`, with or without its backslash
+    pub fn load(repo: impl AsRef<Path>, rev: &str, nonterminal: &str) -> io::Result<Self> {
+        let repo = repo.as_ref();
+        let nonterminal = nonterminal.trim_start_matches('\\');
+        let prefix = "document/core/";
+        let in_core = !git(repo, &["ls-tree", "-d", rev, "document/core"])?.is_empty();
+        let scope = if in_core { "document/core" } else { "." };
+
+        let log = git(
+            repo,
+            &[
+                "log",
+                "--first-parent",
+                "--reverse",
+                "--date=short",
+                "--format=%H%x1f%ad%x1f%an%x1f%s",
+                rev,
+                "--",
+                scope,
+            ],
+        )?;
+
+        // files that mention the nonterminal, as of the last commit read
+        let mut files: BTreeMap<String, Grammar> = BTreeMap::new();
+        let mut entries = vec![];
+        let mut previous: Option<String> = None;
+        for line in log.lines() {
+            let mut fields = line.splitn(4, '\x1f');
+            let mut field = || fields.next().unwrap_or_default().to_string();
+            let commit = Commit {
+                hash: field(),
+                date: field(),
+                author: field(),
+                subject: field(),
+            };
+
+            let changed = match &previous {
+                Some(parent) => git(
+                    repo,
+                    &[
+                        "diff-tree",
+                        "-r",
+                        "--no-renames",
+                        "--name-only",
+                        parent,
+                        &commit.hash,
+                        "--",
+                        scope,
+                    ],
+                )?,
+                None => git(
+                    repo,
+                    &["ls-tree", "-r", "--name-only", &commit.hash, "--", scope],
+                )?,
+            };
+            previous = Some(commit.hash.clone());
+
+            let mut updates: BTreeMap<String, Option<Grammar>> = BTreeMap::new();
+            for file in changed.lines().filter(|f| f.ends_with(".rst")) {
+                let relative = file.strip_prefix(prefix).unwrap_or(file);
+                let spec = format!("{}:{}", commit.hash, file);
+                // a file deleted by the commit
+                let Ok(content) = git(repo, &["show", &spec]) else {
+                    updates.insert(relative.to_string(), None);
+                    continue;
+                };
+                if !content.contains(nonterminal) {
+                    updates.insert(relative.to_string(), None);
+                    continue;
+                }
+                let mut grammar = Grammar::new(&commit.hash);
+                grammar.add_source(relative, &content);
+                // a revision that does not parse keeps the reading before it
+                let before = files.get(relative).map_or(0, |g| count(g, nonterminal));
+                if !grammar.errors().is_empty() && count(&grammar, nonterminal) < before {
+                    continue;
+                }
+                updates.insert(relative.to_string(), Some(grammar));
+            }
+            if updates.is_empty() {
+                continue;
+            }
+
+            let old = alternatives(files.values(), nonterminal);
+            let mut after: BTreeMap<&str, &Grammar> =
+                files.iter().map(|(f, g)| (f.as_str(), g)).collect();
+            for (file, grammar) in &updates {
+                match grammar {
+                    Some(grammar) => after.insert(file, grammar),
+                    None => after.remove(file.as_str()),
+                };
+            }
+            let new = alternatives(after.into_values(), nonterminal);
+
+            let event = match (old.is_empty(), new.is_empty()) {
+                (true, false) => Event::Introduced,
+                (false, true) => Event::Removed,
+                _ => Event::Changed,
+            };
+            let diff = NonterminalDiff::between(nonterminal, old, new).map(|diff| {
+                // without the `~ \x` line of the nonterminal
+                let diff = diff.to_string();
+                diff.split_once('\n').map_or("", |(_, d)| d).to_string()
+            });
+            if let Some(diff) = diff {
+                entries.push(Entry {
+                    commit,
+                    event,
+                    diff,
+                });
+            }
+
+            for (file, grammar) in updates {
+                match grammar {
+                    Some(grammar) => files.insert(file, grammar),
+                    None => files.remove(&file),
+                };
+            }
+        }
+
+        Ok(Self {
+            nonterminal: nonterminal.to_string(),
+            entries,
+        })
+    }
+}
+
+fn alternatives<'g>(
+    grammars: impl Iterator<Item = &'g Grammar>,
+    nonterminal: &str,
+) -> Vec<&'g RhsElem> {
+    grammars
+        .flat_map(|g| g.alternatives(nonterminal))
+        .map(|(_, e)| e)
+        .collect()
+}
+
+fn count(grammar: &Grammar, nonterminal: &str) -> usize {
+    grammar.alternatives(nonterminal).len()
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Introduced => write!(f, "introduced"),
+            Event::Changed => write!(f, "changed"),
+            Event::Removed => write!(f, "removed"),
+        }
+    }
+}
+
+impl Display for History {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            let commit = &entry.commit;
+            writeln!(
+                f,
+                r"{} {} {} \{}: {} ({})",
+                &commit.hash[..commit.hash.len().min(10)],
+                commit.date,
+                entry.event,
+                self.nonterminal,
+                commit.subject,
+                commit.author
+            )?;
+            write!(f, "{}", entry.diff)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Command};
+
+    use crate::syntax::fixtures::TempDir;
+
+    use super::*;
+
+    const MODULES: &str = r"
+.. math::
+   \begin{array}{llll}
+   \production{element segment mode} & \elemmode &::=&
+     \EPASSIVE ~|~ \EACTIVE~\tableidx \\
+   \end{array}
+";
+
+    /// The same alternatives, one per line
+    const REFLOWED: &str = r"
+.. math::
+   \begin{array}{llll}
+   \production{element segment mode} & \elemmode &::=&
+     \EPASSIVE \\&&|&
+     \EACTIVE~\tableidx \\
+   \end{array}
+";
+
+    const DECLARATIVE: &str = r"
+.. math::
+   \begin{array}{llll}
+   \production{element segment mode} & \elemmode &::=&
+     \EPASSIVE ~|~ \EACTIVE~\tableidx ~|~ \EDECLARATIVE \\
+   \end{array}
+";
+
+    const TYPES: &str = r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 \\
+   \end{array}
+";
+
+    fn commit(root: &Path, files: &[(&str, &str)], message: &str) {
+        let syntax = root.join("document").join("core").join("syntax");
+        fs::create_dir_all(&syntax).unwrap();
+        for (file, content) in files {
+            fs::write(syntax.join(file), content).unwrap();
+        }
+        for args in [&["add", "."][..], &["commit", "-q", "-m", message]] {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(root)
+                .args([
+                    "-c",
+                    "user.name=species",
+                    "-c",
+                    "user.email=species@localhost",
+                ])
+                .args(args)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+    }
+
+    #[test]
+    fn structural_changes() {
+        let root = TempDir::new("history");
+        assert!(Command::new("git")
+            .arg("-C")
+            .arg(&root)
+            .args(["init", "-q"])
+            .status()
+            .unwrap()
+            .success());
+        commit(&root, &[("types.rst", TYPES)], "types");
+        commit(&root, &[("modules.rst", MODULES)], "element segments");
+        commit(&root, &[("modules.rst", REFLOWED)], "reflow");
+        commit(&root, &[("types.rst", "")], "unrelated");
+        commit(
+            &root,
+            &[("modules.rst", DECLARATIVE)],
+            "declarative segments",
+        );
+
+        let history = History::load(&root, "HEAD", r"\elemmode").unwrap();
+        let missing = History::load(&root, "no-such-rev", "elemmode");
+
+        let events: Vec<_> = history
+            .entries
+            .iter()
+            .map(|e| (e.commit.subject.as_str(), e.event))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                ("element segments", Event::Introduced),
+                ("declarative segments", Event::Changed),
+            ]
+        );
+        assert_eq!(
+            history.entries[0].diff,
+            "    + \\EPASSIVE\n    + \\EACTIVE~\\tableidx\n"
+        );
+        assert_eq!(history.entries[1].diff, "    + \\EDECLARATIVE\n");
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn not_a_repository() {
+        let root = TempDir::new("history-empty");
+        assert!(History::load(&root, "HEAD", "elemmode").is_err());
+        assert!(History::load(root.join("missing"), "HEAD", "elemmode").is_err());
+
+        // a nonterminal that no revision defines has no history, and a
+        // revision that does not parse keeps the alternatives before it
+        assert!(Command::new("git")
+            .arg("-C")
+            .arg(&root)
+            .args(["init", "-q"])
+            .status()
+            .unwrap()
+            .success());
+        commit(&root, &[("modules.rst", MODULES)], "element segments");
+        commit(
+            &root,
+            &[("modules.rst", &MODULES.replace(r"\tableidx", r"\{"))],
+            "broken",
+        );
+        assert!(History::load(&root, "HEAD", "datamode")
+            .unwrap()
+            .entries
+            .is_empty());
+        let history = History::load(&root, "HEAD", "elemmode").unwrap();
+        assert_eq!(history.entries.len(), 1);
+        assert_eq!(history.entries[0].event, Event::Introduced);
+    }
+}
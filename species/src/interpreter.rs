@@ -0,0 +1,1125 @@
+//! Interpreter that executes the reduction rules of the execution chapter.
+//!
+//! A configuration is reduced by finding its first instruction that is not a
+//! value and matching the instructions up to it against the left-hand side of
+//! each rule, e.g. `(t\K{.}\CONST~c_1)~(t\K{.}\CONST~c_2)~t\K{.}\binop`. The
+//! side condition of the matched rule, e.g. `c \in \binop_t(c_1, c_2)`, is
+//! evaluated with the builtin numerics, and the instructions are replaced by
+//! the instantiated right-hand side until only values remain.
+//!
+//! Only the frame `F` of the state is kept: rules on the store, on labels and
+//! on iterated instructions like `\val^n` are listed as skipped.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    generator::Node,
+    grammar::Origin,
+    parser::{split, top_level},
+    spec::Spec,
+    syntax::{expr::Expr, reduction::Reduction, symbol::Symbol},
+};
+
+/// Interpreter of the reduction rules of a specification
+pub struct Interpreter<'s> {
+    spec: &'s Spec,
+    steps: Vec<Step<'s>>,
+    skipped: Vec<SkippedReduction<'s>>,
+}
+
+/// Reduction rule that the interpreter cannot execute
+#[derive(Debug, PartialEq)]
+pub struct SkippedReduction<'s> {
+    pub origin: &'s Origin,
+    pub reduction: &'s Reduction,
+    pub reason: String,
+}
+
+/// Frame `F` of the configuration, the entries of each of its fields, e.g.
+/// the values of the locals in `F.\ALOCALS`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Frame {
+    fields: HashMap<String, Vec<Node>>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExecError {
+    /// The configuration reduced to `\TRAP`
+    Trap,
+    /// No reduction rule applies to the instruction
+    Stuck(String),
+    /// A frame field is missing or has no entry at the index
+    Lookup { field: String, index: usize },
+    /// A variable of a rule is not determined by its left-hand side or
+    /// conditions
+    Undetermined(String),
+    /// A builtin numeric or a type that the interpreter does not know
+    Unsupported(String),
+}
+
+type Bindings = HashMap<String, Vec<Node>>;
+
+/// Reduction rule together with its parsed side conditions
+struct Step<'s> {
+    reduction: &'s Reduction,
+    /// Name of the frame on the left-hand side, e.g. `F`
+    frame: Option<&'s str>,
+    conditions: Vec<Condition>,
+}
+
+/// Conjunct of a side condition
+#[derive(Debug, PartialEq)]
+enum Condition {
+    /// `c \in \binop_t(c_1, c_2)`, `c \neq 0` or `F.\ALOCALS[x] = \val`
+    Compare {
+        left: Operand,
+        relation: Relation,
+        right: Operand,
+    },
+    /// `F' = F \with \ALOCALS[x] = \val`
+    Update {
+        frame: String,
+        field: String,
+        index: Operand,
+        value: Operand,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Relation {
+    Eq,
+    Neq,
+    In,
+}
+
+#[derive(Debug, PartialEq)]
+enum Operand {
+    Const(u64),
+    /// `\{\}`, the result of a partial numeric without result
+    Empty,
+    /// Variable of the rule, e.g. `c_1` or `\val`
+    Bound(String),
+    /// Builtin numeric, e.g. `\binop_t(c_1, c_2)`, with the variables of the
+    /// operator and the type
+    Call {
+        op: String,
+        ty: String,
+        args: Vec<Operand>,
+    },
+    /// `F.\ALOCALS[x]`
+    Lookup {
+        field: String,
+        index: Box<Operand>,
+    },
+}
+
+impl<'s> Interpreter<'s> {
+    pub fn new(spec: &'s Spec) -> Self {
+        let mut steps = vec![];
+        let mut skipped = vec![];
+        for (origin, reduction) in spec.grammar().reductions() {
+            match Step::new(reduction) {
+                Ok(step) => steps.push(step),
+                Err(reason) => skipped.push(SkippedReduction {
+                    origin,
+                    reduction,
+                    reason,
+                }),
+            }
+        }
+        Self {
+            spec,
+            steps,
+            skipped,
+        }
+    }
+
+    pub fn skipped(&self) -> &[SkippedReduction<'s>] {
+        &self.skipped
+    }
+
+    /// Reduce a flat sequence of instructions to values, updating the frame
+    /// on the way. Blocks and calls have no rules that apply, so folded
+    /// instructions are unfolded first, e.g. `(local.get 0) i32.eqz`.
+    pub fn run(&self, frame: &mut Frame, instrs: &[Node]) -> Result<Vec<Node>, ExecError> {
+        let mut instrs: Vec<_> = instrs.iter().map(atoms).collect();
+        loop {
+            let Some(k) = instrs.iter().position(|instr| !self.is_value(instr)) else {
+                return Ok(instrs.into_iter().map(value_node).collect());
+            };
+            if instrs[k] == [Node::Term("TRAP".to_string())] {
+                return Err(ExecError::Trap);
+            }
+            let (n, reduct) = self.step(frame, &instrs[..=k])?;
+            instrs.splice(k + 1 - n..=k, reduct);
+        }
+    }
+
+    /// Apply the first rule whose left-hand side ends in the last instruction,
+    /// returning the number of instructions it consumes and its result
+    fn step(
+        &self,
+        frame: &mut Frame,
+        instrs: &[Vec<Node>],
+    ) -> Result<(usize, Vec<Vec<Node>>), ExecError> {
+        for step in &self.steps {
+            let lhs = step.reduction.lhs().instrs();
+            if lhs.is_empty() || lhs.len() > instrs.len() {
+                continue;
+            }
+            let window = &instrs[instrs.len() - lhs.len()..];
+            let mut bindings = Bindings::new();
+            if !lhs
+                .iter()
+                .zip(window)
+                .all(|(pattern, instr)| self.matches(pattern, instr, &mut bindings))
+            {
+                continue;
+            }
+            let mut updated = None;
+            if !step.holds(frame, &mut bindings, &mut updated)? {
+                continue;
+            }
+
+            let rhs = step.reduction.rhs();
+            if let [var] = rhs.state() {
+                if Some(var.name.as_str()) != step.frame {
+                    match updated {
+                        Some((name, next)) if name == var.name => *frame = next,
+                        _ => return Err(ExecError::Undetermined(var.name.clone())),
+                    }
+                }
+            }
+            let reduct = rhs
+                .instrs()
+                .iter()
+                .map(|instr| instantiate(instr, &bindings))
+                .collect::<Result<_, _>>()?;
+            return Ok((lhs.len(), reduct));
+        }
+        let instr = instrs.last().cloned().unwrap_or_default();
+        Err(ExecError::Stuck(instr_node(instr).to_string()))
+    }
+
+    fn is_value(&self, instr: &[Node]) -> bool {
+        self.is_instance("val", instr)
+    }
+
+    /// Match one instruction of a left-hand side, e.g. `(\LOCALGET~x)`. A
+    /// nonterminal standing alone, e.g. `\val_1`, stands for the whole
+    /// instruction.
+    fn matches(&self, pattern: &[Expr], instr: &[Node], bindings: &mut Bindings) -> bool {
+        if let [item @ Expr::Meta(..)] = pattern {
+            return self.bind(item, instr, bindings);
+        }
+        pattern.len() == instr.len()
+            && pattern.iter().zip(instr).all(|(item, atom)| match item {
+                Expr::Term(term) => matches!(atom, Node::Term(t) if t == term),
+                Expr::Var(_) | Expr::Meta(..) => {
+                    self.bind(item, std::slice::from_ref(atom), bindings)
+                }
+                _ => false,
+            })
+    }
+
+    fn bind(&self, item: &Expr, atoms: &[Node], bindings: &mut Bindings) -> bool {
+        if let Expr::Meta(nt, _) = item {
+            if !self.is_instance(&nt.name, atoms) {
+                return false;
+            }
+        }
+        let key = item.to_string();
+        match bindings.get(&key) {
+            Some(bound) => bound == atoms,
+            None => {
+                bindings.insert(key, atoms.to_vec());
+                true
+            }
+        }
+    }
+
+    /// Whether the atoms are an instance of one of the alternatives of a
+    /// nonterminal. A nonterminal without production, e.g. `\i32`, takes
+    /// anything.
+    fn is_instance(&self, nonterminal: &str, atoms: &[Node]) -> bool {
+        let alternatives = self.spec.alternatives_of(nonterminal);
+        if alternatives.is_empty() {
+            return atoms.len() == 1;
+        }
+        alternatives.iter().any(|alternative| {
+            let symbols = alternative.elem.symbols();
+            if let [Symbol::SNonterm(nt)] = symbols {
+                return nt.seq_kind().is_none() && self.is_instance(&nt.name, atoms);
+            }
+            let mut atoms = atoms.iter();
+            for symbol in symbols {
+                match symbol {
+                    Symbol::STerm(term) => {
+                        for part in opcode(term) {
+                            if atoms.next() != Some(&part) {
+                                return false;
+                            }
+                        }
+                    }
+                    Symbol::SNonterm(nt) if nt.seq_kind().is_none() => match atoms.next() {
+                        Some(atom) if self.is_instance(&nt.name, std::slice::from_ref(atom)) => {}
+                        _ => return false,
+                    },
+                    _ => return false,
+                }
+            }
+            atoms.next().is_none()
+        })
+    }
+}
+
+impl<'s> Step<'s> {
+    fn new(reduction: &'s Reduction) -> Result<Self, String> {
+        let (lhs, rhs) = (reduction.lhs(), reduction.rhs());
+        if lhs.state().len() > 1 || rhs.state().len() > 1 {
+            return Err("the store is not supported".to_string());
+        }
+        let instrs = lhs.instrs().iter().chain(rhs.instrs()).flatten();
+        for item in instrs {
+            match item {
+                Expr::Term(_) => {}
+                Expr::Var(var) if var.seq_kind.is_none() => {}
+                Expr::Meta(nt, _) if nt.seq_kind().is_none() => {}
+                item => return Err(format!("instruction `{}` is not supported", item)),
+            }
+        }
+        let mut conditions = vec![];
+        if let Some(cond) = reduction.cond() {
+            let cond = cond.strip_prefix(r"\iff").unwrap_or(cond);
+            for conjunct in split_wedge(cond) {
+                match Condition::new(conjunct.trim()) {
+                    Some(condition) => conditions.push(condition),
+                    None => {
+                        return Err(format!("condition `{}` is not supported", conjunct.trim()))
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            reduction,
+            frame: lhs.state().first().map(|var| var.name.as_str()),
+            conditions,
+        })
+    }
+
+    /// Evaluate the conditions in order, binding the variables they define
+    /// and the frame they update
+    fn holds(
+        &self,
+        frame: &Frame,
+        bindings: &mut Bindings,
+        updated: &mut Option<(String, Frame)>,
+    ) -> Result<bool, ExecError> {
+        for condition in &self.conditions {
+            match condition {
+                Condition::Compare {
+                    left,
+                    relation,
+                    right,
+                } => {
+                    // a condition like `c = \testop_t(c_1)` defines its variable
+                    for (var, other) in [(left, right), (right, left)] {
+                        if let Operand::Bound(key) = var {
+                            if !bindings.contains_key(key) && *relation != Relation::Neq {
+                                let Some(value) = other.eval(frame, bindings)? else {
+                                    return Ok(false);
+                                };
+                                bindings.insert(key.clone(), value);
+                            }
+                        }
+                    }
+                    let left = left.eval(frame, bindings)?;
+                    let right = right.eval(frame, bindings)?;
+                    let holds = match relation {
+                        Relation::Eq | Relation::In => left == right,
+                        Relation::Neq => left != right,
+                    };
+                    if !holds {
+                        return Ok(false);
+                    }
+                }
+                Condition::Update {
+                    frame: name,
+                    field,
+                    index,
+                    value,
+                } => {
+                    let index = to_index(index.eval(frame, bindings)?)?;
+                    let Some(value) = value.eval(frame, bindings)? else {
+                        return Ok(false);
+                    };
+                    let mut next = frame.clone();
+                    let entry = next
+                        .fields
+                        .get_mut(field)
+                        .and_then(|entries| entries.get_mut(index))
+                        .ok_or_else(|| ExecError::Lookup {
+                            field: field.clone(),
+                            index,
+                        })?;
+                    *entry = value_node(value);
+                    *updated = Some((name.clone(), next));
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Frame {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the entries of a field, e.g. `locals`
+    pub fn field(mut self, name: impl Into<String>, entries: Vec<Node>) -> Self {
+        self.fields.insert(name.into(), entries);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&[Node]> {
+        self.fields.get(name).map(Vec::as_slice)
+    }
+
+    fn lookup(&self, field: &str, index: usize) -> Result<Vec<Node>, ExecError> {
+        self.fields
+            .get(field)
+            .and_then(|entries| entries.get(index))
+            .map(atoms)
+            .ok_or_else(|| ExecError::Lookup {
+                field: field.to_string(),
+                index,
+            })
+    }
+}
+
+impl Condition {
+    fn new(source: &str) -> Option<Self> {
+        if let Some(with) = source.find(r"\with") {
+            let (frame, _) = source[..with].split_once('=')?;
+            let (lookup, value) = source[with + r"\with".len()..].split_once('=')?;
+            let Operand::Lookup { field, index } = Operand::new(&format!("F.{}", lookup.trim()))?
+            else {
+                return None;
+            };
+            return Some(Self::Update {
+                frame: frame.trim().to_string(),
+                field,
+                index: *index,
+                value: Operand::new(value.trim())?,
+            });
+        }
+        let relations = [
+            (r"\neq", Relation::Neq),
+            (r"\in ", Relation::In),
+            ("=", Relation::Eq),
+        ];
+        relations.into_iter().find_map(|(symbol, relation)| {
+            let at = top_level(source, |rest| rest.starts_with(symbol))?;
+            Some(Self::Compare {
+                left: Operand::new(source[..at].trim())?,
+                relation,
+                right: Operand::new(source[at + symbol.len()..].trim())?,
+            })
+        })
+    }
+}
+
+impl Operand {
+    fn new(source: &str) -> Option<Self> {
+        if source == r"\{\}" {
+            return Some(Self::Empty);
+        }
+        if let Ok(n) = source.parse() {
+            return Some(Self::Const(n));
+        }
+        // `F.\ALOCALS[x]`
+        if let Some((_, lookup)) = source
+            .split_once(r".\")
+            .filter(|(frame, _)| frame.chars().all(|c| c.is_ascii_uppercase() || c == '\''))
+        {
+            let (field, index) = lookup.strip_suffix(']')?.split_once('[')?;
+            return Some(Self::Lookup {
+                field: field_name(field),
+                index: Box::new(Self::new(index.trim())?),
+            });
+        }
+        // `\binop_t(c_1, c_2)` or `\binop_{t}(c_1, c_2)`
+        if let Some(open) = source.find('(').filter(|_| source.ends_with(')')) {
+            let (op, ty) = source[..open].split_once('_')?;
+            let ty = ty.trim_start_matches('{').trim_end_matches('}');
+            if !ty.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return None;
+            }
+            let args = split(&source[open + 1..source.len() - 1], ',')
+                .into_iter()
+                .map(|arg| Self::new(arg.trim()))
+                .collect::<Option<_>>()?;
+            return Some(Self::Call {
+                op: op.to_string(),
+                ty: ty.to_string(),
+                args,
+            });
+        }
+        match Expr::pattern_item(source) {
+            Ok(("", item @ (Expr::Var(_) | Expr::Meta(..)))) => Some(Self::Bound(item.to_string())),
+            _ => None,
+        }
+    }
+
+    /// The atoms of the operand, or `None` for the empty result of a partial
+    /// numeric
+    fn eval(&self, frame: &Frame, bindings: &Bindings) -> Result<Option<Vec<Node>>, ExecError> {
+        match self {
+            Operand::Const(n) => Ok(Some(vec![Node::Value(n.to_string())])),
+            Operand::Empty => Ok(None),
+            Operand::Bound(key) => bindings
+                .get(key)
+                .cloned()
+                .map(Some)
+                .ok_or_else(|| ExecError::Undetermined(key.clone())),
+            Operand::Call { op, ty, args } => {
+                let bound = |key: &str| {
+                    bindings
+                        .get(key)
+                        .ok_or_else(|| ExecError::Undetermined(key.to_string()))
+                };
+                let op = match bound(op)?.as_slice() {
+                    [Node::Term(op)] => op.to_lowercase(),
+                    op => return Err(ExecError::Unsupported(instr_node(op.to_vec()).to_string())),
+                };
+                let width = match bound(ty)?.as_slice() {
+                    [Node::Term(t)] if t == "I32" => 32,
+                    [Node::Term(t)] if t == "I64" => 64,
+                    t => return Err(ExecError::Unsupported(instr_node(t.to_vec()).to_string())),
+                };
+                let mut values = vec![];
+                for arg in args {
+                    values.push(to_u64(arg.eval(frame, bindings)?)?);
+                }
+                let result = numeric(&op, width, &values)?;
+                Ok(result.map(|n| vec![Node::Value(n.to_string())]))
+            }
+            Operand::Lookup { field, index } => {
+                let index = to_index(index.eval(frame, bindings)?)?;
+                frame.lookup(field, index).map(Some)
+            }
+        }
+    }
+}
+
+/// Builtin numerics on integers of the given width, `None` where the
+/// specification gives no result, e.g. for a division by zero
+pub(crate) fn numeric(op: &str, width: u32, args: &[u64]) -> Result<Option<u64>, ExecError> {
+    let mask = if width == 64 {
+        u64::MAX
+    } else {
+        (1 << width) - 1
+    };
+    let result = match (op, args) {
+        ("add", [a, b]) => a.wrapping_add(*b),
+        ("sub", [a, b]) => a.wrapping_sub(*b),
+        ("mul", [a, b]) => a.wrapping_mul(*b),
+        ("divu", [_, 0]) | ("remu", [_, 0]) => return Ok(None),
+        ("divu", [a, b]) => a / b,
+        ("remu", [a, b]) => a % b,
+        ("and", [a, b]) => a & b,
+        ("or", [a, b]) => a | b,
+        ("xor", [a, b]) => a ^ b,
+        ("shl", [a, b]) => a << (b % width as u64),
+        ("shru", [a, b]) => a >> (b % width as u64),
+        ("clz", [a]) => (a.leading_zeros() - (64 - width)) as u64,
+        ("ctz", [a]) => (a.trailing_zeros().min(width)) as u64,
+        ("popcnt", [a]) => a.count_ones() as u64,
+        ("eqz", [a]) => (*a == 0) as u64,
+        ("eq", [a, b]) => (a == b) as u64,
+        ("ne", [a, b]) => (a != b) as u64,
+        ("ltu", [a, b]) => (a < b) as u64,
+        ("gtu", [a, b]) => (a > b) as u64,
+        ("leu", [a, b]) => (a <= b) as u64,
+        ("geu", [a, b]) => (a >= b) as u64,
+        _ => return Err(ExecError::Unsupported(format!("{}{}", op, width))),
+    };
+    Ok(Some(result & mask))
+}
+
+/// Conjuncts of a condition, separated by `\wedge`
+fn split_wedge(cond: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut rest = cond;
+    while let Some(i) = top_level(rest, |r| r.starts_with(r"\wedge")) {
+        parts.push(&rest[..i]);
+        rest = &rest[i + r"\wedge".len()..];
+    }
+    parts.push(rest);
+    parts
+}
+
+/// Atoms of an instruction, e.g. `I32 CONST 1` for
+/// `(instr i32. CONST (i32 1))`
+fn atoms(node: &Node) -> Vec<Node> {
+    match strip(node) {
+        Node::Nonterm { children, .. } => children.iter().flat_map(atom).collect(),
+        node => atom(node),
+    }
+}
+
+fn atom(node: &Node) -> Vec<Node> {
+    match strip(node) {
+        Node::Term(term) => opcode(term),
+        Node::Value(value) => match value.parse::<u64>() {
+            Ok(n) => vec![Node::Value(n.to_string())],
+            Err(_) => vec![Node::Value(value.clone())],
+        },
+        node => vec![node.clone()],
+    }
+}
+
+/// Skip the nodes of nonterminals that just wrap another one
+fn strip(node: &Node) -> &Node {
+    match node {
+        Node::Nonterm { children, .. } if children.len() == 1 => strip(&children[0]),
+        node => node,
+    }
+}
+
+/// Parts of a terminal, e.g. `I32` and `CONST` for `i32.CONST`, or `I32` and
+/// `DIVU` for `i32.div_u`
+fn opcode(term: &str) -> Vec<Node> {
+    term.split('.')
+        .filter(|part| !part.is_empty())
+        .map(|part| Node::Term(part.replace(['\\', '_'], "").to_uppercase()))
+        .collect()
+}
+
+fn instantiate(instr: &[Expr], bindings: &Bindings) -> Result<Vec<Node>, ExecError> {
+    let mut atoms = vec![];
+    for item in instr {
+        match item {
+            Expr::Term(term) => atoms.push(Node::Term(term.clone())),
+            item => {
+                let key = item.to_string();
+                let bound = bindings.get(&key).ok_or(ExecError::Undetermined(key))?;
+                atoms.extend(bound.iter().cloned());
+            }
+        }
+    }
+    Ok(atoms)
+}
+
+fn instr_node(atoms: Vec<Node>) -> Node {
+    Node::Nonterm {
+        name: "instr".to_string(),
+        children: atoms,
+    }
+}
+
+fn value_node(atoms: Vec<Node>) -> Node {
+    Node::Nonterm {
+        name: "val".to_string(),
+        children: atoms,
+    }
+}
+
+/// `locals` for `\ALOCALS`
+fn field_name(field: &str) -> String {
+    field.strip_prefix('A').unwrap_or(field).to_lowercase()
+}
+
+fn to_u64(value: Option<Vec<Node>>) -> Result<u64, ExecError> {
+    match value.as_deref() {
+        Some([Node::Value(n)]) => n
+            .parse()
+            .map_err(|_| ExecError::Unsupported(format!("number `{}`", n))),
+        _ => Err(ExecError::Unsupported(format!("operand `{:?}`", value))),
+    }
+}
+
+fn to_index(value: Option<Vec<Node>>) -> Result<usize, ExecError> {
+    to_u64(value).map(|n| n as usize)
+}
+
+impl fmt::Display for SkippedReduction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.origin.file.display(),
+            self.origin.line,
+            self.reason
+        )
+    }
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecError::Trap => write!(f, "trap"),
+            ExecError::Stuck(instr) => write!(f, "no reduction rule for `{}`", instr),
+            ExecError::Lookup { field, index } => write!(f, "unknown {} {}", field, index),
+            ExecError::Undetermined(var) => write!(f, "`{}` is not determined", var),
+            ExecError::Unsupported(what) => write!(f, "{} is not supported", what),
+        }
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        coverage::wast::{self, atom, items, keyword, Token},
+        syntax::fixtures,
+        Grammar,
+    };
+
+    use super::*;
+
+    fn spec() -> Spec {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "syntax/instructions.rst",
+            r"
+.. math::
+   \begin{array}{llcl}
+   \production{value} & \val &::=&
+     \K{i32.}\CONST~\i32 \\&&|&
+     \K{i64.}\CONST~\i64 \\
+   \production{instruction} & \instr &::=&
+     \K{i32.}\CONST~\i32 \\&&|&
+     \K{i64.}\CONST~\i64 \\&&|&
+     \K{i32.}\binop \\&&|&
+     \K{i64.}\binop \\&&|&
+     \K{i32.}\testop \\&&|&
+     \NOP \\&&|&
+     \DROP \\&&|&
+     \SELECT \\&&|&
+     \LOCALGET~\localidx \\&&|&
+     \LOCALSET~\localidx \\&&|&
+     \LOCALTEE~\localidx \\
+   \production{binary operator} & \binop &::=&
+     \K{add} ~|~ \K{sub} ~|~ \K{mul} ~|~ \DIVU ~|~ \K{and} \\
+   \production{test operator} & \testop &::=&
+     \K{eqz} \\
+   \production{local index} & \localidx &::=& \u32 \\
+   \end{array}
+",
+        );
+        grammar.add_source(
+            "exec/instructions.rst",
+            r"
+.. math::
+   \begin{array}{lcl@{\qquad}l}
+   \NOP &\stepto& \epsilon \\
+   \val~\DROP &\stepto& \epsilon \\
+   \val_1~\val_2~(\I32\K{.}\CONST~c)~\SELECT &\stepto& \val_1 & (\iff c \neq 0) \\
+   \val_1~\val_2~(\I32\K{.}\CONST~c)~\SELECT &\stepto& \val_2 & (\iff c = 0) \\
+   \end{array}
+
+.. math::
+   \begin{array}{lcl@{\qquad}l}
+   (t\K{.}\CONST~c_1)~(t\K{.}\CONST~c_2)~t\K{.}\binop &\stepto& (t\K{.}\CONST~c)
+     & (\iff c \in \binop_t(c_1,c_2)) \\
+   (t\K{.}\CONST~c_1)~(t\K{.}\CONST~c_2)~t\K{.}\binop &\stepto& \TRAP
+     & (\iff \binop_{t}(c_1,c_2) = \{\}) \\
+   (t\K{.}\CONST~c_1)~t\K{.}\testop &\stepto& (\I32\K{.}\CONST~c)
+     & (\iff c = \testop_t(c_1)) \\
+   \end{array}
+
+.. math::
+   F; (\LOCALGET~x) &\stepto& F; \val & (\iff F.\ALOCALS[x] = \val)
+
+.. math::
+   F; \val~(\LOCALSET~x) &\stepto& F'; \epsilon & (\iff F' = F \with \ALOCALS[x] = \val)
+
+.. math::
+   \val~(\LOCALTEE~x) &\stepto& \val~\val~(\LOCALSET~x)
+
+.. math::
+   S; F; (\MEMORYSIZE) &\stepto& S; F; (\I32.\CONST~\X{sz})
+
+.. math::
+   \val^\ast~\val^n~\RETURN &\stepto& \val^n~\RETURN
+",
+        );
+        assert!(grammar.errors().is_empty(), "{:?}", grammar.errors());
+        Spec::new(grammar)
+    }
+
+    /// Read instructions as written in a `.wast` file, e.g.
+    /// `(i32.const 1) (i32.const 2) i32.add`
+    fn instrs(source: &str) -> Vec<Node> {
+        let source = source.replace('(', " ( ").replace(')', " ) ");
+        let mut instrs = vec![];
+        let mut children = vec![];
+        let mut nested = false;
+        for token in source.split_whitespace() {
+            match token {
+                "(" => nested = true,
+                ")" => nested = false,
+                token if token.starts_with(|c: char| c.is_ascii_digit()) => {
+                    children.push(Node::Value(token.to_string()))
+                }
+                // `local.get` is written `\LOCALGET`, but `i32.add` is `\K{i32.}\K{add}`
+                token if token.starts_with('i') => children.push(Node::Term(token.to_string())),
+                token => children.push(Node::Term(token.replace('.', ""))),
+            }
+            if !nested && !children.is_empty() {
+                instrs.push(instr_node(std::mem::take(&mut children)));
+            }
+        }
+        instrs
+    }
+
+    fn run(frame: &mut Frame, source: &str) -> Result<String, ExecError> {
+        let spec = spec();
+        let interpreter = Interpreter::new(&spec);
+        let values = interpreter.run(frame, &instrs(source))?;
+        let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+        Ok(values.join(" "))
+    }
+
+    #[test]
+    fn skipped() {
+        let spec = spec();
+        let interpreter = Interpreter::new(&spec);
+        let reasons: Vec<_> = interpreter
+            .skipped()
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "exec/instructions.rst:30: the store is not supported",
+                r"exec/instructions.rst:33: instruction `\val^\ast` is not supported",
+            ]
+        );
+    }
+
+    #[test]
+    fn assert_return() {
+        let mut frame = Frame::new();
+        let cases = [
+            ("(i32.const 1) (i32.const 2) i32.add", "(val I32 CONST 3)"),
+            (
+                "(i32.const 1) (i32.const 2) i32.sub",
+                "(val I32 CONST 4294967295)",
+            ),
+            (
+                "(i64.const 4294967295) (i64.const 2) i64.mul",
+                "(val I64 CONST 8589934590)",
+            ),
+            ("(i32.const 7) (i32.const 2) i32.div_u", "(val I32 CONST 3)"),
+            ("(i32.const 0) i32.eqz", "(val I32 CONST 1)"),
+            (
+                "(i32.const 1) (i32.const 2) (i32.const 0) select",
+                "(val I32 CONST 2)",
+            ),
+            ("nop (i32.const 1) (i32.const 2) drop", "(val I32 CONST 1)"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(
+                run(&mut frame, source),
+                Ok(expected.to_string()),
+                "{}",
+                source
+            );
+        }
+        assert_eq!(
+            run(&mut frame, "(i64.const 5) i32.eqz"),
+            Err(ExecError::Stuck("(instr I32 EQZ)".to_string()))
+        );
+    }
+
+    #[test]
+    fn width_64() {
+        let mut frame = Frame::new();
+        let max = "18446744073709551615";
+        let cases = [
+            (
+                format!("(i64.const {}) (i64.const {}) i64.mul", max, max),
+                "(val I64 CONST 1)".to_string(),
+            ),
+            (
+                format!("(i64.const {}) (i64.const 2) i64.add", max),
+                "(val I64 CONST 1)".to_string(),
+            ),
+            (
+                "(i64.const 0) (i64.const 1) i64.sub".to_string(),
+                format!("(val I64 CONST {})", max),
+            ),
+            (
+                format!("(i64.const {}) (i64.const 4294967296) i64.div_u", max),
+                "(val I64 CONST 4294967295)".to_string(),
+            ),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(run(&mut frame, &source), Ok(expected), "{}", source);
+        }
+    }
+
+    #[test]
+    fn traps() {
+        let result = run(
+            &mut Frame::new(),
+            "(i32.const 1) (i32.const 0) i32.div_u (i32.const 2) i32.add",
+        );
+        assert_eq!(result, Err(ExecError::Trap));
+    }
+
+    #[test]
+    fn locals() {
+        let mut frame = Frame::new().field("locals", instrs("(i32.const 0) (i64.const 0)"));
+        assert_eq!(
+            run(&mut frame, "(i32.const 5) (local.tee 0) drop (local.get 0)"),
+            Ok("(val I32 CONST 5)".to_string())
+        );
+        assert_eq!(
+            frame.get("locals").unwrap()[0].to_string(),
+            "(val I32 CONST 5)"
+        );
+        assert_eq!(
+            run(&mut frame, "(local.get 2)"),
+            Err(ExecError::Lookup {
+                field: "locals".to_string(),
+                index: 2
+            })
+        );
+    }
+
+    #[test]
+    fn conditions() {
+        assert_eq!(
+            Condition::new(r"c \neq 0"),
+            Some(Condition::Compare {
+                left: Operand::Bound("c".to_string()),
+                relation: Relation::Neq,
+                right: Operand::Const(0),
+            })
+        );
+        assert_eq!(
+            Condition::new(r"\binop_{t}(c_1,c_2) = \{\}"),
+            Some(Condition::Compare {
+                left: Operand::Call {
+                    op: r"\binop".to_string(),
+                    ty: "t".to_string(),
+                    args: vec![
+                        Operand::Bound("c_1".to_string()),
+                        Operand::Bound("c_2".to_string())
+                    ],
+                },
+                relation: Relation::Eq,
+                right: Operand::Empty,
+            })
+        );
+        assert_eq!(Condition::new(r"c = \signed_N^{-1}(i)"), None);
+    }
+
+    /// Type and value of a `(t.const c)` form of an integer type, as the
+    /// numerics compute it, e.g. `4294967295` for `(i32.const -1)`
+    fn constant(form: &[Token<'_>]) -> Option<(&'static str, u64)> {
+        let (ty, bits) = match keyword(form)? {
+            "i32.const" => ("i32", 32),
+            "i64.const" => ("i64", 64),
+            _ => return None,
+        };
+        let literal = atom(items(form).first()?)?.replace('_', "");
+        let (negative, digits) = match literal.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, literal.trim_start_matches('+')),
+        };
+        let magnitude = match digits.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+            None => digits.parse().ok()?,
+        };
+        let value = if negative {
+            magnitude.wrapping_neg()
+        } else {
+            magnitude
+        };
+        Some((ty, value & (u64::MAX >> (64 - bits))))
+    }
+
+    /// Unfold a folded instruction into the form `instrs` reads, with the
+    /// locals referred to by index, e.g. `(local.get 0) (local.get 1)
+    /// i32.add` for `(i32.add (local.get $x) (local.get $y))`
+    fn unfold(form: &[Token<'_>], locals: &[Option<&str>], out: &mut Vec<String>) -> Option<()> {
+        let op = keyword(form)?;
+        let mut instr = vec![op.to_string()];
+        if let Some((_, c)) = constant(form) {
+            instr.push(c.to_string());
+        } else {
+            for item in items(form) {
+                match atom(item) {
+                    Some(x) if op.starts_with("local.") && x.starts_with('$') => {
+                        let index = locals.iter().position(|name| *name == Some(x))?;
+                        instr.push(index.to_string());
+                    }
+                    Some(x) if op.starts_with("local.") => instr.push(x.to_string()),
+                    Some(_) => return None,
+                    None => unfold(item, locals, out)?,
+                }
+            }
+        }
+        out.push(format!("({})", instr.join(" ")));
+        Some(())
+    }
+
+    /// Bodies of the exported functions of a module, none for a function
+    /// that is not a folded instruction on its parameters
+    fn exports(module: &[Token<'_>]) -> HashMap<String, Option<String>> {
+        let mut exports = HashMap::new();
+        for func in items(module)
+            .into_iter()
+            .filter(|f| keyword(f) == Some("func"))
+        {
+            let mut names = vec![];
+            let mut export = None;
+            let mut body = Some(vec![]);
+            for item in items(func) {
+                match keyword(item) {
+                    Some("export") => export = Some(wast::source(&item[2..item.len() - 1])),
+                    Some("param") => match items(item).as_slice() {
+                        [name, _] if atom(name).is_some_and(|name| name.starts_with('$')) => {
+                            names.push(atom(name))
+                        }
+                        types => names.extend(types.iter().map(|_| None)),
+                    },
+                    Some("result") => {}
+                    Some(_) => {
+                        body = body.and_then(|mut body| {
+                            unfold(item, &names, &mut body)?;
+                            Some(body)
+                        })
+                    }
+                    None if atom(item).is_some_and(|id| id.starts_with('$')) => {}
+                    None => body = None,
+                }
+            }
+            if let Some(export) = export {
+                exports.insert(export, body.map(|body| body.join(" ")));
+            }
+        }
+        exports
+    }
+
+    /// Run the `assert_return` and `assert_trap` commands of `scripts` that
+    /// invoke a function whose body is a folded sequence of instructions on
+    /// its parameters, printing the cases that fail, with how many pass, fail
+    /// or cannot be run. Blocks, calls and floats are not interpreted. Every
+    /// case that is run must pass.
+    fn check_scripts(spec: &Spec, scripts: &[(String, String)]) {
+        let interpreter = Interpreter::new(spec);
+        let mut ran = 0;
+        let mut failures = vec![];
+        for (script, source) in scripts {
+            let tokens = wast::tokenize(source).unwrap();
+            let mut funcs = HashMap::new();
+            let (mut passed, mut failed, mut unsupported) = (0, 0, 0);
+            let mut i = 0;
+            while i < tokens.len() {
+                let end = wast::closing(&tokens, i);
+                let command = &tokens[i..end];
+                i = end;
+                let expected = match keyword(command) {
+                    Some("module") => {
+                        funcs = exports(command);
+                        continue;
+                    }
+                    Some("assert_return") => items(command)[1..]
+                        .iter()
+                        .map(|result| {
+                            let (ty, c) = constant(result)?;
+                            Some(format!("(val {} CONST {})", ty.to_uppercase(), c))
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .map(|values| Ok(values.join(" "))),
+                    Some("assert_trap") => Some(Err(ExecError::Trap)),
+                    _ => continue,
+                };
+                let invoke = items(command)[0];
+                let invoke = items(invoke);
+                let body = funcs.get(&wast::source(invoke[0])).cloned().flatten();
+                let args: Option<Vec<_>> = invoke[1..]
+                    .iter()
+                    .map(|arg| constant(arg).map(|(ty, c)| format!("({}.const {})", ty, c)))
+                    .collect();
+                let (Some(expected), Some(body), Some(args)) = (expected, body, args) else {
+                    unsupported += 1;
+                    continue;
+                };
+                let mut frame = Frame::new().field("locals", instrs(&args.join(" ")));
+                let values = interpreter.run(&mut frame, &instrs(&body)).map(|values| {
+                    let values: Vec<_> = values.iter().map(|v| v.to_string()).collect();
+                    values.join(" ")
+                });
+                if values == expected {
+                    passed += 1;
+                } else {
+                    failed += 1;
+                    let line = command[0].line;
+                    failures.push(format!(
+                        "{}:{}: {:?}, expected {:?}",
+                        script, line, values, expected
+                    ));
+                }
+            }
+            println!(
+                "{}: {} passed, {} failed, {} not run",
+                script, passed, failed, unsupported
+            );
+            ran += passed + failed;
+        }
+        assert!(ran > 0, "no cases run");
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    /// Script of cases of the instructions of [`spec`]
+    const SCRIPT: &str = r#"
+(module
+  (func (export "add") (param $x i32) (param $y i32) (result i32) (i32.add (local.get $x) (local.get $y)))
+  (func (export "div_u") (param i32 i32) (result i32) (i32.div_u (local.get 0) (local.get 1)))
+  (func (export "loop") (param i32) (result i32) (loop (br 0)))
+)
+(assert_return (invoke "add" (i32.const 1) (i32.const 2)) (i32.const 3))
+(assert_return (invoke "add" (i32.const -1) (i32.const 1)) (i32.const 0))
+(assert_trap (invoke "div_u" (i32.const 1) (i32.const 0)) "integer divide by zero")
+(assert_return (invoke "loop" (i32.const 1)) (i32.const 1))
+"#;
+
+    /// The checks of the test suite on a script
+    #[test]
+    fn scripts() {
+        let scripts = [("i32.wast".to_string(), SCRIPT.to_string())];
+        check_scripts(&spec(), &scripts);
+    }
+
+    /// A case with another result fails the checks
+    #[test]
+    #[should_panic(
+        expected = "i32.wast:7: Ok(\"(val I32 CONST 3)\"), expected Ok(\"(val I32 CONST 4)\")"
+    )]
+    fn failed() {
+        let script = SCRIPT.replace("(i32.const 3))", "(i32.const 4))");
+        check_scripts(&spec(), &[("i32.wast".to_string(), script)]);
+    }
+
+    /// Run the integer scripts of the test suite
+    #[test]
+    #[ignore = "needs a checkout of wasmmeta in `WASMMETA_PATH`"]
+    fn suite() {
+        let path = fixtures::wasmmeta_path();
+        let spec = fixtures::wasmmeta_spec(&path);
+        let scripts: Vec<_> = fixtures::scripts(&path)
+            .into_iter()
+            .filter(|(name, _)| ["i32.wast", "i64.wast"].contains(&&**name))
+            .collect();
+        check_scripts(&spec, &scripts);
+    }
+}
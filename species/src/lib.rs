@@ -1,32 +1,73 @@
-use std::{fs, path::Path};
+use std::{fs, io, path::Path};
 
 use nom::IResult;
 
+pub mod codegen;
+pub mod coverage;
+pub mod crosscheck;
+pub mod diff;
+pub mod error;
+pub mod formatter;
+pub mod generator;
+pub mod grammar;
+pub mod history;
+pub mod interpreter;
+pub mod lint;
+pub mod loader;
+pub mod lsp;
+pub mod macros;
+pub mod numerics;
 pub mod parser;
+pub mod spec;
+pub mod spectec;
 pub mod syntax;
+pub mod tex;
+pub mod validator;
 
-type PResult<'a, T> = IResult<&'a str, T>;
+pub use error::SpeciesError;
+pub use grammar::{Grammar, Origin, SpecSet};
+pub use loader::SpecLoader;
+pub use spec::Spec;
+
+pub type PResult<'a, T> = IResult<&'a str, T, SpeciesError<&'a str>>;
 
 #[macro_export]
 macro_rules! nom_err {
-    ($input: ident, $tag: expr) => {
-        Err(nom::Err::Error(nom::error::Error::new($input, $tag)))
+    ($input: expr, $variant: ident) => {
+        Err(nom::Err::Error($crate::error::SpeciesError::$variant($input)))
+    };
+    ($input: expr, $variant: ident, $($arg: expr),+) => {
+        Err(nom::Err::Error($crate::error::SpeciesError::$variant($input, $($arg),+)))
     };
 }
 
-fn read_math_blocks(path: impl AsRef<Path>) -> Vec<String> {
-    let content = fs::read_to_string(path).unwrap();
-    let mut blocks: Vec<String> = vec![];
+/// The `.. math::` blocks of an `rst` file
+pub fn read_math_blocks(path: impl AsRef<Path>) -> io::Result<Vec<String>> {
+    let content = fs::read_to_string(path)?;
+    Ok(math_blocks(&content)
+        .into_iter()
+        .map(|(_line, block)| block)
+        .collect())
+}
+
+/// Collect the `.. math::` blocks of an `rst` source together with the
+/// (1-based) line on which each block starts
+pub fn math_blocks(content: &str) -> Vec<(usize, String)> {
+    let mut blocks: Vec<(usize, String)> = vec![];
     let mut math_block: Vec<&str> = vec![];
+    let mut start = 0;
     let lines = content.lines();
     let mut in_block = false;
-    for line in lines {
+    for (i, line) in lines.enumerate() {
         if in_block {
-            if line.len() != 0 {
+            if !line.is_empty() {
+                if math_block.is_empty() {
+                    start = i + 1;
+                }
                 math_block.push(line);
             } else {
                 let s = math_block.concat();
-                blocks.push(s);
+                blocks.push((start, s));
                 math_block.clear();
                 in_block = false;
             }
@@ -35,6 +76,9 @@ fn read_math_blocks(path: impl AsRef<Path>) -> Vec<String> {
             in_block = true;
         }
     }
+    if !math_block.is_empty() {
+        blocks.push((start, math_block.concat()));
+    }
 
     blocks
 }
@@ -0,0 +1,628 @@
+//! Style rules over a parsed specification, beyond the errors of the parser.
+//!
+//! Each rule has a severity, `off`, `warning` or `error`, set in a TOML
+//! config file:
+//!
+//! ```toml
+//! [rules]
+//! production-name = "error"
+//! mixed-iteration = "off"
+//! ```
+//!
+//! Rules left out of the config are warnings. A lint points at the span of
+//! the `rst` source it is about, and suggests a fix where there is one.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    grammar::{document_root, rst_files},
+    macros::Macros,
+    syntax::{
+        symbol::SRecord,
+        visit::{self, Visit},
+    },
+    tex::{self, words, Token},
+    Grammar, Origin,
+};
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Off,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Rule {
+    /// The human name of a production does not abbreviate to its macro,
+    /// e.g. `\production{number type}` for `\valtype`
+    ProductionName,
+    /// A terminal of the syntax has no definition in `util/macros.def`
+    UndefinedTerminal,
+    /// A record field does not start like the other fields of its record,
+    /// e.g. `\MAX` next to `\LMIN`
+    FieldPrefix,
+    /// Both `^\ast` and `^*` are used for iterations
+    MixedIteration,
+}
+
+/// Severity of each rule
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    rules: BTreeMap<Rule, Severity>,
+}
+
+/// A rule broken at a span of a file
+#[derive(Debug, PartialEq)]
+pub struct Lint {
+    pub rule: Rule,
+    pub severity: Severity,
+    /// Path of the `rst` file, relative to `document/core`
+    pub file: PathBuf,
+    /// Bytes of the source the lint is about
+    pub span: Range<usize>,
+    /// Line and column of the start of the span, from 1
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub suggestion: Option<Suggestion>,
+}
+
+/// Fix of a lint, with the source to replace its span with if the fix is
+/// in place
+#[derive(Debug, PartialEq)]
+pub struct Suggestion {
+    pub message: String,
+    pub replacement: Option<String>,
+}
+
+pub struct Linter {
+    config: Config,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 4] = [
+        Rule::ProductionName,
+        Rule::UndefinedTerminal,
+        Rule::FieldPrefix,
+        Rule::MixedIteration,
+    ];
+
+    /// Name of the rule in the config and the reports
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::ProductionName => "production-name",
+            Rule::UndefinedTerminal => "undefined-terminal",
+            Rule::FieldPrefix => "field-prefix",
+            Rule::MixedIteration => "mixed-iteration",
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|rule| rule.name() == s)
+            .ok_or_else(|| format!("unknown lint rule `{}`", s))
+    }
+}
+
+impl Config {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct File {
+            #[serde(default)]
+            rules: BTreeMap<String, Severity>,
+        }
+        let file: File = toml::from_str(source).map_err(|e| e.to_string())?;
+        let rules = file
+            .rules
+            .into_iter()
+            .map(|(name, severity)| Ok((name.parse()?, severity)))
+            .collect::<Result<_, String>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(&source).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn severity(&self, rule: Rule) -> Severity {
+        self.rules.get(&rule).copied().unwrap_or(Severity::Warning)
+    }
+}
+
+impl Linter {
+    pub fn new(config: Config) -> Self {
+        Self { config }
+    }
+
+    /// Lint the `rst` files of a checkout against its `util/macros.def`
+    pub fn lint(&self, root: impl AsRef<Path>) -> io::Result<Vec<Lint>> {
+        let root = document_root(root.as_ref());
+        let macros = Macros::load(&root)?;
+        let mut sources = vec![];
+        for path in rst_files(&root)? {
+            let text = fs::read_to_string(&path)?;
+            let relative = path.strip_prefix(&root).unwrap_or(&path).to_path_buf();
+            sources.push((relative, text));
+        }
+        Ok(self.lint_sources(&sources, &macros))
+    }
+
+    /// Lint sources given as their paths relative to `document/core` and
+    /// their text. Without macros, terminals are not checked.
+    pub fn lint_sources(&self, sources: &[(PathBuf, String)], macros: &Macros) -> Vec<Lint> {
+        let mut grammar = Grammar::new("lint");
+        for (file, text) in sources {
+            grammar.add_source(file, text);
+        }
+        let mut lints = Lints {
+            config: &self.config,
+            sources,
+            lints: vec![],
+        };
+        lints.production_names(&grammar);
+        if macros.names().next().is_some() {
+            lints.undefined_terminals(&grammar, macros);
+        }
+        lints.field_prefixes(&grammar);
+        lints.mixed_iterations();
+
+        let mut lints = lints.lints;
+        lints.sort_by(|a, b| (&a.file, a.span.start).cmp(&(&b.file, b.span.start)));
+        lints
+    }
+}
+
+/// Lints found so far
+struct Lints<'a> {
+    config: &'a Config,
+    sources: &'a [(PathBuf, String)],
+    lints: Vec<Lint>,
+}
+
+impl Lints<'_> {
+    fn push(
+        &mut self,
+        rule: Rule,
+        file: &Path,
+        span: Range<usize>,
+        message: String,
+        suggestion: Option<Suggestion>,
+    ) {
+        let severity = self.config.severity(rule);
+        if severity == Severity::Off {
+            return;
+        }
+        let text = self.text(file);
+        let before = &text[..span.start];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        self.lints.push(Lint {
+            rule,
+            severity,
+            file: file.to_path_buf(),
+            span,
+            line,
+            column,
+            message,
+            suggestion,
+        });
+    }
+
+    fn text(&self, file: &Path) -> &str {
+        self.sources
+            .iter()
+            .find(|(path, _)| path == file)
+            .map_or("", |(_, text)| text)
+    }
+
+    /// Offset of the math block of `origin` in its file
+    fn block_start(&self, origin: &Origin) -> usize {
+        let text = self.text(&origin.file);
+        let mut lines = text.split_inclusive('\n');
+        lines.by_ref().take(origin.line - 1).map(str::len).sum()
+    }
+
+    /// Span of the first control word `\name` of the math block of
+    /// `origin`, or on from it
+    fn word(&self, origin: &Origin, name: &str) -> Option<Range<usize>> {
+        let start = self.block_start(origin);
+        let (_, span) = words(&self.text(&origin.file)[start..]).find(|(word, _)| *word == name)?;
+        Some(start + span.start..start + span.end)
+    }
+
+    fn production_names(&mut self, grammar: &Grammar) {
+        for (origin, production) in grammar.productions() {
+            let nonterminal = production.nonterminal();
+            if abbreviates(production.name(), nonterminal) {
+                continue;
+            }
+            // the span of the name, in `\production{...}`
+            let start = self.block_start(origin);
+            let text = &self.text(&origin.file)[start..];
+            let head = format!(r"\production{{{}}}", production.name());
+            let Some(offset) = text.find(&head) else {
+                continue;
+            };
+            let name = start + offset + r"\production{".len();
+            let message = format!(
+                r"production `{}` is not named after `\{}`",
+                production.name(),
+                nonterminal
+            );
+            let span = name..name + production.name().len();
+            self.push(Rule::ProductionName, &origin.file, span, message, None);
+        }
+    }
+
+    fn undefined_terminals(&mut self, grammar: &Grammar, macros: &Macros) {
+        for (origin, production) in grammar.productions() {
+            let mut terminals = Terminals(vec![]);
+            terminals.visit_production(production);
+            // keywords written inline, like `\K{i32}`, are not macros
+            terminals.0.retain(|name| {
+                !name.chars().any(|c| c.is_ascii_lowercase()) && macros.get(name).is_none()
+            });
+            terminals.0.sort_unstable();
+            terminals.0.dedup();
+            for name in terminals.0 {
+                let Some(span) = self.word(origin, name) else {
+                    continue;
+                };
+                let suggestion = Suggestion {
+                    message: format!(
+                        r"define it in util/macros.def, e.g. `.. |{}| mathdef:: \K{{{}}}`",
+                        name,
+                        name.to_lowercase()
+                    ),
+                    replacement: None,
+                };
+                let message = format!(r"terminal `\{}` is not defined in util/macros.def", name);
+                self.push(
+                    Rule::UndefinedTerminal,
+                    &origin.file,
+                    span,
+                    message,
+                    Some(suggestion),
+                );
+            }
+        }
+    }
+
+    fn field_prefixes(&mut self, grammar: &Grammar) {
+        for (origin, production) in grammar.productions() {
+            let mut records = Records(vec![]);
+            records.visit_production(production);
+            for record in records.0 {
+                let keys: Vec<&str> = record.pairs().iter().map(|(key, _)| key.as_str()).collect();
+                let Some(prefix) = common_initial(&keys) else {
+                    continue;
+                };
+                for key in keys.iter().filter(|key| !key.starts_with(prefix)) {
+                    let Some(span) = self.word(origin, key) else {
+                        continue;
+                    };
+                    let renamed = format!(r"\{}{}", prefix, key);
+                    let message = format!(
+                        r"field `\{}` does not start with `{}` like the other fields of its record",
+                        key, prefix
+                    );
+                    let suggestion = Suggestion {
+                        message: format!("rename it to `{}`", renamed),
+                        replacement: Some(renamed),
+                    };
+                    self.push(
+                        Rule::FieldPrefix,
+                        &origin.file,
+                        span,
+                        message,
+                        Some(suggestion),
+                    );
+                }
+            }
+        }
+    }
+
+    fn mixed_iterations(&mut self) {
+        let mut found = vec![];
+        for (file, text) in self.sources {
+            for range in math_spans(text) {
+                found.extend(
+                    iterations(&text[range.clone()])
+                        .into_iter()
+                        .map(|(ast, span)| {
+                            (file, ast, range.start + span.start..range.start + span.end)
+                        }),
+                );
+            }
+        }
+        let asts = found.iter().filter(|(_, ast, _)| *ast).count();
+        let stars = found.len() - asts;
+        if asts == 0 || stars == 0 {
+            return;
+        }
+        // `^\ast` unless `^*` is used more often
+        let ast = asts >= stars;
+        let (used, other) = if ast { (r"\ast", "*") } else { ("*", r"\ast") };
+        for (file, is_ast, span) in found {
+            if is_ast == ast {
+                continue;
+            }
+            let written = &self.text(file)[span.clone()];
+            let replacement = written.replacen(other, used, 1);
+            let message = format!(
+                "`{}` where the other iterations are written with `{}`",
+                written, used
+            );
+            let suggestion = Suggestion {
+                message: format!("write `{}`", replacement),
+                replacement: Some(replacement),
+            };
+            self.push(Rule::MixedIteration, file, span, message, Some(suggestion));
+        }
+    }
+}
+
+/// Whether a human name abbreviates to a macro: the macro, without the `B`
+/// or `T` of the binary and text formats, is made of a prefix of each of
+/// the words of the name, in order, some words left out. `number type`
+/// abbreviates to `\numtype` and `\Bnumtype`, and `element segment` to
+/// `\elem`.
+fn abbreviates(name: &str, nonterminal: &str) -> bool {
+    let words: Vec<String> = name
+        .split([' ', '-'])
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    // names like `\X{\mathit{ht}}` are not macros
+    if !nonterminal.is_ascii() {
+        return true;
+    }
+    let matches = |nonterminal: &str| prefixes(&nonterminal.to_lowercase(), &words);
+    match nonterminal.strip_prefix(['B', 'T']) {
+        Some(rest) if rest.starts_with(|c: char| c.is_ascii_lowercase()) => matches(rest),
+        _ => matches(nonterminal),
+    }
+}
+
+fn prefixes(rest: &str, words: &[String]) -> bool {
+    let Some((word, others)) = words.split_first() else {
+        // e.g. `\bytes` of `byte vector`
+        return rest.is_empty() || rest == "s";
+    };
+    if rest.is_empty() {
+        return true;
+    }
+    // the letters of a word taken, from its first one, e.g. `idx` of `index`
+    let mut taken = vec![];
+    let mut letters = word.chars();
+    if letters.next() == rest.chars().next() {
+        let mut len = 1;
+        taken.push(len);
+        for c in rest[1..].chars() {
+            if !letters.any(|l| l == c) {
+                break;
+            }
+            len += c.len_utf8();
+            taken.push(len);
+        }
+    }
+    taken
+        .into_iter()
+        .rev()
+        .any(|len| prefixes(&rest[len..], others))
+        || prefixes(rest, others)
+}
+
+/// The initial most record fields start with, if at least two do
+fn common_initial<'k>(keys: &[&'k str]) -> Option<&'k str> {
+    let initials: Vec<&str> = keys.iter().filter_map(|key| key.get(..1)).collect();
+    let (initial, count) = initials
+        .iter()
+        .map(|i| (*i, initials.iter().filter(|j| *j == i).count()))
+        .max_by_key(|(_, count)| *count)?;
+    (count >= 2 && count * 2 > initials.len()).then_some(initial)
+}
+
+/// Spans of the math of an `rst` source, the `.. math::` blocks and the
+/// `:math:` roles
+fn math_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = vec![];
+    let mut offset = 0;
+    let mut block: Option<usize> = None;
+    for line in text.split_inclusive('\n') {
+        let blank = line.trim().is_empty();
+        match block {
+            Some(start) if blank => {
+                spans.push(start..offset);
+                block = None;
+            }
+            Some(_) => {}
+            None if line.starts_with(".. math::") => block = Some(offset + line.len()),
+            None => {
+                let mut rest = line;
+                while let Some(start) = rest.find(":math:`") {
+                    let math = &rest[start + ":math:`".len()..];
+                    let Some(end) = math.find('`') else {
+                        break;
+                    };
+                    let at = offset + line.len() - math.len();
+                    spans.push(at..at + end);
+                    rest = &math[end..];
+                }
+            }
+        }
+        offset += line.len();
+    }
+    if let Some(start) = block {
+        spans.push(start..text.len());
+    }
+    spans
+}
+
+/// Iterations `^\ast`, `^*`, `^{\ast}` and `^{*}` of math, with whether
+/// they are written with `\ast`
+fn iterations(math: &str) -> Vec<(bool, Range<usize>)> {
+    let mut found = vec![];
+    for (offset, token) in tex::tokens(math) {
+        if token != Token::Superscript {
+            continue;
+        }
+        let rest = &math[offset + 1..];
+        let (script, len) = match tex::next(rest) {
+            Some((Token::BeginGroup, _)) => match tex::group(rest) {
+                Some((script, tail)) => (script.trim(), rest.len() - tail.len()),
+                None => continue,
+            },
+            Some((_, tail)) => (&rest[..rest.len() - tail.len()], rest.len() - tail.len()),
+            None => continue,
+        };
+        match script {
+            r"\ast" => found.push((true, offset..offset + 1 + len)),
+            "*" => found.push((false, offset..offset + 1 + len)),
+            _ => {}
+        }
+    }
+    found
+}
+
+struct Terminals<'ast>(Vec<&'ast str>);
+
+impl<'ast> Visit<'ast> for Terminals<'ast> {
+    fn visit_sterm(&mut self, name: &'ast str) {
+        self.0.push(name);
+    }
+}
+
+struct Records<'ast>(Vec<&'ast SRecord>);
+
+impl<'ast> Visit<'ast> for Records<'ast> {
+    fn visit_srecord(&mut self, node: &'ast SRecord) {
+        self.0.push(node);
+        visit::visit_srecord(self, node);
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Off => write!(f, "off"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}[{}]: {}:{}:{}: {}",
+            self.severity,
+            self.rule.name(),
+            self.file.display(),
+            self.line,
+            self.column,
+            self.message
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "\n  help: {}", suggestion.message)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPES: &str = r"Types
+
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 \\
+   \production{value type} & \Bnumtype &::=&
+     \hex{7F} \Rightarrow \I32 \\
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \MAX~\u32^?, \LSTEP~\u32 \} \\
+   \production{result type} & \resulttype &::=&
+     [\vec(\valtype)] ~|~ \valtype^{\ast} ~|~ \K{i8}~\valtype^\ast \\
+   \end{array}
+
+The :math:`\valtype^*` in prose.
+";
+
+    const MACROS: &str = r".. |I32| mathdef:: \xref{syntax/types}{syntax-valtype}{\K{i32}}";
+
+    fn lint(config: &str) -> Vec<Lint> {
+        let sources = [(PathBuf::from("syntax/types.rst"), TYPES.to_string())];
+        let config = Config::parse(config).unwrap();
+        Linter::new(config).lint_sources(&sources, &Macros::parse(MACROS))
+    }
+
+    #[test]
+    fn rules() {
+        let lints = lint("[rules]\nundefined-terminal = \"error\"");
+        let reports: Vec<_> = lints.iter().map(|l| l.to_string()).collect();
+        assert_eq!(
+            reports,
+            vec![
+                r"error[undefined-terminal]: syntax/types.rst:6:15: terminal `\I64` is not defined in util/macros.def
+  help: define it in util/macros.def, e.g. `.. |I64| mathdef:: \K{i64}`",
+                r"warning[production-name]: syntax/types.rst:7:16: production `value type` is not named after `\Bnumtype`",
+                r"warning[field-prefix]: syntax/types.rst:10:21: field `\MAX` does not start with `L` like the other fields of its record
+  help: rename it to `\LMAX`",
+                r"warning[mixed-iteration]: syntax/types.rst:15:20: `^*` where the other iterations are written with `\ast`
+  help: write `^\ast`",
+            ]
+        );
+        let max = &lints[2];
+        assert_eq!(&TYPES[max.span.clone()], r"\MAX");
+
+        let lints = lint("[rules]\nmixed-iteration = \"off\"\nfield-prefix = \"off\"");
+        assert!(lints
+            .iter()
+            .all(|l| matches!(l.rule, Rule::ProductionName | Rule::UndefinedTerminal)));
+    }
+
+    #[test]
+    fn configs() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("[rules]\nno-such-rule = \"off\"")
+            .unwrap_err()
+            .contains("unknown lint rule `no-such-rule`"));
+        assert!(Config::parse("[rules]\nfield-prefix = \"loud\"").is_err());
+        assert!(Config::parse("[other]").is_err());
+    }
+
+    #[test]
+    fn abbreviations() {
+        assert!(abbreviates("number type", "numtype"));
+        assert!(abbreviates("number type", "Tnumtype"));
+        assert!(abbreviates("external types", "externtype"));
+        assert!(abbreviates("element segment", "elem"));
+        assert!(abbreviates("limits", "limits"));
+        assert!(abbreviates("label index", "Tlabelidx"));
+        assert!(abbreviates("byte vector", "Bbytes"));
+        assert!(!abbreviates("value type", "numtype"));
+        assert!(!abbreviates("function type", "tabletype"));
+    }
+}
@@ -0,0 +1,357 @@
+//! Loading of a whole specification checkout.
+//!
+//! The `rst` files are parsed in parallel, each into a grammar of its own,
+//! and the grammars are appended in the order of the files, so that the
+//! result is the same as reading the files one after the other. With a cache
+//! directory, the grammar of each file is also written there as JSON, under
+//! the hash of the file's content, its extension and the version of
+//! `species`, and read back instead of parsing the file again as long as none
+//! of them changes. The cache only saves work: a grammar that cannot be
+//! written there is reported in [`Loaded::cache_errors`] and loaded anyway.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+use crate::{
+    grammar::{document_root, spec_files},
+    Grammar,
+};
+
+/// Loader of the `rst` files of a specification checkout, and of its SpecTec
+/// sources, see [`Grammar::add_spectec_source`]
+#[derive(Debug, Clone)]
+pub struct SpecLoader {
+    name: String,
+    cache: Option<PathBuf>,
+    threads: usize,
+}
+
+/// A loaded grammar, with the files parsed and those read from the cache,
+/// relative to `document/core`, and the errors of writing to the cache
+#[derive(Debug)]
+pub struct Loaded {
+    pub grammar: Grammar,
+    pub parsed: Vec<PathBuf>,
+    pub cached: Vec<PathBuf>,
+    pub cache_errors: Vec<io::Error>,
+}
+
+impl SpecLoader {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            cache: None,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    /// Keep the grammar of each file in `dir`, created if missing
+    pub fn cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache = Some(dir.into());
+        self
+    }
+
+    /// Parse on at most `n` threads, by default one per available CPU
+    pub fn threads(mut self, n: usize) -> Self {
+        self.threads = n.max(1);
+        self
+    }
+
+    /// Parse every `rst` file of a local specification checkout.
+    ///
+    /// `root` is either the repository root, which contains `document/core`,
+    /// or the `document/core` directory itself. The `.watsup` and `.spectec`
+    /// files below it are read too, so `root` can also be a directory of
+    /// SpecTec sources, e.g. `specification/wasm-3.0`.
+    pub fn load(&self, root: impl AsRef<Path>) -> io::Result<Loaded> {
+        let root = document_root(root.as_ref());
+        let files = spec_files(&root)?;
+        if let Some(dir) = &self.cache {
+            // a directory that cannot be created fails each write, which is
+            // reported with the file it was for
+            let _ = fs::create_dir_all(dir);
+        }
+
+        let next = AtomicUsize::new(0);
+        let worker = || {
+            let mut done = vec![];
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(file) = files.get(i) else {
+                    return done;
+                };
+                done.push((i, self.load_file(&root, file)));
+            }
+        };
+        let mut results = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads.min(files.len()))
+                .map(|_| scope.spawn(worker))
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|w| w.join().expect("parser threads do not panic"))
+                .collect::<Vec<_>>()
+        });
+        results.sort_by_key(|(i, _)| *i);
+
+        let mut loaded = Loaded {
+            grammar: Grammar::new(self.name.clone()),
+            parsed: vec![],
+            cached: vec![],
+            cache_errors: vec![],
+        };
+        for (i, result) in results {
+            let (grammar, cached, cache_error) = result?;
+            let relative = relative(&root, &files[i]).to_path_buf();
+            if cached {
+                loaded.cached.push(relative);
+            } else {
+                loaded.parsed.push(relative);
+            }
+            loaded.cache_errors.extend(cache_error);
+            loaded.grammar.append(grammar);
+        }
+        Ok(loaded)
+    }
+
+    /// The grammar of one file, whether it was read from the cache, and the
+    /// error of writing it there
+    fn load_file(
+        &self,
+        root: &Path,
+        file: &Path,
+    ) -> io::Result<(Grammar, bool, Option<io::Error>)> {
+        let content = fs::read_to_string(file).map_err(|e| in_file(file, e))?;
+        let relative = relative(root, file);
+        let entry = self
+            .cache
+            .as_ref()
+            .map(|dir| dir.join(entry_name(file, &content)));
+
+        // an entry that does not read back, e.g. one cut short, is replaced
+        if let Some(mut grammar) = entry.as_deref().and_then(read_entry) {
+            grammar.relabel(&self.name, relative);
+            return Ok((grammar, true, None));
+        }
+
+        let mut grammar = Grammar::new(self.name.clone());
+        if file.extension().is_some_and(|ext| ext == "rst") {
+            grammar.add_source(relative, &content);
+        } else {
+            grammar.add_spectec_source(relative, &content);
+        }
+        // errors are not serialized, so a file with errors is parsed every
+        // time and keeps reporting them
+        let cache_error = entry
+            .filter(|_| grammar.errors().is_empty())
+            .and_then(|entry| write_entry(&entry, &grammar).err())
+            .map(|e| in_file(file, e));
+        Ok((grammar, false, cache_error))
+    }
+}
+
+/// `error` prefixed with the file it is about
+fn in_file(file: &Path, error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {}", file.display(), error))
+}
+
+fn relative<'a>(root: &Path, file: &'a Path) -> &'a Path {
+    file.strip_prefix(root).unwrap_or(file)
+}
+
+fn read_entry(entry: &Path) -> Option<Grammar> {
+    let json = fs::read(entry).ok()?;
+    serde_json::from_slice(&json).ok()
+}
+
+/// Write an entry aside and rename it, so that a concurrent run never reads
+/// half an entry. Files with the same content have the same entry, so each
+/// write goes through a file of its own, and one that loses the race to
+/// another still leaves the entry complete.
+fn write_entry(entry: &Path, grammar: &Grammar) -> io::Result<()> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let write = WRITES.fetch_add(1, Ordering::Relaxed);
+    let partial = entry.with_extension(format!("{}-{}.partial", process::id(), write));
+    let written = serde_json::to_vec(grammar)
+        .map_err(io::Error::other)
+        .and_then(|json| fs::write(&partial, json))
+        .and_then(|()| fs::rename(&partial, entry));
+    match written {
+        Err(_) if entry.is_file() => {
+            let _ = fs::remove_file(&partial);
+            Ok(())
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            Err(io::Error::new(
+                e.kind(),
+                format!("cannot write the cache entry {}: {}", entry.display(), e),
+            ))
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Name of the cache entry of a file, by the FNV-1a hash of its content, its
+/// extension, which picks the parser, and the version of `species`
+fn entry_name(file: &Path, content: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in content.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    let extension = file.extension().unwrap_or_default().to_string_lossy();
+    format!(
+        "{:016x}-{}-{}.json",
+        hash,
+        extension,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::syntax::fixtures::TempDir;
+
+    use super::*;
+
+    const TYPES: &str = r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 \\
+   \end{array}
+";
+
+    const INSTRUCTIONS: &str = r"
+.. math::
+   \begin{array}{llll}
+   \production{instruction} & \instr &::=&
+     \NOP ~|~ \UNREACHABLE \\
+   \end{array}
+";
+
+    const BROKEN: &str = r"
+.. math::
+   \begin{array}{llll}
+   \production{broken} & \broken &::=& \{ \\
+   \end{array}
+";
+
+    #[test]
+    fn cached_files() {
+        let root = TempDir::new("loader");
+        let core = root.join("document").join("core");
+        let cache = root.join("cache");
+        fs::create_dir_all(core.join("syntax")).unwrap();
+        fs::write(core.join("syntax/types.rst"), TYPES).unwrap();
+        fs::write(core.join("syntax/instructions.rst"), INSTRUCTIONS).unwrap();
+        fs::write(core.join("broken.rst"), BROKEN).unwrap();
+
+        let loader = SpecLoader::new("spec").cache(&cache).threads(2);
+        let first = loader.load(&root).unwrap();
+        fs::write(core.join("syntax/types.rst"), TYPES.replace("I64", "I128")).unwrap();
+        let second = SpecLoader::new("edited").cache(&cache).load(&root).unwrap();
+        let serial = Grammar::load("edited", &root).unwrap();
+
+        let files = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+        let all = ["broken.rst", "syntax/instructions.rst", "syntax/types.rst"];
+        assert_eq!(first.parsed, files(&all));
+        assert!(first.cached.is_empty());
+        assert_eq!(first.grammar.errors().len(), 1);
+
+        assert_eq!(second.parsed, files(&["broken.rst", "syntax/types.rst"]));
+        assert_eq!(second.cached, files(&["syntax/instructions.rst"]));
+        assert_eq!(second.grammar.errors().len(), 1);
+        assert_eq!(second.grammar.nonterminals(), serial.nonterminals());
+        assert!(second.grammar.productions().eq(serial.productions()));
+    }
+
+    /// The same bytes read as `rst` and as SpecTec give other grammars
+    #[test]
+    fn extensions() {
+        let root = TempDir::new("loader-extensions");
+        let core = root.join("document").join("core");
+        fs::create_dir_all(&core).unwrap();
+        let content = "syntax numtype = I32 | I64\n";
+        fs::write(core.join("types.rst"), content).unwrap();
+        fs::write(core.join("types.spectec"), content).unwrap();
+
+        let loader = SpecLoader::new("spec").cache(root.join("cache")).threads(1);
+        let first = loader.load(&root).unwrap();
+        let second = loader.load(&root).unwrap();
+        assert_eq!(first.parsed.len(), 2);
+        assert_eq!(second.cached.len(), 2);
+        assert_eq!(fs::read_dir(root.join("cache")).unwrap().count(), 2);
+        let serial = Grammar::load("spec", &root).unwrap();
+        assert_eq!(second.grammar.nonterminals(), serial.nonterminals());
+        assert_eq!(second.grammar.productions().count(), 1);
+    }
+
+    #[test]
+    fn unreadable() {
+        let root = TempDir::new("loader-unreadable");
+        assert!(SpecLoader::new("spec").load(root.join("missing")).is_err());
+
+        let core = root.join("document").join("core");
+        fs::create_dir_all(&core).unwrap();
+        fs::write(core.join("types.rst"), TYPES).unwrap();
+        // a cache directory that is a file is reported, and not used
+        let file = root.join("file");
+        fs::write(&file, "").unwrap();
+        let uncached = SpecLoader::new("spec").cache(&file).load(&root).unwrap();
+        assert_eq!(uncached.parsed, vec![PathBuf::from("types.rst")]);
+        assert_eq!(uncached.grammar.nonterminals(), vec!["numtype"]);
+        assert_eq!(uncached.cache_errors.len(), 1);
+        let message = uncached.cache_errors[0].to_string();
+        assert!(message.starts_with(&core.join("types.rst").display().to_string()));
+
+        // an entry that does not read back is parsed again and replaced
+        let cache = root.join("cache");
+        let loader = SpecLoader::new("spec").cache(&cache);
+        loader.load(&root).unwrap();
+        for entry in fs::read_dir(&cache).unwrap() {
+            fs::write(entry.unwrap().path(), "{ cut short").unwrap();
+        }
+        let reparsed = loader.load(&root).unwrap();
+        assert_eq!(reparsed.parsed, vec![PathBuf::from("types.rst")]);
+        assert!(reparsed.grammar.errors().is_empty());
+        let cached = loader.load(&root).unwrap();
+        assert_eq!(cached.cached, vec![PathBuf::from("types.rst")]);
+
+        // a file that does not read is named
+        fs::write(core.join("latin1.rst"), b"\xe9").unwrap();
+        let error = loader.load(&root).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("latin1.rst"));
+    }
+
+    /// Files of the same content write the same entry from several threads
+    #[test]
+    fn identical_files() {
+        let root = TempDir::new("loader-identical");
+        let core = root.join("document").join("core");
+        fs::create_dir_all(&core).unwrap();
+        for i in 0..200 {
+            fs::write(core.join(format!("types{:03}.rst", i)), TYPES).unwrap();
+        }
+        for run in 0..20 {
+            let cache = root.join(format!("cache{}", run));
+            let loaded = SpecLoader::new("spec")
+                .cache(&cache)
+                .threads(8)
+                .load(&root)
+                .unwrap();
+            assert!(loaded.cache_errors.is_empty(), "{:?}", loaded.cache_errors);
+            assert_eq!(loaded.parsed.len() + loaded.cached.len(), 200);
+            assert_eq!(loaded.grammar.productions().count(), 200);
+            let entries: Vec<_> = fs::read_dir(&cache).unwrap().collect();
+            assert_eq!(entries.len(), 1);
+        }
+    }
+}
@@ -0,0 +1,605 @@
+//! Queries of the language server, `species-lsp`, over the `rst` files of a
+//! specification checkout.
+//!
+//! Every file is parsed into a grammar of its own, and parsed again when it
+//! is edited. Definitions and uses are found on the TeX tokens of the files:
+//! a definition is the control word after `\production{...}`, and a use is
+//! any control word of the same name, in a math block or in `:math:` text.
+//! Positions are those of the protocol, lines and UTF-16 columns from 0.
+//! Files are keyed by their canonical paths, so that the paths read from the
+//! checkout and those of the client's URIs name a file alike. The definitions
+//! of all files are indexed by name, and the index entries of a file are
+//! replaced when it is edited.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, Position};
+
+use crate::{
+    grammar::{document_root, rst_files},
+    macros::Macros,
+    math_blocks,
+    syntax::{
+        symbol::SNonterm,
+        visit::{self, Visit},
+    },
+    tex::{self, words, Token},
+    Grammar, Origin,
+};
+
+/// The `rst` files of a checkout, as last edited
+#[derive(Debug)]
+pub struct Workspace {
+    root: PathBuf,
+    files: BTreeMap<PathBuf, File>,
+    /// Definitions of each nonterminal, in the order of the files
+    definitions: BTreeMap<String, Vec<Location>>,
+    macros: Macros,
+}
+
+#[derive(Debug)]
+struct File {
+    text: String,
+    grammar: Grammar,
+}
+
+/// Span of a file of the workspace
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Location {
+    pub path: PathBuf,
+    pub range: lsp_types::Range,
+}
+
+impl Workspace {
+    /// A workspace without files, for a `root` that is not a checkout
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        Self {
+            root: document_root(&canonical(root.as_ref())),
+            files: BTreeMap::new(),
+            definitions: BTreeMap::new(),
+            macros: Macros::default(),
+        }
+    }
+
+    /// Read the `rst` files and the macros of a checkout, see
+    /// [`Grammar::load`]
+    pub fn load(root: impl AsRef<Path>) -> io::Result<Self> {
+        let mut workspace = Self::new(root);
+        workspace.macros = Macros::load(&workspace.root)?;
+        for path in rst_files(&workspace.root)? {
+            let text = fs::read_to_string(&path)?;
+            workspace.update(&path, text);
+        }
+        Ok(workspace)
+    }
+
+    /// Replace the text of a file and parse it again
+    pub fn update(&mut self, path: &Path, text: String) {
+        let path = canonical(path);
+        if let Some(old) = self.files.get(&path) {
+            for (name, _) in definitions(&old.text) {
+                if let Some(locations) = self.definitions.get_mut(name) {
+                    locations.retain(|location| location.path != path);
+                    if locations.is_empty() {
+                        self.definitions.remove(name);
+                    }
+                }
+            }
+        }
+        let lines = line_starts(&text);
+        for (name, range) in definitions(&text) {
+            let locations = self.definitions.entry(name.to_string()).or_default();
+            let at = locations.partition_point(|location| location.path <= path);
+            let location = Location {
+                path: path.clone(),
+                range: lsp_range_in(&text, &lines, range),
+            };
+            locations.insert(at, location);
+        }
+
+        let relative = path.strip_prefix(&self.root).unwrap_or(&path);
+        let mut grammar = Grammar::new("workspace");
+        grammar.add_source(relative, &text);
+        self.files.insert(path, File { text, grammar });
+    }
+
+    /// Definitions of the nonterminal at `position`
+    pub fn definition(&self, path: &Path, position: Position) -> Vec<Location> {
+        match self.word_at(path, position) {
+            Some(name) => self.definitions_of(name).to_vec(),
+            None => vec![],
+        }
+    }
+
+    /// Uses of the control word at `position`, with the definitions of the
+    /// nonterminal if `declarations`
+    pub fn references(&self, path: &Path, position: Position, declarations: bool) -> Vec<Location> {
+        let Some(name) = self.word_at(path, position) else {
+            return vec![];
+        };
+        let definitions = self.definitions_of(name);
+        let mut references = vec![];
+        for (path, file) in &self.files {
+            let lines = line_starts(&file.text);
+            for (_, range) in words(&file.text).filter(|(word, _)| *word == name) {
+                let location = Location {
+                    path: path.clone(),
+                    range: lsp_range_in(&file.text, &lines, range),
+                };
+                if declarations || !definitions.contains(&location) {
+                    references.push(location);
+                }
+            }
+        }
+        references
+    }
+
+    /// Markdown of the productions of the nonterminal at `position` and of
+    /// the macro it expands to
+    pub fn hover(&self, path: &Path, position: Position) -> Option<String> {
+        let name = self.word_at(path, position)?;
+        let mut sections = vec![];
+        let mut paths: Vec<_> = self
+            .definitions
+            .get(name)
+            .into_iter()
+            .flatten()
+            .map(|location| &location.path)
+            .collect();
+        paths.dedup();
+        let productions: Vec<_> = paths
+            .into_iter()
+            .filter_map(|path| self.files.get(path))
+            .flat_map(|file| file.grammar.productions())
+            .filter(|(_, p)| p.nonterminal() == name)
+            .map(|(_, p)| p.to_string())
+            .collect();
+        if !productions.is_empty() {
+            sections.push(format!("```latex\n{}\n```", productions.join("\n")));
+        }
+        if let Some(def) = self.macros.get(name) {
+            sections.push(format!("`\\{}` expands to `{}`", name, def));
+        }
+        if let Some(keyword) = self.macros.keyword(name) {
+            sections.push(format!("keyword `{}`", keyword));
+        }
+        (!sections.is_empty()).then(|| sections.join("\n\n"))
+    }
+
+    /// Parse errors of a file, and the nonterminals its productions use
+    /// without a definition anywhere in the workspace
+    pub fn diagnostics(&self, path: &Path) -> Vec<Diagnostic> {
+        let Some(file) = self.files.get(&canonical(path)) else {
+            return vec![];
+        };
+        let blocks = math_blocks(&file.text);
+        let lines = line_starts(&file.text);
+        let block_start = |origin: &Origin| {
+            lines
+                .get(origin.line - 1)
+                .copied()
+                .unwrap_or(file.text.len())
+        };
+
+        let mut diagnostics = vec![];
+        for (origin, error) in file.grammar.errors() {
+            let start = match blocks.iter().find(|(line, _)| *line == origin.line) {
+                // the error is raised on a suffix of the block, or of one
+                // of the cells of its array
+                Some((_, block)) => {
+                    let input = error.input();
+                    let offset = match block.strip_suffix(input.as_str()) {
+                        Some(head) => head.len(),
+                        None => block.find(input.as_str()).unwrap_or_default(),
+                    };
+                    block_offset(&file.text, origin.line, offset)
+                }
+                None => block_start(origin),
+            };
+            let end = file.text[start..]
+                .find('\n')
+                .map_or(file.text.len(), |i| start + i);
+            diagnostics.push(diagnostic(
+                &file.text,
+                start..end,
+                DiagnosticSeverity::ERROR,
+                error.to_string(),
+            ));
+        }
+
+        for (origin, production) in file.grammar.productions() {
+            let mut uses = Uses(vec![]);
+            uses.visit_production(production);
+            let start = block_start(origin);
+            let mut undefined: Vec<_> = uses
+                .0
+                .into_iter()
+                .filter(|name| self.definitions_of(name).is_empty())
+                .collect();
+            undefined.dedup();
+            for name in undefined {
+                let found = words(&file.text[start..]).find(|(word, _)| *word == name);
+                let Some((_, range)) = found else {
+                    continue;
+                };
+                let message = format!(r"undefined nonterminal `\{}`", name);
+                let range = start + range.start..start + range.end;
+                let diagnostic =
+                    diagnostic(&file.text, range, DiagnosticSeverity::WARNING, message);
+                if !diagnostics.contains(&diagnostic) {
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+        diagnostics
+    }
+
+    /// Definitions of a nonterminal, or of the one it instantiates, `\uN`
+    /// for `\u32`
+    fn definitions_of(&self, name: &str) -> &[Location] {
+        let find = |name: &str| self.definitions.get(name).map_or(&[][..], Vec::as_slice);
+        let found = find(name);
+        let digits = name.trim_end_matches(|c: char| c.is_ascii_digit());
+        if found.is_empty() && digits.len() < name.len() && !digits.is_empty() {
+            return find(&format!("{}N", digits));
+        }
+        found
+    }
+
+    fn word_at(&self, path: &Path, position: Position) -> Option<&str> {
+        let text = &self.files.get(&canonical(path))?.text;
+        let offset = offset(text, position)?;
+        let line = line_starts(text)[position.line as usize];
+        let end = text[line..].find('\n').map_or(text.len(), |i| line + i);
+        words(&text[line..end])
+            .find(|(_, range)| range.start + line <= offset && offset <= range.end + line)
+            .map(|(word, _)| word)
+    }
+}
+
+/// Nonterminals a production uses, in order
+struct Uses<'ast>(Vec<&'ast str>);
+
+impl<'ast> Visit<'ast> for Uses<'ast> {
+    fn visit_snonterm(&mut self, node: &'ast SNonterm) {
+        self.0.push(&node.name);
+        visit::visit_snonterm(self, node);
+    }
+}
+
+/// Nonterminals defined by `\production{...} & \name`, with their spans
+fn definitions(text: &str) -> Vec<(&str, Range<usize>)> {
+    let mut found = vec![];
+    for (offset, token) in tex::tokens(text) {
+        if token != Token::Word("production") {
+            continue;
+        }
+        let Some((_, mut rest)) = tex::group(&text[offset + r"\production".len()..]) else {
+            continue;
+        };
+        while let Some((token, tail)) = tex::next(rest) {
+            match token {
+                token if token.is_layout() => rest = tail,
+                Token::Word(name) => {
+                    let start = text.len() - rest.len();
+                    found.push((name, start..start + 1 + name.len()));
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+    found
+}
+
+fn diagnostic(
+    text: &str,
+    range: Range<usize>,
+    severity: DiagnosticSeverity,
+    message: String,
+) -> Diagnostic {
+    Diagnostic {
+        range: lsp_range(text, range),
+        severity: Some(severity),
+        source: Some("species".to_string()),
+        message,
+        ..Diagnostic::default()
+    }
+}
+
+/// Offset in the text of the `offset`-th byte of the math block starting on
+/// `line`, whose lines [`math_blocks`] joins without their line breaks
+fn block_offset(text: &str, line: usize, mut offset: usize) -> usize {
+    let starts = line_starts(text);
+    for &start in &starts[line - 1..] {
+        let len = text[start..].find('\n').unwrap_or(text.len() - start);
+        if offset < len {
+            return start + offset;
+        }
+        offset -= len;
+    }
+    text.len()
+}
+
+fn line_starts(text: &str) -> Vec<usize> {
+    let breaks = text.match_indices('\n').map(|(i, _)| i + 1);
+    std::iter::once(0).chain(breaks).collect()
+}
+
+/// Position of an offset, with the [`line_starts`] of the text
+fn position(text: &str, starts: &[usize], offset: usize) -> Position {
+    let line = starts.partition_point(|&start| start <= offset) - 1;
+    let character = text[starts[line]..offset].encode_utf16().count();
+    Position::new(line as u32, character as u32)
+}
+
+fn lsp_range(text: &str, range: Range<usize>) -> lsp_types::Range {
+    lsp_range_in(text, &line_starts(text), range)
+}
+
+fn lsp_range_in(text: &str, starts: &[usize], range: Range<usize>) -> lsp_types::Range {
+    lsp_types::Range::new(
+        position(text, starts, range.start),
+        position(text, starts, range.end),
+    )
+}
+
+/// Byte offset of a position, if it is in the text
+fn offset(text: &str, position: Position) -> Option<usize> {
+    let start = *line_starts(text).get(position.line as usize)?;
+    let mut character = 0;
+    for (i, c) in text[start..].char_indices() {
+        if character >= position.character as usize || c == '\n' {
+            return Some(start + i);
+        }
+        character += c.len_utf16();
+    }
+    Some(text.len())
+}
+
+/// Absolute path of a file with its symbolic links resolved, or of its
+/// directory for a file that is opened before it is saved
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        return path;
+    }
+    let parent = path
+        .parent()
+        .and_then(|parent| fs::canonicalize(parent).ok());
+    match (parent, path.file_name()) {
+        (Some(parent), Some(name)) => parent.join(name),
+        _ => std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use crate::syntax::fixtures::TempDir;
+
+    use super::*;
+
+    const TYPES: &str = r"
+.. math::
+   \begin{array}{llll}
+   \production{number type} & \numtype &::=&
+     \I32 ~|~ \I64 \\
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \end{array}
+
+.. math::
+   \begin{array}{llll}
+   \production{unsigned integer} & \uN &::=& 0 ~|~ 1 \\
+   \end{array}
+";
+
+    const MODULES: &str = r"
+The :math:`\limits` of a memory, in ε units.
+
+.. math::
+   \begin{array}{llll}
+   \production{memory type} & \memtype &::=& \limits~\pagesize \\
+   \end{array}
+
+.. math::
+   \begin{array}{llll}
+   \production{table type} & \tabletype &::=& \limits~\{ \\
+   \end{array}
+";
+
+    #[test]
+    fn queries() {
+        let root = TempDir::new("lsp");
+        let core = root.join("document").join("core");
+        fs::create_dir_all(core.join("util")).unwrap();
+        fs::write(core.join("types.rst"), TYPES).unwrap();
+        fs::write(core.join("modules.rst"), MODULES).unwrap();
+        fs::write(
+            core.join("util/macros.def"),
+            r".. |I32| mathdef:: \xref{syntax/types}{syntax-valtype}{\K{i32}}",
+        )
+        .unwrap();
+        let mut workspace = Workspace::load(&root).unwrap();
+        let types = core.join("types.rst");
+        let modules = core.join("modules.rst");
+        let at = |path: &Path, line, character| Location {
+            path: path.to_path_buf(),
+            range: lsp_types::Range::new(
+                Position::new(line, character),
+                Position::new(line, character + 7),
+            ),
+        };
+
+        // `\limits` in `:math:`, after a non-ASCII character on its line
+        let limits = workspace.definition(&modules, Position::new(1, 15));
+        assert_eq!(limits, vec![at(&types, 5, 25)]);
+        let u32 = workspace.definition(&types, Position::new(6, 16));
+        assert_eq!(u32[0].path, types);
+        assert_eq!(u32[0].range.start, Position::new(11, 35));
+
+        let uses = workspace.references(&types, Position::new(5, 26), false);
+        assert_eq!(
+            uses,
+            vec![
+                at(&modules, 1, 11),
+                at(&modules, 5, 45),
+                at(&modules, 10, 46)
+            ]
+        );
+        assert_eq!(
+            workspace
+                .references(&types, Position::new(5, 26), true)
+                .len(),
+            4
+        );
+
+        let hover = workspace.hover(&types, Position::new(4, 7)).unwrap();
+        assert!(hover.contains(r"expands to `\xref{syntax/types}{syntax-valtype}{\K{i32}}`"));
+        assert!(hover.ends_with("keyword `i32`"));
+        let hover = workspace.hover(&modules, Position::new(1, 12)).unwrap();
+        assert!(hover.starts_with("```latex\n\\production{limits} & \\limits &::="));
+        assert_eq!(workspace.hover(&types, Position::new(0, 0)), None);
+
+        let diagnostics = workspace.diagnostics(&modules);
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("in production `table type`"));
+        assert_eq!(diagnostics[0].range.start, Position::new(10, 54));
+        assert_eq!(messages[1], r"undefined nonterminal `\pagesize`");
+        assert_eq!(diagnostics[1].range.start, Position::new(5, 53));
+
+        let fixed = MODULES
+            .replace(r"\limits~\{", r"\limits")
+            .replace(r"\pagesize", "");
+        workspace.update(&modules, fixed);
+        assert!(workspace.diagnostics(&modules).is_empty());
+        // `\u32` instantiates `\uN`, which the parser does not read as a
+        // nonterminal yet
+        let types = workspace.diagnostics(&types);
+        assert!(types.iter().all(|d| !d.message.starts_with("undefined")));
+    }
+
+    /// Definitions that move between files as they are edited
+    #[test]
+    fn edits() {
+        let root = TempDir::new("lsp-edits");
+        let core = root.join("document").join("core");
+        fs::create_dir_all(&core).unwrap();
+        fs::write(core.join("types.rst"), TYPES).unwrap();
+        fs::write(core.join("modules.rst"), MODULES).unwrap();
+        let mut workspace = Workspace::load(&root).unwrap();
+        let types = core.join("types.rst");
+        let modules = core.join("modules.rst");
+        let limits = Position::new(1, 15);
+        let undefined = |workspace: &Workspace| -> Vec<String> {
+            workspace
+                .diagnostics(&modules)
+                .into_iter()
+                .map(|d| d.message)
+                .filter(|m| m.starts_with("undefined"))
+                .collect()
+        };
+        assert_eq!(workspace.definition(&modules, limits).len(), 1);
+
+        let (head, tail) = TYPES.split_at(TYPES.find(r"   \production{limits}").unwrap());
+        let (limits_rows, tail) = tail.split_at(tail.find(r"   \end{array}").unwrap());
+        workspace.update(&types, format!("{}{}", head, tail));
+        assert!(workspace.definition(&modules, limits).is_empty());
+        assert_eq!(workspace.hover(&modules, limits), None);
+        assert_eq!(
+            undefined(&workspace),
+            vec![
+                r"undefined nonterminal `\limits`",
+                r"undefined nonterminal `\pagesize`"
+            ]
+        );
+
+        let moved = MODULES.replacen(
+            r"   \production{memory type}",
+            &format!("{}   \\production{{memory type}}", limits_rows),
+            1,
+        );
+        workspace.update(&modules, moved);
+        let definition = workspace.definition(&modules, limits);
+        assert_eq!(definition.len(), 1);
+        assert_eq!(definition[0].path, modules);
+        assert_eq!(definition[0].range.start, Position::new(5, 25));
+        assert!(workspace.hover(&modules, limits).is_some());
+        assert_eq!(
+            undefined(&workspace),
+            vec![r"undefined nonterminal `\pagesize`"]
+        );
+
+        workspace.update(&types, TYPES.to_string());
+        let paths: Vec<_> = workspace
+            .definition(&modules, limits)
+            .into_iter()
+            .map(|location| location.path)
+            .collect();
+        assert_eq!(paths, vec![modules.clone(), types.clone()]);
+        assert_eq!(workspace.definitions.values().flatten().count(), 6);
+    }
+
+    /// A root relative to the working directory names the files as the
+    /// client does
+    #[test]
+    fn relative_root() {
+        let root = TempDir::new("lsp-relative");
+        let core = root.join("document").join("core");
+        fs::create_dir_all(&core).unwrap();
+        fs::write(core.join("types.rst"), TYPES).unwrap();
+        let up: PathBuf = env::current_dir()
+            .unwrap()
+            .components()
+            .skip(1)
+            .map(|_| "..")
+            .collect();
+        let relative = up.join(root.strip_prefix("/").unwrap());
+        assert!(relative.is_relative());
+
+        let mut workspace = Workspace::load(&relative).unwrap();
+        let types = fs::canonicalize(core.join("types.rst")).unwrap();
+        workspace.update(&types, TYPES.to_string());
+        assert_eq!(workspace.files.len(), 1);
+        let u32 = workspace.definition(&types, Position::new(6, 16));
+        assert_eq!(u32.len(), 1);
+        assert_eq!(u32[0].path, types);
+        let relative_types = relative.join("document/core/types.rst");
+        assert_eq!(
+            workspace.definition(&relative_types, Position::new(6, 16)),
+            u32
+        );
+    }
+
+    #[test]
+    fn unknown_files() {
+        let root = TempDir::new("lsp-unknown");
+        assert!(Workspace::load(root.join("missing")).is_err());
+
+        let mut workspace = Workspace::new(&root);
+        let path = root.join("types.rst");
+        let position = Position::new(0, 0);
+        assert!(workspace.definition(&path, position).is_empty());
+        assert!(workspace.references(&path, position, true).is_empty());
+        assert_eq!(workspace.hover(&path, position), None);
+        assert!(workspace.diagnostics(&path).is_empty());
+
+        // a file that is only opened, e.g. before it is saved
+        workspace.update(&path, MODULES.replace(r"\pagesize", ""));
+        let messages: Vec<_> = workspace
+            .diagnostics(&path)
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("in production `table type`"));
+        assert_eq!(messages[1], r"undefined nonterminal `\limits`");
+    }
+}
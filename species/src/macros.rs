@@ -0,0 +1,85 @@
+//! Macros of a specification, `document/core/util/macros.def`, e.g.
+//! `.. |I32| mathdef:: \xref{syntax/types}{syntax-valtype}{\K{i32}}`.
+
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::tex::{self, Token};
+
+#[derive(Debug, Default)]
+pub struct Macros {
+    defs: HashMap<String, String>,
+}
+
+impl Macros {
+    /// Read the macros of the `document/core` directory `root`, or none if
+    /// it has no `util/macros.def`
+    pub fn load(root: &Path) -> io::Result<Self> {
+        match fs::read_to_string(root.join("util").join("macros.def")) {
+            Ok(content) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let defs = content
+            .lines()
+            .filter_map(|line| {
+                let (name, body) = line.strip_prefix(".. |")?.split_once("| mathdef::")?;
+                Some((name.to_string(), body.trim().to_string()))
+            })
+            .collect();
+        Self { defs }
+    }
+
+    /// Definition of a macro, e.g. `\xref{..}{..}{\K{i32}}` for `I32`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.defs.get(name).map(String::as_str)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.defs.keys().map(String::as_str)
+    }
+
+    /// Keyword a terminal stands for, `i32` for `I32`, read from the
+    /// `\K{...}` of its definition with escapes like `\_` undone
+    pub fn keyword(&self, name: &str) -> Option<String> {
+        let def = self.get(name)?;
+        let mut rest = def;
+        while let Some((token, tail)) = tex::next(rest) {
+            rest = tail;
+            if token == Token::Word("K") {
+                let (keyword, _) = tex::group(rest.trim_start())?;
+                return Some(keyword.replace('\\', ""));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords() {
+        let macros = Macros::parse(
+            r".. |I32| mathdef:: \xref{syntax/types}{syntax-valtype}{\K{i32}}
+.. |numtype| mathdef:: \xref{syntax/types}{syntax-numtype}{\X{numtype}}
+.. |DATADROP| mathdef:: \xref{syntax/instructions}{syntax-instr-memory}{\K{data.drop}}
+.. |ISHRU| mathdef:: \xref{exec/numerics}{op-ishr_u}{\F{ishr\_u}}
+.. |MEMORYGROW| mathdef:: \K{memory.grow}
+.. comment",
+        );
+        assert_eq!(macros.names().count(), 5);
+        assert_eq!(macros.keyword("I32").as_deref(), Some("i32"));
+        assert_eq!(macros.keyword("DATADROP").as_deref(), Some("data.drop"));
+        assert_eq!(macros.keyword("MEMORYGROW").as_deref(), Some("memory.grow"));
+        assert_eq!(macros.keyword("numtype"), None);
+        assert_eq!(macros.keyword("missing"), None);
+        assert_eq!(
+            macros.get("numtype"),
+            Some(r"\xref{syntax/types}{syntax-numtype}{\X{numtype}}")
+        );
+    }
+}
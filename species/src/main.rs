@@ -0,0 +1,384 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use species::{
+    codegen,
+    coverage::Coverage,
+    crosscheck::{ocaml::TypeDef, spectec::SpecTecCheck, CrossCheck},
+    diff::GrammarDiff,
+    formatter::Formatter,
+    generator::{Config, Generator},
+    history::History,
+    lint::{self, Linter, Severity},
+    Grammar, Spec, SpecLoader,
+};
+
+const USAGE: &str = "usage:
+    species diff <old-spec> <new-spec>
+    species diff --git <repo> <old-rev> <new-rev>
+    species history <repo> <nonterminal> [<rev>]
+    species generate <spec> <nonterminal> [--seed <n>] [--depth <n>] [--count <n>]
+                     [--weights <file.toml>]
+    species codegen <spec> [--out <file>] [--arbitrary]
+    species crosscheck <spec> [<file.ml> ...]
+    species crosscheck --spectec <spec> [<spectec directory>]
+    species coverage <spec> [<file.wast or directory> ...]
+    species fmt [--check] [--width <n>] <spec or file.rst> ...
+    species lint <spec> [--config <file.toml>]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("diff") => diff(&args[1..]),
+        Some("history") => history(&args[1..]),
+        Some("generate") => generate(&args[1..]),
+        Some("codegen") => codegen(&args[1..]),
+        Some("crosscheck") => crosscheck(&args[1..]),
+        Some("coverage") => coverage(&args[1..]),
+        Some("fmt") => format(&args[1..]),
+        Some("lint") => lint(&args[1..]),
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn diff(args: &[String]) -> Result<(), String> {
+    let (old, new) = match args {
+        [git, repo, old, new] if git == "--git" => (
+            Grammar::load_git(old, repo, old),
+            Grammar::load_git(new, repo, new),
+        ),
+        [old, new] => (load(old), load(new)),
+        _ => return Err(USAGE.to_string()),
+    };
+    let old = old.map_err(|e| e.to_string())?;
+    let new = new.map_err(|e| e.to_string())?;
+    report_errors(&old);
+    report_errors(&new);
+
+    print!("{}", GrammarDiff::new(&old, &new));
+    Ok(())
+}
+
+fn history(args: &[String]) -> Result<(), String> {
+    let (repo, nonterminal, rev) = match args {
+        [repo, nonterminal] => (repo, nonterminal, "HEAD"),
+        [repo, nonterminal, rev] => (repo, nonterminal, rev.as_str()),
+        _ => return Err(USAGE.to_string()),
+    };
+    let history = History::load(repo, rev, nonterminal).map_err(|e| e.to_string())?;
+    if history.entries.is_empty() {
+        return Err(format!(
+            r"no production of \{} up to {}",
+            history.nonterminal, rev
+        ));
+    }
+    print!("{}", history);
+    Ok(())
+}
+
+fn generate(args: &[String]) -> Result<(), String> {
+    let (root, nonterminal, options) = match args {
+        [root, nonterminal, options @ ..] => (root, nonterminal, options),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let mut config = Config::default();
+    let mut count = 1;
+    for option in options.chunks(2) {
+        let [name, value] = option else {
+            return Err(USAGE.to_string());
+        };
+        if name == "--weights" {
+            config = config.load_weights(value)?;
+            continue;
+        }
+        let value = value
+            .parse()
+            .map_err(|_| format!("invalid number `{}`", value))?;
+        match name.as_str() {
+            "--seed" => config = config.seed(value),
+            "--depth" => config = config.max_depth(value as usize),
+            "--count" => count = value,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let spec = load(root).map(Spec::new).map_err(|e| e.to_string())?;
+    report_errors(spec.grammar());
+    if let Some(unknown) = config
+        .weighted()
+        .find(|&name| spec.production(name).is_none())
+    {
+        return Err(format!("weights of `{}`, which has no production", unknown));
+    }
+    let mut generator = Generator::new(&spec, config);
+    if generator.infinite().contains(&nonterminal.as_str()) {
+        return Err(format!("`{}` has no finite instance", nonterminal));
+    }
+    for _ in 0..count {
+        let node = generator
+            .generate(nonterminal)
+            .ok_or_else(|| format!("no production for `{}`", nonterminal))?;
+        println!("{}", node);
+    }
+    Ok(())
+}
+
+fn codegen(args: &[String]) -> Result<(), String> {
+    let (root, options) = match args {
+        [root, options @ ..] => (root, options),
+        _ => return Err(USAGE.to_string()),
+    };
+    let mut config = codegen::Config::default();
+    let mut out = None;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--out" => out = Some(options.next().ok_or_else(|| USAGE.to_string())?),
+            "--arbitrary" => config = config.arbitrary(true),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let spec = load(root).map(Spec::new).map_err(|e| e.to_string())?;
+    report_errors(spec.grammar());
+    let generated = codegen::module_with(&spec, &config);
+    for skipped in &generated.skipped {
+        eprintln!("warning: skipped {}", skipped);
+    }
+    match out {
+        Some(out) => fs::write(out, generated.source).map_err(|e| format!("{}: {}", out, e)),
+        None => {
+            print!("{}", generated.source);
+            Ok(())
+        }
+    }
+}
+
+fn crosscheck(args: &[String]) -> Result<(), String> {
+    if let [option, args @ ..] = args {
+        if option == "--spectec" {
+            return crosscheck_spectec(args);
+        }
+    }
+    let (root, files) = match args {
+        [root, files @ ..] => (root, files),
+        _ => return Err(USAGE.to_string()),
+    };
+    let files: Vec<PathBuf> = if files.is_empty() {
+        let syntax = in_repository(Path::new(root), "interpreter/syntax");
+        vec![syntax.join("types.ml"), syntax.join("ast.ml")]
+    } else {
+        files.iter().map(PathBuf::from).collect()
+    };
+
+    let spec = load(root).map(Spec::new).map_err(|e| e.to_string())?;
+    report_errors(spec.grammar());
+    let mut types = vec![];
+    for file in &files {
+        let mut defs = TypeDef::load(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+        types.append(&mut defs);
+    }
+    print!("{}", CrossCheck::new(&spec, &types));
+    Ok(())
+}
+
+/// Compare the `rst` sources with the SpecTec sources, by default those of
+/// the latest version in the repository
+fn crosscheck_spectec(args: &[String]) -> Result<(), String> {
+    let (root, dir) = match args {
+        [root] => {
+            let root = Path::new(root);
+            let dir = ["specification/wasm-3.0", "spectec/spec/wasm-3.0"]
+                .iter()
+                .map(|dir| in_repository(root, dir))
+                .find(|dir| dir.is_dir())
+                .ok_or_else(|| format!("{}: no SpecTec sources", root.display()))?;
+            (root, dir)
+        }
+        [root, dir] => (Path::new(root), PathBuf::from(dir)),
+        _ => return Err(USAGE.to_string()),
+    };
+    let rst = load(&root.to_string_lossy()).map_err(|e| e.to_string())?;
+    let spectec = load(&dir.to_string_lossy()).map_err(|e| e.to_string())?;
+    report_errors(&rst);
+    report_errors(&spectec);
+    print!("{}", SpecTecCheck::new(&rst, &spectec));
+    Ok(())
+}
+
+fn coverage(args: &[String]) -> Result<(), String> {
+    let (root, paths) = match args {
+        [root, paths @ ..] => (root, paths),
+        _ => return Err(USAGE.to_string()),
+    };
+    let paths: Vec<PathBuf> = if paths.is_empty() {
+        vec![in_repository(Path::new(root), "test/core")]
+    } else {
+        paths.iter().map(PathBuf::from).collect()
+    };
+    let mut scripts = vec![];
+    for path in &paths {
+        files_with(path, "wast", &mut scripts).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+
+    let spec = load(root).map(Spec::new).map_err(|e| e.to_string())?;
+    report_errors(spec.grammar());
+    let mut coverage = Coverage::new(&spec);
+    for script in scripts {
+        let source =
+            fs::read_to_string(&script).map_err(|e| format!("{}: {}", script.display(), e))?;
+        let name = script.display().to_string();
+        if let Err(error) = coverage.add_script(&name, &source) {
+            eprintln!("warning: {}:{}: no token", name, error.0);
+        }
+    }
+    print!("{}", coverage);
+    Ok(())
+}
+
+fn format(args: &[String]) -> Result<(), String> {
+    let mut formatter = Formatter::new();
+    let mut check = false;
+    let mut paths = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--width" => {
+                let value = args.next().ok_or_else(|| USAGE.to_string())?;
+                let width = value
+                    .parse()
+                    .map_err(|_| format!("invalid number `{}`", value))?;
+                formatter = formatter.width(width);
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    if paths.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    let mut files = vec![];
+    for path in &paths {
+        // the `rst` files of a checkout are those of the core specification
+        let core = path.join("document").join("core");
+        let path = if core.is_dir() { &core } else { path };
+        files_with(path, "rst", &mut files).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    let mut unformatted = 0;
+    for file in &files {
+        let source = fs::read_to_string(file).map_err(|e| format!("{}: {}", file.display(), e))?;
+        let formatted = formatter.format(&source);
+        if formatted.changed.is_empty() {
+            continue;
+        }
+        if check {
+            for line in &formatted.changed {
+                println!("{}:{}: not formatted", file.display(), line);
+            }
+            unformatted += 1;
+        } else {
+            fs::write(file, formatted.text).map_err(|e| format!("{}: {}", file.display(), e))?;
+            println!("formatted {}", file.display());
+        }
+    }
+    match unformatted {
+        0 => Ok(()),
+        n => Err(format!("{} of {} files not formatted", n, files.len())),
+    }
+}
+
+fn lint(args: &[String]) -> Result<(), String> {
+    let (root, config) = match args {
+        [root] => (root, None),
+        [root, option, config] if option == "--config" => (root, Some(Path::new(config))),
+        _ => return Err(USAGE.to_string()),
+    };
+    // `species-lint.toml` of the working directory, if there is one
+    let default = Path::new("species-lint.toml");
+    let config = match config {
+        Some(config) => lint::Config::load(config)?,
+        None if default.exists() => lint::Config::load(default)?,
+        None => lint::Config::default(),
+    };
+
+    let lints = Linter::new(config).lint(root).map_err(|e| e.to_string())?;
+    for lint in &lints {
+        println!("{}", lint);
+    }
+    let errors = lints
+        .iter()
+        .filter(|l| l.severity == Severity::Error)
+        .count();
+    match errors {
+        0 => Ok(()),
+        n => Err(format!("{} of {} lints are errors", n, lints.len())),
+    }
+}
+
+/// The grammar of a checkout, with the parse of each file cached in
+/// `$SPECIES_CACHE` if it is set
+fn load(root: &str) -> std::io::Result<Grammar> {
+    let mut loader = SpecLoader::new(root);
+    if let Some(dir) = env::var_os("SPECIES_CACHE") {
+        loader = loader.cache(dir);
+    }
+    let loaded = loader.load(root)?;
+    for error in &loaded.cache_errors {
+        eprintln!("warning: {}", error);
+    }
+    Ok(loaded.grammar)
+}
+
+/// Files with an extension, e.g. `.wast`, of a directory and its
+/// subdirectories, in order, or the path itself if it is a file
+fn files_with(path: &Path, extension: &str, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries: Vec<_> = fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|e| e == extension) {
+            files_with(&entry, extension, files)?;
+        }
+    }
+    Ok(())
+}
+
+/// Path in the repository that contains the specification, where `root` is
+/// either the repository root or its `document/core` directory
+fn in_repository(root: &Path, path: &str) -> PathBuf {
+    if root.join(path).exists() {
+        root.join(path)
+    } else {
+        root.join("../..").join(path)
+    }
+}
+
+fn report_errors(grammar: &Grammar) {
+    for (origin, error) in grammar.errors() {
+        eprintln!(
+            "warning: {}: {}:{}: {}",
+            origin.spec,
+            origin.file.display(),
+            origin.line,
+            error
+        );
+    }
+}
@@ -0,0 +1,557 @@
+//! Evaluator of the integer definitions of the numerics chapter.
+//!
+//! A call like `\iadd_N(i_1, i_2)` picks the first case of the definition
+//! whose parameters match the arguments and whose guard holds, then evaluates
+//! its body. Values are exact fractions, so that `\truncz(i_1 / i_2)` and
+//! guards comparing quotients are evaluated as written. The operands of a
+//! sum or product under `\mod` are reduced first, so that
+//! `(i_1 \cdot i_2) \mod 2^N` is evaluated at width 64. Definitions on bit
+//! sequences, e.g. `\ishl_N`, are not evaluated.
+
+use std::{cmp::Ordering, collections::HashMap, fmt};
+
+use crate::{
+    spec::Spec,
+    syntax::function::{Arith, Case, Cond, FunctionDef, Guard, Op, Relation},
+};
+
+/// Evaluator of the numeric definitions of a specification
+pub struct Numerics<'s> {
+    functions: HashMap<(&'s str, bool), &'s FunctionDef>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum EvalError {
+    /// No definition of the function, e.g. `signed^{-1}`
+    Unknown(String),
+    /// No case of the definition matches the arguments
+    NoCase(String),
+    /// A variable is neither a parameter nor the width
+    Undetermined(String),
+    /// An expression or condition that is kept as written
+    Unsupported(String),
+    /// A result or an operand of `\mod` or `^` that is not an integer
+    NotAnInteger(String),
+    Overflow,
+}
+
+/// Fraction in lowest terms with a positive denominator
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Ratio {
+    num: i128,
+    den: i128,
+}
+
+type Env = HashMap<String, Ratio>;
+
+impl<'s> Numerics<'s> {
+    pub fn new(spec: &'s Spec) -> Self {
+        let functions = spec
+            .grammar()
+            .functions()
+            .map(|(_, f)| ((f.name(), f.inverse()), f))
+            .collect();
+        Self { functions }
+    }
+
+    pub fn get(&self, name: &str, inverse: bool) -> Option<&'s FunctionDef> {
+        self.functions.get(&(name, inverse)).copied()
+    }
+
+    /// Apply a definition at a width, e.g. `iadd` at 32, `None` where the
+    /// definition gives no result
+    pub fn call(&self, name: &str, width: u32, args: &[i128]) -> Result<Option<i128>, EvalError> {
+        let args = args.iter().map(|&n| Ratio::int(n)).collect();
+        match self.apply(name, false, Some(Ratio::int(width.into())), args)? {
+            Some(result) => result.to_int(name).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn apply(
+        &self,
+        name: &str,
+        inverse: bool,
+        width: Option<Ratio>,
+        args: Vec<Ratio>,
+    ) -> Result<Option<Ratio>, EvalError> {
+        let function = self.get(name, inverse).ok_or_else(|| {
+            EvalError::Unknown(match inverse {
+                true => format!("{}^{{-1}}", name),
+                false => name.to_string(),
+            })
+        })?;
+        for case in function.cases() {
+            let mut env = Env::new();
+            if let (Some(param), Some(width)) = (function.width(), width) {
+                env.insert(param.to_string(), width);
+            }
+            if !self.bind(case, &args, &mut env)? {
+                continue;
+            }
+            let holds = match case.guard() {
+                Guard::Always | Guard::Otherwise => true,
+                Guard::When(cond) => self.holds(cond, &env)?,
+            };
+            if holds {
+                return self.eval(case.body(), &env);
+            }
+        }
+        Err(EvalError::NoCase(function.name().to_string()))
+    }
+
+    /// Bind the parameters of a case, e.g. `(i_1, 0)`, to the arguments
+    fn bind(&self, case: &Case, args: &[Ratio], env: &mut Env) -> Result<bool, EvalError> {
+        if case.params().len() != args.len() {
+            return Ok(false);
+        }
+        for (param, &arg) in case.params().iter().zip(args) {
+            match param {
+                Arith::Var(name) => match env.get(name) {
+                    Some(&bound) if bound != arg => return Ok(false),
+                    Some(_) => {}
+                    None => {
+                        env.insert(name.clone(), arg);
+                    }
+                },
+                param => {
+                    if self.eval(param, env)? != Some(arg) {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    fn holds(&self, cond: &Cond, env: &Env) -> Result<bool, EvalError> {
+        match cond {
+            Cond::Compare(left, relation, right) => {
+                let (left, right) = (self.eval(left, env)?, self.eval(right, env)?);
+                let ordering = match (left, right) {
+                    (Some(left), Some(right)) => left.cmp(&right),
+                    (None, None) => Ordering::Equal,
+                    _ => return Ok(*relation == Relation::Neq),
+                };
+                Ok(match relation {
+                    Relation::Eq => ordering.is_eq(),
+                    Relation::Neq => ordering.is_ne(),
+                    Relation::Lt => ordering.is_lt(),
+                    Relation::Gt => ordering.is_gt(),
+                    Relation::Le => ordering.is_le(),
+                    Relation::Ge => ordering.is_ge(),
+                })
+            }
+            Cond::And(conds) => {
+                for cond in conds {
+                    if !self.holds(cond, env)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Cond::Or(conds) => {
+                for cond in conds {
+                    if self.holds(cond, env)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Cond::Other(cond) => Err(EvalError::Unsupported(cond.clone())),
+        }
+    }
+
+    /// Value of an expression, `None` for `\{\}`
+    fn eval(&self, arith: &Arith, env: &Env) -> Result<Option<Ratio>, EvalError> {
+        let value = match arith {
+            Arith::Num(n) => Ratio::int((*n).into()),
+            Arith::Var(name) => *env
+                .get(name)
+                .ok_or_else(|| EvalError::Undetermined(name.clone()))?,
+            Arith::Neg(arith) => match self.eval(arith, env)? {
+                Some(value) => value.neg()?,
+                None => return Ok(None),
+            },
+            Arith::Binary(left, Op::Mod, right) => {
+                let Some(right) = self.eval(right, env)? else {
+                    return Ok(None);
+                };
+                // the remainder is the same modulo `-m`
+                let m = right
+                    .to_int(arith)?
+                    .checked_abs()
+                    .ok_or(EvalError::Overflow)?;
+                if m == 0 {
+                    return Ok(None);
+                }
+                // the parts of a sum may be fractions, e.g. `(i / 2 + i / 2) \mod m`
+                let value = match self.eval_mod(left, env, m) {
+                    Err(EvalError::NotAnInteger(_)) => match self.eval(left, env)? {
+                        Some(left) => Some(left.to_int(arith)?.rem_euclid(m)),
+                        None => None,
+                    },
+                    result => result?,
+                };
+                match value {
+                    Some(value) => Ratio::int(value),
+                    None => return Ok(None),
+                }
+            }
+            Arith::Binary(left, op, right) => {
+                let (Some(left), Some(right)) = (self.eval(left, env)?, self.eval(right, env)?)
+                else {
+                    return Ok(None);
+                };
+                match op {
+                    Op::Add => left.add(right)?,
+                    Op::Sub => left.add(right.neg()?)?,
+                    Op::Mul => left.mul(right)?,
+                    Op::Div => match right.recip() {
+                        Some(right) => left.mul(right)?,
+                        None => return Ok(None),
+                    },
+                    Op::Mod => unreachable!("`\\mod` is evaluated above"),
+                    Op::Pow => {
+                        let exponent = u32::try_from(right.to_int(arith)?)
+                            .map_err(|_| EvalError::NotAnInteger(arith.to_string()))?;
+                        left.pow(exponent)?
+                    }
+                }
+            }
+            Arith::Call {
+                name,
+                width,
+                inverse,
+                args,
+            } => {
+                let mut values = vec![];
+                for arg in args {
+                    match self.eval(arg, env)? {
+                        Some(value) => values.push(value),
+                        None => return Ok(None),
+                    }
+                }
+                match (name.as_str(), values.as_slice()) {
+                    ("truncz", [value]) => Ratio::int(value.num / value.den),
+                    _ => {
+                        let width = match width {
+                            Some(width) => self.eval(width, env)?,
+                            None => None,
+                        };
+                        return self.apply(name, *inverse, width, values);
+                    }
+                }
+            }
+            Arith::Bool(cond) => Ratio::int(self.holds(cond, env)?.into()),
+            Arith::Empty => return Ok(None),
+            Arith::Other(source) => return Err(EvalError::Unsupported(source.clone())),
+        };
+        Ok(Some(value))
+    }
+
+    /// Value of `arith \mod m` for a positive `m`. The operands of sums,
+    /// differences and products are reduced first, so that e.g.
+    /// `(i_1 \cdot i_2) \mod 2^N` does not overflow at width 64.
+    fn eval_mod(&self, arith: &Arith, env: &Env, m: i128) -> Result<Option<i128>, EvalError> {
+        let Arith::Binary(left, op @ (Op::Add | Op::Sub | Op::Mul), right) = arith else {
+            return match self.eval(arith, env)? {
+                Some(value) => Ok(Some(value.to_int(arith)?.rem_euclid(m))),
+                None => Ok(None),
+            };
+        };
+        let (Some(a), Some(b)) = (self.eval_mod(left, env, m)?, self.eval_mod(right, env, m)?)
+        else {
+            return Ok(None);
+        };
+        // residues are below `m`, so a sum fits and a product of residues
+        // modulo `2^64` fits unsigned
+        let (a, b, m) = (a as u128, b as u128, m as u128);
+        let value = match op {
+            Op::Add => (a + b) % m,
+            Op::Sub => (a + m - b) % m,
+            _ => a.checked_mul(b).ok_or(EvalError::Overflow)? % m,
+        };
+        Ok(Some(value as i128))
+    }
+}
+
+impl Ratio {
+    fn int(n: i128) -> Self {
+        Self { num: n, den: 1 }
+    }
+
+    fn new(num: i128, den: i128) -> Result<Self, EvalError> {
+        let divisor = gcd(num, den);
+        let sign = den.signum();
+        let num = (num / divisor)
+            .checked_mul(sign)
+            .ok_or(EvalError::Overflow)?;
+        Ok(Self {
+            num,
+            den: den / divisor * sign,
+        })
+    }
+
+    fn to_int(self, arith: impl fmt::Display) -> Result<i128, EvalError> {
+        match self.den {
+            1 => Ok(self.num),
+            _ => Err(EvalError::NotAnInteger(arith.to_string())),
+        }
+    }
+
+    fn add(self, other: Self) -> Result<Self, EvalError> {
+        let num = (self.num.checked_mul(other.den))
+            .zip(other.num.checked_mul(self.den))
+            .and_then(|(a, b)| a.checked_add(b));
+        let den = self.den.checked_mul(other.den);
+        match (num, den) {
+            (Some(num), Some(den)) => Self::new(num, den),
+            _ => Err(EvalError::Overflow),
+        }
+    }
+
+    fn mul(self, other: Self) -> Result<Self, EvalError> {
+        match (
+            self.num.checked_mul(other.num),
+            self.den.checked_mul(other.den),
+        ) {
+            (Some(num), Some(den)) => Self::new(num, den),
+            _ => Err(EvalError::Overflow),
+        }
+    }
+
+    fn neg(self) -> Result<Self, EvalError> {
+        let num = self.num.checked_neg().ok_or(EvalError::Overflow)?;
+        Ok(Self { num, ..self })
+    }
+
+    fn recip(self) -> Option<Self> {
+        match self.num {
+            0 => None,
+            _ => Self::new(self.den, self.num).ok(),
+        }
+    }
+
+    fn pow(self, exponent: u32) -> Result<Self, EvalError> {
+        match (
+            self.num.checked_pow(exponent),
+            self.den.checked_pow(exponent),
+        ) {
+            (Some(num), Some(den)) => Ok(Self { num, den }),
+            _ => Err(EvalError::Overflow),
+        }
+    }
+}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // denominators are positive, so cross multiplication keeps the order
+        let left = self.num.saturating_mul(other.den);
+        let right = other.num.saturating_mul(self.den);
+        left.cmp(&right)
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1) as i128
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::Unknown(name) => write!(f, "no definition of `{}`", name),
+            EvalError::NoCase(name) => write!(f, "no case of `{}` applies", name),
+            EvalError::Undetermined(var) => write!(f, "`{}` is not determined", var),
+            EvalError::Unsupported(source) => write!(f, "`{}` is not supported", source),
+            EvalError::NotAnInteger(source) => write!(f, "`{}` is not an integer", source),
+            EvalError::Overflow => write!(f, "overflow"),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{interpreter::numeric, Grammar};
+
+    use super::*;
+
+    fn spec() -> Spec {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "exec/numerics.rst",
+            r"
+.. math::
+   \begin{array}{lll@{\qquad}l}
+   \signed_N(i) &=& i & (0 \leq i < 2^{N-1}) \\
+   \signed_N(i) &=& i - 2^N & (2^{N-1} \leq i < 2^N) \\
+   \end{array}
+
+.. math::
+   \begin{array}{lll@{\qquad}l}
+   \signed^{-1}_N(i) &=& i & (0 \leq i < 2^{N-1}) \\
+   \signed^{-1}_N(i) &=& 2^N + i & (-2^{N-1} \leq i < 0) \\
+   \end{array}
+
+.. math::
+   \begin{array}{@{}lcll}
+   \iadd_N(i_1, i_2) &=& (i_1 + i_2) \mod 2^N
+   \end{array}
+
+.. math::
+   \begin{array}{@{}lcll}
+   \isub_N(i_1, i_2) &=& (i_1 - i_2 + 2^N) \mod 2^N
+   \end{array}
+
+.. math::
+   \begin{array}{@{}lcll}
+   \imul_N(i_1, i_2) &=& (i_1 \cdot i_2) \mod 2^N
+   \end{array}
+
+.. math::
+   \begin{array}{@{}lcll}
+   \idivu_N(i_1, 0) &=& \{\} \\
+   \idivu_N(i_1, i_2) &=& \truncz(i_1 / i_2) \\
+   \end{array}
+
+.. math::
+   \begin{array}{@{}lcll}
+   \idivs_N(i_1, 0) &=& \{\} \\
+   \idivs_N(i_1, i_2) &=& \{\} & (\iff \signed_N(i_1) / \signed_N(i_2) = 2^{N-1}) \\
+   \idivs_N(i_1, i_2) &=& \signed_N^{-1}(\truncz(\signed_N(i_1) / \signed_N(i_2))) \\
+   \end{array}
+
+.. math::
+   \begin{array}{@{}lcll}
+   \iremu_N(i_1, 0) &=& \{\} \\
+   \iremu_N(i_1, i_2) &=& i_1 - i_2 \cdot \truncz(i_1 / i_2) \\
+   \end{array}
+
+.. math::
+   \begin{array}{@{}lcll}
+   \ieqz_N(i) &=& 1 & (\iff i = 0) \\
+   \ieqz_N(i) &=& 0 & (\otherwise) \\
+   \end{array}
+
+.. math::
+   \begin{array}{@{}lcll}
+   \ieq_N(i_1, i_2) &=& \bool(i_1 = i_2)
+   \end{array}
+
+.. math::
+   \begin{array}{@{}lcll}
+   \iltu_N(i_1, i_2) &=& \bool(i_1 < i_2)
+   \end{array}
+
+.. math::
+   \begin{array}{@{}lcll}
+   \ishl_N(i_1, i_2) &=& \bits_N^{-1}(d_2^{N-k}~0^k) & (\iff \bits_N(i_1) = d_1^k~d_2^{N-k} \wedge k = i_2 \mod N)
+   \end{array}
+",
+        );
+        assert!(grammar.errors().is_empty(), "{:?}", grammar.errors());
+        Spec::new(grammar)
+    }
+
+    #[test]
+    fn definitions() {
+        let spec = spec();
+        let numerics = Numerics::new(&spec);
+        let max = (1 << 32) - 1;
+        assert_eq!(numerics.call("iadd", 32, &[max, 2]), Ok(Some(1)));
+        assert_eq!(numerics.call("isub", 32, &[1, 2]), Ok(Some(max)));
+        assert_eq!(numerics.call("idivu", 32, &[7, 0]), Ok(None));
+        assert_eq!(numerics.call("signed", 32, &[max]), Ok(Some(-1)));
+        // -7 / 2 truncates toward zero
+        assert_eq!(numerics.call("idivs", 32, &[max - 6, 2]), Ok(Some(max - 2)));
+        // the quotient of -2^31 by -1 is not representable
+        assert_eq!(numerics.call("idivs", 32, &[1 << 31, max]), Ok(None));
+        assert_eq!(numerics.call("ieqz", 64, &[0]), Ok(Some(1)));
+        assert_eq!(numerics.call("ieqz", 64, &[3]), Ok(Some(0)));
+    }
+
+    #[test]
+    fn width_64() {
+        let spec = spec();
+        let numerics = Numerics::new(&spec);
+        let max = u64::MAX.into();
+        assert_eq!(numerics.call("imul", 64, &[max, max]), Ok(Some(1)));
+        assert_eq!(numerics.call("imul", 64, &[1 << 32, 1 << 32]), Ok(Some(0)));
+        assert_eq!(numerics.call("iadd", 64, &[max, 2]), Ok(Some(1)));
+        assert_eq!(numerics.call("isub", 64, &[1, 2]), Ok(Some(max)));
+        assert_eq!(numerics.call("signed", 64, &[max]), Ok(Some(-1)));
+        assert_eq!(numerics.call("idivs", 64, &[max - 6, 2]), Ok(Some(max - 2)));
+        assert_eq!(numerics.call("idivs", 64, &[1 << 63, max]), Ok(None));
+        assert_eq!(
+            numerics.call("iremu", 64, &[max, 1 << 63]),
+            Ok(Some((1 << 63) - 1))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let spec = spec();
+        let numerics = Numerics::new(&spec);
+        assert!(matches!(
+            numerics.call("ishl", 32, &[1, 1]),
+            Err(EvalError::Unsupported(_))
+        ));
+        assert_eq!(
+            numerics.call("irotl", 32, &[1, 1]),
+            Err(EvalError::Unknown("irotl".to_string()))
+        );
+        assert_eq!(
+            numerics.call("signed", 8, &[256]),
+            Err(EvalError::NoCase("signed".to_string()))
+        );
+    }
+
+    /// The builtin numerics of the interpreter agree with the definitions
+    #[test]
+    fn interpreter_numerics() {
+        let spec = spec();
+        let numerics = Numerics::new(&spec);
+        for width in [32, 64] {
+            let max = u64::MAX >> (64 - width);
+            let half = 1 << (width - 1);
+            let values = [0, 1, 2, 7, half - 1, half, max];
+            for op in ["add", "sub", "mul", "divu", "remu", "eq", "ltu"] {
+                for a in values {
+                    for b in values {
+                        let name = format!("i{}", op);
+                        let expected = numerics.call(&name, width, &[a.into(), b.into()]);
+                        let found = numeric(op, width, &[a, b]).unwrap();
+                        assert_eq!(
+                            Ok(found.map(i128::from)),
+                            expected,
+                            "{}{} {} {}",
+                            op,
+                            width,
+                            a,
+                            b
+                        );
+                    }
+                }
+            }
+            for a in values {
+                let expected = numerics.call("ieqz", width, &[a.into()]);
+                assert_eq!(
+                    Ok(numeric("eqz", width, &[a]).unwrap().map(i128::from)),
+                    expected
+                );
+            }
+        }
+    }
+}
@@ -1,35 +1,33 @@
-use nom::character::complete::{alphanumeric1, char, one_of};
+pub mod memo;
+
+use std::fmt;
+
+use nom::character::complete::{alphanumeric1, char};
 use nom::{
-    branch::alt,
     bytes::complete::tag,
-    character::complete::multispace1,
-    combinator::{map, opt, recognize},
+    combinator::opt,
     multi::{many0, separated_list1},
-    sequence::{delimited, preceded},
-    IResult, Parser,
+    sequence::delimited,
 };
+use serde::{Deserialize, Serialize};
 
-const EXTENDED_LETTERS: &str =
-    "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789./-# ";
+use crate::{
+    nom_err,
+    tex::{self, Token},
+    PResult,
+};
 
-pub fn equal(input: &str) -> IResult<&str, ()> {
+pub fn equal(input: &str) -> PResult<'_, ()> {
     let (tail, _s) = tag("::=")(input)?;
     let (tail, _) = ws(tail)?;
     Ok((tail, ()))
 }
 
-pub fn ws(input: &str) -> IResult<&str, ()> {
-    let tex_spaces = alt((
-        tag(r"\quad"),
-        tag(r"\qquad"),
-        tag(r"\\"),
-        tag(r"\ "),
-        tag(r"&"),
-        tag(r"~"),
-        multispace1,
-    ));
-
-    let (tail, _) = many0(tex_spaces)(input)?;
+/// Skips layout: spaces, comments, `~`, `\quad` and the like, and also the
+/// alignment tabs `&` and row ends `\\` of an array, which the grammar
+/// parsers do not tell apart from spaces
+pub fn ws(input: &str) -> PResult<'_, ()> {
+    let (tail, _) = many0(tex::token_if(Token::is_layout))(input)?;
     Ok((tail, ()))
 }
 
@@ -52,7 +50,7 @@ pub enum Argument<'a> {
     Cmd(Box<Command<'a>>),
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 pub enum SeqKind {
     OptSeq,
     ManyPossibleEmpty,
@@ -61,22 +59,52 @@ pub enum SeqKind {
 }
 
 impl SeqKind {
-    fn parser(input: &str) -> IResult<&str, Option<Self>> {
-        let mut parser = opt(alt((tag("^?"), tag("^n"), tag("^+"), tag(r"^\ast"))));
-        let (tail, t) = parser(input)?;
-        match t {
-            Some(s) if s == "^?" => Ok((tail, Some(SeqKind::OptSeq))),
-            Some(s) if s == "^n" => Ok((tail, Some(SeqKind::ManyN))),
-            Some(s) if s == "^+" => Ok((tail, Some(SeqKind::ManyNonEmpty))),
-            Some(s) if s == r"^\ast" => Ok((tail, Some(SeqKind::ManyPossibleEmpty))),
-            None => Ok((tail, None)),
-            _ => unreachable!(),
+    /// Superscript of a sequence, either bare as in `^\ast` or in a group as
+    /// in `^{\ast}`
+    pub fn parser(input: &str) -> PResult<'_, Option<Self>> {
+        let Ok((script, Token::Superscript)) = tex::token(input) else {
+            return Ok((input, None));
+        };
+        let (body, tail) = match tex::group(script) {
+            Some((body, tail)) => (body.trim(), tail),
+            None => match tex::next(script) {
+                Some((_, tail)) => (&script[..script.len() - tail.len()], tail),
+                None => return Ok((input, None)),
+            },
+        };
+        let kind = match body {
+            "?" => SeqKind::OptSeq,
+            "n" => SeqKind::ManyN,
+            "+" => SeqKind::ManyNonEmpty,
+            r"\ast" => SeqKind::ManyPossibleEmpty,
+            _ => return Ok((input, None)),
+        };
+        Ok((tail, Some(kind)))
+    }
+}
+
+impl fmt::Display for SeqKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SeqKind::OptSeq => write!(f, "^?"),
+            SeqKind::ManyPossibleEmpty => write!(f, r"^\ast"),
+            SeqKind::ManyN => write!(f, "^n"),
+            SeqKind::ManyNonEmpty => write!(f, "^+"),
         }
     }
 }
 
 impl<'a> Command<'a> {
-    pub fn parser(input: &'a str) -> IResult<&str, Self> {
+    /// A command with its arguments and iteration, and the layout after it,
+    /// from the [`memo`] if the input was read before
+    pub fn parser(input: &'a str) -> PResult<'a, Self> {
+        match memo::command(input, |input| Self::read(input).ok()) {
+            Some(parsed) => Ok(parsed),
+            None => nom_err!(input, Nom, nom::error::ErrorKind::Tag),
+        }
+    }
+
+    fn read(input: &'a str) -> PResult<'a, Self> {
         let (input, head) = CommandHead::parser(input)?;
         let (input, args) = many0(Argument::parser)(input)?;
         let (input, upnote) = SeqKind::parser(input)?;
@@ -87,9 +115,11 @@ impl<'a> Command<'a> {
 }
 
 impl<'a> CommandHead<'a> {
-    pub fn parser(input: &'a str) -> IResult<&str, Self> {
-        let mut name_parser = preceded(tag("\\"), alphanumeric1);
-        let (input, name) = name_parser(input)?;
+    pub fn parser(input: &'a str) -> PResult<'a, Self> {
+        let (input, name) = match tex::next(input) {
+            Some((Token::Word(name), tail)) => (tail, name),
+            _ => return nom_err!(input, Nom, nom::error::ErrorKind::Tag),
+        };
 
         let mut params_parser = opt(delimited(
             char('['),
@@ -99,35 +129,73 @@ impl<'a> CommandHead<'a> {
         let (input, params) = params_parser(input)?;
         let params = params.unwrap_or_default();
 
-        // let params = match params {
-        //     Some(ps) => ps.iter().map(|cs| cs.iter().collect()).collect(),
-        //     None => vec![],
-        // };
-
         Ok((input, Self { name, params }))
     }
 }
 
-impl<'a> Argument<'a> {
-    pub fn parser(input: &'a str) -> IResult<&str, Self> {
-        let arg_parser = alt((
-            map(Command::parser, |out| Self::Cmd(Box::new(out))),
-            Self::str_parser,
-        ));
-        delimited(char('{'), arg_parser, char('}'))(input)
+/// Closing counterpart of an already consumed opening brace
+pub fn closing(brace: &'static str) -> impl Fn(&str) -> PResult<'_, &str> {
+    move |input| match tag::<_, _, ()>(brace)(input) {
+        Ok(res) => Ok(res),
+        Err(_) => nom_err!(input, UnbalancedBrace),
     }
+}
 
-    fn str_parser_inner(input: &str) -> IResult<&str, &str> {
-        let mut parser = alt((
-            delimited(char('{'), Self::str_parser_inner, char('}')),
-            recognize(many0(one_of(EXTENDED_LETTERS))),
-        ));
+/// Balanced `{...}` group, returned without its braces
+pub fn group(input: &str) -> PResult<'_, &str> {
+    let (body, _) = char('{')(input)?;
+    match tex::group(input) {
+        Some((content, tail)) => Ok((tail, content)),
+        None => nom_err!(body, UnbalancedBrace),
+    }
+}
+
+/// Offset of the first token outside of braces, brackets and parentheses at
+/// which `at` holds. The escaped braces `\{` and `\}` of records nest as
+/// well.
+pub fn top_level(source: &str, at: impl Fn(&str) -> bool) -> Option<usize> {
+    let mut depth = 0i32;
+    for (i, token) in tex::tokens(source) {
+        if depth == 0 && at(&source[i..]) {
+            return Some(i);
+        }
+        match token {
+            Token::Char('(' | '[') | Token::BeginGroup | Token::Symbol('{') => depth += 1,
+            Token::Char(')' | ']') | Token::EndGroup | Token::Symbol('}') => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
 
-        parser.parse(input)
+/// Parts separated by `separator` outside of braces
+pub fn split(source: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut rest = source;
+    while let Some(i) = top_level(rest, |r| r.starts_with(separator)) {
+        parts.push(&rest[..i]);
+        rest = &rest[i + separator.len_utf8()..];
     }
+    parts.push(rest);
+    parts
+}
 
-    fn str_parser(input: &'a str) -> IResult<&str, Self> {
-        map(Self::str_parser_inner, Self::Str)(input)
+impl<'a> Argument<'a> {
+    /// A group that holds a single command, as in `{\K{i32}}`, or text as it
+    /// is written, as in `{div\_u}`. Braces that wrap a whole group, as in
+    /// `{{x}}`, are left out.
+    pub fn parser(input: &'a str) -> PResult<'a, Self> {
+        let (tail, mut content) = group(input)?;
+        while let Some((inner, "")) = tex::group(content) {
+            content = inner;
+        }
+        if let Ok(("", cmd)) = Command::parser(content) {
+            return Ok((tail, Self::Cmd(Box::new(cmd))));
+        }
+        if !tex::tokens(content).all(|(_, token)| tex::is_text(&token)) {
+            return nom_err!(input, Nom, nom::error::ErrorKind::Verify);
+        }
+        Ok((tail, Self::Str(content)))
     }
 
     pub fn name(&self) -> &'a str {
@@ -167,6 +235,15 @@ mod tests {
         assert_eq!(cmd.head.name, "production");
     }
 
+    #[test]
+    fn unbalanced_brace() {
+        let err = closing("}")("").expect_err("closing brace should be required");
+        assert_eq!(
+            err,
+            nom::Err::Error(crate::SpeciesError::UnbalancedBrace(""))
+        );
+    }
+
     #[test]
     fn upnote() {
         let (input, cmd) =
@@ -175,4 +252,29 @@ mod tests {
         assert_eq!(cmd.upnote, Some(SeqKind::ManyPossibleEmpty));
         assert_eq!(input, "");
     }
+
+    #[test]
+    fn arguments() {
+        let (input, cmd) = Command::parser(r"\K{div\_u}~x").expect("escapes in a text argument");
+        assert_eq!(input, "x");
+        assert_eq!(cmd.args, vec![Argument::Str(r"div\_u")]);
+
+        let (input, cmd) = Command::parser(r"\X{in}{:}").expect("command with one argument");
+        assert_eq!(input, "{:}");
+        assert_eq!(cmd.args, vec![Argument::Str("in")]);
+
+        let (_, cmd) = Command::parser(r"\F{{\K{i32}}}").expect("nested command argument");
+        assert!(matches!(&cmd.args[0], Argument::Cmd(c) if c.head.name == "K"));
+
+        let (input, cmd) = Command::parser(r"\instr^{\ast}").expect("upnote in a group");
+        assert_eq!(cmd.upnote, Some(SeqKind::ManyPossibleEmpty));
+        assert_eq!(input, "");
+    }
+
+    #[test]
+    fn escaped_braces() {
+        assert_eq!(group(r"{\{ a \}}b"), Ok(("b", r"\{ a \}")));
+        assert_eq!(top_level(r"\{a, b\}, c", |r| r.starts_with(',')), Some(8));
+        assert_eq!(split(r"f(a,b),\{c,d\}", ','), vec!["f(a,b)", r"\{c,d\}"]);
+    }
 }
@@ -0,0 +1,198 @@
+//! Packrat memo of [`Command::parser`].
+//!
+//! The parsers of symbols, attributes and rules still try a command where
+//! they may backtrack, e.g. `\Rightarrow` after the symbols of an
+//! alternative, `\to` after a nonterminal or `\X` as a variable and then as
+//! a nonterminal. While a source, e.g. a math block, is parsed under
+//! [`memoized`], every command read from it is kept by the position and the
+//! end of its input, as the offsets of its parts, and is rebuilt from them
+//! when the same span is read again, however far the parser backtracked.
+
+use std::{cell::RefCell, collections::HashMap, ops::Range};
+
+use super::{Argument, Command, CommandHead, SeqKind};
+
+thread_local! {
+    static MEMO: RefCell<Option<Memo>> = const { RefCell::new(None) };
+}
+
+/// The commands read from the source being parsed, by the offsets of the
+/// start and the end of the input they were read from
+struct Memo {
+    /// Addresses of the source
+    source: Range<usize>,
+    commands: HashMap<(usize, usize), Option<Parsed>>,
+}
+
+/// Command as the offsets of its parts in its input
+struct Parsed {
+    len: usize,
+    name: Range<usize>,
+    params: Vec<Range<usize>>,
+    args: Vec<ParsedArg>,
+    upnote: Option<SeqKind>,
+}
+
+enum ParsedArg {
+    Str(Range<usize>),
+    Cmd(Box<Parsed>),
+}
+
+/// Runs `f` with the commands read from `source`, and from any part of it,
+/// memoized. The memo is dropped when `f` returns.
+pub fn memoized<T>(source: &str, f: impl FnOnce() -> T) -> T {
+    let start = source.as_ptr() as usize;
+    let fresh = MEMO.with(|memo| {
+        let mut memo = memo.borrow_mut();
+        if memo.is_some() {
+            return false;
+        }
+        *memo = Some(Memo {
+            source: start..start + source.len(),
+            commands: HashMap::new(),
+        });
+        true
+    });
+    let _guard = fresh.then_some(Guard);
+    f()
+}
+
+/// Drops the memo, also when the parser panics
+struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        MEMO.with(|memo| *memo.borrow_mut() = None);
+    }
+}
+
+/// The command at the start of `input`, from the memo if the same span was
+/// read before, or else by `read`
+pub(super) fn command<'a>(
+    input: &'a str,
+    read: impl FnOnce(&'a str) -> Option<(&'a str, Command<'a>)>,
+) -> Option<(&'a str, Command<'a>)> {
+    let start = input.as_ptr() as usize;
+    let cached = MEMO.with(|memo| {
+        let memo = memo.borrow();
+        let memo = memo.as_ref()?;
+        if start < memo.source.start || memo.source.end < start + input.len() {
+            return None;
+        }
+        let offset = start - memo.source.start;
+        let key = (offset, offset + input.len());
+        let parsed = memo.commands.get(&key);
+        Some((key, parsed.map(|p| p.as_ref().map(|p| p.rebuild(input)))))
+    });
+    match cached {
+        // no memo for this input
+        None => read(input),
+        Some((_, Some(hit))) => hit,
+        Some((key, None)) => {
+            let result = read(input);
+            let parsed = result
+                .as_ref()
+                .map(|(tail, cmd)| Parsed::new(input, cmd, input.len() - tail.len()));
+            MEMO.with(|memo| {
+                if let Some(memo) = memo.borrow_mut().as_mut() {
+                    memo.commands.insert(key, parsed);
+                }
+            });
+            result
+        }
+    }
+}
+
+impl Parsed {
+    fn new(input: &str, cmd: &Command<'_>, len: usize) -> Self {
+        Self {
+            len,
+            name: span(input, cmd.head.name),
+            params: cmd.head.params.iter().map(|p| span(input, p)).collect(),
+            args: cmd
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    Argument::Str(s) => ParsedArg::Str(span(input, s)),
+                    Argument::Cmd(cmd) => ParsedArg::Cmd(Box::new(Self::new(input, cmd, 0))),
+                })
+                .collect(),
+            upnote: cmd.upnote.clone(),
+        }
+    }
+
+    fn rebuild<'a>(&self, input: &'a str) -> (&'a str, Command<'a>) {
+        (&input[self.len..], self.command(input))
+    }
+
+    fn command<'a>(&self, input: &'a str) -> Command<'a> {
+        Command {
+            head: CommandHead {
+                name: &input[self.name.clone()],
+                params: self.params.iter().map(|p| &input[p.clone()]).collect(),
+            },
+            args: self
+                .args
+                .iter()
+                .map(|arg| match arg {
+                    ParsedArg::Str(s) => Argument::Str(&input[s.clone()]),
+                    ParsedArg::Cmd(cmd) => Argument::Cmd(Box::new(cmd.command(input))),
+                })
+                .collect(),
+            upnote: self.upnote.clone(),
+        }
+    }
+}
+
+/// Offsets of `part` in `input`, of which it is a slice
+fn span(input: &str, part: &str) -> Range<usize> {
+    let start = part.as_ptr() as usize - input.as_ptr() as usize;
+    start..start + part.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebuilt_commands() {
+        let source = r"\K{div\_u}^\ast \F[a,b]{{\X{in}}} \hex";
+        let keys = || {
+            MEMO.with(|m| {
+                let mut keys: Vec<_> = m
+                    .borrow()
+                    .as_ref()
+                    .unwrap()
+                    .commands
+                    .keys()
+                    .copied()
+                    .collect();
+                keys.sort();
+                keys
+            })
+        };
+        let (fresh, cached) = memoized(source, || {
+            let fresh = Command::parser(source).unwrap();
+            assert!(keys().contains(&(0, source.len())));
+            let second = fresh.0;
+            let start = source.len() - second.len();
+            assert_eq!(Command::parser(second), Command::parser(second));
+            assert!(keys().contains(&(start, source.len())));
+            // a command read before the last one is still in the memo
+            let read = keys();
+            let cached = Command::parser(source).unwrap();
+            assert_eq!(keys(), read);
+
+            // a shorter span of the same source is another input
+            assert_eq!(Command::parser(&second[..2]).unwrap().1.head.name, "F");
+            assert!(keys().contains(&(start, start + 2)));
+            (fresh, cached)
+        });
+        assert_eq!(fresh, cached);
+        assert!(MEMO.with(|m| m.borrow().is_none()));
+
+        let (_, f) = Command::parser(fresh.0).unwrap();
+        assert_eq!(f.head.params, vec!["a", "b"]);
+        assert!(matches!(&f.args[0], Argument::Cmd(x) if x.args == vec![Argument::Str("in")]));
+    }
+}
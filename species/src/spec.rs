@@ -0,0 +1,315 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    io,
+    path::Path,
+};
+
+use crate::{
+    grammar::{Grammar, Origin},
+    syntax::{
+        symbol::{SNonterm, SRecord},
+        visit::{self, Visit},
+        Production, RhsElem,
+    },
+};
+
+/// A loaded grammar together with the indexes answering common queries
+#[derive(Debug)]
+pub struct Spec {
+    grammar: Grammar,
+    productions: HashMap<String, Vec<usize>>,
+    uses: HashMap<String, Vec<Location>>,
+    fields: HashMap<String, Vec<(Location, usize)>>,
+    terminals: BTreeSet<String>,
+}
+
+/// Position of an alternative in the grammar
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Location {
+    production: usize,
+    elem: usize,
+}
+
+/// An alternative together with the production it belongs to
+#[derive(Debug, PartialEq)]
+pub struct Alternative<'s> {
+    pub origin: &'s Origin,
+    pub production: &'s Production,
+    pub elem: &'s RhsElem,
+}
+
+impl Spec {
+    pub fn new(grammar: Grammar) -> Self {
+        let mut spec = Self {
+            grammar,
+            productions: HashMap::new(),
+            uses: HashMap::new(),
+            fields: HashMap::new(),
+            terminals: BTreeSet::new(),
+        };
+
+        let mut indexer = Indexer {
+            location: Location {
+                production: 0,
+                elem: 0,
+            },
+            records: 0,
+            uses: vec![],
+            fields: vec![],
+            terminals: vec![],
+        };
+        for (i, (_, production)) in spec.grammar.productions().enumerate() {
+            spec.productions
+                .entry(production.nonterminal().to_string())
+                .or_default()
+                .push(i);
+            for (j, elem) in production.rhs().elems().iter().enumerate() {
+                if elem.is_continuation() {
+                    continue;
+                }
+                indexer.location = Location {
+                    production: i,
+                    elem: j,
+                };
+                indexer.records = 0;
+                indexer.visit_rhs_elem(elem);
+            }
+        }
+
+        for (name, location) in indexer.uses {
+            let uses = spec.uses.entry(name.to_string()).or_default();
+            if !uses.contains(&location) {
+                uses.push(location);
+            }
+        }
+        for (name, location, record) in indexer.fields {
+            spec.fields
+                .entry(name.to_string())
+                .or_default()
+                .push((location, record));
+        }
+        spec.terminals = indexer.terminals.into_iter().map(String::from).collect();
+        spec
+    }
+
+    /// Load a local specification checkout, see [`Grammar::load`]
+    pub fn load(name: impl Into<String>, root: impl AsRef<Path>) -> io::Result<Self> {
+        Grammar::load(name, root).map(Self::new)
+    }
+
+    pub fn grammar(&self) -> &Grammar {
+        &self.grammar
+    }
+
+    /// The production defining a nonterminal, e.g. `limits`
+    pub fn production(&self, nonterminal: &str) -> Option<&Production> {
+        self.productions(nonterminal).next()
+    }
+
+    /// The productions defining or continuing (`\dots`) a nonterminal
+    pub fn productions(&self, nonterminal: &str) -> impl Iterator<Item = &Production> {
+        self.productions
+            .get(nonterminal)
+            .into_iter()
+            .flatten()
+            .map(|&i| self.entry(i).1)
+    }
+
+    /// Alternatives of a nonterminal over all of its productions
+    pub fn alternatives_of(&self, nonterminal: &str) -> Vec<Alternative<'_>> {
+        let mut alternatives = vec![];
+        for &i in self.productions.get(nonterminal).into_iter().flatten() {
+            let (origin, production) = self.entry(i);
+            let elems = production.rhs().elems().iter();
+            alternatives.extend(
+                elems
+                    .filter(|e| !e.is_continuation())
+                    .map(|elem| Alternative {
+                        origin,
+                        production,
+                        elem,
+                    }),
+            );
+        }
+        alternatives
+    }
+
+    /// Alternatives that mention a nonterminal on their right-hand side
+    pub fn uses_of(&self, nonterminal: &str) -> Vec<Alternative<'_>> {
+        let locations = self.uses.get(nonterminal).into_iter().flatten();
+        locations.map(|&l| self.alternative(l)).collect()
+    }
+
+    /// Records that have a field, e.g. `LMAX`, with the alternative they
+    /// appear in
+    pub fn records_with_field(&self, field: &str) -> Vec<(Alternative<'_>, &SRecord)> {
+        let records = self.fields.get(field).into_iter().flatten();
+        records
+            .map(|&(location, record)| {
+                let alternative = self.alternative(location);
+                let record = records_of(alternative.elem)[record];
+                (alternative, record)
+            })
+            .collect()
+    }
+
+    /// Terminals used on right-hand sides, in alphabetical order. Record
+    /// fields are not included.
+    pub fn terminals(&self) -> impl Iterator<Item = &str> {
+        self.terminals.iter().map(String::as_str)
+    }
+
+    fn entry(&self, i: usize) -> (&Origin, &Production) {
+        self.grammar.entry(i)
+    }
+
+    fn alternative(&self, location: Location) -> Alternative<'_> {
+        let (origin, production) = self.entry(location.production);
+        Alternative {
+            origin,
+            production,
+            elem: &production.rhs().elems()[location.elem],
+        }
+    }
+}
+
+/// Records of an alternative, in visiting order
+fn records_of(elem: &RhsElem) -> Vec<&SRecord> {
+    struct Records<'ast>(Vec<&'ast SRecord>);
+
+    impl<'ast> Visit<'ast> for Records<'ast> {
+        fn visit_srecord(&mut self, node: &'ast SRecord) {
+            self.0.push(node);
+            visit::visit_srecord(self, node);
+        }
+    }
+
+    let mut records = Records(vec![]);
+    records.visit_rhs_elem(elem);
+    records.0
+}
+
+struct Indexer<'ast> {
+    location: Location,
+    /// Records seen so far in the current alternative
+    records: usize,
+    uses: Vec<(&'ast str, Location)>,
+    fields: Vec<(&'ast str, Location, usize)>,
+    terminals: Vec<&'ast str>,
+}
+
+impl<'ast> Visit<'ast> for Indexer<'ast> {
+    fn visit_sterm(&mut self, name: &'ast str) {
+        self.terminals.push(name);
+    }
+
+    fn visit_snonterm(&mut self, node: &'ast SNonterm) {
+        self.uses.push((&node.name, self.location));
+        visit::visit_snonterm(self, node);
+    }
+
+    fn visit_srecord(&mut self, node: &'ast SRecord) {
+        for (key, _) in node.pairs() {
+            self.fields.push((key, self.location, self.records));
+        }
+        self.records += 1;
+        visit::visit_srecord(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> Spec {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "syntax/modules.rst",
+            r"
+.. math::
+   \begin{array}{llll}
+   \production{limits} & \limits &::=&
+     \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+   \production{table type} & \tabletype &::=&
+     \limits~\reftype \\
+   \production{instruction} & \instr &::=&
+     \TABLEGET~\tableidx ~|~
+     \TABLECOPY~\tableidx~\tableidx \\
+   \end{array}
+
+.. math::
+   \begin{array}{llll}
+   \production{instruction} & \instr &::=&
+     \dots ~|~
+     \ELEMDROP~\elemidx \\
+   \production{element segment mode} & \elemmode &::=&
+     \EPASSIVE ~|~
+     \EACTIVE~\{ \ETABLE~\tableidx, \EOFFSET~\expr \} \\
+   \end{array}
+",
+        );
+        assert!(grammar.errors().is_empty());
+        Spec::new(grammar)
+    }
+
+    #[test]
+    fn production() {
+        let spec = spec();
+        assert_eq!(spec.production("limits").unwrap().name(), "limits");
+        assert_eq!(spec.productions("instr").count(), 2);
+        assert!(spec.production("u32").is_none());
+    }
+
+    #[test]
+    fn alternatives_of() {
+        let spec = spec();
+        let alternatives: Vec<_> = spec
+            .alternatives_of("instr")
+            .iter()
+            .map(|a| a.elem.to_string())
+            .collect();
+        assert_eq!(
+            alternatives,
+            vec![
+                r"\TABLEGET~\tableidx",
+                r"\TABLECOPY~\tableidx~\tableidx",
+                r"\ELEMDROP~\elemidx"
+            ]
+        );
+    }
+
+    #[test]
+    fn uses_of() {
+        let spec = spec();
+        let users: Vec<_> = spec
+            .uses_of("tableidx")
+            .iter()
+            .map(|a| a.production.nonterminal())
+            .collect();
+        assert_eq!(users, vec!["instr", "instr", "elemmode"]);
+        assert_eq!(spec.uses_of("u32").len(), 1);
+        assert!(spec.uses_of("dots").is_empty());
+    }
+
+    #[test]
+    fn records_with_field() {
+        let spec = spec();
+        let records = spec.records_with_field("LMAX");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].0.production.nonterminal(), "limits");
+        assert_eq!(records[0].1.pairs()[1].0, "LMAX");
+
+        let records = spec.records_with_field("EOFFSET");
+        assert_eq!(records[0].0.production.nonterminal(), "elemmode");
+    }
+
+    #[test]
+    fn terminals() {
+        let spec = spec();
+        let terminals: Vec<_> = spec.terminals().collect();
+        assert_eq!(
+            terminals,
+            vec!["EACTIVE", "ELEMDROP", "EPASSIVE", "TABLECOPY", "TABLEGET"]
+        );
+    }
+}
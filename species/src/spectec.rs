@@ -0,0 +1,535 @@
+//! Front-end of the SpecTec sources of newer specifications, the `.watsup`
+//! and `.spectec` files with declarations like
+//! `syntax numtype = I32 | I64 | F32 | F64`.
+//!
+//! The `syntax` declarations are read into the same [`Production`] model as
+//! the math blocks of the `rst` sources, so that a grammar loaded from either
+//! works with the same tools:
+//!
+//! - atoms, e.g. `I32` or `MUT`, are terminals and lowercase names are
+//!   nonterminals, as are the parameters of the declaration;
+//! - the iterations `*`, `?`, `+` and `^N` become [`SeqKind`]s, and an atom
+//!   or a group with one is a [`SGroup`](crate::syntax::symbol::SGroup);
+//! - applications of parameterised syntax name their nonterminal, and like
+//!   the `rst` macros, `uN(32)` is `\u32` and `list(valtype)` is
+//!   `\vec(\valtype)`;
+//! - records are records, `a -> b` is an arrow and `...` continues the
+//!   fragments of a nonterminal, like `\dots`, e.g. in `syntax instr/control`;
+//! - premises `-- if ...` are kept as written as the side condition.
+//!
+//! Alternatives that are literals, like `0 | ... | 2^N-1`, are terminals as
+//! written, and so are mixfix operators, e.g. `..` in `` `[u32 .. u32] ``.
+//! `hint(..)`s but the `desc` of the declaration, which names the production,
+//! and the other declarations, `def`, `relation`, `rule`, `grammar` and
+//! `var`, are skipped.
+
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while},
+    character::complete::{char, multispace0, multispace1, satisfy},
+    combinator::{opt, recognize},
+    error::ErrorKind,
+    multi::{many0, separated_list0},
+    sequence::{delimited, pair, preceded},
+};
+
+use crate::{
+    nom_err,
+    parser::SeqKind,
+    syntax::{
+        symbol::{SArrow, SGroup, SNonterm, SRecord, SVec, Symbol},
+        Lhs, Production, Rhs, RhsElem,
+    },
+    PResult, SpeciesError,
+};
+
+/// The `syntax` declarations of a SpecTec source, each with the (1-based)
+/// line it starts on, or the error it fails with
+pub fn syntax_definitions(source: &str) -> Vec<(usize, Result<Production, SpeciesError<String>>)> {
+    let mut definitions = vec![];
+    for (line, declaration) in declarations(source) {
+        let Some(body) = declaration.strip_prefix("syntax") else {
+            continue;
+        };
+        if !body.starts_with(char::is_whitespace) {
+            continue;
+        }
+        match Definition::parser(&declaration) {
+            Ok((_, None)) => {}
+            Ok((_, Some(production))) => definitions.push((line, Ok(production))),
+            Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                let name = declaration.split_whitespace().nth(1).unwrap_or_default();
+                let error = e
+                    .into_owned()
+                    .in_context(declaration.clone(), format!("syntax `{}`", name));
+                definitions.push((line, Err(error)));
+            }
+            Err(nom::Err::Incomplete(_)) => unreachable!("parsers are complete"),
+        }
+    }
+    definitions
+}
+
+/// The top-level declarations, without comments: a line that starts in the
+/// first column and the indented lines after it
+fn declarations(source: &str) -> Vec<(usize, String)> {
+    let mut declarations: Vec<(usize, String)> = vec![];
+    for (i, line) in source.lines().enumerate() {
+        let line = line.split_once(";;").map_or(line, |(code, _)| code);
+        if line.trim().is_empty() {
+            continue;
+        }
+        match declarations.last_mut() {
+            Some((_, declaration)) if line.starts_with(char::is_whitespace) => {
+                declaration.push('\n');
+                declaration.push_str(line);
+            }
+            _ => declarations.push((i + 1, line.to_string())),
+        }
+    }
+    declarations
+}
+
+/// Reader of one declaration, which knows its parameters
+struct Definition<'d> {
+    params: Vec<&'d str>,
+}
+
+impl<'d> Definition<'d> {
+    /// The production of a declaration, or none if it only declares the
+    /// name, e.g. `syntax typeuse` before its recursive definition
+    fn parser(input: &'d str) -> PResult<'d, Option<Production>> {
+        let (input, _) = pair(tag("syntax"), multispace1)(input)?;
+        let (input, id) = ident(input)?;
+        let (input, fragment) = opt(preceded(char('/'), ident))(input)?;
+        let (input, params) = opt(parenthesized)(input)?;
+        let (input, hints) = many0(preceded(multispace0, hint))(input)?;
+        let (input, _) = multispace0(input)?;
+        if input.is_empty() {
+            return Ok((input, None));
+        }
+        let (input, _) = pair(char('='), multispace0)(input)?;
+
+        let definition = Definition {
+            params: params.map_or(vec![], parameters),
+        };
+        let alternatives = alternatives(input);
+        // a range of literals, where `...` is not a continuation
+        let literal = alternatives.iter().any(|a| is_literal(a.trim()));
+        let mut elems = vec![];
+        for alternative in alternatives {
+            let alternative = alternative.trim();
+            let elem = if literal {
+                let (symbol, cond) = premises(alternative);
+                RhsElem {
+                    symbols: vec![Symbol::STerm(symbol.trim().to_string())],
+                    action: None,
+                    cond,
+                }
+            } else {
+                definition.alternative(alternative)?
+            };
+            elems.push(elem);
+        }
+
+        let name = hints
+            .iter()
+            .find_map(|hint| desc(hint))
+            .map(String::from)
+            .unwrap_or_else(|| match fragment {
+                Some(fragment) => format!("{}/{}", id, fragment),
+                None => id.to_string(),
+            });
+        let production = Production::new(name, Lhs::new(vec![nonterminal(id)]), Rhs { elems });
+        Ok(("", Some(production)))
+    }
+
+    fn alternative(&self, input: &'d str) -> Result<RhsElem, nom::Err<SpeciesError<&'d str>>> {
+        if input == "..." {
+            return Ok(RhsElem {
+                symbols: vec![Symbol::SNonterm(SNonterm::new("dots", None))],
+                action: None,
+                cond: None,
+            });
+        }
+        let (input, cond) = premises(input);
+        let (rest, symbols) = self.symbols(input)?;
+        if !rest.trim().is_empty() {
+            return nom_err!(rest, Nom, ErrorKind::Eof);
+        }
+        if symbols.is_empty() {
+            return nom_err!(input, Nom, ErrorKind::Many1);
+        }
+        Ok(RhsElem {
+            symbols,
+            action: None,
+            cond,
+        })
+    }
+
+    /// Symbols separated by spaces, with `a -> b` read as one arrow
+    fn symbols<'a>(&self, input: &'a str) -> PResult<'a, Vec<Symbol>> {
+        let (input, symbols) = many0(preceded(
+            pair(multispace0, many0(pair(hint, multispace0))),
+            |i| self.symbol(i),
+        ))(input)?;
+        let (input, _) = pair(multispace0, many0(pair(hint, multispace0)))(input)?;
+
+        let mut folded: Vec<Symbol> = vec![];
+        let mut symbols = symbols.into_iter().peekable();
+        while let Some(symbol) = symbols.next() {
+            let arrow = matches!(&symbol, Symbol::STerm(op) if op == "->");
+            match (arrow, folded.last(), symbols.peek()) {
+                (true, Some(Symbol::SNonterm(_)), Some(Symbol::SNonterm(_))) => {
+                    let Some(Symbol::SNonterm(from)) = folded.pop() else {
+                        unreachable!()
+                    };
+                    let Some(Symbol::SNonterm(to)) = symbols.next() else {
+                        unreachable!()
+                    };
+                    folded.push(Symbol::SArrow(SArrow { from, to }));
+                }
+                _ => folded.push(symbol),
+            }
+        }
+        Ok((input, folded))
+    }
+
+    /// A symbol and its iterations, e.g. `(valtype*)?`
+    fn symbol<'a>(&self, input: &'a str) -> PResult<'a, Symbol> {
+        let (mut input, mut symbol) = alt((
+            |i| self.group(i),
+            |i| self.record(i),
+            |i| self.name(i),
+            operator,
+        ))(input)?;
+        while let Ok((tail, seq_kind)) = iteration(input) {
+            symbol = iterated(symbol, seq_kind);
+            input = tail;
+        }
+        Ok((input, symbol))
+    }
+
+    fn group<'a>(&self, input: &'a str) -> PResult<'a, Symbol> {
+        let (input, symbols) = delimited(
+            char('('),
+            |i| self.symbols(i),
+            preceded(multispace0, char(')')),
+        )(input)?;
+        let group = SGroup {
+            symbols,
+            seq_kind: None,
+        };
+        Ok((input, Symbol::SGroup(group)))
+    }
+
+    /// A record, e.g. `{LOCALS (val?)*, MODULE moduleinst}`
+    fn record<'a>(&self, input: &'a str) -> PResult<'a, Symbol> {
+        let field = |input: &'a str| {
+            let (input, _) = multispace0(input)?;
+            let (input, key) = ident(input)?;
+            if !is_atom(key) {
+                return nom_err!(input, NotATerminal, key.to_string());
+            }
+            let (input, mut symbols) = self.symbols(input)?;
+            let value = match symbols.len() {
+                0 => return nom_err!(input, Nom, ErrorKind::Many1),
+                1 => symbols.remove(0),
+                _ => Symbol::SGroup(SGroup {
+                    symbols,
+                    seq_kind: None,
+                }),
+            };
+            Ok((input, (key.to_string(), value)))
+        };
+        let (input, pairs) = delimited(
+            char('{'),
+            separated_list0(preceded(multispace0, char(',')), field),
+            preceded(multispace0, char('}')),
+        )(input)?;
+        Ok((input, Symbol::SRecord(SRecord { pairs })))
+    }
+
+    /// An atom, a nonterminal, or the application of parameterised syntax
+    fn name<'a>(&self, input: &'a str) -> PResult<'a, Symbol> {
+        let (tail, id) = ident(input)?;
+        if id == "hint" && tail.starts_with('(') {
+            return nom_err!(input, Nom, ErrorKind::Tag);
+        }
+        if let Ok((tail, args)) = parenthesized(tail) {
+            let args = args.trim();
+            let symbol = match (id, args.parse::<u32>()) {
+                ("list" | "vec", _) => {
+                    let (rest, over) = ident(args)?;
+                    if !rest.trim().is_empty() {
+                        return nom_err!(rest, Nom, ErrorKind::Eof);
+                    }
+                    Symbol::SVec(SVec {
+                        head: "vec".to_string(),
+                        over: Box::new(SNonterm::new(nonterminal(over), None)),
+                    })
+                }
+                // `uN(32)` is `\u32`
+                (_, Ok(n)) if id.ends_with('N') => {
+                    let name = format!("{}{}", &id[..id.len() - 1], n);
+                    Symbol::SNonterm(SNonterm::new(name, None))
+                }
+                _ => Symbol::SNonterm(SNonterm::new(nonterminal(id), None)),
+            };
+            return Ok((tail, symbol));
+        }
+        if is_atom(id) && !self.params.contains(&id) {
+            return Ok((tail, Symbol::STerm(id.to_string())));
+        }
+        Ok((tail, Symbol::SNonterm(SNonterm::new(nonterminal(id), None))))
+    }
+}
+
+/// Alternatives of a right-hand side, split at the `|` outside brackets and
+/// premises, with the leading `|` of the first one dropped
+fn alternatives(rhs: &str) -> Vec<&str> {
+    let mut alternatives = vec![];
+    let mut depth = 0;
+    let mut premise = false;
+    let mut start = 0;
+    let mut chars = rhs.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            '\n' => premise = false,
+            '-' if depth == 0 && matches!(chars.peek(), Some((_, '-'))) => premise = true,
+            '|' if depth == 0 && !premise => {
+                alternatives.push(&rhs[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&rhs[start..]);
+    if alternatives.len() > 1 && alternatives[0].trim().is_empty() {
+        alternatives.remove(0);
+    }
+    alternatives
+}
+
+/// An alternative without its premises, and the premises as written, e.g.
+/// `-- if |X*| < 2^32`, with runs of spaces and line breaks made single
+fn premises(alternative: &str) -> (&str, Option<String>) {
+    match alternative.find("--") {
+        Some(i) => {
+            let cond = alternative[i..].split_whitespace().collect::<Vec<_>>();
+            (&alternative[..i], Some(cond.join(" ")))
+        }
+        None => (alternative, None),
+    }
+}
+
+fn is_literal(alternative: &str) -> bool {
+    alternative.starts_with(|c: char| c.is_ascii_digit())
+        || alternative.starts_with("U+")
+        || alternative.starts_with('$')
+}
+
+/// Whether a name is an atom, e.g. `I32`, `_IDX` or `ANY.CONVERT_EXTERN`,
+/// rather than a nonterminal
+fn is_atom(id: &str) -> bool {
+    id.chars().any(|c| c.is_ascii_uppercase()) && !id.chars().any(|c| c.is_ascii_lowercase())
+}
+
+/// Name of the nonterminal of an identifier, without its primes and
+/// subscripts, e.g. `valtype` of `valtype_1'`, and without the trailing `_`
+/// of parameterised families, e.g. `binop` of `binop_`
+fn nonterminal(id: &str) -> String {
+    let id = id.trim_end_matches('\'');
+    let id = match id.rsplit_once('_') {
+        Some((name, index)) if !name.is_empty() && index.chars().all(|c| c.is_ascii_digit()) => {
+            name
+        }
+        _ => id,
+    };
+    id.trim_end_matches('_').to_string()
+}
+
+fn ident(input: &str) -> PResult<'_, &str> {
+    recognize(pair(
+        satisfy(|c| c.is_ascii_alphabetic() || c == '_'),
+        take_while(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '\'' || c == '.'),
+    ))(input)
+}
+
+/// The text between a `(` and its `)`
+fn parenthesized(input: &str) -> PResult<'_, &str> {
+    if !input.starts_with('(') {
+        return nom_err!(input, Nom, ErrorKind::Char);
+    }
+    let mut depth = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 1 => return Ok((&input[i + 1..], &input[1..i])),
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    nom_err!(input, UnbalancedBrace)
+}
+
+/// Names of the parameters of a declaration, e.g. `X` and `N` of
+/// `(syntax X, N)`
+fn parameters(params: &str) -> Vec<&str> {
+    params
+        .split(',')
+        .filter_map(|param| {
+            let param = param.trim();
+            let param = param.strip_prefix("syntax").unwrap_or(param).trim_start();
+            ident(param).ok().map(|(_, id)| id)
+        })
+        .collect()
+}
+
+/// The arguments of a `hint(..)`
+fn hint(input: &str) -> PResult<'_, &str> {
+    preceded(pair(tag("hint"), multispace0), parenthesized)(input)
+}
+
+/// The description of a `hint(desc "number type")`
+fn desc(hint: &str) -> Option<&str> {
+    let text = hint.trim().strip_prefix("desc")?.trim();
+    text.strip_prefix('"')?.strip_suffix('"')
+}
+
+/// A mixfix operator, e.g. `->`, or a bracket escaped by a backtick, e.g.
+/// `` `[ ``
+fn operator(input: &str) -> PResult<'_, Symbol> {
+    let (input, op) = alt((
+        preceded(char('`'), recognize(satisfy(|c| "([{".contains(c)))),
+        tag("->"),
+        tag("=>"),
+        tag(".."),
+        recognize(satisfy(|c| "[].;:%<>".contains(c))),
+    ))(input)?;
+    Ok((input, Symbol::STerm(op.to_string())))
+}
+
+fn iteration(input: &str) -> PResult<'_, SeqKind> {
+    alt((
+        |i| char('*')(i).map(|(i, _)| (i, SeqKind::ManyPossibleEmpty)),
+        |i| char('?')(i).map(|(i, _)| (i, SeqKind::OptSeq)),
+        |i| char('+')(i).map(|(i, _)| (i, SeqKind::ManyNonEmpty)),
+        |i| preceded(char('^'), ident)(i).map(|(i, _)| (i, SeqKind::ManyN)),
+    ))(input)
+}
+
+/// A symbol with an iteration, in a group unless it is a nonterminal or a
+/// group without one
+fn iterated(symbol: Symbol, seq_kind: SeqKind) -> Symbol {
+    match symbol {
+        Symbol::SNonterm(mut nt) if nt.seq_kind.is_none() => {
+            nt.seq_kind = Some(seq_kind);
+            Symbol::SNonterm(nt)
+        }
+        Symbol::SGroup(mut group) if group.seq_kind.is_none() => {
+            group.seq_kind = Some(seq_kind);
+            Symbol::SGroup(group)
+        }
+        symbol => Symbol::SGroup(SGroup {
+            symbols: vec![symbol],
+            seq_kind: Some(seq_kind),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Grammar;
+
+    const SYNTAX: &str = r#"
+;; Types
+
+syntax numtype hint(desc "number type") = I32 | I64 | F32 | F64
+syntax mut = MUT?
+syntax globaltype = mut valtype
+syntax functype = resulttype -> resulttype
+syntax resulttype = list(valtype)
+syntax u32 = uN(32)
+syntax uN(N) hint(desc "unsigned integer") =
+  0 | ... | 2^N-1
+syntax list(syntax X) = X*  -- if |X*| < $(2^32)
+
+syntax typeuse
+
+syntax frame =
+  { LOCALS (val?)*,
+    MODULE moduleinst }
+
+syntax elemmode =
+  | ACTIVE tableidx expr
+  | PASSIVE
+  | DECLARE
+
+syntax instr/parametric hint(desc "parametric instruction") =
+  | NOP
+  | SELECT (valtype*)?
+  | ...
+
+def $size(numtype) : nat
+def $size(I32) = 32
+
+syntax broken = { LOCALS val
+"#;
+
+    #[test]
+    fn syntax_declarations() {
+        let definitions = syntax_definitions(SYNTAX);
+        let (lines, productions): (Vec<_>, Vec<_>) = definitions
+            .iter()
+            .filter_map(|(line, p)| p.as_ref().ok().map(|p| (*line, p)))
+            .unzip();
+        assert_eq!(lines, vec![4, 5, 6, 7, 8, 9, 10, 12, 16, 20, 25]);
+
+        let show = |p: &Production| {
+            let elems: Vec<_> = p.rhs().elems.iter().map(|e| e.to_string()).collect();
+            format!("{}: {}", p.nonterminal(), elems.join(" | "))
+        };
+        let shown: Vec<_> = productions.iter().map(|p| show(p)).collect();
+        assert_eq!(
+            shown,
+            vec![
+                r"numtype: \I32 | \I64 | \F32 | \F64",
+                r"mut: (\MUT)^?",
+                r"globaltype: \mut~\valtype",
+                r"functype: \resulttype \to \resulttype",
+                r"resulttype: \vec(\valtype)",
+                r"u32: \u32",
+                r"uN: \0 | \... | \2^N-1",
+                r"list: \X^\ast (-- if |X*| < $(2^32))",
+                r"frame: \{ \LOCALS~(\val^?)^\ast, \MODULE~\moduleinst \}",
+                r"elemmode: \ACTIVE~\tableidx~\expr | \PASSIVE | \DECLARE",
+                r"instr: \NOP | \SELECT~(\valtype^\ast)^? | \dots",
+            ]
+        );
+        assert_eq!(productions[0].name(), "number type");
+        assert_eq!(productions[2].name(), "globaltype");
+        assert_eq!(productions[10].name(), "parametric instruction");
+        assert_eq!(
+            productions[7].rhs().elems[0].cond.as_deref(),
+            Some("-- if |X*| < $(2^32)")
+        );
+
+        let (line, error) = definitions.last().unwrap();
+        assert_eq!(*line, 33);
+        let error = error.as_ref().unwrap_err();
+        assert_eq!(error.context(), vec!["syntax `broken`"]);
+    }
+
+    #[test]
+    fn spectec_grammar() {
+        let mut grammar = Grammar::new("spectec");
+        grammar.add_spectec_source("wasm-3.0/1.1-syntax.values.spectec", SYNTAX);
+        assert_eq!(grammar.errors().len(), 1);
+        assert_eq!(grammar.alternatives("instr").len(), 2);
+        assert!(grammar.nonterminals().contains(&"frame"));
+    }
+}
@@ -1,80 +1,201 @@
+pub mod array;
+pub mod expr;
+pub mod fold;
+pub mod function;
+pub mod reduction;
+pub mod rule;
 pub mod symbol;
+pub mod visit;
+pub mod visit_mut;
 
-use std::fmt::Debug;
+use std::fmt::{self, Debug, Display};
 
 use nom::{
     bytes::complete::tag,
+    combinator::opt,
     error::ErrorKind,
-    multi::{many1, separated_list1}, sequence::preceded,
+    multi::{many0, many1, separated_list1},
+    sequence::{pair, preceded},
 };
+use serde::{Deserialize, Serialize};
 
 use crate::{
     nom_err,
-    parser::{equal, ws, Command},
+    parser::{equal, group, ws, Command},
     syntax::symbol::Symbol,
-    PResult,
+    PResult, SpeciesError,
 };
 
-use self::symbol::SNonterm;
+use self::{
+    array::{Array, Row},
+    expr::{condition, Expr},
+    symbol::SNonterm,
+};
 
 #[derive(Debug, PartialEq)]
-pub struct MathBlock<'a> {
-    productions: Vec<Production<'a>>,
+pub struct MathBlock {
+    pub(crate) productions: Vec<Production>,
+    pub(crate) abbreviations: Vec<Abbreviation>,
 }
 
-#[derive(Debug, PartialEq)]
-pub struct Production<'a> {
-    name: &'a str,
-    lhs: Lhs,
-    rhs: Rhs,
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Production {
+    pub(crate) name: String,
+    pub(crate) lhs: Lhs,
+    pub(crate) rhs: Rhs,
+}
+
+/// Shorthand of the text format and the form it stands for, e.g.
+/// `\text{(}~\text{param}~\Tvaltype^\ast~\text{)} \equiv
+/// (\text{(}~\text{param}~\Tvaltype~\text{)})^\ast`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Abbreviation {
+    /// Human readable name of the production it abbreviates, if given
+    pub(crate) name: Option<String>,
+    pub(crate) short: Vec<Symbol>,
+    pub(crate) long: Vec<Symbol>,
+    pub(crate) cond: Option<String>,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Serialize, Deserialize)]
 pub struct Lhs {
-    names: Vec<String>,
+    pub(crate) names: Vec<String>,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Serialize, Deserialize)]
 pub struct Rhs {
-    elems: Vec<RhsElem>,
+    pub(crate) elems: Vec<RhsElem>,
 }
 
-#[derive(PartialEq)]
-struct RhsElem {
-    symbols: Vec<Symbol>,
-    cond: Option<()>,
+#[derive(PartialEq, Serialize, Deserialize)]
+pub struct RhsElem {
+    pub(crate) symbols: Vec<Symbol>,
+    /// Attribute of binary and text alternatives, i.e. the abstract syntax
+    /// right of `\Rightarrow`
+    pub(crate) action: Option<Vec<Expr>>,
+    /// Side condition, e.g. `\iff n < 2^{32}`, kept as written
+    pub(crate) cond: Option<String>,
 }
 
-impl<'a> Production<'a> {
-    pub fn parser(input: &'a str) -> PResult<Self> {
+impl Production {
+    pub fn parser(input: &str) -> PResult<'_, Self> {
         let (input, name) = Self::production_name(input)?;
-        let (input, lhs) = Lhs::parser(input)?;
-        let (input, _) = equal(input)?;
-        let (input, rhs) = Rhs::parser(input)?;
+        let (input, (lhs, rhs)) = Self::body(input)
+            .map_err(|e| e.map(|e| e.in_context(input, format!("production `{}`", name))))?;
         let (input, _) = ws(input)?;
 
-        let production = Self { name, lhs, rhs };
-        Ok((input, dbg!(production)))
+        let production = Self {
+            name: name.to_string(),
+            lhs,
+            rhs,
+        };
+        Ok((input, production))
     }
 
-    /// Check if next is a production, but do not comsume
-    pub fn is_production(source: &'a str) -> PResult<()> {
-        let (_input, _prod) = Self::parser(source)?;
+    /// Check if next is a production, by its `\production{...}` head, but do
+    /// not consume
+    pub fn is_production(source: &str) -> PResult<'_, ()> {
+        let (_input, _name) = Self::production_name(source)?;
         Ok((source, ()))
     }
 
-    fn production_name(input: &'a str) -> PResult<&str> {
-        let (input, cmd) = Command::parser(input)?;
-        if cmd.head.name == "production" {
-            Ok((input, cmd.args[0].name()))
+    pub fn new(name: impl Into<String>, lhs: Lhs, rhs: Rhs) -> Self {
+        Self {
+            name: name.into(),
+            lhs,
+            rhs,
+        }
+    }
+
+    /// Human readable name, e.g. `number type` in `\production{number type}`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn lhs(&self) -> &Lhs {
+        &self.lhs
+    }
+
+    pub fn rhs(&self) -> &Rhs {
+        &self.rhs
+    }
+
+    /// Nonterminal defined by this production, i.e. the first name of its lhs
+    pub fn nonterminal(&self) -> &str {
+        &self.lhs.names[0]
+    }
+
+    fn production_name(input: &str) -> PResult<'_, &str> {
+        let (tail, cmd) = Command::parser(input)?;
+        if cmd.head.name == "production" && !cmd.args.is_empty() {
+            Ok((tail, cmd.args[0].name()))
         } else {
-            nom_err!(input, ErrorKind::Tag)
+            nom_err!(input, ExpectedProduction)
+        }
+    }
+
+    fn from_rows<'a>(
+        array: &Array<'a>,
+        rows: &[&Row<'a>],
+    ) -> Result<Self, nom::Err<SpeciesError<&'a str>>> {
+        let relation = array.relation_column();
+        let first = rows[0];
+        let name_cell = relation.checked_sub(2).map(|column| first.cell(column));
+        let name = match name_cell.map(Self::production_name) {
+            Some(Ok(("", name))) => name,
+            _ => return nom_err!(first.source, ExpectedProduction),
+        };
+        let in_production =
+            |e: SpeciesError<&'a str>| e.in_context(first.source, format!("production `{}`", name));
+        let lhs =
+            complete(Lhs::parser, lhs_cell(first, relation)).map_err(|e| e.map(in_production))?;
+
+        let mut elems = vec![];
+        let mut start = 0;
+        while start < rows.len() {
+            let end = rows[start + 1..]
+                .iter()
+                .position(|row| !row.cell(relation).is_empty())
+                .map_or(rows.len(), |i| start + 1 + i);
+            let alternative = &rows[start..end];
+            if start > 0 && alternative[0].cell(relation) != "|" {
+                let cell = alternative[0].cell(relation);
+                return Err(nom::Err::Error(in_production(SpeciesError::UnknownMacro(
+                    cell,
+                    cell.to_string(),
+                ))));
+            }
+            let rhs = complete(Rhs::parser, array.span(alternative, relation + 1))
+                .map_err(|e| e.map(in_production))?;
+            elems.extend(rhs.elems);
+            start = end;
         }
+
+        Ok(Self {
+            name: name.to_string(),
+            lhs,
+            rhs: Rhs { elems },
+        })
+    }
+
+    fn body(input: &str) -> PResult<'_, (Lhs, Rhs)> {
+        let (input, lhs) = Lhs::parser(input)?;
+        let (input, _) = equal(input)?;
+        let (input, rhs) = Rhs::parser(input)?;
+        Ok((input, (lhs, rhs)))
     }
 }
 
 impl Lhs {
-    fn parser(input: &str) -> PResult<Self> {
+    pub fn new(names: Vec<String>) -> Self {
+        Self { names }
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    fn parser(input: &str) -> PResult<'_, Self> {
         // let (input, cmd) = Command::parser(input)?;
         let comma = preceded(tag(","), ws);
         let (input, nts) = separated_list1(comma, SNonterm::parser)(input)?;
@@ -84,67 +205,322 @@ impl Lhs {
     }
 }
 
-impl<'a> MathBlock<'a> {
-    pub fn parser(input: &'a str) -> PResult<Self> {
+impl Abbreviation {
+    pub fn new(name: Option<String>, short: Vec<Symbol>, long: Vec<Symbol>) -> Self {
+        Self {
+            name,
+            short,
+            long,
+            cond: None,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Symbols left of `\equiv`
+    pub fn short(&self) -> &[Symbol] {
+        &self.short
+    }
+
+    /// Symbols right of `\equiv`
+    pub fn long(&self) -> &[Symbol] {
+        &self.long
+    }
+
+    pub fn cond(&self) -> Option<&str> {
+        self.cond.as_deref()
+    }
+
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let (input, name) = opt(Production::production_name)(input)?;
         let (input, _) = ws(input)?;
-        let (input, _begin) = begin(input)?;
-        let (input, productions) = many1(Production::parser)(input)?;
-        let (input, _end) = end(input)?;
+        let (input, short) = many1(Symbol::parser)(input)?;
+        let (input, _) = equiv(input)?;
+        let (input, long) = many1(Symbol::parser)(input)?;
+        let (input, cond) = opt(condition)(input)?;
         let (input, _) = ws(input)?;
 
+        let name = name.map(String::from);
         Ok((
             input,
-            MathBlock {
-                productions: productions,
+            Self {
+                name,
+                short,
+                long,
+                cond,
             },
         ))
     }
+
+    fn from_rows<'a>(
+        array: &Array<'a>,
+        rows: &[&Row<'a>],
+    ) -> Result<Self, nom::Err<SpeciesError<&'a str>>> {
+        let relation = array.relation_column();
+        let first = rows[0];
+        let name = match relation.checked_sub(2).map(|column| first.cell(column)) {
+            None | Some("") => None,
+            Some(cell) => Some(complete(Production::production_name, cell)?.to_string()),
+        };
+        let short = complete(many1(Symbol::parser), lhs_cell(first, relation))?;
+        let (long, cond) = complete(
+            pair(many1(Symbol::parser), opt(condition)),
+            array.span(rows, relation + 1),
+        )?;
+        Ok(Self {
+            name,
+            short,
+            long,
+            cond,
+        })
+    }
+}
+
+impl MathBlock {
+    pub fn new(productions: Vec<Production>) -> Self {
+        Self {
+            productions,
+            abbreviations: vec![],
+        }
+    }
+
+    pub fn productions(&self) -> &[Production] {
+        &self.productions
+    }
+
+    pub fn abbreviations(&self) -> &[Abbreviation] {
+        &self.abbreviations
+    }
+
+    pub fn into_productions(self) -> Vec<Production> {
+        self.productions
+    }
+
+    /// Productions and abbreviations of a `\begin{array}`. The cells of a row
+    /// are read by their column: the production name, the lhs, the relation
+    /// `::=`, `|` or `\equiv`, and the rhs in the cells after it. A row with
+    /// `|` starts another alternative, and a row with no relation continues
+    /// the one before.
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let (tail, array) = Array::parser(input)?;
+        let relation = array.relation_column();
+        let rows: Vec<_> = array.rows.iter().filter(|row| !row.is_blank()).collect();
+
+        let mut block = MathBlock::new(vec![]);
+        let mut start = 0;
+        while start < rows.len() {
+            let end = rows[start + 1..]
+                .iter()
+                .position(|row| matches!(row.cell(relation), "::=" | r"\equiv"))
+                .map_or(rows.len(), |i| start + 1 + i);
+            let statement = &rows[start..end];
+            match statement[0].cell(relation) {
+                "::=" => {
+                    let production = Production::from_rows(&array, statement)?;
+                    block.productions.push(production);
+                }
+                r"\equiv" => {
+                    let abbreviation = Abbreviation::from_rows(&array, statement)?;
+                    block.abbreviations.push(abbreviation);
+                }
+                _ => return nom_err!(statement[0].source, ExpectedProduction),
+            }
+            start = end;
+        }
+        Ok((tail, block))
+    }
+}
+
+/// Runs `parser` on the whole of `input`, layout around it aside
+fn complete<'a, T>(
+    mut parser: impl FnMut(&'a str) -> PResult<'a, T>,
+    input: &'a str,
+) -> Result<T, nom::Err<SpeciesError<&'a str>>> {
+    let (rest, _) = ws(input)?;
+    let (rest, out) = parser(rest)?;
+    let (rest, _) = ws(rest)?;
+    if rest.is_empty() {
+        Ok(out)
+    } else {
+        nom_err!(rest, Nom, ErrorKind::Eof)
+    }
+}
+
+/// Cell left of the relation, the lhs of a production or the short form of
+/// an abbreviation
+fn lhs_cell<'a>(row: &Row<'a>, relation: usize) -> &'a str {
+    relation
+        .checked_sub(1)
+        .map_or("", |column| row.cell(column))
 }
 
 impl Rhs {
-    pub fn parser(input: &str) -> PResult<Self> {
+    pub fn new(elems: Vec<RhsElem>) -> Self {
+        Self { elems }
+    }
+
+    /// Alternatives, i.e. the parts separated by `|`
+    pub fn elems(&self) -> &[RhsElem] {
+        &self.elems
+    }
+
+    pub fn parser(input: &str) -> PResult<'_, Self> {
         let (input, elems) = separated_list1(or, RhsElem::parser)(input)?;
         Ok((input, Self { elems }))
     }
 }
 
 impl RhsElem {
-    pub fn parser(input: &str) -> PResult<Self> {
+    pub fn new(symbols: Vec<Symbol>) -> Self {
+        Self {
+            symbols,
+            action: None,
+            cond: None,
+        }
+    }
+
+    pub fn with_action(mut self, action: Vec<Expr>) -> Self {
+        self.action = Some(action);
+        self
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn action(&self) -> Option<&[Expr]> {
+        self.action.as_deref()
+    }
+
+    pub fn cond(&self) -> Option<&str> {
+        self.cond.as_deref()
+    }
+
+    /// Whether the alternative is the `\dots` placeholder that continues
+    /// a production defined in an earlier block
+    pub fn is_continuation(&self) -> bool {
+        matches!(
+            self.symbols.as_slice(),
+            [Symbol::SNonterm(nt)] if nt.name == "dots"
+        )
+    }
+
+    pub fn parser(input: &str) -> PResult<'_, Self> {
         let (input, symbols) = many1(Symbol::parser)(input)?;
+        let (input, action) = opt(preceded(rightarrow, Expr::action))(input)?;
+        let (input, cond) = opt(condition)(input)?;
         Ok((
             input,
             Self {
                 symbols,
-                cond: None,
+                action,
+                cond,
             },
         ))
     }
 }
 
-pub fn begin(input: &str) -> PResult<()> {
-    let (input, cmd) = Command::parser(input)?;
+fn rightarrow(input: &str) -> PResult<'_, ()> {
+    let (tail, cmd) = Command::parser(input)?;
+    if cmd.head.name == "Rightarrow" {
+        Ok((tail, ()))
+    } else {
+        nom_err!(input, UnknownMacro, cmd.head.name.to_string())
+    }
+}
+
+fn equiv(input: &str) -> PResult<'_, ()> {
+    let (tail, cmd) = Command::parser(input)?;
+    if cmd.head.name == "equiv" {
+        Ok((tail, ()))
+    } else {
+        nom_err!(input, UnknownMacro, cmd.head.name.to_string())
+    }
+}
+
+pub fn begin(input: &str) -> PResult<'_, ()> {
+    let (tail, cmd) = Command::parser(input)?;
     if cmd.head.name == "begin" {
-        Ok((input, ()))
+        // column specifications like `{llcl@{\qquad}l}` are not arguments
+        // the command parser understands
+        let (tail, _) = many0(group)(tail)?;
+        let (tail, _) = ws(tail)?;
+        Ok((tail, ()))
     } else {
-        nom_err!(input, ErrorKind::Tag)
+        nom_err!(input, UnknownMacro, cmd.head.name.to_string())
     }
 }
 
-pub fn end(input: &str) -> PResult<()> {
-    let (input, cmd) = Command::parser(input)?;
+pub fn end(input: &str) -> PResult<'_, ()> {
+    let (tail, cmd) = Command::parser(input)?;
     if cmd.head.name == "end" {
-        Ok((input, ()))
+        Ok((tail, ()))
     } else {
-        nom_err!(input, ErrorKind::Tag)
+        nom_err!(input, UnknownMacro, cmd.head.name.to_string())
     }
 }
 
-pub fn or(input: &str) -> PResult<()> {
+pub fn or(input: &str) -> PResult<'_, ()> {
     let (input, _) = tag("|")(input)?;
     let (input, _) = ws(input)?;
     Ok((input, ()))
 }
 
+impl Display for Production {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            r"\production{{{}}} & {} &::=& {}",
+            self.name, self.lhs, self.rhs
+        )
+    }
+}
+
+impl Display for Abbreviation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, r"\production{{{}}} & ", name)?;
+        }
+        let short: Vec<_> = self.short.iter().map(|s| s.to_string()).collect();
+        let long: Vec<_> = self.long.iter().map(|s| s.to_string()).collect();
+        write!(f, r"{} &\equiv& {}", short.join("~"), long.join("~"))?;
+        match &self.cond {
+            Some(cond) => write!(f, " ({})", cond),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for Lhs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = self.names.iter().map(|n| format!(r"\{}", n)).collect();
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+impl Display for Rhs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let elems: Vec<_> = self.elems.iter().map(|e| e.to_string()).collect();
+        write!(f, "{}", elems.join(" ~|~ "))
+    }
+}
+
+impl Display for RhsElem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbols: Vec<_> = self.symbols.iter().map(|s| s.to_string()).collect();
+        write!(f, "{}", symbols.join("~"))?;
+        if let Some(action) = &self.action {
+            write!(f, r" \Rightarrow {}", expr::join(action))?;
+        }
+        match &self.cond {
+            Some(cond) => write!(f, " ({})", cond),
+            None => Ok(()),
+        }
+    }
+}
+
 impl Debug for Lhs {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("Lhs").field(&self.names).finish()
@@ -161,25 +537,113 @@ impl Debug for RhsElem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("RhsElem")
             .field(&self.symbols)
+            .field(&self.action)
             .field(&self.cond)
             .finish()
     }
 }
 
+/// Helpers for the tests that read or write files
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use std::{
+        env,
+        ffi::OsStr,
+        fs,
+        ops::Deref,
+        path::{Path, PathBuf},
+        process,
+    };
+
+    use crate::{Grammar, Spec};
+
+    /// Checkout of wasmmeta named by `WASMMETA_PATH`. The tests reading the
+    /// specification and its test suite are ignored unless run with
+    /// `--ignored`, and fail if the variable is not set.
+    pub(crate) fn wasmmeta_path() -> PathBuf {
+        env::var_os("WASMMETA_PATH")
+            .map(PathBuf::from)
+            .expect("`WASMMETA_PATH` must name a checkout of wasmmeta")
+    }
+
+    /// Spec of the `rst` files of the checkout
+    pub(crate) fn wasmmeta_spec(path: &Path) -> Spec {
+        let grammar = Grammar::load("spec", path.join("resources/spec")).unwrap();
+        Spec::new(grammar)
+    }
+
+    /// Name and content of the `.wast` scripts of the core test suite, in
+    /// the order of their names
+    pub(crate) fn scripts(path: &Path) -> Vec<(String, String)> {
+        let dir = path.join("resources/spec/test/core");
+        let mut scripts: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|file| file.extension().is_some_and(|ext| ext == "wast"))
+            .map(|file| {
+                let name = file.file_name().unwrap().to_string_lossy().into_owned();
+                (name, fs::read_to_string(&file).unwrap())
+            })
+            .collect();
+        scripts.sort();
+        scripts
+    }
+
+    /// Empty directory `species-<name>-<pid>` in the temporary directory,
+    /// removed with its content when dropped
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("species-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl AsRef<OsStr> for TempDir {
+        fn as_ref(&self) -> &OsStr {
+            self.0.as_os_str()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::env;
+    use std::{env, fs, io};
 
     use crate::read_math_blocks;
 
-    use super::*;
+    use super::{fixtures::TempDir, *};
 
     macro_rules! test_file {
         ($test_name:ident, $file_name:expr) => {
             #[test]
             fn $test_name() {
-                let path = env::var("WASMMETA_PATH").expect("Environment Variable `WASMMETA_PATH` is not set");
-                let blocks = read_math_blocks(path + $file_name);
+                let path = env::var("WASMMETA_PATH")
+                    .expect("Environment Variable `WASMMETA_PATH` is not set");
+                let blocks = read_math_blocks(path + $file_name).unwrap();
                 for block in blocks {
                     let (input, mb) = MathBlock::parser(&block).unwrap();
                     assert_eq!(input, "");
@@ -189,6 +653,16 @@ mod tests {
         };
     }
 
+    #[test]
+    fn read_blocks() {
+        let dir = TempDir::new("math-blocks");
+        let file = dir.join("types.rst");
+        fs::write(&file, ".. math::\n   \\numtype\n\nProse\n").unwrap();
+        assert_eq!(read_math_blocks(&file).unwrap(), ["   \\numtype"]);
+        let error = read_math_blocks(dir.join("missing.rst")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
     macro_rules! test_block {
         ($prod_name:ident, $s:expr, $prod_num:expr) => {
             #[test]
@@ -323,6 +797,102 @@ mod tests {
         1
     );
 
+    #[test]
+    fn error_context() {
+        let s = r"\begin{array}{llll}
+        \production{limits} & \limits &::=&
+          \{ \LMIN~\u32, \lmax~\u32^? \} \\
+        \end{array}";
+        let err = match MathBlock::parser(s) {
+            Err(nom::Err::Error(err)) => err,
+            res => panic!("limits with a lowercase field should not parse: {:?}", res),
+        };
+        assert_eq!(err.context(), vec!["production `limits`", "record", "pair"]);
+        assert_eq!(
+            err.root(),
+            &crate::SpeciesError::NotATerminal(r"\lmax~\u32^? \} ", "lmax".to_string())
+        );
+    }
+
+    #[test]
+    fn columns() {
+        // the condition of an alternative in a column of its own, and an
+        // rhs that starts on the row after its lhs
+        let (input, mb) = MathBlock::parser(
+            r"\begin{array}{llcll}
+    \production{memory argument} & \Tmemarg &::=&
+      o{:}\Toffset &\Rightarrow& o & (\iff o < 2^{32}) \\
+    \production{block type} & \Tblocktype &::=& \\&&&
+      \Tresult &\Rightarrow& t \\&&|&
+      \text{type}~~x{:}\Ttypeidx \\&&&
+      &\Rightarrow& x \\
+    \end{array}",
+        )
+        .unwrap();
+        assert_eq!(input, "");
+        let [memarg, blocktype] = mb.productions.as_slice() else {
+            panic!("two productions expected: {:?}", mb.productions);
+        };
+        assert_eq!(memarg.rhs.elems[0].cond(), Some(r"\iff o < 2^{32}"));
+        assert_eq!(blocktype.rhs.elems.len(), 2);
+        assert_eq!(
+            blocktype.rhs.elems[1].to_string(),
+            r"\text{type}~x{:}\Ttypeidx \Rightarrow x"
+        );
+
+        // no production name column
+        let err = MathBlock::parser(
+            r"\begin{array}{lcl}
+    \valtype &::=& \I32 \\&|& \I64 \\
+    \end{array}",
+        );
+        assert!(err.is_err());
+
+        let err = MathBlock::parser(
+            r"\begin{array}{llll}
+    \production{value type} & \valtype &::=& \I32 \\&&=& \I64 \\
+    \end{array}",
+        )
+        .unwrap_err();
+        let nom::Err::Error(err) = err else {
+            panic!("{:?}", err)
+        };
+        assert_eq!(err.context(), vec!["production `value type`"]);
+        assert_eq!(
+            err.root(),
+            &crate::SpeciesError::UnknownMacro("=", "=".to_string())
+        );
+    }
+
+    #[test]
+    fn abbreviations() {
+        let (input, mb) = MathBlock::parser(
+            r"\begin{array}{llclll}
+    \production{parameter} &
+      \text{(}~~\text{param}~~\Tvaltype^\ast~~\text{)} &\equiv&
+      (\text{(}~~\text{param}~~\Tvaltype~~\text{)})^\ast \\
+    \end{array}",
+        )
+        .unwrap();
+        assert_eq!(input, "");
+        assert!(mb.productions.is_empty());
+        assert_eq!(
+            mb.abbreviations[0].to_string(),
+            r"\production{parameter} & \text{(}~\text{param}~\Tvaltype^\ast~\text{)} &\equiv& (\text{(}~\text{param}~\Tvaltype~\text{)})^\ast"
+        );
+    }
+
+    #[test]
+    fn expected_production() {
+        let err = Production::parser(r"\limits &::=& \u32").unwrap_err();
+        assert_eq!(
+            err,
+            nom::Err::Error(crate::SpeciesError::ExpectedProduction(
+                r"\limits &::=& \u32"
+            ))
+        );
+    }
+
     #[test]
     fn parse_rhs() {
         let s = r"\I32 ~|~ \I64 ~|~ \F32 ~|~ \F64 \\";
@@ -0,0 +1,249 @@
+use nom::error::ErrorKind;
+
+use crate::{
+    nom_err,
+    parser::{group, ws, Argument, Command},
+    tex::{self, Token},
+    PResult,
+};
+
+use super::end;
+
+/// `\begin{array}{...} ... \end{array}`, read into rows and cells
+#[derive(Debug, PartialEq)]
+pub struct Array<'a> {
+    pub columns: Vec<Column>,
+    pub rows: Vec<Row<'a>>,
+    /// The rows as written, up to `\end{array}`
+    pub source: &'a str,
+}
+
+/// Alignment of a column of the column spec, e.g. `l` in `{llcl}`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Column {
+    Left,
+    Center,
+    Right,
+}
+
+/// Row of an array, up to its `\\`
+#[derive(Debug, PartialEq)]
+pub struct Row<'a> {
+    /// The row as written, without its `\\`
+    pub source: &'a str,
+    /// Cells between the `&`, trimmed
+    pub cells: Vec<&'a str>,
+}
+
+impl<'a> Array<'a> {
+    pub fn parser(input: &'a str) -> PResult<'a, Self> {
+        let (input, _) = ws(input)?;
+        let (body, cmd) = Command::parser(input)?;
+        if cmd.head.name != "begin" || cmd.args.first() != Some(&Argument::Str("array")) {
+            return nom_err!(input, UnknownMacro, cmd.head.name.to_string());
+        }
+        // `Command::parser` takes a column spec of letters like `{llll}` as
+        // an argument, but not one like `{@{}lcll}`
+        let (body, columns) = match cmd.args.get(1) {
+            Some(Argument::Str(spec)) => (body, Self::columns(spec)),
+            Some(Argument::Cmd(_)) => (body, vec![]),
+            None => {
+                let (body, spec) = group(body)?;
+                (body, Self::columns(spec))
+            }
+        };
+
+        let mut rows = vec![];
+        let mut row_start = 0;
+        let mut cells = vec![];
+        let mut cell_start = 0;
+        let mut depth = 0;
+        let mut tokens = tex::tokens(body);
+        let end_offset = loop {
+            let Some((offset, token)) = tokens.next() else {
+                return nom_err!(body, Nom, ErrorKind::Eof);
+            };
+            match token {
+                Token::BeginGroup | Token::Word("begin") => depth += 1,
+                Token::EndGroup => depth -= 1,
+                Token::Word("end") if depth > 0 => depth -= 1,
+                Token::Word("end") => {
+                    cells.push(body[cell_start..offset].trim());
+                    rows.push(Row {
+                        source: &body[row_start..offset],
+                        cells,
+                    });
+                    break offset;
+                }
+                Token::AlignTab if depth == 0 => {
+                    cells.push(body[cell_start..offset].trim());
+                    cell_start = offset + 1;
+                }
+                Token::RowEnd if depth == 0 => {
+                    cells.push(body[cell_start..offset].trim());
+                    rows.push(Row {
+                        source: &body[row_start..offset],
+                        cells: std::mem::take(&mut cells),
+                    });
+                    row_start = offset + 2;
+                    cell_start = row_start;
+                }
+                _ => {}
+            }
+        };
+        // the `\\` after the last row leaves a blank one behind
+        if rows.last().is_some_and(Row::is_blank) {
+            rows.pop();
+        }
+        let (tail, _) = end(&body[end_offset..])?;
+        let (tail, _) = ws(tail)?;
+        let source = &body[..end_offset];
+        Ok((
+            tail,
+            Self {
+                columns,
+                rows,
+                source,
+            },
+        ))
+    }
+
+    /// Columns of a spec like `{llcl@{\qquad}l}`, leaving out the `@{...}`
+    /// material between columns and the `|` rules
+    fn columns(spec: &str) -> Vec<Column> {
+        let mut columns = vec![];
+        let mut rest = spec;
+        while let Some((token, tail)) = tex::next(rest) {
+            rest = tail;
+            match token {
+                Token::Char('l') => columns.push(Column::Left),
+                Token::Char('c') => columns.push(Column::Center),
+                Token::Char('r') => columns.push(Column::Right),
+                Token::Char('@' | 'p') => {
+                    if let Some((_, tail)) = tex::group(rest) {
+                        rest = tail;
+                    }
+                }
+                _ => {}
+            }
+        }
+        columns
+    }
+
+    /// Column of the relation of a grammar array, `::=`, `|` or `\equiv`:
+    /// the centered one if the spec has one, as in `{llcll}`, or else the
+    /// third one, after the production name and the lhs, as in `{llll}`
+    pub fn relation_column(&self) -> usize {
+        self.columns
+            .iter()
+            .position(|c| *c == Column::Center)
+            .unwrap_or(2)
+    }
+
+    /// The source of consecutive rows, from the `column`-th cell of the
+    /// first one to the end of the last one
+    pub fn span(&self, rows: &[&Row<'a>], column: usize) -> &'a str {
+        let start = offset(self.source, rows[0].from(column));
+        let last = rows[rows.len() - 1].source;
+        &self.source[start..offset(self.source, last) + last.len()]
+    }
+}
+
+impl<'a> Row<'a> {
+    /// The `i`-th cell, or an empty one after the last cell
+    pub fn cell(&self, i: usize) -> &'a str {
+        self.cells.get(i).copied().unwrap_or_default()
+    }
+
+    /// The row from the `i`-th cell on, `&` included, or the empty end of
+    /// the row after the last cell
+    pub fn from(&self, i: usize) -> &'a str {
+        match self.cells.get(i) {
+            Some(cell) => &self.source[offset(self.source, cell)..],
+            None => &self.source[self.source.len()..],
+        }
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.cells.iter().all(|cell| cell.is_empty())
+    }
+}
+
+/// Offset of `part` in `source`, of which it is a slice
+fn offset(source: &str, part: &str) -> usize {
+    part.as_ptr() as usize - source.as_ptr() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_and_cells() {
+        let (input, array) = Array::parser(
+            r"\begin{array}{llcl@{\qquad}l}
+   \production{module} & \module &::=& \{ &
+     \MTYPES~\vec(\functype), \\&&&&
+     \MSTART~\start^? \quad\} \\
+   \production{limits} & \limits &::=& \{ \LMIN~\u32, \LMAX~\u32^? \} \\&&|&
+     \text{\&}~x \\
+   \end{array}  rest",
+        )
+        .unwrap();
+        assert_eq!(input, "rest");
+        assert_eq!(
+            array.columns,
+            vec![
+                Column::Left,
+                Column::Left,
+                Column::Center,
+                Column::Left,
+                Column::Left
+            ]
+        );
+        assert_eq!(array.relation_column(), 2);
+        let cells: Vec<_> = array.rows.iter().map(|r| r.cells.clone()).collect();
+        assert_eq!(
+            cells,
+            vec![
+                vec![
+                    r"\production{module}",
+                    r"\module",
+                    "::=",
+                    r"\{",
+                    r"\MTYPES~\vec(\functype),"
+                ],
+                vec!["", "", "", "", r"\MSTART~\start^? \quad\}"],
+                vec![
+                    r"\production{limits}",
+                    r"\limits",
+                    "::=",
+                    r"\{ \LMIN~\u32, \LMAX~\u32^? \}"
+                ],
+                vec!["", "", "|", r"\text{\&}~x"],
+            ]
+        );
+        assert_eq!(array.rows[3].from(3), r"\text{\&}~x ");
+        assert_eq!(array.rows[3].cell(7), "");
+        let module = [&array.rows[0], &array.rows[1]];
+        assert_eq!(
+            array.span(&module, 3),
+            r"\{ &
+     \MTYPES~\vec(\functype), \\&&&&
+     \MSTART~\start^? \quad\} "
+        );
+    }
+
+    #[test]
+    fn column_specs() {
+        let (_, array) = Array::parser(r"\begin{array}{@{}lcll} a &=& b \end{array}").unwrap();
+        assert_eq!(array.relation_column(), 1);
+        assert_eq!(array.rows[0].cells, vec!["a", "=", "b"]);
+
+        let (_, array) = Array::parser(r"\begin{array}{llll} a & {b & c} \end{array}").unwrap();
+        assert_eq!(array.relation_column(), 2);
+        assert_eq!(array.rows[0].cells, vec!["a", "{b & c}"]);
+
+        assert!(Array::parser(r"\begin{array}{ll} a & b").is_err());
+    }
+}
@@ -0,0 +1,395 @@
+use std::fmt::{self, Debug, Display};
+
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag},
+    character::complete::{char, digit1, satisfy},
+    combinator::{map, opt, recognize},
+    multi::{many0, many1},
+    sequence::{delimited, pair, preceded, terminated},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    nom_err,
+    parser::{closing, ws, Command, SeqKind},
+    syntax::symbol::{SNonterm, STerm},
+    PResult,
+};
+
+/// Meta variable of the binary and text formats, e.g. `n`, `\X{rt}_1` or
+/// `t^\ast`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Var {
+    pub name: String,
+    /// Subscript, e.g. `1` in `\X{rt}_1`
+    pub sub: Option<String>,
+    pub seq_kind: Option<SeqKind>,
+}
+
+/// Abstract syntax built by an attribute, i.e. the right of `\Rightarrow`
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+pub enum Expr {
+    /// Constructor, e.g. `\I32`
+    Term(String),
+    Var(Var),
+    /// `\epsilon`, the absence of a value
+    Epsilon,
+    /// `\{ \LMIN~n, \LMAX~\epsilon \}`
+    Record(Vec<(String, Vec<Expr>)>),
+    /// `[t^\ast]`
+    List(Vec<Expr>),
+    /// `\X{rt}_1 \to \X{rt}_2`
+    Arrow(Vec<Expr>, Vec<Expr>),
+    /// Identifier context extended by a record, e.g.
+    /// `\{\ILABELS~v\} \compose I`
+    Compose(Vec<Expr>, Vec<Expr>),
+    /// Nonterminal standing for one of its instances in a typing or
+    /// reduction rule, e.g. `\blocktype`, `\instr^\ast` or `\val_1`, with
+    /// its subscript
+    Meta(SNonterm, Option<String>),
+}
+
+impl Var {
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let (input, (name, upnote)) = alt((
+            Self::macro_name,
+            map(digit1, |n: &str| (n.to_string(), None)),
+            // primes name another variable, e.g. the context `I'`
+            map(
+                recognize(pair(
+                    satisfy(|c| c.is_ascii_alphabetic()),
+                    many0(char('\'')),
+                )),
+                |name: &str| (name.to_string(), None),
+            ),
+        ))(input)?;
+
+        let subscript = alt((
+            delimited(char('{'), is_not("}"), char('}')),
+            recognize(satisfy(|c| c.is_ascii_alphanumeric())),
+        ));
+        let (input, sub) = opt(preceded(char('_'), subscript))(input)?;
+        let (input, seq_kind) = match upnote {
+            Some(seq_kind) => (input, Some(seq_kind)),
+            None => SeqKind::parser(input)?,
+        };
+
+        let sub = sub.map(String::from);
+        Ok((
+            input,
+            Self {
+                name,
+                sub,
+                seq_kind,
+            },
+        ))
+    }
+
+    /// `\X{name}`
+    fn macro_name(input: &str) -> PResult<'_, (String, Option<SeqKind>)> {
+        let (tail, cmd) = Command::parser(input)?;
+        match (cmd.head.name, cmd.args.first()) {
+            ("X", Some(arg)) => Ok((tail, (arg.name().to_string(), cmd.upnote))),
+            _ => nom_err!(input, UnknownMacro, cmd.head.name.to_string()),
+        }
+    }
+
+    /// Abstract nonterminal naming the value it binds, as `\functype^\ast`
+    /// in `\functype^\ast{:\,}\Btypesec`
+    pub(crate) fn nonterminal(input: &str) -> PResult<'_, Self> {
+        let (tail, nt) = SNonterm::parser(input)?;
+        let abstract_name = nt.name.chars().all(|c| c.is_ascii_lowercase());
+        if !abstract_name || nt.context().is_some() || !nt.args().is_empty() {
+            return nom_err!(input, NotANonterminal, nt.name);
+        }
+        Ok((
+            tail,
+            Self {
+                name: nt.name,
+                sub: None,
+                seq_kind: nt.seq_kind,
+            },
+        ))
+    }
+
+    /// Whether the variable is a number that the binary format expects
+    /// literally, like `12` in `\hex{FC}~~12{:}\Bu32`
+    pub fn literal(&self) -> Option<u64> {
+        self.name.parse().ok()
+    }
+}
+
+impl Expr {
+    /// Sequence of expressions, optionally split by `\to` or `\compose`
+    pub fn action(input: &str) -> PResult<'_, Vec<Self>> {
+        Self::sequence(input, false)
+    }
+
+    /// Like [`Expr::action`], but also taking nonterminals as meta
+    /// variables, as the typing rules do in `C \vdash \instr^\ast : \X{ft}`
+    pub fn pattern(input: &str) -> PResult<'_, Vec<Self>> {
+        Self::sequence(input, true)
+    }
+
+    /// Single item of a pattern, e.g. `[t^\ast]` or `\blocktype`
+    pub fn pattern_item(input: &str) -> PResult<'_, Self> {
+        Self::item(input, true)
+    }
+
+    fn sequence(input: &str, meta: bool) -> PResult<'_, Vec<Self>> {
+        let (input, left) = many1(|i| Self::item(i, meta))(input)?;
+        match Command::parser(input) {
+            Ok((tail, cmd)) if cmd.head.name == "to" => {
+                let (tail, right) = many1(|i| Self::item(i, meta))(tail)?;
+                Ok((tail, vec![Self::Arrow(left, right)]))
+            }
+            Ok((tail, cmd)) if cmd.head.name == "compose" => {
+                let (tail, right) = many1(|i| Self::item(i, meta))(tail)?;
+                Ok((tail, vec![Self::Compose(left, right)]))
+            }
+            _ => Ok((input, left)),
+        }
+    }
+
+    fn item(input: &str, meta: bool) -> PResult<'_, Self> {
+        terminated(
+            alt((
+                |i| Self::record(i, meta),
+                |i| Self::list(i, meta),
+                |i| Self::paren(i, meta),
+                |i| Self::command(i, meta),
+                map(Var::parser, Self::Var),
+            )),
+            ws,
+        )(input)
+    }
+
+    /// Parenthesized expression, e.g. `(\epsilon)`
+    fn paren(input: &str, meta: bool) -> PResult<'_, Self> {
+        let (tail, _) = char('(')(input)?;
+        let (tail, _) = ws(tail)?;
+        let (tail, item) = Self::item(tail, meta)?;
+        let (tail, _) = char(')')(tail)?;
+        Ok((tail, item))
+    }
+
+    fn command(input: &str, meta: bool) -> PResult<'_, Self> {
+        let (tail, cmd) = Command::parser(input)?;
+        match cmd.head.name {
+            "epsilon" => Ok((tail, Self::Epsilon)),
+            "X" => map(Var::parser, Self::Var)(input),
+            _ => match cmd.is_terminal() {
+                Some(name) => Ok((tail, Self::Term(name.to_string()))),
+                None if meta => Self::meta(input),
+                None => nom_err!(input, NotATerminal, cmd.head.name.to_string()),
+            },
+        }
+    }
+
+    /// `\val_1^\ast`, whose subscript the nonterminal parser leaves
+    fn meta(input: &str) -> PResult<'_, Self> {
+        let (input, mut nt) = SNonterm::parser(input)?;
+        let subscript = alt((
+            delimited(char('{'), is_not("}"), char('}')),
+            recognize(satisfy(|c| c.is_ascii_lowercase() || c.is_ascii_digit())),
+        ));
+        let (input, sub) = opt(preceded(char('_'), subscript))(input)?;
+        let (input, seq_kind) = SeqKind::parser(input)?;
+        if seq_kind.is_some() {
+            nt.seq_kind = seq_kind;
+        }
+        Ok((input, Self::Meta(nt, sub.map(String::from))))
+    }
+
+    fn record(input: &str, meta: bool) -> PResult<'_, Self> {
+        let (mut input, _) = tag(r"\{")(input)?;
+        let mut pairs = vec![];
+        loop {
+            let (tail, _) = ws(input)?;
+            let (tail, key) = STerm::parser(tail)?;
+            let (tail, value) = many1(|i| Self::item(i, meta))(tail)?;
+            pairs.push((key, value));
+            match tag::<_, _, ()>(",")(tail) {
+                Ok((tail, _)) => input = tail,
+                Err(_) => {
+                    input = tail;
+                    break;
+                }
+            }
+        }
+        let (input, _) = closing(r"\}")(input)?;
+        Ok((input, Self::Record(pairs)))
+    }
+
+    /// `[t^\ast]`, or `[]` for the empty result type
+    fn list(input: &str, meta: bool) -> PResult<'_, Self> {
+        let (input, _) = char('[')(input)?;
+        let (input, _) = ws(input)?;
+        let (input, items) = many0(|i| Self::item(i, meta))(input)?;
+        let (input, _) = closing("]")(input)?;
+        Ok((input, Self::List(items)))
+    }
+}
+
+/// Side condition of an alternative, e.g. `(\iff x \geq 0)`, kept as written
+pub fn condition(input: &str) -> PResult<'_, String> {
+    let (input, _) = tag(r"(\iff")(input)?;
+    let mut depth = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                let cond = format!(r"\iff{}", &input[..i]);
+                let (tail, _) = ws(&input[i + 1..])?;
+                return Ok((tail, cond.trim_end().to_string()));
+            }
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    nom_err!(input, UnbalancedBrace)
+}
+
+impl Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.name.trim_end_matches('\'').chars().count() == 1 || self.literal().is_some() {
+            write!(f, "{}", self.name)?;
+        } else {
+            write!(f, r"\X{{{}}}", self.name)?;
+        }
+        match &self.sub {
+            Some(sub) if sub.chars().count() == 1 => write!(f, "_{}", sub)?,
+            Some(sub) => write!(f, "_{{{}}}", sub)?,
+            None => {}
+        }
+        match &self.seq_kind {
+            Some(seq_kind) => write!(f, "{}", seq_kind),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Term(name) => write!(f, r"\{}", name),
+            Expr::Var(var) => write!(f, "{}", var),
+            Expr::Epsilon => write!(f, r"\epsilon"),
+            Expr::Record(pairs) => {
+                let pairs: Vec<_> = pairs
+                    .iter()
+                    .map(|(key, value)| format!(r"\{}~{}", key, join(value)))
+                    .collect();
+                write!(f, r"\{{ {} \}}", pairs.join(", "))
+            }
+            Expr::List(items) => write!(f, "[{}]", join(items)),
+            Expr::Arrow(from, to) => write!(f, r"{} \to {}", join(from), join(to)),
+            Expr::Compose(record, context) => {
+                write!(f, r"{} \compose {}", join(record), join(context))
+            }
+            Expr::Meta(nt, None) => write!(f, "{}", nt),
+            Expr::Meta(nt, Some(sub)) => {
+                let seq_kind = nt.seq_kind().map(|s| s.to_string()).unwrap_or_default();
+                let nt = SNonterm::new(nt.name.clone(), None);
+                match sub.chars().count() {
+                    1 => write!(f, "{}_{}{}", nt, sub, seq_kind),
+                    _ => write!(f, "{}_{{{}}}{}", nt, sub, seq_kind),
+                }
+            }
+        }
+    }
+}
+
+impl Debug for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// Juxtaposed expressions, e.g. `\BLOCK~\X{bt}~\X{in}^\ast~\END`
+pub fn join(exprs: &[Expr]) -> String {
+    let exprs: Vec<_> = exprs.iter().map(|e| e.to_string()).collect();
+    exprs.join("~")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! test_action {
+        ($test_name: ident, $s: expr, $rest: expr, $printed: expr) => {
+            #[test]
+            fn $test_name() {
+                let (input, action) = Expr::action($s).unwrap();
+                assert_eq!(input, $rest);
+                assert_eq!(join(&action), $printed);
+            }
+        };
+    }
+
+    test_action!(action_term, r"\I32", "", r"\I32");
+    test_action!(action_var_pair, r"m~t \\ &&|&", "|&", "m~t");
+    test_action!(
+        action_record,
+        r"\{ \LMIN~n, \LMAX~\epsilon \} \\",
+        "",
+        r"\{ \LMIN~n, \LMAX~\epsilon \}"
+    );
+    test_action!(
+        action_arrow,
+        r"\X{rt}_1 \to \X{rt}_2",
+        "",
+        r"\X{rt}_1 \to \X{rt}_2"
+    );
+    test_action!(
+        action_list,
+        r"[t^\ast] \\ \end{array}",
+        r"\end{array}",
+        r"[t^\ast]"
+    );
+    test_action!(
+        action_compose,
+        r"\{\ILABELS~(\epsilon)\} \compose I \\",
+        "",
+        r"\{ \ILABELS~\epsilon \} \compose I"
+    );
+    test_action!(
+        action_constructor,
+        r"\BLOCK~\X{bt}~\X{in}^\ast~\END \\ \production{x}",
+        r"\production{x}",
+        r"\BLOCK~\X{bt}~\X{in}^\ast~\END"
+    );
+
+    #[test]
+    fn patterns() {
+        let (input, pattern) = Expr::pattern(r"\BLOCK~\blocktype~\instr^\ast~\END : t").unwrap();
+        assert_eq!(input, ": t");
+        assert!(matches!(&pattern[1], Expr::Meta(nt, None) if nt.name == "blocktype"));
+        assert_eq!(join(&pattern), r"\BLOCK~\blocktype~\instr^\ast~\END");
+        assert!(Expr::action(r"\blocktype").is_err());
+
+        let (_, pattern) = Expr::pattern(r"[] \to [t_1^\ast~\I32]").unwrap();
+        assert_eq!(join(&pattern), r"[] \to [t_1^\ast~\I32]");
+    }
+
+    #[test]
+    fn vars() {
+        let (input, var) = Var::parser(r"\X{in}_{12}^\ast").unwrap();
+        assert_eq!(input, "");
+        assert_eq!(var.name, "in");
+        assert_eq!(var.sub.as_deref(), Some("12"));
+        assert_eq!(var.seq_kind, Some(SeqKind::ManyPossibleEmpty));
+
+        let (input, var) = Var::parser(r"12{:}").unwrap();
+        assert_eq!(input, "{:}");
+        assert_eq!(var.literal(), Some(12));
+    }
+
+    #[test]
+    fn conditions() {
+        let (input, cond) = condition(r"(\iff x \geq 0 \wedge (x < 2)) \\ end").unwrap();
+        assert_eq!(input, "end");
+        assert_eq!(cond, r"\iff x \geq 0 \wedge (x < 2)");
+    }
+}
@@ -0,0 +1,324 @@
+//! Transformation of an owned syntax tree, in the style of `syn::fold`.
+//!
+//! Every method of [`Fold`] defaults to the free function of the same name,
+//! which rebuilds the node from its folded children.
+
+use crate::{
+    parser::SeqKind,
+    syntax::{
+        expr::{Expr, Var},
+        symbol::{SArrow, SBind, SBracedVec, SGroup, SNonterm, SRecord, SVec, Symbol},
+        Abbreviation, Lhs, MathBlock, Production, Rhs, RhsElem,
+    },
+};
+
+pub trait Fold {
+    fn fold_math_block(&mut self, node: MathBlock) -> MathBlock {
+        fold_math_block(self, node)
+    }
+
+    fn fold_abbreviation(&mut self, node: Abbreviation) -> Abbreviation {
+        fold_abbreviation(self, node)
+    }
+
+    fn fold_production(&mut self, node: Production) -> Production {
+        fold_production(self, node)
+    }
+
+    fn fold_lhs(&mut self, node: Lhs) -> Lhs {
+        fold_lhs(self, node)
+    }
+
+    /// Nonterminal defined by a left-hand side, e.g. `numtype`
+    fn fold_lhs_name(&mut self, name: String) -> String {
+        name
+    }
+
+    fn fold_rhs(&mut self, node: Rhs) -> Rhs {
+        fold_rhs(self, node)
+    }
+
+    fn fold_rhs_elem(&mut self, node: RhsElem) -> RhsElem {
+        fold_rhs_elem(self, node)
+    }
+
+    fn fold_symbol(&mut self, node: Symbol) -> Symbol {
+        fold_symbol(self, node)
+    }
+
+    /// Terminal, e.g. `I32` for `\I32`
+    fn fold_sterm(&mut self, name: String) -> String {
+        name
+    }
+
+    fn fold_snonterm(&mut self, node: SNonterm) -> SNonterm {
+        fold_snonterm(self, node)
+    }
+
+    fn fold_srecord(&mut self, node: SRecord) -> SRecord {
+        fold_srecord(self, node)
+    }
+
+    /// Record field, e.g. `LMAX` and `\u32^?` in `\LMAX~\u32^?`
+    fn fold_field(&mut self, key: String, value: Symbol) -> (String, Symbol) {
+        fold_field(self, key, value)
+    }
+
+    fn fold_sbraced_vec(&mut self, node: SBracedVec) -> SBracedVec {
+        fold_sbraced_vec(self, node)
+    }
+
+    fn fold_svec(&mut self, node: SVec) -> SVec {
+        fold_svec(self, node)
+    }
+
+    fn fold_sarrow(&mut self, node: SArrow) -> SArrow {
+        fold_sarrow(self, node)
+    }
+
+    /// Byte of the binary format, e.g. `0x7F` for `\hex{7F}`
+    fn fold_shex(&mut self, byte: u8) -> u8 {
+        byte
+    }
+
+    fn fold_sbind(&mut self, node: SBind) -> SBind {
+        fold_sbind(self, node)
+    }
+
+    fn fold_sgroup(&mut self, node: SGroup) -> SGroup {
+        fold_sgroup(self, node)
+    }
+
+    /// Keyword of the text format, e.g. `func` for `\text{func}`
+    fn fold_stext(&mut self, keyword: String) -> String {
+        keyword
+    }
+
+    fn fold_seq_kind(&mut self, node: SeqKind) -> SeqKind {
+        node
+    }
+
+    /// Side condition, e.g. `\iff n < 2^{32}`, kept as written
+    fn fold_cond(&mut self, cond: String) -> String {
+        cond
+    }
+
+    /// Expression of an attribute, right of `\Rightarrow`
+    fn fold_expr(&mut self, node: Expr) -> Expr {
+        fold_expr(self, node)
+    }
+
+    /// Constructor of an attribute, e.g. `I32` for `\Rightarrow \I32`
+    fn fold_expr_term(&mut self, name: String) -> String {
+        name
+    }
+
+    /// Record field of an attribute, e.g. `LMIN` and `n` in `\{ \LMIN~n \}`
+    fn fold_expr_field(&mut self, key: String, value: Vec<Expr>) -> (String, Vec<Expr>) {
+        fold_expr_field(self, key, value)
+    }
+
+    fn fold_var(&mut self, node: Var) -> Var {
+        fold_var(self, node)
+    }
+}
+
+pub fn fold_math_block<F: Fold + ?Sized>(f: &mut F, node: MathBlock) -> MathBlock {
+    MathBlock {
+        productions: node
+            .productions
+            .into_iter()
+            .map(|p| f.fold_production(p))
+            .collect(),
+        abbreviations: node
+            .abbreviations
+            .into_iter()
+            .map(|a| f.fold_abbreviation(a))
+            .collect(),
+    }
+}
+
+pub fn fold_abbreviation<F: Fold + ?Sized>(f: &mut F, node: Abbreviation) -> Abbreviation {
+    Abbreviation {
+        name: node.name,
+        short: node.short.into_iter().map(|s| f.fold_symbol(s)).collect(),
+        long: node.long.into_iter().map(|s| f.fold_symbol(s)).collect(),
+        cond: node.cond.map(|c| f.fold_cond(c)),
+    }
+}
+
+pub fn fold_production<F: Fold + ?Sized>(f: &mut F, node: Production) -> Production {
+    Production {
+        name: node.name,
+        lhs: f.fold_lhs(node.lhs),
+        rhs: f.fold_rhs(node.rhs),
+    }
+}
+
+pub fn fold_lhs<F: Fold + ?Sized>(f: &mut F, node: Lhs) -> Lhs {
+    Lhs {
+        names: node.names.into_iter().map(|n| f.fold_lhs_name(n)).collect(),
+    }
+}
+
+pub fn fold_rhs<F: Fold + ?Sized>(f: &mut F, node: Rhs) -> Rhs {
+    Rhs {
+        elems: node.elems.into_iter().map(|e| f.fold_rhs_elem(e)).collect(),
+    }
+}
+
+pub fn fold_rhs_elem<F: Fold + ?Sized>(f: &mut F, node: RhsElem) -> RhsElem {
+    RhsElem {
+        symbols: node.symbols.into_iter().map(|s| f.fold_symbol(s)).collect(),
+        action: node
+            .action
+            .map(|action| action.into_iter().map(|e| f.fold_expr(e)).collect()),
+        cond: node.cond.map(|c| f.fold_cond(c)),
+    }
+}
+
+pub fn fold_symbol<F: Fold + ?Sized>(f: &mut F, node: Symbol) -> Symbol {
+    match node {
+        Symbol::STerm(name) => Symbol::STerm(f.fold_sterm(name)),
+        Symbol::SNonterm(nt) => Symbol::SNonterm(f.fold_snonterm(nt)),
+        Symbol::SRecord(record) => Symbol::SRecord(f.fold_srecord(record)),
+        Symbol::SBracedVec(vec) => Symbol::SBracedVec(f.fold_sbraced_vec(vec)),
+        Symbol::SVec(vec) => Symbol::SVec(f.fold_svec(vec)),
+        Symbol::SArrow(arrow) => Symbol::SArrow(f.fold_sarrow(arrow)),
+        Symbol::SHex(byte) => Symbol::SHex(f.fold_shex(byte)),
+        Symbol::SBind(bind) => Symbol::SBind(f.fold_sbind(bind)),
+        Symbol::SGroup(group) => Symbol::SGroup(f.fold_sgroup(group)),
+        Symbol::SText(keyword) => Symbol::SText(f.fold_stext(keyword)),
+    }
+}
+
+pub fn fold_snonterm<F: Fold + ?Sized>(f: &mut F, node: SNonterm) -> SNonterm {
+    SNonterm {
+        name: node.name,
+        seq_kind: node.seq_kind.map(|s| f.fold_seq_kind(s)),
+        context: node.context,
+        args: node.args.into_iter().map(|s| f.fold_symbol(s)).collect(),
+    }
+}
+
+pub fn fold_srecord<F: Fold + ?Sized>(f: &mut F, node: SRecord) -> SRecord {
+    SRecord {
+        pairs: node
+            .pairs
+            .into_iter()
+            .map(|(key, value)| f.fold_field(key, value))
+            .collect(),
+    }
+}
+
+pub fn fold_field<F: Fold + ?Sized>(f: &mut F, key: String, value: Symbol) -> (String, Symbol) {
+    (key, f.fold_symbol(value))
+}
+
+pub fn fold_sbraced_vec<F: Fold + ?Sized>(f: &mut F, node: SBracedVec) -> SBracedVec {
+    SBracedVec {
+        inner: f.fold_svec(node.inner),
+    }
+}
+
+pub fn fold_svec<F: Fold + ?Sized>(f: &mut F, node: SVec) -> SVec {
+    SVec {
+        head: node.head,
+        over: Box::new(f.fold_snonterm(*node.over)),
+    }
+}
+
+pub fn fold_sarrow<F: Fold + ?Sized>(f: &mut F, node: SArrow) -> SArrow {
+    SArrow {
+        from: f.fold_snonterm(node.from),
+        to: f.fold_snonterm(node.to),
+    }
+}
+
+pub fn fold_sbind<F: Fold + ?Sized>(f: &mut F, node: SBind) -> SBind {
+    SBind {
+        var: f.fold_var(node.var),
+        symbol: Box::new(f.fold_symbol(*node.symbol)),
+    }
+}
+
+pub fn fold_sgroup<F: Fold + ?Sized>(f: &mut F, node: SGroup) -> SGroup {
+    SGroup {
+        symbols: node.symbols.into_iter().map(|s| f.fold_symbol(s)).collect(),
+        seq_kind: node.seq_kind.map(|s| f.fold_seq_kind(s)),
+    }
+}
+
+pub fn fold_expr<F: Fold + ?Sized>(f: &mut F, node: Expr) -> Expr {
+    let fold_all = |f: &mut F, exprs: Vec<Expr>| -> Vec<Expr> {
+        exprs.into_iter().map(|e| f.fold_expr(e)).collect()
+    };
+    match node {
+        Expr::Term(name) => Expr::Term(f.fold_expr_term(name)),
+        Expr::Var(var) => Expr::Var(f.fold_var(var)),
+        Expr::Epsilon => Expr::Epsilon,
+        Expr::Record(pairs) => Expr::Record(
+            pairs
+                .into_iter()
+                .map(|(key, value)| f.fold_expr_field(key, value))
+                .collect(),
+        ),
+        Expr::List(exprs) => Expr::List(fold_all(f, exprs)),
+        Expr::Arrow(left, right) => Expr::Arrow(fold_all(f, left), fold_all(f, right)),
+        Expr::Compose(left, right) => Expr::Compose(fold_all(f, left), fold_all(f, right)),
+        Expr::Meta(nt, sub) => Expr::Meta(f.fold_snonterm(nt), sub),
+    }
+}
+
+pub fn fold_expr_field<F: Fold + ?Sized>(
+    f: &mut F,
+    key: String,
+    value: Vec<Expr>,
+) -> (String, Vec<Expr>) {
+    (key, value.into_iter().map(|e| f.fold_expr(e)).collect())
+}
+
+pub fn fold_var<F: Fold + ?Sized>(f: &mut F, node: Var) -> Var {
+    Var {
+        name: node.name,
+        sub: node.sub,
+        seq_kind: node.seq_kind.map(|s| f.fold_seq_kind(s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replace every vector `\vec(\x)` with `\x^\ast`
+    struct Unvec;
+
+    impl Fold for Unvec {
+        fn fold_symbol(&mut self, node: Symbol) -> Symbol {
+            match node {
+                Symbol::SVec(vec) => Symbol::SNonterm(SNonterm::new(
+                    vec.over().name.clone(),
+                    Some(SeqKind::ManyPossibleEmpty),
+                )),
+                node => fold_symbol(self, node),
+            }
+        }
+    }
+
+    #[test]
+    fn unvec() {
+        let (_, mb) = MathBlock::parser(
+            r"\begin{array}{llll}
+    \production{element segment} & \elem &::=&
+      \{ \ETYPE~\reftype, \EINIT~\vec(\expr), \EMODE~\elemmode \} \\
+    \end{array}",
+        )
+        .unwrap();
+
+        let mb = Unvec.fold_math_block(mb);
+        assert_eq!(
+            mb.productions()[0].rhs().to_string(),
+            r"\{ \ETYPE~\reftype, \EINIT~\expr^\ast, \EMODE~\elemmode \}"
+        );
+    }
+}
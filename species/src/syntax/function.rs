@@ -0,0 +1,686 @@
+use std::fmt::{self, Debug, Display};
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{alpha1, char, digit1, satisfy},
+    combinator::{map, opt, recognize},
+    multi::{many0, separated_list0},
+    sequence::{pair, preceded},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    nom_err,
+    parser::{closing, group, top_level, ws},
+    syntax::{begin, end},
+    PResult,
+};
+
+/// Definition of a numeric operator by cases, e.g.
+/// `\iadd_N(i_1, i_2) = (i_1 + i_2) \mod 2^N`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct FunctionDef {
+    pub(crate) name: String,
+    /// Whether this defines the inverse, e.g. `\signed^{-1}_N`
+    pub(crate) inverse: bool,
+    /// Parameter of the width, e.g. `N`
+    pub(crate) width: Option<String>,
+    pub(crate) cases: Vec<Case>,
+}
+
+/// One equation of a definition, e.g.
+/// `\signed_N(i) = i - 2^N \quad (2^{N-1} \leq i < 2^N)`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Case {
+    pub(crate) params: Vec<Arith>,
+    pub(crate) body: Arith,
+    pub(crate) guard: Guard,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum Guard {
+    Always,
+    /// `(\iff i = 0)` or `(0 \leq i < 2^{N-1})`
+    When(Cond),
+    /// `(\otherwise)`
+    Otherwise,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Cond {
+    Compare(Arith, Relation, Arith),
+    /// `\wedge`, also standing for chains like `0 \leq i < 2^{N-1}`
+    And(Vec<Cond>),
+    /// `\vee`
+    Or(Vec<Cond>),
+    /// Any other condition, kept as written, e.g. one on bit sequences
+    Other(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Relation {
+    Eq,
+    Neq,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Arith {
+    Num(u64),
+    /// Parameter or bound variable, e.g. `i_1` or `N`
+    Var(String),
+    Neg(Box<Arith>),
+    Binary(Box<Arith>, Op, Box<Arith>),
+    /// Application of another definition or of a builtin like `\truncz`, e.g.
+    /// `\signed_N^{-1}(j)`
+    Call {
+        name: String,
+        width: Option<Box<Arith>>,
+        inverse: bool,
+        args: Vec<Arith>,
+    },
+    /// `\bool(i_1 = i_2)`
+    Bool(Box<Cond>),
+    /// `\{\}`, the absence of a result
+    Empty,
+    /// Any other expression, kept as written, e.g. one on bit sequences
+    Other(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+}
+
+impl FunctionDef {
+    /// Definitions in the rows of a block, the consecutive rows on one
+    /// operator being its cases
+    pub fn block(input: &str) -> PResult<'_, Vec<Self>> {
+        let (input, _) = ws(input)?;
+        let (mut input, array) = match begin(input) {
+            Ok((tail, ())) => (tail, true),
+            Err(_) => (input, false),
+        };
+        let mut definitions: Vec<Self> = vec![];
+        while !input.is_empty() && end(input).is_err() {
+            let (tail, (head, case)) = Self::row(input)?;
+            match definitions.last_mut() {
+                Some(last) if last.name == head.name && last.inverse == head.inverse => {
+                    last.cases.push(case)
+                }
+                _ => definitions.push(Self {
+                    cases: vec![case],
+                    ..head
+                }),
+            }
+            input = tail;
+        }
+        if array {
+            input = end(input)?.0;
+        }
+        let (input, _) = ws(input)?;
+        Ok((input, definitions))
+    }
+
+    /// Whether a block starts with an equation like `\iadd_N(i_1, i_2) =`
+    pub fn is_definition(input: &str) -> PResult<'_, ()> {
+        let (input, _) = ws(input)?;
+        let (input, _) = opt(begin)(input)?;
+        let (input, _) = Self::head(input)?;
+        let (input, _) = ws(input)?;
+        let (input, _) = char('=')(input)?;
+        Ok((input, ()))
+    }
+
+    /// One row, up to the next `\\`
+    fn row(input: &str) -> PResult<'_, (Self, Case)> {
+        let row_end = top_level(input, |rest| {
+            rest.starts_with(r"\\") || rest.starts_with(r"\end{")
+        })
+        .unwrap_or(input.len());
+        let (row, tail) = input.split_at(row_end);
+        let tail = tail.strip_prefix(r"\\").unwrap_or(tail);
+        let (tail, _) = ws(tail)?;
+
+        let (rest, (head, params)) = Self::head(row)?;
+        let (rest, _) = ws(rest)?;
+        let (rest, _) = char('=')(rest)?;
+        let (rest, _) = ws(rest)?;
+        let (body, guard) = match top_level(rest, |r| r.starts_with('&')) {
+            Some(i) => (&rest[..i], Guard::new(&rest[i + 1..])),
+            None => (rest, Guard::Always),
+        };
+        let case = Case {
+            params,
+            body: Arith::new(body),
+            guard,
+        };
+        Ok((tail, (head, case)))
+    }
+
+    /// `\iadd_N(i_1, i_2)` or `\signed^{-1}_N(i)`
+    fn head(input: &str) -> PResult<'_, (Self, Vec<Arith>)> {
+        let (input, (name, width, inverse)) = callee(input)?;
+        let width = match width {
+            None => None,
+            Some(Arith::Var(width)) => Some(width),
+            Some(_) => return nom_err!(input, Nom, nom::error::ErrorKind::Alpha),
+        };
+        let (input, params) = args(input)?;
+        let head = Self {
+            name: name.to_string(),
+            inverse,
+            width,
+            cases: vec![],
+        };
+        Ok((input, (head, params)))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn inverse(&self) -> bool {
+        self.inverse
+    }
+
+    pub fn width(&self) -> Option<&str> {
+        self.width.as_deref()
+    }
+
+    pub fn cases(&self) -> &[Case] {
+        &self.cases
+    }
+}
+
+impl Case {
+    pub fn params(&self) -> &[Arith] {
+        &self.params
+    }
+
+    pub fn body(&self) -> &Arith {
+        &self.body
+    }
+
+    pub fn guard(&self) -> &Guard {
+        &self.guard
+    }
+}
+
+impl Guard {
+    /// Read the last cell of a row, e.g. `(\iff i = 0)`
+    fn new(cell: &str) -> Self {
+        let cell = cell.trim_matches(|c: char| c == '&' || c.is_whitespace());
+        let cell = match cell.strip_prefix('(').and_then(|c| c.strip_suffix(')')) {
+            Some(inner) => inner.trim(),
+            None => cell,
+        };
+        let cell = cell.strip_prefix(r"\iff").unwrap_or(cell).trim();
+        match cell {
+            "" => Self::Always,
+            r"\otherwise" => Self::Otherwise,
+            cell => Self::When(Cond::new(cell)),
+        }
+    }
+}
+
+impl Cond {
+    /// Read a condition, keeping it as written unless it is arithmetic
+    pub fn new(source: &str) -> Self {
+        match Self::parser(source.trim()) {
+            Ok(("", cond)) => cond,
+            _ => Self::Other(source.trim().to_string()),
+        }
+    }
+
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let (input, first) = Self::conjunction(input)?;
+        let (input, rest) = many0(preceded(token(r"\vee"), Self::conjunction))(input)?;
+        Ok((input, flatten(first, rest, Self::Or)))
+    }
+
+    fn conjunction(input: &str) -> PResult<'_, Self> {
+        let (input, first) = Self::chain(input)?;
+        let (input, rest) = many0(preceded(token(r"\wedge"), Self::chain))(input)?;
+        Ok((input, flatten(first, rest, Self::And)))
+    }
+
+    /// `0 \leq i < 2^{N-1}`, i.e. `0 \leq i \wedge i < 2^{N-1}`
+    fn chain(input: &str) -> PResult<'_, Self> {
+        let (mut input, mut left) = Arith::parser(input)?;
+        let mut compares = vec![];
+        while let Ok((tail, relation)) = Relation::parser(input) {
+            let (tail, right) = Arith::parser(tail)?;
+            let next = right.clone();
+            compares.push(Self::Compare(left, relation, right));
+            left = next;
+            input = tail;
+        }
+        match compares.len() {
+            0 => nom_err!(input, Nom, nom::error::ErrorKind::Tag),
+            1 => Ok((input, compares.remove(0))),
+            _ => Ok((input, Self::And(compares))),
+        }
+    }
+}
+
+fn flatten(first: Cond, rest: Vec<Cond>, wrap: fn(Vec<Cond>) -> Cond) -> Cond {
+    if rest.is_empty() {
+        return first;
+    }
+    let mut conds = vec![first];
+    conds.extend(rest);
+    wrap(conds)
+}
+
+impl Relation {
+    fn parser(input: &str) -> PResult<'_, Self> {
+        let (input, _) = ws(input)?;
+        let (input, relation) = alt((
+            map(tag(r"\neq"), |_| Self::Neq),
+            map(tag(r"\leq"), |_| Self::Le),
+            map(tag(r"\geq"), |_| Self::Ge),
+            map(char('='), |_| Self::Eq),
+            map(char('<'), |_| Self::Lt),
+            map(char('>'), |_| Self::Gt),
+        ))(input)?;
+        let (input, _) = ws(input)?;
+        Ok((input, relation))
+    }
+}
+
+impl Arith {
+    /// Read an expression, keeping it as written unless it is arithmetic
+    pub fn new(source: &str) -> Self {
+        let source = source.trim_matches(|c: char| c == '&' || c.is_whitespace());
+        match Self::parser(source) {
+            Ok(("", arith)) => arith,
+            _ => Self::Other(source.to_string()),
+        }
+    }
+
+    /// Sum of products, e.g. `i_1 - i_2 \cdot \truncz(i_1 / i_2)`
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let (input, _) = ws(input)?;
+        let (mut input, mut sum) = Self::product(input)?;
+        loop {
+            let mut op = alt((map(token("+"), |_| Op::Add), map(token("-"), |_| Op::Sub)));
+            let Ok((tail, op)) = op(input) else {
+                break;
+            };
+            let (tail, right) = Self::product(tail)?;
+            sum = Self::Binary(Box::new(sum), op, Box::new(right));
+            input = tail;
+        }
+        Ok((input, sum))
+    }
+
+    fn product(input: &str) -> PResult<'_, Self> {
+        let (mut input, mut product) = Self::factor(input)?;
+        loop {
+            let mut op = alt((
+                map(token(r"\cdot"), |_| Op::Mul),
+                map(token("/"), |_| Op::Div),
+                map(token(r"\mod"), |_| Op::Mod),
+            ));
+            let Ok((tail, op)) = op(input) else {
+                break;
+            };
+            let (tail, right) = Self::factor(tail)?;
+            product = Self::Binary(Box::new(product), op, Box::new(right));
+            input = tail;
+        }
+        Ok((input, product))
+    }
+
+    /// `-x`, `2^N` or `2^{N-1}`
+    fn factor(input: &str) -> PResult<'_, Self> {
+        let (input, _) = ws(input)?;
+        if let Some(input) = input.strip_prefix('-') {
+            let (input, factor) = Self::factor(input)?;
+            return Ok((input, Self::Neg(Box::new(factor))));
+        }
+        let (input, base) = Self::atom(input)?;
+        match input.strip_prefix('^') {
+            Some(tail) => {
+                let (tail, exponent) = script(tail)?;
+                let power = Self::Binary(Box::new(base), Op::Pow, Box::new(exponent));
+                Ok((tail, power))
+            }
+            None => Ok((input, base)),
+        }
+    }
+
+    fn atom(input: &str) -> PResult<'_, Self> {
+        alt((
+            map(digit1, |n: &str| {
+                Self::Num(n.parse().expect("digits fit a number"))
+            }),
+            map(tag(r"\{\}"), |_| Self::Empty),
+            |input| {
+                let (input, _) = char('(')(input)?;
+                let (input, arith) = Self::parser(input)?;
+                let (input, _) = ws(input)?;
+                let (input, _) = closing(")")(input)?;
+                Ok((input, arith))
+            },
+            |input| {
+                let (input, _) = tag(r"\bool")(input)?;
+                let (input, _) = char('(')(input)?;
+                let (input, cond) = Cond::parser(input)?;
+                let (input, _) = closing(")")(input)?;
+                Ok((input, Self::Bool(Box::new(cond))))
+            },
+            |input| {
+                let (input, (name, width, inverse)) = callee(input)?;
+                let (input, args) = args(input)?;
+                let call = Self::Call {
+                    name: name.to_string(),
+                    width: width.map(Box::new),
+                    inverse,
+                    args,
+                };
+                Ok((input, call))
+            },
+            map(var, |name: &str| Self::Var(name.to_string())),
+        ))(input)
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Binary(_, Op::Add | Op::Sub, _) => 1,
+            Self::Binary(_, Op::Mul | Op::Div | Op::Mod, _) => 2,
+            Self::Binary(_, Op::Pow, _) | Self::Neg(_) => 3,
+            _ => 4,
+        }
+    }
+}
+
+/// `\signed`, then its width and whether it is the inverse, in either
+/// order, e.g. `\signed_N^{-1}` or `\signed^{-1}_N`
+fn callee(input: &str) -> PResult<'_, (&str, Option<Arith>, bool)> {
+    let (input, name) = preceded(char('\\'), alpha1)(input)?;
+    if matches!(name, "mod" | "cdot" | "wedge" | "vee" | "bool") {
+        return nom_err!(input, UnknownMacro, name.to_string());
+    }
+    let (input, width) = opt(preceded(char('_'), script))(input)?;
+    let (input, inverse) = opt(tag("^{-1}"))(input)?;
+    let (input, width) = match width {
+        Some(width) => (input, Some(width)),
+        None => opt(preceded(char('_'), script))(input)?,
+    };
+    Ok((input, (name, width, inverse.is_some())))
+}
+
+/// `(i_1, i_2)`
+fn args(input: &str) -> PResult<'_, Vec<Arith>> {
+    let (input, _) = char('(')(input)?;
+    let (input, args) = separated_list0(char(','), Arith::parser)(input)?;
+    let (input, _) = ws(input)?;
+    let (input, _) = closing(")")(input)?;
+    Ok((input, args))
+}
+
+/// Sub- or superscript, e.g. `N` or `{N-1}`
+fn script(input: &str) -> PResult<'_, Arith> {
+    if let Ok((tail, inner)) = group(input) {
+        return match Arith::parser(inner) {
+            Ok((rest, arith)) if rest.trim().is_empty() => Ok((tail, arith)),
+            _ => nom_err!(input, Nom, nom::error::ErrorKind::Verify),
+        };
+    }
+    alt((
+        map(satisfy(|c| c.is_ascii_digit()), |d| {
+            Arith::Num(d.to_digit(10).expect("a digit").into())
+        }),
+        map(satisfy(|c| c.is_ascii_alphabetic()), |c| {
+            Arith::Var(c.to_string())
+        }),
+    ))(input)
+}
+
+/// `i`, `i_1` or `j_{12}`
+fn var(input: &str) -> PResult<'_, &str> {
+    recognize(pair(
+        satisfy(|c| c.is_ascii_alphabetic()),
+        opt(preceded(
+            char('_'),
+            alt((
+                recognize(group),
+                recognize(satisfy(|c| c.is_ascii_alphanumeric())),
+            )),
+        )),
+    ))(input)
+}
+
+/// A symbol between optional spaces
+fn token(symbol: &'static str) -> impl Fn(&str) -> PResult<'_, &str> {
+    move |input| {
+        let (input, _) = ws(input)?;
+        let (input, symbol) = tag(symbol)(input)?;
+        let (input, _) = ws(input)?;
+        Ok((input, symbol))
+    }
+}
+
+impl Display for FunctionDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<_> = self
+            .cases
+            .iter()
+            .map(|case| {
+                let head = Arith::Call {
+                    name: self.name.clone(),
+                    width: self.width.clone().map(|w| Box::new(Arith::Var(w))),
+                    inverse: self.inverse,
+                    args: case.params.clone(),
+                };
+                match &case.guard {
+                    Guard::Always => format!("{} = {}", head, case.body),
+                    guard => format!("{} = {} \\quad ({})", head, case.body, guard),
+                }
+            })
+            .collect();
+        write!(f, r"{}", rows.join(r" \\ "))
+    }
+}
+
+impl Display for Guard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Guard::Always => Ok(()),
+            Guard::When(cond) => write!(f, r"\iff {}", cond),
+            Guard::Otherwise => write!(f, r"\otherwise"),
+        }
+    }
+}
+
+impl Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cond::Compare(left, relation, right) => write!(f, "{} {} {}", left, relation, right),
+            Cond::And(conds) => write!(f, "{}", join(conds, r" \wedge ")),
+            Cond::Or(conds) => write!(f, "{}", join(conds, r" \vee ")),
+            Cond::Other(cond) => write!(f, "{}", cond),
+        }
+    }
+}
+
+fn join(conds: &[Cond], separator: &str) -> String {
+    let conds: Vec<_> = conds.iter().map(|c| c.to_string()).collect();
+    conds.join(separator)
+}
+
+impl Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let relation = match self {
+            Relation::Eq => "=",
+            Relation::Neq => r"\neq",
+            Relation::Lt => "<",
+            Relation::Gt => ">",
+            Relation::Le => r"\leq",
+            Relation::Ge => r"\geq",
+        };
+        write!(f, "{}", relation)
+    }
+}
+
+impl Display for Arith {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arith::Num(n) => write!(f, "{}", n),
+            Arith::Var(name) => write!(f, "{}", name),
+            Arith::Neg(arith) if arith.precedence() < 4 => write!(f, "-({})", arith),
+            Arith::Neg(arith) => write!(f, "-{}", arith),
+            Arith::Binary(base, Op::Pow, exponent) => {
+                if base.precedence() < 4 {
+                    write!(f, "({})", base)?;
+                } else {
+                    write!(f, "{}", base)?;
+                }
+                write!(f, "^{}", Script(exponent))
+            }
+            Arith::Binary(left, op, right) => {
+                let precedence = self.precedence();
+                if left.precedence() < precedence {
+                    write!(f, "({})", left)?;
+                } else {
+                    write!(f, "{}", left)?;
+                }
+                write!(f, " {} ", op)?;
+                if right.precedence() <= precedence {
+                    write!(f, "({})", right)
+                } else {
+                    write!(f, "{}", right)
+                }
+            }
+            Arith::Call {
+                name,
+                width,
+                inverse,
+                args,
+            } => {
+                write!(f, r"\{}", name)?;
+                if let Some(width) = width {
+                    write!(f, "_{}", Script(width))?;
+                }
+                if *inverse {
+                    write!(f, "^{{-1}}")?;
+                }
+                let args: Vec<_> = args.iter().map(|a| a.to_string()).collect();
+                write!(f, "({})", args.join(", "))
+            }
+            Arith::Bool(cond) => write!(f, r"\bool({})", cond),
+            Arith::Empty => write!(f, r"\{{\}}"),
+            Arith::Other(source) => write!(f, "{}", source),
+        }
+    }
+}
+
+/// Sub- or superscript, braced unless it is a single character
+struct Script<'a>(&'a Arith);
+
+impl Display for Script<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let script = self.0.to_string();
+        if script.chars().count() == 1 {
+            write!(f, "{}", script)
+        } else {
+            write!(f, "{{{}}}", script)
+        }
+    }
+}
+
+impl Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => r"\cdot",
+            Op::Div => "/",
+            Op::Mod => r"\mod",
+            Op::Pow => "^",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definitions(source: &str) -> Vec<String> {
+        let (input, definitions) = FunctionDef::block(source).unwrap();
+        assert_eq!(input, "");
+        definitions.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(
+            definitions(
+                r"\begin{array}{@{}lcll}
+   \iadd_N(i_1, i_2) &=& (i_1 + i_2) \mod 2^N
+   \end{array}"
+            ),
+            vec![r"\iadd_N(i_1, i_2) = (i_1 + i_2) \mod 2^N"]
+        );
+        assert_eq!(
+            definitions(r"\iremu_N(i_1, i_2) &=& i_1 - i_2 \cdot \truncz(i_1 / i_2)"),
+            vec![r"\iremu_N(i_1, i_2) = i_1 - i_2 \cdot \truncz(i_1 / i_2)"]
+        );
+    }
+
+    #[test]
+    fn cases() {
+        let (_, definitions) = FunctionDef::block(
+            r"\begin{array}{lll@{\qquad}l}
+   \signed_N(i) &=& i & (0 \leq i < 2^{N-1}) \\
+   \signed_N(i) &=& i - 2^N & (2^{N-1} \leq i < 2^N) \\
+   \signed^{-1}_N(i) &=& i \mod 2^N \\
+   \end{array}",
+        )
+        .unwrap();
+        assert_eq!(definitions.len(), 2);
+        assert_eq!(definitions[0].cases().len(), 2);
+        assert_eq!(
+            definitions[0].cases()[1].guard().to_string(),
+            r"\iff 2^{N - 1} \leq i \wedge i < 2^N"
+        );
+        assert!(definitions[1].inverse());
+        assert_eq!(definitions[1].width(), Some("N"));
+
+        let (_, definitions) = FunctionDef::block(
+            r"\begin{array}{@{}lcll}
+   \ieqz_N(i) &=& 1 & (\iff i = 0) \\
+   \ieqz_N(i) &=& 0 & (\otherwise) \\
+   \end{array}",
+        )
+        .unwrap();
+        assert_eq!(definitions[0].cases()[1].guard(), &Guard::Otherwise);
+    }
+
+    #[test]
+    fn bit_sequences() {
+        let (_, definitions) = FunctionDef::block(
+            r"\ishl_N(i_1, i_2) &=& \bits_N^{-1}(d_2^{N-k}~0^k) & (\iff \bits_N(i_1) = d_1^k~d_2^{N-k} \wedge k = i_2 \mod N)",
+        )
+        .unwrap();
+        let case = &definitions[0].cases()[0];
+        assert_eq!(
+            case.body(),
+            &Arith::Other(r"\bits_N^{-1}(d_2^{N-k}~0^k)".to_string())
+        );
+        assert!(matches!(case.guard(), Guard::When(Cond::Other(_))));
+        assert!(FunctionDef::is_definition(r"\F{expand}(\X{ft}) &=& \X{ft}").is_err());
+    }
+}
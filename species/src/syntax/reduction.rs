@@ -0,0 +1,263 @@
+use std::fmt::{self, Debug, Display};
+
+use nom::error::ErrorKind;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    nom_err,
+    parser::{split, top_level, ws},
+    syntax::{
+        begin, end,
+        expr::{condition, join, Expr, Var},
+    },
+    PResult, SpeciesError,
+};
+
+/// Reduction rule of the execution chapter, e.g.
+/// `F; (\LOCALGET~x) \stepto F; \val \quad (\iff F.\ALOCALS[x] = \val)`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reduction {
+    pub(crate) lhs: Configuration,
+    pub(crate) rhs: Configuration,
+    /// Side condition, kept as written
+    pub(crate) cond: Option<String>,
+}
+
+/// State and instruction sequence, e.g. `F; \val~(\LOCALSET~x)`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Configuration {
+    /// Store and frame, e.g. `S` and `F` in `S; F; \instr^\ast`
+    pub(crate) state: Vec<Var>,
+    /// Instructions, each the items between `~`, e.g.
+    /// `[\I32, \CONST, c]` for `(\I32.\CONST~c)`
+    pub(crate) instrs: Vec<Vec<Expr>>,
+}
+
+impl Reduction {
+    /// Rows of a reduction block, with or without `\begin{array}`
+    pub fn block(input: &str) -> PResult<'_, Vec<Self>> {
+        let (input, _) = ws(input)?;
+        let (mut input, array) = match begin(input) {
+            Ok((tail, ())) => (tail, true),
+            Err(_) => (input, false),
+        };
+        let mut reductions = vec![];
+        while !input.is_empty() && end(input).is_err() {
+            let (tail, reduction) = Self::parser(input)?;
+            reductions.push(reduction);
+            input = tail;
+        }
+        if array {
+            input = end(input)?.0;
+        }
+        let (input, _) = ws(input)?;
+        Ok((input, reductions))
+    }
+
+    /// One row, up to the next `\\`
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let row_end = top_level(input, |rest| {
+            rest.starts_with(r"\\") || rest.starts_with(r"\end{")
+        })
+        .unwrap_or(input.len());
+        let (row, tail) = input.split_at(row_end);
+        let tail = tail.strip_prefix(r"\\").unwrap_or(tail);
+        let (tail, _) = ws(tail)?;
+
+        let Some(arrow) = top_level(row, |rest| rest.starts_with(r"\stepto")) else {
+            return nom_err!(input, Nom, ErrorKind::Tag);
+        };
+        let lhs = &row[..arrow];
+        let rhs = &row[arrow + r"\stepto".len()..];
+        let (rhs, cond) = match top_level(rhs, |rest| rest.starts_with(r"(\iff")) {
+            Some(i) => {
+                let (rest, cond) = condition(&rhs[i..])?;
+                if !rest.trim().is_empty() {
+                    return nom_err!(rest, Nom, ErrorKind::Eof);
+                }
+                (&rhs[..i], Some(cond))
+            }
+            None => (rhs, None),
+        };
+
+        let lhs =
+            Configuration::new(lhs).map_err(|e| e.map(|e| e.in_context(input, "reduction")))?;
+        let rhs =
+            Configuration::new(rhs).map_err(|e| e.map(|e| e.in_context(input, "reduction")))?;
+        Ok((tail, Self { lhs, rhs, cond }))
+    }
+
+    pub fn lhs(&self) -> &Configuration {
+        &self.lhs
+    }
+
+    pub fn rhs(&self) -> &Configuration {
+        &self.rhs
+    }
+
+    pub fn cond(&self) -> Option<&str> {
+        self.cond.as_deref()
+    }
+}
+
+impl Configuration {
+    /// Read a configuration written between the `&` of a row
+    pub fn new(source: &str) -> Result<Self, nom::Err<SpeciesError<&str>>> {
+        let source = source.trim_matches(|c: char| c == '&' || c.is_whitespace());
+        let mut parts = split(source, ';');
+        let instrs = parts.pop().unwrap_or_default();
+
+        let mut state = vec![];
+        for part in parts {
+            match Var::parser(part.trim()) {
+                Ok(("", var)) => state.push(var),
+                Ok((rest, _)) => return nom_err!(rest, Nom, ErrorKind::Eof),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let mut items = vec![];
+        for instr in split(instrs, '~') {
+            let instr = instr.trim();
+            let instr = match instr.strip_prefix('(').and_then(|i| i.strip_suffix(')')) {
+                Some(inner) if top_level(inner, |rest| rest.starts_with(')')).is_none() => inner,
+                _ => instr,
+            };
+            if instr.is_empty() || instr == r"\epsilon" {
+                continue;
+            }
+            items.push(Self::instr(instr)?);
+        }
+        Ok(Self {
+            state,
+            instrs: items,
+        })
+    }
+
+    /// `\I32.\CONST~c` or `t\K{.}\binop`, with the dots left out
+    fn instr(source: &str) -> Result<Vec<Expr>, nom::Err<SpeciesError<&str>>> {
+        // dots separate the parts of an opcode, as in `\I32.\CONST`
+        let (rest, items) = {
+            let mut items = vec![];
+            let mut input = source;
+            loop {
+                let (tail, _) = ws(input)?;
+                if tail.is_empty() {
+                    break (tail, items);
+                }
+                if let Some(tail) = tail.strip_prefix('.') {
+                    input = tail;
+                    continue;
+                }
+                match Expr::pattern_item(tail) {
+                    Ok((tail, item)) => {
+                        items.push(item);
+                        input = tail;
+                    }
+                    Err(_) => break (tail, items),
+                }
+            }
+        };
+        if !rest.is_empty() || items.is_empty() {
+            return nom_err!(rest, Nom, ErrorKind::Eof);
+        }
+        Ok(items.into_iter().flat_map(opcode).collect())
+    }
+
+    pub fn state(&self) -> &[Var] {
+        &self.state
+    }
+
+    pub fn instrs(&self) -> &[Vec<Expr>] {
+        &self.instrs
+    }
+}
+
+/// Split the terms of an opcode at their dots, e.g. `\K{i32.}` into `\I32`
+pub fn opcode(item: Expr) -> Vec<Expr> {
+    match item {
+        Expr::Term(term) => term
+            .split('.')
+            .filter(|part| !part.is_empty())
+            .map(|part| Expr::Term(part.to_uppercase()))
+            .collect(),
+        item => vec![item],
+    }
+}
+
+impl Display for Reduction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, r"{} \stepto {}", self.lhs, self.rhs)?;
+        match &self.cond {
+            Some(cond) => write!(f, " ({})", cond),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for Configuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for var in &self.state {
+            write!(f, "{}; ", var)?;
+        }
+        if self.instrs.is_empty() {
+            return write!(f, r"\epsilon");
+        }
+        let instrs: Vec<_> = self
+            .instrs
+            .iter()
+            .map(|instr| match instr.as_slice() {
+                [item] => item.to_string(),
+                items => format!("({})", join(items)),
+            })
+            .collect();
+        write!(f, "{}", instrs.join("~"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows() {
+        let (input, reductions) = Reduction::block(
+            r"\begin{array}{lcl@{\qquad}l}
+   \val_1~\val_2~(\I32.\CONST~c)~\SELECT &\stepto& \val_1 & (\iff c \neq 0) \\
+   (t\K{.}\CONST~c_1)~t\K{.}\unop &\stepto& (t\K{.}\CONST~c) & (\iff c = \unop_t(c_1)) \\
+   \NOP &\stepto& \epsilon \\
+   \end{array}",
+        )
+        .unwrap();
+        assert_eq!(input, "");
+        let printed: Vec<_> = reductions.iter().map(|r| r.to_string()).collect();
+        assert_eq!(
+            printed,
+            vec![
+                r"\val_1~\val_2~(\I32~\CONST~c)~\SELECT \stepto \val_1 (\iff c \neq 0)",
+                r"(t~\CONST~c_1)~(t~\unop) \stepto (t~\CONST~c) (\iff c = \unop_t(c_1))",
+                r"\NOP \stepto \epsilon",
+            ]
+        );
+    }
+
+    #[test]
+    fn frames() {
+        let (_, reduction) =
+            Reduction::parser(r"F; \val~(\LOCALSET~x) &\stepto& F'; \epsilon & (\iff F' = F \with \ALOCALS[x] = \val)")
+                .unwrap();
+        assert_eq!(reduction.lhs().state()[0].name, "F");
+        assert_eq!(reduction.rhs().state()[0].name, "F'");
+        assert!(reduction.rhs().instrs().is_empty());
+        assert_eq!(
+            reduction.cond(),
+            Some(r"\iff F' = F \with \ALOCALS[x] = \val")
+        );
+    }
+
+    #[test]
+    fn errors() {
+        assert!(Reduction::parser(r"\NOP &=& \epsilon").is_err());
+        assert!(Reduction::parser(r"\NOP &\stepto& \val ? \val").is_err());
+    }
+}
@@ -0,0 +1,355 @@
+use std::fmt::{self, Debug, Display};
+
+use nom::{
+    bytes::complete::tag,
+    character::complete::{alphanumeric1, char, satisfy},
+    combinator::{opt, recognize},
+    multi::{many0, many1},
+    sequence::preceded,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    nom_err,
+    parser::{group, ws},
+    syntax::expr::{join, Expr},
+    PResult,
+};
+
+/// Typing rule of the validation chapter, e.g.
+/// `\frac{C.\CLOCALS[x] = t}{C \vdash \LOCALGET~x : [] \to [t]}`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub(crate) premises: Vec<Premise>,
+    pub(crate) conclusion: Judgement,
+}
+
+/// `C \vdash \X{phrase} : \X{type}`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Judgement {
+    pub(crate) context: Context,
+    pub(crate) phrase: Vec<Expr>,
+    pub(crate) ty: Vec<Expr>,
+}
+
+/// Context of a judgement, possibly extended in front, e.g.
+/// `C,\CLABELS\,[t^\ast]`
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Context {
+    pub(crate) name: String,
+    /// Fields and the entries prepended to them, e.g. `(CLABELS, [t^\ast])`
+    pub(crate) extensions: Vec<(String, Expr)>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub enum Premise {
+    Judgement(Judgement),
+    /// Projection of the context, e.g. `C.\CLOCALS[x] = t` or
+    /// `C.\CRETURN = [t^\ast]`
+    Lookup {
+        field: String,
+        index: Option<Expr>,
+        value: Vec<Expr>,
+    },
+    /// Any other premise, kept as written
+    Other(String),
+}
+
+impl Rule {
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let (input, _) = tag(r"\frac")(input)?;
+        let (input, _) = ws(input)?;
+        let (input, premises) = group(input)?;
+        let (input, _) = ws(input)?;
+        let (input, conclusion) = group(input)?;
+        let (input, _) = ws(input)?;
+
+        let conclusion = conclusion.trim();
+        let conclusion = match Judgement::parser(conclusion) {
+            Ok(("", judgement)) => judgement,
+            Ok((rest, _)) => return nom_err!(rest, UnknownMacro, "vdash".to_string()),
+            Err(e) => return Err(e.map(|e| e.in_context(conclusion, "conclusion"))),
+        };
+        let premises = split_premises(premises)
+            .into_iter()
+            .map(Premise::new)
+            .collect();
+        Ok((
+            input,
+            Self {
+                premises,
+                conclusion,
+            },
+        ))
+    }
+
+    pub fn new(premises: Vec<Premise>, conclusion: Judgement) -> Self {
+        Self {
+            premises,
+            conclusion,
+        }
+    }
+
+    pub fn premises(&self) -> &[Premise] {
+        &self.premises
+    }
+
+    pub fn conclusion(&self) -> &Judgement {
+        &self.conclusion
+    }
+}
+
+impl Judgement {
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let (input, context) = Context::parser(input)?;
+        let (input, _) = tag(r"\vdash")(input)?;
+        let (input, _) = ws(input)?;
+        let (input, phrase) = many1(Expr::pattern_item)(input)?;
+        let (input, _) = char(':')(input)?;
+        let (input, _) = ws(input)?;
+        let (input, ty) = Expr::pattern(input)?;
+        Ok((
+            input,
+            Self {
+                context,
+                phrase,
+                ty,
+            },
+        ))
+    }
+
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Phrase being typed, e.g. `\LOCALGET~x`
+    pub fn phrase(&self) -> &[Expr] {
+        &self.phrase
+    }
+
+    /// Type of the phrase, e.g. `[] \to [t]`
+    pub fn ty(&self) -> &[Expr] {
+        &self.ty
+    }
+}
+
+impl Context {
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let (input, name) = recognize(preceded(
+            satisfy(|c| c.is_ascii_uppercase()),
+            many0(char('\'')),
+        ))(input)?;
+        let (input, extensions) = many0(Self::extension)(input)?;
+        let (input, _) = ws(input)?;
+        let context = Self {
+            name: name.to_string(),
+            extensions,
+        };
+        Ok((input, context))
+    }
+
+    /// `,\CLABELS\,[t^\ast]`
+    fn extension(input: &str) -> PResult<'_, (String, Expr)> {
+        let (input, _) = char(',')(input)?;
+        let (input, _) = ws(input)?;
+        let (input, field) = preceded(char('\\'), alphanumeric1)(input)?;
+        let (input, _) = opt(tag(r"\,"))(input)?;
+        let (input, _) = ws(input)?;
+        let (input, entry) = Expr::pattern_item(input)?;
+        Ok((input, (field.to_string(), entry)))
+    }
+
+    /// Name of the context, e.g. `C`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn extensions(&self) -> &[(String, Expr)] {
+        &self.extensions
+    }
+}
+
+impl Premise {
+    /// Read a premise, keeping it as written unless it is a judgement or a
+    /// lookup in the context
+    pub fn new(premise: &str) -> Self {
+        if let Ok(("", judgement)) = Judgement::parser(premise) {
+            return Self::Judgement(judgement);
+        }
+        if let Ok(("", lookup)) = Self::lookup(premise) {
+            return lookup;
+        }
+        Self::Other(premise.to_string())
+    }
+
+    /// `C.\CLOCALS[x] = t`
+    fn lookup(input: &str) -> PResult<'_, Self> {
+        let (input, _) = satisfy(|c| c.is_ascii_uppercase())(input)?;
+        let (input, _) = char('.')(input)?;
+        let (input, field) = preceded(char('\\'), alphanumeric1)(input)?;
+        let (input, index) = opt(|input| {
+            let (input, _) = char('[')(input)?;
+            let (input, _) = ws(input)?;
+            let (input, index) = Expr::pattern_item(input)?;
+            let (input, _) = char(']')(input)?;
+            Ok((input, index))
+        })(input)?;
+        let (input, _) = ws(input)?;
+        let (input, _) = char('=')(input)?;
+        let (input, _) = ws(input)?;
+        let (input, value) = Expr::pattern(input)?;
+        let lookup = Self::Lookup {
+            field: field.to_string(),
+            index,
+            value,
+        };
+        Ok((input, lookup))
+    }
+}
+
+/// Premises separated by `\qquad` outside of braces
+fn split_premises(premises: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    let mut i = 0;
+    while i < premises.len() {
+        let rest = &premises[i..];
+        match rest.as_bytes()[0] {
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            _ if depth == 0 && rest.starts_with(r"\qquad") => {
+                parts.push(&premises[start..i]);
+                i += r"\qquad".len();
+                start = i;
+                continue;
+            }
+            _ => {}
+        }
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+    parts.push(&premises[start..]);
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let premises: Vec<_> = self.premises.iter().map(|p| p.to_string()).collect();
+        write!(
+            f,
+            r"\frac{{{}}}{{{}}}",
+            premises.join(r" \qquad "),
+            self.conclusion
+        )
+    }
+}
+
+impl Display for Judgement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            r"{} \vdash {} : {}",
+            self.context,
+            join(&self.phrase),
+            join(&self.ty)
+        )
+    }
+}
+
+impl Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        for (field, entry) in &self.extensions {
+            write!(f, r",\{}\,{}", field, entry)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Premise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Premise::Judgement(judgement) => write!(f, "{}", judgement),
+            Premise::Lookup {
+                field,
+                index: Some(index),
+                value,
+            } => write!(f, r"C.\{}[{}] = {}", field, index, join(value)),
+            Premise::Lookup {
+                field,
+                index: None,
+                value,
+            } => write!(f, r"C.\{} = {}", field, join(value)),
+            Premise::Other(premise) => write!(f, "{}", premise),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_get() {
+        let (input, rule) = Rule::parser(
+            r"\frac{
+     C.\CLOCALS[x] = t
+   }{
+     C \vdash \LOCALGET~x : [] \to [t]
+   }",
+        )
+        .unwrap();
+        assert_eq!(input, "");
+        assert_eq!(
+            rule.to_string(),
+            r"\frac{C.\CLOCALS[x] = t}{C \vdash \LOCALGET~x : [] \to [t]}"
+        );
+        assert!(matches!(
+            &rule.premises()[0],
+            Premise::Lookup { field, index: Some(_), .. } if field == "CLOCALS"
+        ));
+    }
+
+    #[test]
+    fn block() {
+        let (input, rule) = Rule::parser(
+            r"\frac{
+     C \vdash \blocktype : [t_1^\ast] \to [t_2^\ast]
+     \qquad
+     C,\CLABELS\,[t_2^\ast] \vdash \instr^\ast : [t_1^\ast] \to [t_2^\ast]
+   }{
+     C \vdash \BLOCK~\blocktype~\instr^\ast~\END : [t_1^\ast] \to [t_2^\ast]
+   }",
+        )
+        .unwrap();
+        assert_eq!(input, "");
+        let Premise::Judgement(body) = &rule.premises()[1] else {
+            panic!("{:?}", rule.premises()[1]);
+        };
+        assert_eq!(body.context().to_string(), r"C,\CLABELS\,[t_2^\ast]");
+        assert_eq!(join(body.phrase()), r"\instr^\ast");
+        assert_eq!(
+            rule.conclusion().to_string(),
+            r"C \vdash \BLOCK~\blocktype~\instr^\ast~\END : [t_1^\ast] \to [t_2^\ast]"
+        );
+    }
+
+    #[test]
+    fn axioms_and_other_premises() {
+        let (_, rule) = Rule::parser(r"\frac{}{C \vdash \NOP : [] \to []}").unwrap();
+        assert!(rule.premises().is_empty());
+
+        let (_, rule) =
+            Rule::parser(r"\frac{t_1 \neq t_2}{C \vdash \SELECT~t_1 : [t_1] \to [t_2]}").unwrap();
+        assert_eq!(
+            rule.premises(),
+            [Premise::Other(r"t_1 \neq t_2".to_string())]
+        );
+
+        assert!(Rule::parser(r"\frac{}{C \vdash t.\CONST~c : [] \to [t]}").is_err());
+    }
+}
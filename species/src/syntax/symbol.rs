@@ -1,17 +1,27 @@
-use std::fmt::Debug;
+use std::fmt::{self, Debug, Display};
 
 use nom::{
-    branch::alt, bytes::complete::tag, character::complete::char, combinator::map,
-    error::ErrorKind, multi::separated_list1, sequence::delimited, InputIter,
+    branch::alt,
+    bytes::complete::is_not,
+    bytes::complete::tag,
+    character::complete::{char, digit1, satisfy},
+    combinator::{map, not, opt, recognize},
+    error::context,
+    multi::{many0, many1, separated_list1},
+    sequence::{delimited, pair, preceded, terminated},
+    InputIter,
 };
+use serde::{Deserialize, Serialize};
 
 use crate::{
     nom_err,
-    parser::{ws, Command, SeqKind},
+    parser::{closing, group, ws, Command, SeqKind},
+    syntax::expr::Var,
+    tex::{self, Token},
     PResult,
 };
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
 pub enum Symbol {
     STerm(String),
     SNonterm(SNonterm),
@@ -19,20 +29,34 @@ pub enum Symbol {
     SBracedVec(SBracedVec),
     SVec(SVec),
     SArrow(SArrow),
+    /// Byte of the binary format, e.g. `\hex{7F}`
+    SHex(u8),
+    SBind(SBind),
+    SGroup(SGroup),
+    /// Keyword of the text format, e.g. `(` for `\text{(}`
+    SText(String),
 }
 
 #[derive(Debug, PartialEq)]
-struct STerm;
+pub(crate) struct STerm;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct SNonterm {
     pub name: String,
-    seq_kind: Option<SeqKind>,
+    pub(crate) seq_kind: Option<SeqKind>,
+    /// Identifier context of the text format, e.g. `I'` in `\Tinstr_{I'}`,
+    /// or the subscript of an applied nonterminal, e.g. `1` in
+    /// `\Bsection_1(\Bvec(\Bfunctype))`
+    pub(crate) context: Option<String>,
+    /// Arguments of a parameterized nonterminal, e.g. `\Bvec(\Bfunctype)` in
+    /// `\Bsection_1(\Bvec(\Bfunctype))`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) args: Vec<Symbol>,
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
 pub struct SRecord {
-    pairs: Vec<(String, Symbol)>,
+    pub(crate) pairs: Vec<(String, Symbol)>,
 }
 
 impl Debug for SRecord {
@@ -41,39 +65,194 @@ impl Debug for SRecord {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
 pub struct SBracedVec {
-    inner: SVec,
+    pub(crate) inner: SVec,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct SVec {
-    over: Box<SNonterm>,
+    /// `vec`, or `Bvec` and `Tvec` in the binary and text formats
+    pub(crate) head: String,
+    pub(crate) over: Box<SNonterm>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct SArrow {
-    from: SNonterm,
-    to: SNonterm,
+    pub(crate) from: SNonterm,
+    pub(crate) to: SNonterm,
+}
+
+/// Symbol whose value is named for the attribute, e.g. `n{:}\Bu32`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SBind {
+    pub(crate) var: Var,
+    pub(crate) symbol: Box<Symbol>,
+}
+
+/// Parenthesized symbols, e.g. `(\X{in}{:}\Binstr)^\ast`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct SGroup {
+    pub(crate) symbols: Vec<Symbol>,
+    pub(crate) seq_kind: Option<SeqKind>,
+}
+
+impl SNonterm {
+    pub fn new(name: impl Into<String>, seq_kind: Option<SeqKind>) -> Self {
+        Self {
+            name: name.into(),
+            seq_kind,
+            context: None,
+            args: vec![],
+        }
+    }
+
+    /// Iteration written as a superscript, e.g. `^?` in `\u32^?`
+    pub fn seq_kind(&self) -> Option<&SeqKind> {
+        self.seq_kind.as_ref()
+    }
+
+    pub fn context(&self) -> Option<&str> {
+        self.context.as_deref()
+    }
+
+    pub fn args(&self) -> &[Symbol] {
+        &self.args
+    }
+}
+
+impl SRecord {
+    pub fn new(pairs: Vec<(String, Symbol)>) -> Self {
+        Self { pairs }
+    }
+
+    /// Fields and their types, e.g. `(LMIN, \u32)` in `\{ \LMIN~\u32 \}`
+    pub fn pairs(&self) -> &[(String, Symbol)] {
+        &self.pairs
+    }
+}
+
+impl SBracedVec {
+    pub fn new(inner: SVec) -> Self {
+        Self { inner }
+    }
+
+    pub fn inner(&self) -> &SVec {
+        &self.inner
+    }
+}
+
+impl SVec {
+    pub fn new(over: SNonterm) -> Self {
+        Self {
+            head: "vec".to_string(),
+            over: Box::new(over),
+        }
+    }
+
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    pub fn over(&self) -> &SNonterm {
+        &self.over
+    }
+}
+
+impl SArrow {
+    pub fn new(from: SNonterm, to: SNonterm) -> Self {
+        Self { from, to }
+    }
+
+    pub fn from(&self) -> &SNonterm {
+        &self.from
+    }
+
+    pub fn to(&self) -> &SNonterm {
+        &self.to
+    }
+}
+
+impl SBind {
+    pub fn new(var: Var, symbol: Symbol) -> Self {
+        Self {
+            var,
+            symbol: Box::new(symbol),
+        }
+    }
+
+    pub fn var(&self) -> &Var {
+        &self.var
+    }
+
+    pub fn symbol(&self) -> &Symbol {
+        &self.symbol
+    }
+}
+
+impl SGroup {
+    pub fn new(symbols: Vec<Symbol>, seq_kind: Option<SeqKind>) -> Self {
+        Self { symbols, seq_kind }
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    pub fn seq_kind(&self) -> Option<&SeqKind> {
+        self.seq_kind.as_ref()
+    }
 }
 
 impl Symbol {
-    pub fn parser(input: &str) -> PResult<Self> {
-        let mut parser = alt((
-            map(SRecord::parser, Symbol::SRecord),
-            map(SArrow::parser, Symbol::SArrow),
-            map(SBracedVec::parser, Symbol::SBracedVec),
-            map(SVec::parser, Symbol::SVec),
-            map(SNonterm::parser, Symbol::SNonterm),
-            map(STerm::parser, Symbol::STerm),
-        ));
+    /// The first token tells the kind of symbol apart, and a command is read
+    /// once and then classified by its name
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        match tex::next(input) {
+            Some((Token::Symbol('{'), _)) => map(SRecord::parser, Symbol::SRecord)(input),
+            Some((Token::Char('['), _)) => map(SBracedVec::parser, Symbol::SBracedVec)(input),
+            Some((Token::Char('('), _)) => map(SGroup::parser, Symbol::SGroup)(input),
+            Some((Token::Word("text"), _)) => map(text, Symbol::SText)(input),
+            Some((Token::Word(_), _)) => Self::command(input),
+            _ => map(SBind::parser, Symbol::SBind)(input),
+        }
+    }
 
-        parser(input)
+    fn command(input: &str) -> PResult<'_, Self> {
+        let (tail, cmd) = Command::parser(input)?;
+        // `\X{in}` names a variable of a bind, as in `\X{rt}_1{:}\Bresulttype`,
+        // and so does an abstract nonterminal, as in `\typeidx^n{:\,}\Bfuncsec`
+        if cmd.head.name == "X" || tail.starts_with("{:") {
+            if let Ok((tail, bind)) = SBind::parser(input) {
+                return Ok((tail, Symbol::SBind(bind)));
+            }
+        }
+        match cmd.head.name {
+            "hex" => {
+                let (tail, byte) = hex(input, tail, &cmd)?;
+                return Ok((tail, Symbol::SHex(byte)));
+            }
+            "vec" | "Bvec" | "Tvec" => {
+                let (tail, vec) = SVec::command(input, tail, &cmd)?;
+                return Ok((tail, Symbol::SVec(vec)));
+            }
+            _ => {}
+        }
+        match SNonterm::command(input, tail, &cmd) {
+            Ok((tail, from)) => match SArrow::arrow_to(tail) {
+                Ok((tail, to)) => Ok((tail, Symbol::SArrow(SArrow { from, to }))),
+                Err(_) => Ok((tail, Symbol::SNonterm(from))),
+            },
+            Err(_) => match cmd.is_terminal() {
+                Some(name) => Ok((tail, Symbol::STerm(name.to_string()))),
+                None => nom_err!(input, NotATerminal, cmd.head.name.to_string()),
+            },
+        }
     }
 }
 
 impl<'a> Command<'a> {
-    fn is_terminal(&self) -> Option<&'a str> {
+    pub(crate) fn is_terminal(&self) -> Option<&'a str> {
         if self.head.name == "K" {
             let name = self
                 .args
@@ -93,10 +272,10 @@ impl<'a> Command<'a> {
             return Some(self.head.name);
         }
 
-        return None;
+        None
     }
 
-    fn is_nonterminal(&self) -> Option<&'a str> {
+    pub(crate) fn is_nonterminal(&self) -> Option<&'a str> {
         if self.head.name == "X" {
             let name = self
                 .args
@@ -106,7 +285,20 @@ impl<'a> Command<'a> {
             return Some(name);
         }
 
-        if self.head.name.iter_elements().all(|c| {
+        // `\B{B}`, a parameter standing for a binary nonterminal
+        if matches!(self.head.name, "B" | "T") {
+            if let Some(arg) = self.args.first() {
+                return Some(arg.name());
+            }
+        }
+
+        // `\Bu32` and `\Tvaltype` of the binary and text formats
+        let name = self.head.name;
+        let unprefixed = match name.strip_prefix(['B', 'T']) {
+            Some(rest) if !rest.is_empty() => rest,
+            _ => name,
+        };
+        if unprefixed.iter_elements().all(|c| {
             if c.is_ascii_alphabetic() {
                 c.is_ascii_lowercase()
             } else {
@@ -116,59 +308,131 @@ impl<'a> Command<'a> {
             return Some(self.head.name);
         }
 
-        return None;
+        None
     }
 }
 
+/// `\hex{7F}`
+fn hex<'a>(input: &'a str, tail: &'a str, cmd: &Command<'a>) -> PResult<'a, u8> {
+    let digits = cmd.args.first().map(|arg| arg.name()).unwrap_or_default();
+    match u8::from_str_radix(digits, 16) {
+        Ok(byte) => Ok((tail, byte)),
+        Err(_) => nom_err!(input, UnknownMacro, format!("hex{{{}}}", digits)),
+    }
+}
+
+/// `\text{func}`
+fn text(input: &str) -> PResult<'_, String> {
+    let (input, _) = tag(r"\text")(input)?;
+    let (input, keyword) = group(input)?;
+    let (input, _) = ws(input)?;
+    Ok((input, keyword.to_string()))
+}
+
 impl STerm {
-    pub fn parser(input: &str) -> PResult<String> {
-        let (input, cmd) = Command::parser(input)?;
+    pub fn parser(input: &str) -> PResult<'_, String> {
+        let (tail, cmd) = Command::parser(input)?;
         if let Some(name) = cmd.is_terminal() {
-            Ok((input, name.to_string()))
+            Ok((tail, name.to_string()))
         } else {
-            nom_err!(input, ErrorKind::Tag)
+            nom_err!(input, NotATerminal, cmd.head.name.to_string())
         }
     }
 }
 
 impl SNonterm {
-    pub fn parser(input: &str) -> PResult<Self> {
+    pub fn parser(input: &str) -> PResult<'_, Self> {
         let (tail, cmd) = Command::parser(input)?;
-        if cmd.head.name == "end" || cmd.head.name == "production" {
-            return nom_err!(input, ErrorKind::Tag);
-        }
-        if let Some(name) = cmd.is_nonterminal() {
-            Ok((
-                tail,
-                Self {
-                    name: name.to_string(),
-                    seq_kind: cmd.upnote,
-                },
-            ))
-        } else {
-            nom_err!(input, ErrorKind::Tag)
+        Self::command(input, tail, &cmd)
+    }
+
+    /// The nonterminal of a command already read from `input`, with the
+    /// identifier context and iteration that may follow it in `tail`
+    fn command<'a>(input: &'a str, tail: &'a str, cmd: &Command<'a>) -> PResult<'a, Self> {
+        if matches!(
+            cmd.head.name,
+            "end" | "production" | "equiv" | "to" | "compose" | "vdash"
+        ) {
+            return nom_err!(input, UnknownMacro, cmd.head.name.to_string());
         }
+        let Some(name) = cmd.is_nonterminal() else {
+            return nom_err!(input, NotANonterminal, cmd.head.name.to_string());
+        };
+        // `\Bsection_N(\B{B})` and `\Bsection_1(\Bvec(\Bfunctype))`
+        let mut applied = pair(
+            preceded(char('_'), alt((Self::context_name, digit1))),
+            Self::arguments,
+        );
+        let (tail, (context, args)) = match applied(tail) {
+            Ok((tail, (context, args))) => (tail, (Some(context), args)),
+            Err(_) => {
+                let (tail, context) = opt(preceded(char('_'), Self::context_name))(tail)?;
+                (tail, (context, vec![]))
+            }
+        };
+        let (tail, seq_kind) = match (&cmd.upnote, context.is_some()) {
+            (Some(seq_kind), _) => (tail, Some(seq_kind.clone())),
+            (None, true) => terminated(SeqKind::parser, ws)(tail)?,
+            (None, false) => (tail, None),
+        };
+
+        let context = context.map(String::from);
+        Ok((
+            tail,
+            Self {
+                name: name.to_string(),
+                seq_kind,
+                context,
+                args,
+            },
+        ))
+    }
+
+    /// `(\Bvec(\Bfunctype))`, right after the subscript
+    fn arguments(input: &str) -> PResult<'_, Vec<Symbol>> {
+        let (input, _) = char('(')(input)?;
+        let comma = preceded(char(','), ws);
+        let (input, args) = separated_list1(comma, preceded(ws, Symbol::parser))(input)?;
+        let (input, _) = closing(")")(input)?;
+        Ok((input, args))
+    }
+
+    /// `I` or `{I'}`
+    fn context_name(input: &str) -> PResult<'_, &str> {
+        alt((
+            delimited(char('{'), is_not("}"), char('}')),
+            recognize(pair(satisfy(|c| c.is_ascii_uppercase()), many0(char('\'')))),
+        ))(input)
     }
 }
 
 impl SRecord {
-    pub fn parser(input: &str) -> PResult<Self> {
-        let (input, pairs) = delimited(
-            tag(r"\{"),
-            separated_list1(tag(","), Self::pair),
-            tag(r"\}"),
-        )(input)?;
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let (input, _) = tag(r"\{")(input)?;
+        let (input, pairs) = context("record", terminated(Self::pair_list, closing(r"\}")))(input)?;
         let (input, _) = ws(input)?;
         Ok((input, Self { pairs }))
     }
 
-    fn pair(input: &str) -> PResult<(String, Symbol)> {
+    fn pair_list(input: &str) -> PResult<'_, Vec<(String, Symbol)>> {
+        let (mut input, pair) = Self::pair(input)?;
+        let mut pairs = vec![pair];
+        while let Ok((tail, _)) = tag::<_, _, ()>(",")(input) {
+            let (tail, pair) = Self::pair(tail)?;
+            pairs.push(pair);
+            input = tail;
+        }
+        Ok((input, pairs))
+    }
+
+    fn pair(input: &str) -> PResult<'_, (String, Symbol)> {
         let (input, _) = ws(input)?;
-        let (input, key) = STerm::parser(input)?;
+        let (input, key) = context("pair", STerm::parser)(input)?;
 
         let vec_parser = map(SVec::parser, Symbol::SVec);
         let nt_parser = map(SNonterm::parser, Symbol::SNonterm);
-        let (input, value) = alt((vec_parser, nt_parser))(input)?;
+        let (input, value) = alt((vec_parser, nt_parser))(input)
+            .map_err(|e| e.map(|e| e.in_context(input, format!("pair `{}`", key))))?;
 
         let (input, _) = ws(input)?;
         Ok((input, (key, value)))
@@ -176,37 +440,82 @@ impl SRecord {
 }
 
 impl SBracedVec {
-    pub fn parser(input: &str) -> PResult<Self> {
+    pub fn parser(input: &str) -> PResult<'_, Self> {
         let (input, _) = char('[')(input)?;
         let (input, inner) = SVec::parser(input)?;
-        let (input, _) = char(']')(input)?;
+        let (input, _) = closing("]")(input)?;
+        let (input, _) = ws(input)?;
 
         Ok((input, Self { inner }))
     }
 }
 
 impl SVec {
-    pub fn parser(input: &str) -> PResult<Self> {
-        let (input, vec) = Command::parser(input)?;
-        if vec.head.name != "vec" {
-            return nom_err!(input, ErrorKind::Tag);
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let (tail, cmd) = Command::parser(input)?;
+        Self::command(input, tail, &cmd)
+    }
+
+    fn command<'a>(input: &'a str, tail: &'a str, vec: &Command<'a>) -> PResult<'a, Self> {
+        if !matches!(vec.head.name, "vec" | "Bvec" | "Tvec") {
+            return nom_err!(input, UnknownMacro, vec.head.name.to_string());
         }
-        let (input, nt) = delimited(tag("("), SNonterm::parser, tag(")"))(input)?;
+        let (input, _) = tag("(")(tail)?;
+        let (input, nt) = terminated(SNonterm::parser, closing(")"))(input)?;
+        let (input, _) = ws(input)?;
 
-        Ok((input, Self { over: Box::new(nt) }))
+        let head = vec.head.name.to_string();
+        Ok((
+            input,
+            Self {
+                head,
+                over: Box::new(nt),
+            },
+        ))
     }
 }
 
 impl SArrow {
-    pub fn parser(input: &str) -> PResult<Self> {
+    pub fn parser(input: &str) -> PResult<'_, Self> {
         let (input, from) = SNonterm::parser(input)?;
-        let (input, arrow) = Command::parser(input)?;
+        let (input, to) = Self::arrow_to(input)?;
+        Ok((input, Self { from, to }))
+    }
+
+    /// `\to` and the nonterminal after it
+    fn arrow_to(input: &str) -> PResult<'_, SNonterm> {
+        let (tail, arrow) = Command::parser(input)?;
         if arrow.head.name != "to" {
-            return nom_err!(input, ErrorKind::Tag);
+            return nom_err!(input, UnknownMacro, arrow.head.name.to_string());
         }
-        let (input, to) = SNonterm::parser(input)?;
+        SNonterm::parser(tail)
+    }
+}
 
-        Ok((input, Self { from, to }))
+impl SBind {
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        let (input, var) = alt((Var::parser, Var::nonterminal))(input)?;
+        let (input, _) = alt((tag("{:}"), tag(r"{:\,}")))(input)?;
+        let vec_parser = map(SVec::parser, Symbol::SVec);
+        let nt_parser = map(SNonterm::parser, Symbol::SNonterm);
+        let (input, symbol) = alt((vec_parser, nt_parser))(input)?;
+
+        Ok((input, Self::new(var, symbol)))
+    }
+}
+
+impl SGroup {
+    pub fn parser(input: &str) -> PResult<'_, Self> {
+        // `(\iff ...)` is the side condition of an alternative
+        let (input, _) = not(tag(r"(\iff"))(input)?;
+        let (input, _) = char('(')(input)?;
+        let (input, _) = ws(input)?;
+        let (input, symbols) = many1(Symbol::parser)(input)?;
+        let (input, _) = closing(")")(input)?;
+        let (input, seq_kind) = SeqKind::parser(input)?;
+        let (input, _) = ws(input)?;
+
+        Ok((input, Self { symbols, seq_kind }))
     }
 }
 
@@ -219,10 +528,74 @@ impl Debug for Symbol {
             Self::SBracedVec(arg0) => write!(f, "{:?}", arg0),
             Self::SVec(arg0) => write!(f, "{:?}", arg0),
             Self::SArrow(arg0) => write!(f, "{:?}", arg0),
+            Self::SHex(arg0) => write!(f, "SHex({:02X})", arg0),
+            Self::SBind(arg0) => write!(f, "{:?}", arg0),
+            Self::SGroup(arg0) => write!(f, "{:?}", arg0),
+            Self::SText(arg0) => f.debug_tuple("SText").field(arg0).finish(),
+        }
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::STerm(name) => write!(f, r"\{}", name),
+            Self::SNonterm(nt) => write!(f, "{}", nt),
+            Self::SRecord(record) => write!(f, "{}", record),
+            Self::SBracedVec(vec) => write!(f, "[{}]", vec.inner),
+            Self::SVec(vec) => write!(f, "{}", vec),
+            Self::SArrow(arrow) => write!(f, r"{} \to {}", arrow.from, arrow.to),
+            Self::SHex(byte) => write!(f, r"\hex{{{:02X}}}", byte),
+            Self::SBind(bind) => write!(f, "{}{{:}}{}", bind.var, bind.symbol),
+            Self::SGroup(group) => {
+                let symbols: Vec<_> = group.symbols.iter().map(|s| s.to_string()).collect();
+                write!(f, "({})", symbols.join("~"))?;
+                match &group.seq_kind {
+                    Some(seq_kind) => write!(f, "{}", seq_kind),
+                    None => Ok(()),
+                }
+            }
+            Self::SText(keyword) => write!(f, r"\text{{{}}}", keyword),
+        }
+    }
+}
+
+impl Display for SNonterm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, r"\{}", self.name)?;
+        match &self.context {
+            Some(context) if context.chars().count() == 1 => write!(f, "_{}", context)?,
+            Some(context) => write!(f, "_{{{}}}", context)?,
+            None => {}
+        }
+        if !self.args.is_empty() {
+            let args: Vec<_> = self.args.iter().map(|a| a.to_string()).collect();
+            write!(f, "({})", args.join(", "))?;
+        }
+        match &self.seq_kind {
+            Some(seq_kind) => write!(f, "{}", seq_kind),
+            None => Ok(()),
         }
     }
 }
 
+impl Display for SRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs: Vec<_> = self
+            .pairs
+            .iter()
+            .map(|(key, value)| format!(r"\{}~{}", key, value))
+            .collect();
+        write!(f, r"\{{ {} \}}", pairs.join(", "))
+    }
+}
+
+impl Display for SVec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, r"\{}({})", self.head, self.over)
+    }
+}
+
 impl Debug for SBracedVec {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("SBracedVec").field(&self.inner.over).finish()
@@ -250,10 +623,7 @@ mod tests {
         parse_nonterminal,
         r"\numtype",
         SNonterm,
-        SNonterm {
-            name: "numtype".to_string(),
-            seq_kind: None
-        }
+        SNonterm::new("numtype", None)
     );
 
     test_symbol!(
@@ -264,19 +634,71 @@ mod tests {
             pairs: vec![
                 (
                     "LMIN".to_string(),
-                    Symbol::SNonterm(SNonterm {
-                        name: "u32".to_string(),
-                        seq_kind: None
-                    })
+                    Symbol::SNonterm(SNonterm::new("u32", None))
                 ),
                 (
                     "LMAX".to_string(),
-                    Symbol::SNonterm(SNonterm {
-                        name: "u32".to_string(),
-                        seq_kind: Some(SeqKind::OptSeq),
-                    })
+                    Symbol::SNonterm(SNonterm::new("u32", Some(SeqKind::OptSeq)))
                 ),
             ]
         }
     );
+
+    #[test]
+    fn display_symbols() {
+        for s in [
+            r"\{ \LMIN~\u32, \LMAX~\u32^? \}",
+            r"[\vec(\valtype)]",
+            r"\resulttype \to \resulttype",
+            r"\instr^\ast",
+            r"\hex{7F}",
+            r"\X{rt}_1{:}\Bresulttype",
+            r"t^\ast{:}\Bvec(\Bvaltype)",
+            r"(\X{in}{:}\Binstr)^\ast",
+            r"\text{(}",
+            r"\Ttypeidx_I",
+            r"(\X{in}{:}\Tinstr_{I'})^\ast",
+            r"I'{:}\Tlabel_I",
+        ] {
+            let (input, symbol) = Symbol::parser(s).unwrap();
+            assert_eq!(input, "");
+            assert_eq!(symbol.to_string(), s);
+        }
+    }
+
+    #[test]
+    fn sections() {
+        let s = r"\X{ft}^\ast{:}\Bsection_1(\Bvec(\Bfunctype))";
+        let (input, symbol) = Symbol::parser(s).unwrap();
+        assert_eq!(input, "");
+        assert_eq!(symbol.to_string(), s);
+
+        let (_, symbol) =
+            Symbol::parser(r"\X{code}^\ast{:\,}\Bsection_{10}(\Bvec(\Bcode))").unwrap();
+        let Symbol::SBind(bind) = symbol else {
+            panic!("not a bind: {:?}", symbol);
+        };
+        let Symbol::SNonterm(section) = bind.symbol() else {
+            panic!("not a nonterminal: {:?}", bind.symbol());
+        };
+        assert_eq!(section.context(), Some("10"));
+        assert_eq!(section.args()[0].to_string(), r"\Bvec(\Bcode)");
+
+        // an abstract nonterminal names the value it binds
+        let (_, symbol) = Symbol::parser(r"\functype^\ast{:\,}\Btypesec").unwrap();
+        let Symbol::SBind(bind) = symbol else {
+            panic!("not a bind: {:?}", symbol);
+        };
+        assert_eq!(bind.var().name, "functype");
+        assert_eq!(bind.var().seq_kind, Some(SeqKind::ManyPossibleEmpty));
+    }
+
+    #[test]
+    fn unbalanced_vec() {
+        let err = SVec::parser(r"\vec(\functype").unwrap_err();
+        assert_eq!(
+            err,
+            nom::Err::Error(crate::SpeciesError::UnbalancedBrace(""))
+        );
+    }
 }
@@ -0,0 +1,384 @@
+//! Traversal of a syntax tree by shared reference, in the style of
+//! `syn::visit`.
+//!
+//! Every method of [`Visit`] defaults to the free function of the same name,
+//! which walks into the children of the node. Override a method to inspect a
+//! node and call the free function to keep walking below it.
+
+use crate::{
+    parser::SeqKind,
+    syntax::{
+        expr::{Expr, Var},
+        symbol::{SArrow, SBind, SBracedVec, SGroup, SNonterm, SRecord, SVec, Symbol},
+        Abbreviation, Lhs, MathBlock, Production, Rhs, RhsElem,
+    },
+};
+
+pub trait Visit<'ast> {
+    fn visit_math_block(&mut self, node: &'ast MathBlock) {
+        visit_math_block(self, node)
+    }
+
+    fn visit_abbreviation(&mut self, node: &'ast Abbreviation) {
+        visit_abbreviation(self, node)
+    }
+
+    fn visit_production(&mut self, node: &'ast Production) {
+        visit_production(self, node)
+    }
+
+    fn visit_lhs(&mut self, node: &'ast Lhs) {
+        visit_lhs(self, node)
+    }
+
+    /// Nonterminal defined by a left-hand side, e.g. `numtype`
+    fn visit_lhs_name(&mut self, _name: &'ast str) {}
+
+    fn visit_rhs(&mut self, node: &'ast Rhs) {
+        visit_rhs(self, node)
+    }
+
+    fn visit_rhs_elem(&mut self, node: &'ast RhsElem) {
+        visit_rhs_elem(self, node)
+    }
+
+    fn visit_symbol(&mut self, node: &'ast Symbol) {
+        visit_symbol(self, node)
+    }
+
+    /// Terminal, e.g. `I32` for `\I32`
+    fn visit_sterm(&mut self, _name: &'ast str) {}
+
+    fn visit_snonterm(&mut self, node: &'ast SNonterm) {
+        visit_snonterm(self, node)
+    }
+
+    fn visit_srecord(&mut self, node: &'ast SRecord) {
+        visit_srecord(self, node)
+    }
+
+    /// Record field, e.g. `LMAX` and `\u32^?` in `\LMAX~\u32^?`
+    fn visit_field(&mut self, key: &'ast str, value: &'ast Symbol) {
+        visit_field(self, key, value)
+    }
+
+    fn visit_sbraced_vec(&mut self, node: &'ast SBracedVec) {
+        visit_sbraced_vec(self, node)
+    }
+
+    fn visit_svec(&mut self, node: &'ast SVec) {
+        visit_svec(self, node)
+    }
+
+    fn visit_sarrow(&mut self, node: &'ast SArrow) {
+        visit_sarrow(self, node)
+    }
+
+    /// Byte of the binary format, e.g. `0x7F` for `\hex{7F}`
+    fn visit_shex(&mut self, _byte: u8) {}
+
+    fn visit_sbind(&mut self, node: &'ast SBind) {
+        visit_sbind(self, node)
+    }
+
+    fn visit_sgroup(&mut self, node: &'ast SGroup) {
+        visit_sgroup(self, node)
+    }
+
+    /// Keyword of the text format, e.g. `func` for `\text{func}`
+    fn visit_stext(&mut self, _keyword: &'ast str) {}
+
+    fn visit_seq_kind(&mut self, _node: &'ast SeqKind) {}
+
+    /// Side condition, e.g. `\iff n < 2^{32}`, kept as written
+    fn visit_cond(&mut self, _cond: &'ast str) {}
+
+    /// Expression of an attribute, right of `\Rightarrow`
+    fn visit_expr(&mut self, node: &'ast Expr) {
+        visit_expr(self, node)
+    }
+
+    /// Constructor of an attribute, e.g. `I32` for `\Rightarrow \I32`
+    fn visit_expr_term(&mut self, _name: &'ast str) {}
+
+    /// Record field of an attribute, e.g. `LMIN` and `n` in `\{ \LMIN~n \}`
+    fn visit_expr_field(&mut self, key: &'ast str, value: &'ast [Expr]) {
+        visit_expr_field(self, key, value)
+    }
+
+    fn visit_var(&mut self, node: &'ast Var) {
+        visit_var(self, node)
+    }
+}
+
+pub fn visit_math_block<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast MathBlock) {
+    for production in &node.productions {
+        v.visit_production(production);
+    }
+    for abbreviation in &node.abbreviations {
+        v.visit_abbreviation(abbreviation);
+    }
+}
+
+pub fn visit_abbreviation<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast Abbreviation) {
+    for symbol in node.short.iter().chain(&node.long) {
+        v.visit_symbol(symbol);
+    }
+    if let Some(cond) = &node.cond {
+        v.visit_cond(cond);
+    }
+}
+
+pub fn visit_production<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast Production) {
+    v.visit_lhs(&node.lhs);
+    v.visit_rhs(&node.rhs);
+}
+
+pub fn visit_lhs<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast Lhs) {
+    for name in &node.names {
+        v.visit_lhs_name(name);
+    }
+}
+
+pub fn visit_rhs<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast Rhs) {
+    for elem in &node.elems {
+        v.visit_rhs_elem(elem);
+    }
+}
+
+pub fn visit_rhs_elem<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast RhsElem) {
+    for symbol in &node.symbols {
+        v.visit_symbol(symbol);
+    }
+    for expr in node.action.iter().flatten() {
+        v.visit_expr(expr);
+    }
+    if let Some(cond) = &node.cond {
+        v.visit_cond(cond);
+    }
+}
+
+pub fn visit_symbol<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast Symbol) {
+    match node {
+        Symbol::STerm(name) => v.visit_sterm(name),
+        Symbol::SNonterm(nt) => v.visit_snonterm(nt),
+        Symbol::SRecord(record) => v.visit_srecord(record),
+        Symbol::SBracedVec(vec) => v.visit_sbraced_vec(vec),
+        Symbol::SVec(vec) => v.visit_svec(vec),
+        Symbol::SArrow(arrow) => v.visit_sarrow(arrow),
+        Symbol::SHex(byte) => v.visit_shex(*byte),
+        Symbol::SBind(bind) => v.visit_sbind(bind),
+        Symbol::SGroup(group) => v.visit_sgroup(group),
+        Symbol::SText(keyword) => v.visit_stext(keyword),
+    }
+}
+
+pub fn visit_snonterm<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast SNonterm) {
+    for arg in &node.args {
+        v.visit_symbol(arg);
+    }
+    if let Some(seq_kind) = &node.seq_kind {
+        v.visit_seq_kind(seq_kind);
+    }
+}
+
+pub fn visit_srecord<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast SRecord) {
+    for (key, value) in &node.pairs {
+        v.visit_field(key, value);
+    }
+}
+
+pub fn visit_field<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, _key: &'ast str, value: &'ast Symbol) {
+    v.visit_symbol(value);
+}
+
+pub fn visit_sbraced_vec<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast SBracedVec) {
+    v.visit_svec(&node.inner);
+}
+
+pub fn visit_svec<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast SVec) {
+    v.visit_snonterm(&node.over);
+}
+
+pub fn visit_sarrow<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast SArrow) {
+    v.visit_snonterm(&node.from);
+    v.visit_snonterm(&node.to);
+}
+
+pub fn visit_sbind<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast SBind) {
+    v.visit_var(&node.var);
+    v.visit_symbol(&node.symbol);
+}
+
+pub fn visit_sgroup<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast SGroup) {
+    for symbol in &node.symbols {
+        v.visit_symbol(symbol);
+    }
+    if let Some(seq_kind) = &node.seq_kind {
+        v.visit_seq_kind(seq_kind);
+    }
+}
+
+pub fn visit_expr<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast Expr) {
+    match node {
+        Expr::Term(name) => v.visit_expr_term(name),
+        Expr::Var(var) => v.visit_var(var),
+        Expr::Epsilon => {}
+        Expr::Record(pairs) => {
+            for (key, value) in pairs {
+                v.visit_expr_field(key, value);
+            }
+        }
+        Expr::List(exprs) => {
+            for expr in exprs {
+                v.visit_expr(expr);
+            }
+        }
+        Expr::Arrow(left, right) | Expr::Compose(left, right) => {
+            for expr in left.iter().chain(right) {
+                v.visit_expr(expr);
+            }
+        }
+        Expr::Meta(nt, _) => v.visit_snonterm(nt),
+    }
+}
+
+pub fn visit_expr_field<'ast, V: Visit<'ast> + ?Sized>(
+    v: &mut V,
+    _key: &'ast str,
+    value: &'ast [Expr],
+) {
+    for expr in value {
+        v.visit_expr(expr);
+    }
+}
+
+pub fn visit_var<'ast, V: Visit<'ast> + ?Sized>(v: &mut V, node: &'ast Var) {
+    if let Some(seq_kind) = &node.seq_kind {
+        v.visit_seq_kind(seq_kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Names<'ast> {
+        terminals: Vec<&'ast str>,
+        nonterminals: Vec<&'ast str>,
+        fields: Vec<&'ast str>,
+    }
+
+    impl<'ast> Visit<'ast> for Names<'ast> {
+        fn visit_sterm(&mut self, name: &'ast str) {
+            self.terminals.push(name);
+        }
+
+        fn visit_snonterm(&mut self, node: &'ast SNonterm) {
+            self.nonterminals.push(&node.name);
+            visit_snonterm(self, node);
+        }
+
+        fn visit_field(&mut self, key: &'ast str, value: &'ast Symbol) {
+            self.fields.push(key);
+            visit_field(self, key, value);
+        }
+    }
+
+    #[test]
+    fn collect_names() {
+        let (_, mb) = MathBlock::parser(
+            r"\begin{array}{llll}
+    \production{element segment mode} & \elemmode &::=&
+      \EPASSIVE \\&&|&
+      \EACTIVE~\{ \ETABLE~\tableidx, \EOFFSET~\expr \} \\&&|&
+      \EDECLARATIVE \\
+    \production{function type} & \functype &::=&
+      \resulttype \to \resulttype \\
+    \end{array}",
+        )
+        .unwrap();
+
+        let mut names = Names::default();
+        names.visit_math_block(&mb);
+        assert_eq!(names.terminals, vec!["EPASSIVE", "EACTIVE", "EDECLARATIVE"]);
+        assert_eq!(
+            names.nonterminals,
+            vec!["tableidx", "expr", "resulttype", "resulttype"]
+        );
+        assert_eq!(names.fields, vec!["ETABLE", "EOFFSET"]);
+    }
+
+    /// Number of nodes visited, by kind
+    #[derive(Default)]
+    struct Count(std::collections::BTreeMap<&'static str, usize>);
+
+    impl Count {
+        fn add(&mut self, kind: &'static str) {
+            *self.0.entry(kind).or_default() += 1;
+        }
+    }
+
+    impl<'ast> Visit<'ast> for Count {
+        fn visit_lhs_name(&mut self, _name: &'ast str) {
+            self.add("lhs");
+        }
+
+        fn visit_sterm(&mut self, _name: &'ast str) {
+            self.add("term");
+        }
+
+        fn visit_shex(&mut self, _byte: u8) {
+            self.add("hex");
+        }
+
+        fn visit_snonterm(&mut self, node: &'ast SNonterm) {
+            self.add("nonterminal");
+            visit_snonterm(self, node);
+        }
+
+        fn visit_cond(&mut self, _cond: &'ast str) {
+            self.add("cond");
+        }
+
+        fn visit_expr_field(&mut self, key: &'ast str, value: &'ast [Expr]) {
+            self.add("field");
+            visit_expr_field(self, key, value);
+        }
+
+        fn visit_var(&mut self, node: &'ast Var) {
+            self.add("var");
+            visit_var(self, node);
+        }
+    }
+
+    #[test]
+    fn count_action_nodes() {
+        let (_, mb) = MathBlock::parser(
+            r"\begin{array}{llll}
+    \production{limits} & \Blimits &::=&
+      \hex{00}~~n{:}\Bu32 &\Rightarrow& \{ \LMIN~n, \LMAX~\epsilon \} \\ &&|&
+      \hex{01}~~n{:}\Bu32~~m{:}\Bu32 &\Rightarrow& \{ \LMIN~n, \LMAX~m \}
+        &(\iff m \geq n) \\
+    \end{array}",
+        )
+        .unwrap();
+
+        let mut count = Count::default();
+        count.visit_math_block(&mb);
+        let count: Vec<_> = count.0.into_iter().collect();
+        assert_eq!(
+            count,
+            vec![
+                ("cond", 1),
+                ("field", 4),
+                ("hex", 2),
+                ("lhs", 1),
+                ("nonterminal", 3),
+                // the binders `n`, `n` and `m`, and their uses in the records
+                ("var", 6),
+            ]
+        );
+    }
+}
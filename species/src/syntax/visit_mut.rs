@@ -0,0 +1,301 @@
+//! Traversal of a syntax tree by mutable reference, in the style of
+//! `syn::visit_mut`.
+//!
+//! Every method of [`VisitMut`] defaults to the free function of the same
+//! name, which walks into the children of the node.
+
+use crate::{
+    parser::SeqKind,
+    syntax::{
+        expr::{Expr, Var},
+        symbol::{SArrow, SBind, SBracedVec, SGroup, SNonterm, SRecord, SVec, Symbol},
+        Abbreviation, Lhs, MathBlock, Production, Rhs, RhsElem,
+    },
+};
+
+pub trait VisitMut {
+    fn visit_math_block_mut(&mut self, node: &mut MathBlock) {
+        visit_math_block_mut(self, node)
+    }
+
+    fn visit_abbreviation_mut(&mut self, node: &mut Abbreviation) {
+        visit_abbreviation_mut(self, node)
+    }
+
+    fn visit_production_mut(&mut self, node: &mut Production) {
+        visit_production_mut(self, node)
+    }
+
+    fn visit_lhs_mut(&mut self, node: &mut Lhs) {
+        visit_lhs_mut(self, node)
+    }
+
+    /// Nonterminal defined by a left-hand side, e.g. `numtype`
+    fn visit_lhs_name_mut(&mut self, _name: &mut String) {}
+
+    fn visit_rhs_mut(&mut self, node: &mut Rhs) {
+        visit_rhs_mut(self, node)
+    }
+
+    fn visit_rhs_elem_mut(&mut self, node: &mut RhsElem) {
+        visit_rhs_elem_mut(self, node)
+    }
+
+    fn visit_symbol_mut(&mut self, node: &mut Symbol) {
+        visit_symbol_mut(self, node)
+    }
+
+    /// Terminal, e.g. `I32` for `\I32`
+    fn visit_sterm_mut(&mut self, _name: &mut String) {}
+
+    fn visit_snonterm_mut(&mut self, node: &mut SNonterm) {
+        visit_snonterm_mut(self, node)
+    }
+
+    fn visit_srecord_mut(&mut self, node: &mut SRecord) {
+        visit_srecord_mut(self, node)
+    }
+
+    /// Record field, e.g. `LMAX` and `\u32^?` in `\LMAX~\u32^?`
+    fn visit_field_mut(&mut self, key: &mut String, value: &mut Symbol) {
+        visit_field_mut(self, key, value)
+    }
+
+    fn visit_sbraced_vec_mut(&mut self, node: &mut SBracedVec) {
+        visit_sbraced_vec_mut(self, node)
+    }
+
+    fn visit_svec_mut(&mut self, node: &mut SVec) {
+        visit_svec_mut(self, node)
+    }
+
+    fn visit_sarrow_mut(&mut self, node: &mut SArrow) {
+        visit_sarrow_mut(self, node)
+    }
+
+    /// Byte of the binary format, e.g. `0x7F` for `\hex{7F}`
+    fn visit_shex_mut(&mut self, _byte: &mut u8) {}
+
+    fn visit_sbind_mut(&mut self, node: &mut SBind) {
+        visit_sbind_mut(self, node)
+    }
+
+    fn visit_sgroup_mut(&mut self, node: &mut SGroup) {
+        visit_sgroup_mut(self, node)
+    }
+
+    /// Keyword of the text format, e.g. `func` for `\text{func}`
+    fn visit_stext_mut(&mut self, _keyword: &mut String) {}
+
+    fn visit_seq_kind_mut(&mut self, _node: &mut SeqKind) {}
+
+    /// Side condition, e.g. `\iff n < 2^{32}`, kept as written
+    fn visit_cond_mut(&mut self, _cond: &mut String) {}
+
+    /// Expression of an attribute, right of `\Rightarrow`
+    fn visit_expr_mut(&mut self, node: &mut Expr) {
+        visit_expr_mut(self, node)
+    }
+
+    /// Constructor of an attribute, e.g. `I32` for `\Rightarrow \I32`
+    fn visit_expr_term_mut(&mut self, _name: &mut String) {}
+
+    /// Record field of an attribute, e.g. `LMIN` and `n` in `\{ \LMIN~n \}`
+    fn visit_expr_field_mut(&mut self, key: &mut String, value: &mut [Expr]) {
+        visit_expr_field_mut(self, key, value)
+    }
+
+    fn visit_var_mut(&mut self, node: &mut Var) {
+        visit_var_mut(self, node)
+    }
+}
+
+pub fn visit_math_block_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut MathBlock) {
+    for production in &mut node.productions {
+        v.visit_production_mut(production);
+    }
+    for abbreviation in &mut node.abbreviations {
+        v.visit_abbreviation_mut(abbreviation);
+    }
+}
+
+pub fn visit_abbreviation_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut Abbreviation) {
+    for symbol in node.short.iter_mut().chain(&mut node.long) {
+        v.visit_symbol_mut(symbol);
+    }
+    if let Some(cond) = &mut node.cond {
+        v.visit_cond_mut(cond);
+    }
+}
+
+pub fn visit_production_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut Production) {
+    v.visit_lhs_mut(&mut node.lhs);
+    v.visit_rhs_mut(&mut node.rhs);
+}
+
+pub fn visit_lhs_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut Lhs) {
+    for name in &mut node.names {
+        v.visit_lhs_name_mut(name);
+    }
+}
+
+pub fn visit_rhs_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut Rhs) {
+    for elem in &mut node.elems {
+        v.visit_rhs_elem_mut(elem);
+    }
+}
+
+pub fn visit_rhs_elem_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut RhsElem) {
+    for symbol in &mut node.symbols {
+        v.visit_symbol_mut(symbol);
+    }
+    for expr in node.action.iter_mut().flatten() {
+        v.visit_expr_mut(expr);
+    }
+    if let Some(cond) = &mut node.cond {
+        v.visit_cond_mut(cond);
+    }
+}
+
+pub fn visit_symbol_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut Symbol) {
+    match node {
+        Symbol::STerm(name) => v.visit_sterm_mut(name),
+        Symbol::SNonterm(nt) => v.visit_snonterm_mut(nt),
+        Symbol::SRecord(record) => v.visit_srecord_mut(record),
+        Symbol::SBracedVec(vec) => v.visit_sbraced_vec_mut(vec),
+        Symbol::SVec(vec) => v.visit_svec_mut(vec),
+        Symbol::SArrow(arrow) => v.visit_sarrow_mut(arrow),
+        Symbol::SHex(byte) => v.visit_shex_mut(byte),
+        Symbol::SBind(bind) => v.visit_sbind_mut(bind),
+        Symbol::SGroup(group) => v.visit_sgroup_mut(group),
+        Symbol::SText(keyword) => v.visit_stext_mut(keyword),
+    }
+}
+
+pub fn visit_snonterm_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut SNonterm) {
+    for arg in &mut node.args {
+        v.visit_symbol_mut(arg);
+    }
+    if let Some(seq_kind) = &mut node.seq_kind {
+        v.visit_seq_kind_mut(seq_kind);
+    }
+}
+
+pub fn visit_srecord_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut SRecord) {
+    for (key, value) in &mut node.pairs {
+        v.visit_field_mut(key, value);
+    }
+}
+
+pub fn visit_field_mut<V: VisitMut + ?Sized>(v: &mut V, _key: &mut String, value: &mut Symbol) {
+    v.visit_symbol_mut(value);
+}
+
+pub fn visit_sbraced_vec_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut SBracedVec) {
+    v.visit_svec_mut(&mut node.inner);
+}
+
+pub fn visit_svec_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut SVec) {
+    v.visit_snonterm_mut(&mut node.over);
+}
+
+pub fn visit_sarrow_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut SArrow) {
+    v.visit_snonterm_mut(&mut node.from);
+    v.visit_snonterm_mut(&mut node.to);
+}
+
+pub fn visit_sbind_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut SBind) {
+    v.visit_var_mut(&mut node.var);
+    v.visit_symbol_mut(&mut node.symbol);
+}
+
+pub fn visit_sgroup_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut SGroup) {
+    for symbol in &mut node.symbols {
+        v.visit_symbol_mut(symbol);
+    }
+    if let Some(seq_kind) = &mut node.seq_kind {
+        v.visit_seq_kind_mut(seq_kind);
+    }
+}
+
+pub fn visit_expr_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut Expr) {
+    match node {
+        Expr::Term(name) => v.visit_expr_term_mut(name),
+        Expr::Var(var) => v.visit_var_mut(var),
+        Expr::Epsilon => {}
+        Expr::Record(pairs) => {
+            for (key, value) in pairs {
+                v.visit_expr_field_mut(key, value);
+            }
+        }
+        Expr::List(exprs) => {
+            for expr in exprs {
+                v.visit_expr_mut(expr);
+            }
+        }
+        Expr::Arrow(left, right) | Expr::Compose(left, right) => {
+            for expr in left.iter_mut().chain(right) {
+                v.visit_expr_mut(expr);
+            }
+        }
+        Expr::Meta(nt, _) => v.visit_snonterm_mut(nt),
+    }
+}
+
+pub fn visit_expr_field_mut<V: VisitMut + ?Sized>(
+    v: &mut V,
+    _key: &mut String,
+    value: &mut [Expr],
+) {
+    for expr in value {
+        v.visit_expr_mut(expr);
+    }
+}
+
+pub fn visit_var_mut<V: VisitMut + ?Sized>(v: &mut V, node: &mut Var) {
+    if let Some(seq_kind) = &mut node.seq_kind {
+        v.visit_seq_kind_mut(seq_kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Rename<'a> {
+        from: &'a str,
+        to: &'a str,
+    }
+
+    impl VisitMut for Rename<'_> {
+        fn visit_snonterm_mut(&mut self, node: &mut SNonterm) {
+            if node.name == self.from {
+                node.name = self.to.to_string();
+            }
+        }
+    }
+
+    #[test]
+    fn rename_nonterminal() {
+        let (_, mut mb) = MathBlock::parser(
+            r"\begin{array}{llll}
+    \production{limits} & \limits &::=&
+      \{ \LMIN~\u32, \LMAX~\u32^? \} \\
+    \production{table type} & \tabletype &::=&
+      \limits~\reftype \\
+    \end{array}",
+        )
+        .unwrap();
+
+        let mut rename = Rename {
+            from: "u32",
+            to: "u64",
+        };
+        rename.visit_math_block_mut(&mut mb);
+        assert_eq!(
+            mb.productions[0].rhs.to_string(),
+            r"\{ \LMIN~\u64, \LMAX~\u64^? \}"
+        );
+        assert_eq!(mb.productions[1].rhs.to_string(), r"\limits~\reftype");
+    }
+}
@@ -0,0 +1,223 @@
+//! Tokens of TeX math, the layer under the nom parsers.
+//!
+//! A token is read at a time from the input, so that the parsers keep
+//! working on `&str` and their errors keep pointing into the source. Control
+//! words take digits as well as letters, as the spec's macros do, e.g.
+//! `\I32`. A `%` comment runs to the end of the line and reads as a space.
+
+use std::ops::Range;
+
+use nom::error::ErrorKind;
+
+use crate::{nom_err, PResult};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Token<'a> {
+    /// `\instr`, without the backslash
+    Word(&'a str),
+    /// Backslash and one other character, e.g. `\{`, `\_`, `\,` or `\ `
+    Symbol(char),
+    /// `{`
+    BeginGroup,
+    /// `}`
+    EndGroup,
+    /// `^`
+    Superscript,
+    /// `_`
+    Subscript,
+    /// `&`
+    AlignTab,
+    /// `\\`
+    RowEnd,
+    /// `~`
+    Tie,
+    /// Whitespace and comments
+    Space,
+    /// Any other character, including non-ASCII ones like `ε`
+    Char(char),
+}
+
+impl Token<'_> {
+    /// Whether the token only lays out the math, spacing like `~`, `\quad`
+    /// and `\ `, or the alignment tabs and row ends of an array
+    pub fn is_layout(&self) -> bool {
+        match self {
+            Token::Space | Token::Tie | Token::AlignTab | Token::RowEnd => true,
+            Token::Symbol(c) => matches!(c, ' ' | ',' | ';' | '!'),
+            Token::Word(name) => matches!(*name, "quad" | "qquad"),
+            _ => false,
+        }
+    }
+}
+
+/// Whether the token may be part of a name written in text, e.g. in
+/// `\K{div\_u}` or `\production{external types}`
+pub fn is_text(token: &Token<'_>) -> bool {
+    match token {
+        Token::Char(c) => c.is_alphanumeric() || "./-#".contains(*c),
+        Token::Symbol(c) => "_#$%&".contains(*c),
+        Token::Space | Token::BeginGroup | Token::EndGroup => true,
+        _ => false,
+    }
+}
+
+/// The token at the start of the input and the input after it
+pub fn next(input: &str) -> Option<(Token<'_>, &str)> {
+    let mut chars = input.chars();
+    let c = chars.next()?;
+    let rest = chars.as_str();
+    let token = match c {
+        '\\' => {
+            let name_len = rest
+                .find(|c: char| !c.is_ascii_alphanumeric())
+                .unwrap_or(rest.len());
+            if name_len > 0 {
+                return Some((Token::Word(&rest[..name_len]), &rest[name_len..]));
+            }
+            match rest.chars().next() {
+                Some('\\') => return Some((Token::RowEnd, &rest[1..])),
+                Some(c) => return Some((Token::Symbol(c), &rest[c.len_utf8()..])),
+                // a lone backslash at the end
+                None => Token::Char('\\'),
+            }
+        }
+        '{' => Token::BeginGroup,
+        '}' => Token::EndGroup,
+        '^' => Token::Superscript,
+        '_' => Token::Subscript,
+        '&' => Token::AlignTab,
+        '~' => Token::Tie,
+        '%' => {
+            let end = rest.find('\n').map_or(rest.len(), |i| i + 1);
+            return Some((Token::Space, &rest[end..]));
+        }
+        c if c.is_whitespace() => {
+            let end = rest
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(rest.len());
+            return Some((Token::Space, &rest[end..]));
+        }
+        c => Token::Char(c),
+    };
+    Some((token, rest))
+}
+
+/// Tokens of a source with their byte offsets
+pub fn tokens(source: &str) -> Tokens<'_> {
+    Tokens { source, offset: 0 }
+}
+
+pub struct Tokens<'a> {
+    source: &'a str,
+    offset: usize,
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = (usize, Token<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.source[self.offset..];
+        let (token, tail) = next(rest)?;
+        let offset = self.offset;
+        self.offset = self.source.len() - tail.len();
+        Some((offset, token))
+    }
+}
+
+/// Control words of a text, with their spans, backslash included
+pub fn words(text: &str) -> impl Iterator<Item = (&str, Range<usize>)> {
+    tokens(text).filter_map(|(offset, token)| match token {
+        Token::Word(name) => Some((name, offset..offset + 1 + name.len())),
+        _ => None,
+    })
+}
+
+/// nom parser of one token
+pub fn token(input: &str) -> PResult<'_, Token<'_>> {
+    match next(input) {
+        Some((token, rest)) => Ok((rest, token)),
+        None => nom_err!(input, Nom, ErrorKind::Eof),
+    }
+}
+
+/// nom parser of one token that satisfies `predicate`
+pub fn token_if<'a>(
+    predicate: impl Fn(&Token<'a>) -> bool,
+) -> impl Fn(&'a str) -> PResult<'a, Token<'a>> {
+    move |input| match next(input) {
+        Some((token, rest)) if predicate(&token) => Ok((rest, token)),
+        _ => nom_err!(input, Nom, ErrorKind::Verify),
+    }
+}
+
+/// Content of the balanced group at the start of the input, without its
+/// braces, and the input after it. Escaped braces `\{` and `\}` do not
+/// count.
+pub fn group(input: &str) -> Option<(&str, &str)> {
+    let mut tokens = tokens(input);
+    let (_, Token::BeginGroup) = tokens.next()? else {
+        return None;
+    };
+    let mut depth = 0;
+    for (offset, token) in tokens {
+        match token {
+            Token::BeginGroup => depth += 1,
+            Token::EndGroup if depth == 0 => {
+                return Some((&input[1..offset], &input[offset + 1..]))
+            }
+            Token::EndGroup => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize() {
+        let source = r"\Tu32^\ast_{I'} & \{ \\ ~ε% comment
+\quad\I32 \_";
+        let tokens: Vec<_> = tokens(source).map(|(_, t)| t).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Word("Tu32"),
+                Token::Superscript,
+                Token::Word("ast"),
+                Token::Subscript,
+                Token::BeginGroup,
+                Token::Char('I'),
+                Token::Char('\''),
+                Token::EndGroup,
+                Token::Space,
+                Token::AlignTab,
+                Token::Space,
+                Token::Symbol('{'),
+                Token::Space,
+                Token::RowEnd,
+                Token::Space,
+                Token::Tie,
+                Token::Char('ε'),
+                Token::Space,
+                Token::Word("quad"),
+                Token::Word("I32"),
+                Token::Space,
+                Token::Symbol('_'),
+            ]
+        );
+        let offsets: Vec<_> = super::tokens(r"\{ab").map(|(i, _)| i).collect();
+        assert_eq!(offsets, vec![0, 2, 3]);
+        assert_eq!(next("\\"), Some((Token::Char('\\'), "")));
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(group(r"{a{b}\}c} d"), Some((r"a{b}\}c", " d")));
+        assert_eq!(group(r"{\{}"), Some((r"\{", "")));
+        assert_eq!(group(r"{a{b}"), None);
+        assert_eq!(group("a"), None);
+    }
+}
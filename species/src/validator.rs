@@ -0,0 +1,1470 @@
+//! Type checker that interprets the typing rules of the validation chapter.
+//!
+//! A phrase is checked by matching it against the phrase of each rule's
+//! conclusion and establishing the rule's premises: lookups like
+//! `C.\CLOCALS[x] = t` read the [`Context`], and judgements like
+//! `C \vdash \blocktype : [t_1^\ast] \to [t_2^\ast]` are checked recursively.
+//! Instead of the rules for instruction sequences, a sequence is checked with
+//! an operand stack: every instruction pops the operands of its type and
+//! pushes its results, and a type like `[t_1^\ast] \to [t_2^\ast]` whose
+//! results are left open makes the rest of the stack polymorphic.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    generator::Node,
+    grammar::Origin,
+    spec::Spec,
+    syntax::{
+        expr::Expr,
+        rule::{Judgement, Premise, Rule},
+    },
+};
+
+/// Interpreter of the typing rules of a specification
+pub struct Validator<'s> {
+    rules: Vec<&'s Rule>,
+    skipped: Vec<SkippedRule<'s>>,
+}
+
+/// Typing rule that the validator cannot interpret
+#[derive(Debug, PartialEq)]
+pub struct SkippedRule<'s> {
+    pub origin: &'s Origin,
+    pub rule: &'s Rule,
+    pub reason: String,
+}
+
+/// Validation context `C`, the entries of each of its fields, e.g. the types
+/// of the locals in `C.\CLOCALS`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Context {
+    fields: HashMap<String, Node>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ValidationError {
+    /// No typing rule concludes on the phrase
+    NoRule(String),
+    /// An instruction pops more operands than the stack holds
+    Underflow(String),
+    /// A phrase or an operand has another type than required
+    Mismatch { expected: String, found: String },
+    /// A context field is missing or has no entry at the index
+    Lookup { field: String, index: Option<usize> },
+    /// A variable of a rule is not determined by its premises or operands
+    Undetermined(String),
+}
+
+type Bindings = HashMap<String, Node>;
+
+/// Operand stack of the instruction sequence being checked. `None` is an
+/// operand of unknown type, popped off a polymorphic stack.
+#[derive(Debug, Clone)]
+struct Stack {
+    values: Vec<Option<Node>>,
+    polymorphic: bool,
+}
+
+impl<'s> Validator<'s> {
+    pub fn new(spec: &'s Spec) -> Self {
+        let mut rules = vec![];
+        let mut skipped = vec![];
+        for (origin, rule) in spec.grammar().rules() {
+            let other = rule.premises().iter().find_map(|premise| match premise {
+                Premise::Other(premise) => Some(premise),
+                _ => None,
+            });
+            let reason = match other {
+                Some(premise) => format!("premise `{}` is not supported", premise),
+                None if !rule.conclusion().context().extensions().is_empty() => {
+                    "the conclusion extends the context".to_string()
+                }
+                None => {
+                    rules.push(rule);
+                    continue;
+                }
+            };
+            skipped.push(SkippedRule {
+                origin,
+                rule,
+                reason,
+            });
+        }
+        Self { rules, skipped }
+    }
+
+    pub fn skipped(&self) -> &[SkippedRule<'s>] {
+        &self.skipped
+    }
+
+    /// Type of a phrase other than an instruction sequence, e.g. of a block
+    /// type
+    pub fn check(&self, ctx: &Context, phrase: &Node) -> Result<Node, ValidationError> {
+        let mut error = ValidationError::NoRule(phrase.to_string());
+        for rule in &self.rules {
+            let conclusion = rule.conclusion();
+            let mut bindings = Bindings::new();
+            if !phrase_matches(conclusion.phrase(), phrase, &mut bindings) {
+                continue;
+            }
+            let ty = self
+                .premises(ctx, rule, &mut bindings)
+                .and_then(|()| instantiate_all(conclusion.ty(), &bindings));
+            match ty {
+                Ok(ty) => return Ok(normalize(&ty)),
+                Err(e) => error = e,
+            }
+        }
+        Err(error)
+    }
+
+    /// Check `C \vdash \instr^\ast : [from] \to [to]`
+    pub fn check_instrs(
+        &self,
+        ctx: &Context,
+        instrs: &[Node],
+        from: &[Node],
+        to: &[Node],
+    ) -> Result<(), ValidationError> {
+        let mut stack = Stack {
+            values: from.iter().map(|t| Some(normalize(t))).collect(),
+            polymorphic: false,
+        };
+        for instr in instrs {
+            self.instr(ctx, instr, &mut stack)?;
+        }
+        let expected: Vec<_> = to.iter().map(normalize).collect();
+        stack.expect(&expected)
+    }
+
+    /// Pop the operands of an instruction and push its results
+    fn instr(&self, ctx: &Context, instr: &Node, stack: &mut Stack) -> Result<(), ValidationError> {
+        let mut error = ValidationError::NoRule(instr.to_string());
+        for rule in &self.rules {
+            let conclusion = rule.conclusion();
+            let [Expr::Arrow(from, to)] = conclusion.ty() else {
+                continue;
+            };
+            let (Some(from), Some(to)) = (stack_type(from), stack_type(to)) else {
+                continue;
+            };
+            let mut bindings = Bindings::new();
+            if !phrase_matches(conclusion.phrase(), instr, &mut bindings) {
+                continue;
+            }
+            let mut attempt = stack.clone();
+            let result = self
+                .premises(ctx, rule, &mut bindings)
+                .and_then(|()| attempt.pop(from, &mut bindings, instr))
+                .and_then(|()| attempt.push(to, &bindings));
+            match result {
+                Ok(()) => {
+                    *stack = attempt;
+                    return Ok(());
+                }
+                Err(e) => error = e,
+            }
+        }
+        Err(error)
+    }
+
+    fn premises(
+        &self,
+        ctx: &Context,
+        rule: &Rule,
+        bindings: &mut Bindings,
+    ) -> Result<(), ValidationError> {
+        for premise in rule.premises() {
+            match premise {
+                Premise::Lookup {
+                    field,
+                    index,
+                    value,
+                } => {
+                    let index = match index {
+                        Some(index) => Some(to_index(&instantiate(index, bindings)?)?),
+                        None => None,
+                    };
+                    let entry = ctx.lookup(field, index)?;
+                    if !matches_all(value, entry, bindings) {
+                        return Err(mismatch(value, entry, bindings));
+                    }
+                }
+                Premise::Judgement(judgement) => {
+                    let ctx = extend(ctx, judgement, bindings)?;
+                    self.judgement(&ctx, judgement, bindings)?;
+                }
+                Premise::Other(premise) => unreachable!("rule with `{}` is skipped", premise),
+            }
+        }
+        Ok(())
+    }
+
+    fn judgement(
+        &self,
+        ctx: &Context,
+        judgement: &Judgement,
+        bindings: &mut Bindings,
+    ) -> Result<(), ValidationError> {
+        let phrase = instantiate_all(judgement.phrase(), bindings)?;
+        // instruction sequences are checked with the operand stack
+        if let (Node::Seq(instrs), [Expr::Arrow(from, to)]) = (&phrase, judgement.ty()) {
+            if let (Some(from), Some(to)) = (stack_type(from), stack_type(to)) {
+                let from = instantiate_seq(from, bindings)?;
+                let mut stack = Stack {
+                    values: from.iter().map(|t| Some(normalize(t))).collect(),
+                    polymorphic: false,
+                };
+                for instr in instrs {
+                    self.instr(ctx, instr, &mut stack)?;
+                }
+                let end = Node::Term("END".to_string());
+                stack.pop(to, bindings, &end)?;
+                return stack.expect(&[]);
+            }
+        }
+        let ty = self.check(ctx, &phrase)?;
+        if matches_all(judgement.ty(), &ty, bindings) {
+            Ok(())
+        } else {
+            Err(mismatch(judgement.ty(), &ty, bindings))
+        }
+    }
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set a field, named without its `C`, e.g. `locals` for `C.\CLOCALS`
+    pub fn field(mut self, name: impl Into<String>, entries: Vec<Node>) -> Self {
+        self.fields.insert(name.into(), Node::Seq(entries));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Node> {
+        self.fields.get(name)
+    }
+
+    /// `C.\CLOCALS[x]`, or `C.\CRETURN` without index
+    fn lookup(&self, field: &str, index: Option<usize>) -> Result<&Node, ValidationError> {
+        let name = field_name(field);
+        let entry = match (self.fields.get(&name), index) {
+            (Some(Node::Seq(entries)), Some(index)) => entries.get(index),
+            (entry, None) => entry,
+            _ => None,
+        };
+        entry.ok_or(ValidationError::Lookup { field: name, index })
+    }
+}
+
+impl Stack {
+    /// Pop operands from the right, binding the variables of the rule
+    fn pop(
+        &mut self,
+        from: &[Expr],
+        bindings: &mut Bindings,
+        instr: &Node,
+    ) -> Result<(), ValidationError> {
+        for item in from.iter().rev() {
+            let key = item.to_string();
+            if starred(item) {
+                match bindings.get(&key) {
+                    Some(Node::Seq(types)) => {
+                        for ty in types.clone().iter().rev() {
+                            self.pop_one(Some(&normalize(ty)), instr)?;
+                        }
+                    }
+                    Some(bound) => {
+                        return Err(ValidationError::Mismatch {
+                            expected: key,
+                            found: bound.to_string(),
+                        })
+                    }
+                    // an open sequence takes the rest of the stack
+                    None => {
+                        let rest: Option<Vec<Node>> = self.values.drain(..).collect();
+                        if let Some(rest) = rest {
+                            bindings.insert(key, Node::Seq(rest));
+                        }
+                    }
+                }
+                continue;
+            }
+            match self.pop_one(None, instr)? {
+                Some(operand) if !matches(item, &operand, bindings) => {
+                    return Err(mismatch(std::slice::from_ref(item), &operand, bindings));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn pop_one(
+        &mut self,
+        expected: Option<&Node>,
+        instr: &Node,
+    ) -> Result<Option<Node>, ValidationError> {
+        let operand = match self.values.pop() {
+            Some(operand) => operand,
+            None if self.polymorphic => None,
+            None => return Err(ValidationError::Underflow(instr.to_string())),
+        };
+        match (expected, &operand) {
+            (Some(expected), Some(found)) if expected != found => Err(ValidationError::Mismatch {
+                expected: expected.to_string(),
+                found: found.to_string(),
+            }),
+            _ => Ok(operand),
+        }
+    }
+
+    fn push(&mut self, to: &[Expr], bindings: &Bindings) -> Result<(), ValidationError> {
+        for item in to {
+            match instantiate(item, bindings) {
+                Ok(Node::Seq(types)) if starred(item) => {
+                    self.values.extend(types.iter().map(|t| Some(normalize(t))))
+                }
+                Ok(ty) => self.values.push(Some(normalize(&ty))),
+                // results left open, e.g. after a branch
+                Err(_) if starred(item) => {
+                    self.values.clear();
+                    self.polymorphic = true;
+                }
+                Err(_) if self.polymorphic => self.values.push(None),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Require the stack to hold exactly the given types
+    fn expect(&mut self, types: &[Node]) -> Result<(), ValidationError> {
+        let found = self.to_string();
+        let end = Node::Term("END".to_string());
+        for ty in types.iter().rev() {
+            self.pop_one(Some(ty), &end)
+                .map_err(|_| ValidationError::Mismatch {
+                    expected: Node::Seq(types.to_vec()).to_string(),
+                    found: found.clone(),
+                })?;
+        }
+        if self.values.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError::Mismatch {
+                expected: Node::Seq(types.to_vec()).to_string(),
+                found,
+            })
+        }
+    }
+}
+
+/// `[t_1^\ast~t]`, the operand types of an instruction type
+fn stack_type(exprs: &[Expr]) -> Option<&[Expr]> {
+    match exprs {
+        [Expr::List(items)] => Some(items),
+        _ => None,
+    }
+}
+
+/// `C,\CLABELS\,[t^\ast]` prepends the instantiated entry to the labels
+fn extend(
+    ctx: &Context,
+    judgement: &Judgement,
+    bindings: &Bindings,
+) -> Result<Context, ValidationError> {
+    let mut ctx = ctx.clone();
+    for (field, entry) in judgement.context().extensions() {
+        let entry = instantiate(entry, bindings)?;
+        let entries = match ctx.fields.remove(&field_name(field)) {
+            Some(Node::Seq(entries)) => entries,
+            _ => vec![],
+        };
+        ctx.fields.insert(
+            field_name(field),
+            Node::Seq(std::iter::once(entry).chain(entries).collect()),
+        );
+    }
+    Ok(ctx)
+}
+
+/// `locals` for `\CLOCALS`
+fn field_name(field: &str) -> String {
+    field.strip_prefix('C').unwrap_or(field).to_lowercase()
+}
+
+fn to_index(node: &Node) -> Result<usize, ValidationError> {
+    match normalize(node) {
+        Node::Value(value) => value.parse().map_err(|_| ValidationError::Mismatch {
+            expected: "index".to_string(),
+            found: value,
+        }),
+        found => Err(ValidationError::Mismatch {
+            expected: "index".to_string(),
+            found: found.to_string(),
+        }),
+    }
+}
+
+fn starred(expr: &Expr) -> bool {
+    match expr {
+        Expr::Var(var) => var.seq_kind.is_some(),
+        Expr::Meta(nt, _) => nt.seq_kind().is_some(),
+        _ => false,
+    }
+}
+
+/// Skip the nodes of nonterminals that just wrap another one, e.g.
+/// `(valtype (numtype I32))` is `I32`
+fn strip(node: &Node) -> &Node {
+    match node {
+        Node::Nonterm { children, .. } if children.len() == 1 => strip(&children[0]),
+        node => node,
+    }
+}
+
+/// [`strip`] all the way down, so that types compare structurally
+fn normalize(node: &Node) -> Node {
+    match strip(node) {
+        Node::Nonterm { name, children } => Node::Nonterm {
+            name: name.clone(),
+            children: children.iter().map(normalize).collect(),
+        },
+        Node::Record(pairs) => Node::Record(
+            pairs
+                .iter()
+                .map(|(key, value)| (key.clone(), normalize(value)))
+                .collect(),
+        ),
+        Node::Seq(items) => Node::Seq(items.iter().map(normalize).collect()),
+        Node::Arrow(from, to) => Node::Arrow(Box::new(normalize(from)), Box::new(normalize(to))),
+        node => node.clone(),
+    }
+}
+
+/// Whether a nonterminal wraps the node, or the node carries no
+/// nonterminal at all, as entries of the context may not
+fn is_instance(name: &str, node: &Node) -> bool {
+    let mut node = node;
+    let mut named = false;
+    while let Node::Nonterm {
+        name: nonterminal,
+        children,
+    } = node
+    {
+        if nonterminal == name {
+            return true;
+        }
+        named = true;
+        match children.as_slice() {
+            [child] => node = child,
+            _ => break,
+        }
+    }
+    !named
+}
+
+/// Match the phrase of a conclusion, e.g. `\LOCALGET~x`, against a node
+fn phrase_matches(phrase: &[Expr], node: &Node, bindings: &mut Bindings) -> bool {
+    if let [item] = phrase {
+        return matches(item, node, bindings);
+    }
+    let mut node = node;
+    loop {
+        match node {
+            Node::Nonterm { children, .. } if children.len() == phrase.len() => {
+                return phrase
+                    .iter()
+                    .zip(children)
+                    .all(|(item, child)| matches(item, child, bindings));
+            }
+            Node::Nonterm { children, .. } if children.len() == 1 => node = &children[0],
+            _ => return false,
+        }
+    }
+}
+
+fn matches_all(pattern: &[Expr], node: &Node, bindings: &mut Bindings) -> bool {
+    match pattern {
+        [item] => matches(item, node, bindings),
+        items => match strip(node) {
+            Node::Seq(values) => matches_seq(items, values, bindings),
+            _ => false,
+        },
+    }
+}
+
+/// Match a pattern against a node, binding its unbound variables
+fn matches(pattern: &Expr, node: &Node, bindings: &mut Bindings) -> bool {
+    match pattern {
+        Expr::Var(_) | Expr::Meta(..) => {
+            if let Expr::Meta(nt, _) = pattern {
+                if !is_instance(&nt.name, node) && nt.seq_kind().is_none() {
+                    return false;
+                }
+            }
+            let key = pattern.to_string();
+            match bindings.get(&key) {
+                Some(bound) => normalize(bound) == normalize(node),
+                None => {
+                    bindings.insert(key, node.clone());
+                    true
+                }
+            }
+        }
+        Expr::Term(term) => matches!(strip(node), Node::Term(t) if t == term),
+        Expr::Epsilon => matches!(strip(node), Node::Seq(items) if items.is_empty()),
+        Expr::List(items) => match strip(node) {
+            Node::Seq(values) => matches_seq(items, values, bindings),
+            _ => false,
+        },
+        Expr::Arrow(from, to) => match strip(node) {
+            Node::Arrow(a, b) => matches_all(from, a, bindings) && matches_all(to, b, bindings),
+            _ => false,
+        },
+        Expr::Record(_) | Expr::Compose(..) => false,
+    }
+}
+
+/// Match items against a sequence, the one open iteration among them taking
+/// the values that the others leave
+fn matches_seq(items: &[Expr], values: &[Node], bindings: &mut Bindings) -> bool {
+    let open: Vec<_> = (0..items.len()).filter(|&i| starred(&items[i])).collect();
+    match open.as_slice() {
+        [] => {
+            items.len() == values.len()
+                && items
+                    .iter()
+                    .zip(values)
+                    .all(|(item, value)| matches(item, value, bindings))
+        }
+        [k] => {
+            let k = *k;
+            let tail = items.len() - k - 1;
+            if values.len() < k + tail {
+                return false;
+            }
+            let middle = Node::Seq(values[k..values.len() - tail].to_vec());
+            items[..k]
+                .iter()
+                .zip(values)
+                .all(|(item, value)| matches(item, value, bindings))
+                && items[k + 1..]
+                    .iter()
+                    .zip(&values[values.len() - tail..])
+                    .all(|(item, value)| matches(item, value, bindings))
+                && matches(&items[k], &middle, bindings)
+        }
+        _ => match instantiate_seq(items, bindings) {
+            Ok(instance) => {
+                normalize(&Node::Seq(instance)) == normalize(&Node::Seq(values.to_vec()))
+            }
+            Err(_) => false,
+        },
+    }
+}
+
+fn instantiate(expr: &Expr, bindings: &Bindings) -> Result<Node, ValidationError> {
+    match expr {
+        Expr::Var(_) | Expr::Meta(..) => bindings
+            .get(&expr.to_string())
+            .cloned()
+            .ok_or_else(|| ValidationError::Undetermined(expr.to_string())),
+        Expr::Term(term) => Ok(Node::Term(term.clone())),
+        Expr::Epsilon => Ok(Node::Seq(vec![])),
+        Expr::List(items) => instantiate_seq(items, bindings).map(Node::Seq),
+        Expr::Arrow(from, to) => Ok(Node::Arrow(
+            Box::new(instantiate_all(from, bindings)?),
+            Box::new(instantiate_all(to, bindings)?),
+        )),
+        Expr::Record(_) | Expr::Compose(..) => Err(ValidationError::Undetermined(expr.to_string())),
+    }
+}
+
+fn instantiate_all(exprs: &[Expr], bindings: &Bindings) -> Result<Node, ValidationError> {
+    match exprs {
+        [expr] => instantiate(expr, bindings),
+        exprs => instantiate_seq(exprs, bindings).map(Node::Seq),
+    }
+}
+
+/// Items of a sequence, splicing in the values of iterations
+fn instantiate_seq(items: &[Expr], bindings: &Bindings) -> Result<Vec<Node>, ValidationError> {
+    let mut values = vec![];
+    for item in items {
+        match instantiate(item, bindings)? {
+            Node::Seq(items) if starred(item) => values.extend(items),
+            value => values.push(value),
+        }
+    }
+    Ok(values)
+}
+
+fn mismatch(pattern: &[Expr], found: &Node, bindings: &Bindings) -> ValidationError {
+    let expected = match instantiate_all(pattern, bindings) {
+        Ok(expected) => normalize(&expected).to_string(),
+        Err(_) => pattern
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("~"),
+    };
+    ValidationError::Mismatch {
+        expected,
+        found: normalize(found).to_string(),
+    }
+}
+
+impl fmt::Display for Stack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values: Vec<_> = self
+            .values
+            .iter()
+            .map(|v| v.as_ref().map_or("_".to_string(), |v| v.to_string()))
+            .collect();
+        write!(f, "[{}]", values.join(" "))
+    }
+}
+
+impl fmt::Display for SkippedRule<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.origin.file.display(),
+            self.origin.line,
+            self.reason
+        )
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::NoRule(phrase) => write!(f, "no typing rule for `{}`", phrase),
+            ValidationError::Underflow(instr) => write!(f, "`{}` pops an empty stack", instr),
+            ValidationError::Mismatch { expected, found } => {
+                write!(
+                    f,
+                    "type mismatch: expected `{}`, found `{}`",
+                    expected, found
+                )
+            }
+            ValidationError::Lookup {
+                field,
+                index: Some(index),
+            } => write!(f, "unknown {} {}", field, index),
+            ValidationError::Lookup { field, index: None } => write!(f, "no {} in context", field),
+            ValidationError::Undetermined(var) => write!(f, "`{}` is not determined", var),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        coverage::wast::{self, atom, items, keyword, Token, TokenKind},
+        syntax::fixtures,
+        Grammar,
+    };
+
+    use super::*;
+
+    fn spec() -> Spec {
+        let mut grammar = Grammar::new("spec");
+        grammar.add_source(
+            "valid/instructions.rst",
+            r"
+.. math::
+   \frac{
+   }{
+     C \vdash \NOP : [] \to []
+   }
+
+.. math::
+   \frac{
+   }{
+     C \vdash \UNREACHABLE : [t_1^\ast] \to [t_2^\ast]
+   }
+
+.. math::
+   \frac{
+   }{
+     C \vdash \DROP : [t] \to []
+   }
+
+.. math::
+   \frac{
+     t = \numtype \vee t = \vectype
+   }{
+     C \vdash \SELECT : [t~t~\I32] \to [t]
+   }
+
+.. math::
+   \frac{
+     C.\CLOCALS[x] = t
+   }{
+     C \vdash \LOCALGET~x : [] \to [t]
+   }
+
+.. math::
+   \frac{
+     C.\CLOCALS[x] = t
+   }{
+     C \vdash \LOCALSET~x : [t] \to []
+   }
+
+.. math::
+   \frac{
+     C \vdash \blocktype : [t_1^\ast] \to [t_2^\ast]
+     \qquad
+     C,\CLABELS\,[t_2^\ast] \vdash \instr^\ast : [t_1^\ast] \to [t_2^\ast]
+   }{
+     C \vdash \BLOCK~\blocktype~\instr^\ast~\END : [t_1^\ast] \to [t_2^\ast]
+   }
+
+.. math::
+   \frac{
+     C.\CLABELS[l] = [t^\ast]
+   }{
+     C \vdash \BR~l : [t_1^\ast~t^\ast] \to [t_2^\ast]
+   }
+
+.. math::
+   \frac{
+     C.\CTYPES[\typeidx] = [t_1^\ast] \to [t_2^\ast]
+   }{
+     C \vdash \typeidx : [t_1^\ast] \to [t_2^\ast]
+   }
+
+.. math::
+   \frac{
+   }{
+     C \vdash [\valtype^?] : [] \to [\valtype^?]
+   }
+",
+        );
+        assert!(grammar.errors().is_empty(), "{:?}", grammar.errors());
+        Spec::new(grammar)
+    }
+
+    /// Read a node as printed, e.g. `(instr LOCALGET (localidx 0))`, with
+    /// upper case atoms as terminals
+    fn node(source: &str) -> Node {
+        let source = source.replace('(', " ( ").replace(')', " ) ");
+        let source = source.replace('[', " [ ").replace(']', " ] ");
+        let mut tokens = source.split_whitespace().peekable();
+        let node = read(&mut tokens);
+        assert!(tokens.next().is_none());
+        node
+    }
+
+    fn read<'a>(tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>) -> Node {
+        let node = match tokens.next().unwrap() {
+            "(" => {
+                let name = tokens.next().unwrap().to_string();
+                let mut children = vec![];
+                while tokens.peek() != Some(&")") {
+                    children.push(read(tokens));
+                }
+                tokens.next();
+                Node::Nonterm { name, children }
+            }
+            "[" => {
+                let mut items = vec![];
+                while tokens.peek() != Some(&"]") {
+                    items.push(read(tokens));
+                }
+                tokens.next();
+                Node::Seq(items)
+            }
+            atom if atom.starts_with(|c: char| c.is_ascii_uppercase()) => {
+                Node::Term(atom.to_string())
+            }
+            atom => Node::Value(atom.to_string()),
+        };
+        if tokens.peek() == Some(&"->") {
+            tokens.next();
+            return Node::Arrow(Box::new(node), Box::new(read(tokens)));
+        }
+        node
+    }
+
+    fn instrs(source: &str) -> Vec<Node> {
+        match node(&format!("[{}]", source)) {
+            Node::Seq(instrs) => instrs,
+            _ => unreachable!(),
+        }
+    }
+
+    fn context() -> Context {
+        let i32 = node("(valtype (numtype I32))");
+        let i64 = node("(valtype (numtype I64))");
+        Context::new()
+            .field("locals", vec![i32.clone(), i64])
+            .field(
+                "types",
+                vec![node("(functype (resulttype [I32]) -> (resulttype []))")],
+            )
+    }
+
+    fn check(source: &str, from: &str, to: &str) -> Result<(), ValidationError> {
+        let spec = spec();
+        let validator = Validator::new(&spec);
+        let (from, to) = (instrs(from), instrs(to));
+        validator.check_instrs(&context(), &instrs(source), &from, &to)
+    }
+
+    #[test]
+    fn skipped() {
+        let spec = spec();
+        let validator = Validator::new(&spec);
+        let skipped: Vec<_> = validator.skipped().iter().map(|s| s.to_string()).collect();
+        assert_eq!(
+            skipped,
+            vec![
+                r"valid/instructions.rst:21: premise `t = \numtype \vee t = \vectype` is not supported"
+            ]
+        );
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(check("(instr LOCALGET (localidx 0))", "", "I32"), Ok(()));
+        assert_eq!(
+            check(
+                "(instr LOCALGET (localidx 1)) (instr DROP) (instr NOP)",
+                "",
+                ""
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check(
+                "(instr LOCALSET (localidx 1))",
+                "(valtype (numtype I64))",
+                ""
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check(
+                "(instr BLOCK (blocktype [(valtype (numtype I32))]) [(instr LOCALGET (localidx 0))] END)",
+                "",
+                "I32"
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check(
+                "(instr LOCALGET (localidx 0)) (instr BLOCK (blocktype (typeidx 0)) [(instr DROP)] END)",
+                "",
+                ""
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn polymorphic_stack() {
+        assert_eq!(
+            check("(instr UNREACHABLE) (instr DROP) (instr DROP)", "", "I64"),
+            Ok(())
+        );
+        // the branch takes its operands, the rest of the block is dead
+        assert_eq!(
+            check(
+                "(instr BLOCK (blocktype [(valtype (numtype I64))]) [(instr LOCALGET (localidx 1)) (instr BR (labelidx 0)) (instr DROP)] END)",
+                "",
+                "I64"
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            check("(instr LOCALGET (localidx 1))", "", "I32"),
+            Err(ValidationError::Mismatch {
+                expected: "[I32]".to_string(),
+                found: "[I64]".to_string()
+            })
+        );
+        assert_eq!(
+            check("(instr DROP)", "", ""),
+            Err(ValidationError::Underflow("(instr DROP)".to_string()))
+        );
+        assert_eq!(
+            check("(instr LOCALGET (localidx 2))", "", "I32"),
+            Err(ValidationError::Lookup {
+                field: "locals".to_string(),
+                index: Some(2)
+            })
+        );
+        assert_eq!(
+            check("(instr LOCALSET (localidx 0))", "I64", ""),
+            Err(ValidationError::Mismatch {
+                expected: "I32".to_string(),
+                found: "I64".to_string()
+            })
+        );
+        assert_eq!(
+            check(
+                "(instr BLOCK (blocktype [(valtype (numtype I32))]) [(instr LOCALGET (localidx 1))] END)",
+                "",
+                "I32"
+            ),
+            Err(ValidationError::Mismatch {
+                expected: "I32".to_string(),
+                found: "I64".to_string()
+            })
+        );
+        assert_eq!(
+            check(
+                "(instr BLOCK (blocktype []) [(instr BR (labelidx 1))] END)",
+                "",
+                ""
+            ),
+            Err(ValidationError::Lookup {
+                field: "labels".to_string(),
+                index: Some(1)
+            })
+        );
+        assert_eq!(
+            check("(instr SELECT)", "", ""),
+            Err(ValidationError::NoRule("(instr SELECT)".to_string()))
+        );
+    }
+
+    #[test]
+    fn block_types() {
+        let spec = spec();
+        let validator = Validator::new(&spec);
+        let ty = validator.check(&context(), &node("(blocktype (typeidx 0))"));
+        assert_eq!(ty.map(|t| t.to_string()), Ok("[I32] -> []".to_string()));
+        let ty = validator.check(&context(), &node("(blocktype [(valtype (numtype F32))])"));
+        assert_eq!(ty.map(|t| t.to_string()), Ok("[] -> [F32]".to_string()));
+        assert!(validator
+            .check(&context(), &node("(blocktype (typeidx 3))"))
+            .is_err());
+    }
+
+    /// Identifier at `i`, e.g. the label of a block, skipped if any
+    fn id<'a>(items: &[&[Token<'a>]], i: &mut usize) -> Option<&'a str> {
+        let id = items
+            .get(*i)
+            .and_then(|item| atom(item))
+            .filter(|atom| atom.starts_with('$'));
+        *i += usize::from(id.is_some());
+        id
+    }
+
+    /// Index of `x`, e.g. `$f` or `1`, in a space of optionally named
+    /// entries
+    fn index(names: &[Option<&str>], x: &str) -> Option<usize> {
+        match x.starts_with('$') {
+            true => names.iter().position(|name| *name == Some(x)),
+            false => x.parse().ok(),
+        }
+    }
+
+    fn valtype(atom: &str) -> Option<Node> {
+        matches!(
+            atom,
+            "i32" | "i64" | "f32" | "f64" | "v128" | "funcref" | "externref"
+        )
+        .then(|| Node::Term(atom.to_uppercase()))
+    }
+
+    /// Names and types of `(param ...)`, `(result ...)` or `(local ...)`
+    fn declared<'a>(form: &[Token<'a>]) -> Option<Vec<(Option<&'a str>, Node)>> {
+        let items = items(form);
+        let mut i = 0;
+        let name = id(&items, &mut i);
+        items[i..]
+            .iter()
+            .map(|ty| Some((name, valtype(atom(ty)?)?)))
+            .collect()
+    }
+
+    fn nonterm(name: &str, children: Vec<Node>) -> Node {
+        Node::Nonterm {
+            name: name.to_string(),
+            children,
+        }
+    }
+
+    /// Instruction of an opcode and its immediates in the form of the
+    /// typing rules, e.g. `(instr I32 . ADD)` for `i32.add`
+    fn instr(op: &str, immediates: Vec<Node>) -> Node {
+        let term = |s: &str| Node::Term(s.replace(['.', '_'], "").to_uppercase());
+        let dot = Node::Term(".".to_string());
+        let mut children = match op.split_once('.') {
+            Some((t, rest)) if valtype(t).is_some() => match rest.rsplit_once('_') {
+                Some((name, t2)) if valtype(t2).is_some() => {
+                    let underscore = Node::Term("_".to_string());
+                    vec![term(t), dot, term(name), underscore, term(t2)]
+                }
+                _ => vec![term(t), dot, term(rest)],
+            },
+            _ => vec![term(op)],
+        };
+        children.extend(immediates);
+        nonterm("instr", children)
+    }
+
+    /// Types and functions of a text module, the index spaces the
+    /// instructions refer to
+    #[derive(Default)]
+    struct Module<'a> {
+        types: Vec<Node>,
+        type_names: Vec<Option<&'a str>>,
+        funcs: Vec<Node>,
+        func_names: Vec<Option<&'a str>>,
+    }
+
+    /// Defined function, with the names of its parameters and locals
+    struct Func<'t, 'a> {
+        locals: Vec<(Option<&'a str>, Node)>,
+        results: Vec<Node>,
+        body: Vec<&'t [Token<'a>]>,
+    }
+
+    impl<'a> Module<'a> {
+        /// Index spaces and defined functions of a `(module ...)` form, or
+        /// none if a field refers to something it does not read
+        fn read<'t>(module: &'t [Token<'a>]) -> Option<(Self, Vec<Func<'t, 'a>>)> {
+            let fields: Vec<_> = items(module)
+                .into_iter()
+                .filter(|field| atom(field).is_none())
+                .collect();
+            let mut this = Module::default();
+            for field in fields.iter().filter(|f| keyword(f) == Some("type")) {
+                let items = items(field);
+                let mut i = 0;
+                this.type_names.push(id(&items, &mut i));
+                let (_, params, results, _) = this.signature(items.get(i)?)?;
+                this.types.push(arrow(params, results));
+            }
+            // imported functions come first in the index space
+            let mut imports = vec![];
+            let mut defined = vec![];
+            for field in &fields {
+                match keyword(field) {
+                    Some("import") => imports.extend(
+                        items(field)
+                            .into_iter()
+                            .filter(|desc| keyword(desc) == Some("func")),
+                    ),
+                    Some("func") => match items(field).iter().any(|i| keyword(i) == Some("import"))
+                    {
+                        true => imports.push(*field),
+                        false => defined.push(*field),
+                    },
+                    _ => {}
+                }
+            }
+            let mut funcs = vec![];
+            for func in imports.into_iter().chain(defined) {
+                let (name, params, results, body) = this.signature(func)?;
+                this.func_names.push(name);
+                this.funcs.push(arrow(params.clone(), results.clone()));
+                if let Some(body) = body {
+                    let mut locals = params;
+                    let items = items(func);
+                    let declarations = items.len() - body;
+                    for item in &items[..declarations] {
+                        if keyword(item) == Some("local") {
+                            locals.extend(declared(item)?);
+                        }
+                    }
+                    let body = items[declarations..].to_vec();
+                    funcs.push(Func {
+                        locals,
+                        results,
+                        body,
+                    });
+                }
+            }
+            Some((this, funcs))
+        }
+
+        /// Name, parameters and results of a `(func ...)` form, and the
+        /// number of its items that are instructions, none if imported
+        #[allow(clippy::type_complexity)]
+        fn signature(
+            &self,
+            func: &[Token<'a>],
+        ) -> Option<(
+            Option<&'a str>,
+            Vec<(Option<&'a str>, Node)>,
+            Vec<Node>,
+            Option<usize>,
+        )> {
+            let items = items(func);
+            let mut i = 0;
+            let name = id(&items, &mut i);
+            let (mut params, mut results, mut ty, mut imported) = (vec![], vec![], None, false);
+            while let Some(item) = items.get(i) {
+                match keyword(item) {
+                    Some("export") | Some("local") => {}
+                    Some("import") => imported = true,
+                    Some("type") => {
+                        ty = Some(index(&self.type_names, atom(self::items(item).first()?)?)?)
+                    }
+                    Some("param") => params.extend(declared(item)?),
+                    Some("result") => results.extend(declared(item)?.into_iter().map(|(_, t)| t)),
+                    _ => break,
+                }
+                i += 1;
+            }
+            if let (Some(ty), true) = (ty, params.is_empty() && results.is_empty()) {
+                let Node::Arrow(from, to) = self.types.get(ty)? else {
+                    return None;
+                };
+                let (Node::Seq(from), Node::Seq(to)) = (&**from, &**to) else {
+                    return None;
+                };
+                params = from.iter().map(|t| (None, t.clone())).collect();
+                results = to.clone();
+            }
+            let body = (!imported).then_some(items.len() - i);
+            Some((name, params, results, body))
+        }
+    }
+
+    fn arrow(params: Vec<(Option<&str>, Node)>, results: Vec<Node>) -> Node {
+        let params = params.into_iter().map(|(_, t)| t).collect();
+        Node::Arrow(Box::new(Node::Seq(params)), Box::new(Node::Seq(results)))
+    }
+
+    /// Reader of the plain and folded instructions of a function body, with
+    /// the names of the labels around the instruction being read, the
+    /// innermost first
+    struct Body<'m, 'a> {
+        module: &'m Module<'a>,
+        locals: Vec<Option<&'a str>>,
+        labels: Vec<Option<&'a str>>,
+    }
+
+    impl<'a> Body<'_, 'a> {
+        /// Instructions from `i` up to one of the `ends`, e.g. `else`
+        fn instrs(
+            &mut self,
+            items: &[&[Token<'a>]],
+            i: &mut usize,
+            ends: &[&str],
+        ) -> Option<Vec<Node>> {
+            let mut instrs = vec![];
+            while let Some(item) = items.get(*i) {
+                *i += 1;
+                match atom(item) {
+                    Some(op) if ends.contains(&op) => {
+                        *i -= 1;
+                        break;
+                    }
+                    Some(op @ ("block" | "loop" | "if")) => {
+                        let label = id(items, i);
+                        let blocktype = self.blocktype(items, i)?;
+                        self.labels.insert(0, label);
+                        let body = self.instrs(items, i, &["else", "end"])?;
+                        let mut alternative = None;
+                        if op == "if" && atom(items.get(*i)?) == Some("else") {
+                            *i += 1;
+                            id(items, i);
+                            alternative = Some(self.instrs(items, i, &["end"])?);
+                        }
+                        if atom(items.get(*i)?) != Some("end") {
+                            return None;
+                        }
+                        *i += 1;
+                        id(items, i);
+                        self.labels.remove(0);
+                        instrs.push(block(op, blocktype, body, alternative));
+                    }
+                    Some(op) => {
+                        let immediates = self.immediates(op, items, i)?;
+                        instrs.push(instr(op, immediates));
+                    }
+                    None => self.folded(item, &mut instrs)?,
+                }
+            }
+            Some(instrs)
+        }
+
+        /// Instructions of a folded instruction, its operands first
+        fn folded(&mut self, form: &[Token<'a>], instrs: &mut Vec<Node>) -> Option<()> {
+            let op = keyword(form)?;
+            let items = items(form);
+            let mut i = 0;
+            match op {
+                "block" | "loop" => {
+                    let label = id(&items, &mut i);
+                    let blocktype = self.blocktype(&items, &mut i)?;
+                    self.labels.insert(0, label);
+                    let body = self.instrs(&items, &mut i, &[])?;
+                    self.labels.remove(0);
+                    instrs.push(block(op, blocktype, body, None));
+                }
+                "if" => {
+                    let label = id(&items, &mut i);
+                    let blocktype = self.blocktype(&items, &mut i)?;
+                    let branches = items
+                        .iter()
+                        .position(|item| keyword(item) == Some("then"))?;
+                    for condition in &items[i..branches] {
+                        self.folded(condition, instrs)?;
+                    }
+                    self.labels.insert(0, label);
+                    let mut branches = items[branches..].iter().map(|branch| {
+                        let items = self::items(branch);
+                        self.instrs(&items, &mut 0, &[])
+                    });
+                    let body = branches.next()??;
+                    let alternative = match branches.next() {
+                        Some(alternative) => Some(alternative?),
+                        None => None,
+                    };
+                    self.labels.remove(0);
+                    instrs.push(block(op, blocktype, body, alternative));
+                }
+                op => {
+                    let immediates = self.immediates(op, &items, &mut i)?;
+                    for operand in &items[i..] {
+                        self.folded(operand, instrs)?;
+                    }
+                    instrs.push(instr(op, immediates));
+                }
+            }
+            Some(())
+        }
+
+        /// `(type x)` or the `(result ...)` forms of a block, as its block
+        /// type
+        fn blocktype(&self, items: &[&[Token<'a>]], i: &mut usize) -> Option<Node> {
+            let mut results = vec![];
+            let mut ty = None;
+            while let Some(item) = items.get(*i) {
+                match keyword(item) {
+                    Some("type") => {
+                        let x = index(&self.module.type_names, atom(self::items(item).first()?)?)?;
+                        ty = Some(nonterm("typeidx", vec![Node::Value(x.to_string())]));
+                    }
+                    Some("result") => results.extend(declared(item)?.into_iter().map(|(_, t)| t)),
+                    Some("param") if ty.is_some() => {}
+                    Some("param") => return None,
+                    _ => break,
+                }
+                *i += 1;
+            }
+            Some(nonterm("blocktype", vec![ty.unwrap_or(Node::Seq(results))]))
+        }
+
+        /// Immediates of an instruction, with names resolved to indices
+        fn immediates(&self, op: &str, items: &[&[Token<'a>]], i: &mut usize) -> Option<Vec<Node>> {
+            let mut immediates = vec![];
+            while let Some(immediate) = items.get(*i).and_then(|item| atom(item)) {
+                let constant = op.ends_with(".const") && immediates.is_empty();
+                let reference = immediate.starts_with(|c: char| c.is_ascii_digit() || c == '$');
+                if !(constant || reference || immediate.contains('=')) {
+                    break;
+                }
+                *i += 1;
+                let names: &[Option<&str>] = match op {
+                    "local.get" | "local.set" | "local.tee" => &self.locals,
+                    "br" | "br_if" | "br_table" => &self.labels,
+                    "call" | "return_call" | "ref.func" => &self.module.func_names,
+                    _ => &[],
+                };
+                immediates.push(match constant || !reference {
+                    true => immediate.to_string(),
+                    false => index(names, immediate)?.to_string(),
+                });
+            }
+            if op.contains("load") || op.contains("store") {
+                return Some(vec![Node::Value(immediates.join(" "))]);
+            }
+            let mut immediates: Vec<_> = immediates.into_iter().map(Node::Value).collect();
+            if op == "br_table" {
+                let default = immediates.pop()?;
+                immediates = vec![Node::Seq(immediates), default];
+            }
+            Some(immediates)
+        }
+    }
+
+    fn block(op: &str, blocktype: Node, body: Vec<Node>, alternative: Option<Vec<Node>>) -> Node {
+        let term = |name: &str| Node::Term(name.to_string());
+        let mut children = vec![term(&op.to_uppercase()), blocktype, Node::Seq(body)];
+        if op == "if" {
+            children.push(term("ELSE"));
+            children.push(Node::Seq(alternative.unwrap_or_default()));
+        }
+        children.push(term("END"));
+        nonterm("instr", children)
+    }
+
+    /// Results of checking the functions of a text module, none if the
+    /// module cannot be read
+    fn check_module(
+        validator: &Validator,
+        module: &[Token<'_>],
+    ) -> Option<Vec<Result<(), ValidationError>>> {
+        let (module, funcs) = Module::read(module)?;
+        let mut results = vec![];
+        for func in funcs {
+            let mut body = Body {
+                module: &module,
+                locals: func.locals.iter().map(|(name, _)| *name).collect(),
+                labels: vec![],
+            };
+            let instrs = body.instrs(&func.body, &mut 0, &[])?;
+            let ctx = Context::new()
+                .field("types", module.types.clone())
+                .field("funcs", module.funcs.clone())
+                .field("locals", func.locals.into_iter().map(|(_, t)| t).collect())
+                .field("labels", vec![Node::Seq(func.results.clone())])
+                .field("return", func.results.clone());
+            results.push(validator.check_instrs(&ctx, &instrs, &[], &func.results));
+        }
+        Some(results)
+    }
+
+    #[test]
+    fn module() {
+        let spec = spec();
+        let validator = Validator::new(&spec);
+        let check = |source: &str| {
+            let tokens = wast::tokenize(source).unwrap();
+            check_module(&validator, &tokens)
+        };
+        assert_eq!(
+            check(
+                "(module (type $t (func (param i32))) \
+                 (func $f (param $x i32) (result i32) (local i64) \
+                 (block $b (result i32) (local.get $x)) (block (type $t) drop) (local.get $x)))"
+            ),
+            Some(vec![Ok(())])
+        );
+        assert_eq!(
+            check("(module (func (result i32) (local i64) local.get 0))"),
+            Some(vec![Err(ValidationError::Mismatch {
+                expected: "[I32]".to_string(),
+                found: "[I64]".to_string()
+            })])
+        );
+        assert_eq!(
+            check("(module (func (block br 1)) (func (local.get $y)))"),
+            None
+        );
+        assert_eq!(
+            check("(module (func (block br 2)))"),
+            Some(vec![Err(ValidationError::Lookup {
+                field: "labels".to_string(),
+                index: Some(2)
+            })])
+        );
+    }
+
+    /// Check the functions of the text modules of `scripts`, printing the
+    /// failures and the counts of each script. The modules at the top of a
+    /// script must not be rejected. The modules of `assert_invalid` with a
+    /// `"type mismatch"`, the error of a function body, must not be accepted.
+    /// Modules the validator cannot read, e.g. with instructions without a
+    /// typing rule, are unchecked.
+    fn check_scripts(spec: &Spec, scripts: &[(String, String)]) {
+        let validator = Validator::new(spec);
+
+        // accepted, rejected and unchecked, by script
+        let mut counts: BTreeMap<&str, [usize; 3]> = BTreeMap::new();
+        let mut failures = vec![];
+        let mut modules = 0;
+        for (script, source) in scripts {
+            let tokens = wast::tokenize(source).unwrap();
+            let count = counts.entry(script).or_default();
+            for module in wast::modules(&tokens) {
+                modules += 1;
+                let command = match module
+                    .start
+                    .checked_sub(2)
+                    .map(|i| &tokens[i..module.start])
+                {
+                    Some([open, command]) if open.kind == TokenKind::LParen => atom(&[*command]),
+                    _ => None,
+                };
+                let message = match tokens.get(module.end).map(|t| t.kind) {
+                    Some(TokenKind::Str(message)) => message,
+                    _ => "",
+                };
+                let line = tokens[module.start].line;
+                let results = check_module(&validator, &tokens[module]).unwrap_or_default();
+                let rejected = results
+                    .iter()
+                    .any(|r| !matches!(r, Ok(()) | Err(ValidationError::NoRule(_))));
+                let accepted = !results.is_empty() && results.iter().all(Result::is_ok);
+                match command {
+                    Some("assert_invalid") if rejected => count[1] += 1,
+                    Some("assert_invalid") if accepted && message == "type mismatch" => failures
+                        .push(format!(
+                            "{}:{}: accepted, expected {}",
+                            script, line, message
+                        )),
+                    Some("assert_invalid") => count[2] += 1,
+                    _ if rejected => failures.push(format!("{}:{}: {:?}", script, line, results)),
+                    _ if accepted => count[0] += 1,
+                    _ => count[2] += 1,
+                }
+            }
+        }
+        for (script, [accepted, rejected, unchecked]) in &counts {
+            println!(
+                "{}: {} accepted, {} invalid rejected, {} unchecked",
+                script, accepted, rejected, unchecked
+            );
+        }
+        assert!(modules > 0, "no text modules");
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+
+    /// Script of valid and invalid modules of [`spec`]
+    const SCRIPT: &str = r#"
+(module (func (param $x i32) (result i32) (local.get $x)))
+(assert_invalid (module (func (result i32) (local i64) local.get 0)) "type mismatch")
+(assert_invalid (module (func (block br 2))) "unknown label")
+(assert_invalid (module (func (block br 1)) (func (local.get $y))) "unknown local")
+"#;
+
+    /// The checks of the test suite on a script
+    #[test]
+    fn scripts() {
+        let scripts = [("locals.wast".to_string(), SCRIPT.to_string())];
+        check_scripts(&spec(), &scripts);
+    }
+
+    /// An invalid module that is accepted fails the checks
+    #[test]
+    #[should_panic(expected = "accepted.wast:1: accepted, expected type mismatch")]
+    fn accepted() {
+        let script = r#"(assert_invalid (module (func (param i32) (result i32) local.get 0)) "type mismatch")"#;
+        let scripts = [("accepted.wast".to_string(), script.to_string())];
+        check_scripts(&spec(), &scripts);
+    }
+
+    /// Check the modules of the test suite
+    #[test]
+    #[ignore = "needs a checkout of wasmmeta in `WASMMETA_PATH`"]
+    fn suite() {
+        let path = fixtures::wasmmeta_path();
+        let spec = fixtures::wasmmeta_spec(&path);
+        check_scripts(&spec, &fixtures::scripts(&path));
+    }
+}